@@ -0,0 +1,85 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Run with `cargo bench --features bench`. Set `SUBPARSE_BENCH_DIR` to a directory with subtitle files to
+//! benchmark them instead of the generated corpora.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::path::Path;
+use subparse::bench::{ass_corpus, default_corpora, load_dir, vobsub_corpora, Corpus};
+use subparse::{parse_bytes, sniff_encoding};
+
+fn corpora() -> Vec<Corpus> {
+    match std::env::var_os("SUBPARSE_BENCH_DIR") {
+        Some(dir) => load_dir(Path::new(&dir)).expect("loading the corpus directory"),
+        None => default_corpora().expect("generating the corpora"),
+    }
+}
+
+fn parse_benchmarks(c: &mut Criterion) {
+    let corpora = corpora();
+
+    // the encoding is given, so only the decoding and parsing is measured (see `detect_encoding`)
+    let mut group = c.benchmark_group("parse");
+    for corpus in &corpora {
+        let encoding = corpus.encoding();
+        group.throughput(Throughput::Bytes(corpus.data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(&corpus.name), corpus, |b, corpus| {
+            b.iter(|| parse_bytes(corpus.format, &corpus.data, encoding, 25.0).unwrap())
+        });
+    }
+    group.finish();
+
+    // `sniff_encoding()` and the `chardet` detection of `parse_bytes()` without an encoding
+    let mut group = c.benchmark_group("detect_encoding");
+    for corpus in corpora.iter().filter(|corpus| corpus.encoding().is_some()) {
+        group.throughput(Throughput::Bytes(corpus.data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("sniff_encoding", &corpus.name), corpus, |b, corpus| {
+            b.iter(|| sniff_encoding(&corpus.data))
+        });
+        group.bench_with_input(BenchmarkId::new("parse_bytes_detected", &corpus.name), corpus, |b, corpus| {
+            b.iter(|| parse_bytes(corpus.format, &corpus.data, None, 25.0).unwrap())
+        });
+    }
+    group.finish();
+
+    // the parser of `.idx` files reserves its file parts from the number of lines (`.ssa` files have too many file
+    // parts per line for that); compare runs with `--save-baseline` and `--baseline`
+    if std::env::var_os("SUBPARSE_BENCH_DIR").is_none() {
+        let mut group = c.benchmark_group("parse_by_entry_count");
+        for &entry_count in &[1_000, 10_000, 50_000] {
+            let (idx, _) = vobsub_corpora(entry_count).expect("generating the corpora");
+            for corpus in [ass_corpus(entry_count), idx] {
+                let encoding = corpus.encoding();
+                group.throughput(Throughput::Bytes(corpus.data.len() as u64));
+                group.bench_with_input(BenchmarkId::new(corpus.format.get_name(), entry_count), &corpus, |b, corpus| {
+                    b.iter(|| parse_bytes(corpus.format, &corpus.data, encoding, 25.0).unwrap())
+                });
+            }
+        }
+        group.finish();
+    }
+
+    let mut group = c.benchmark_group("get_subtitle_entries");
+    for corpus in &corpora {
+        let file = parse_bytes(corpus.format, &corpus.data, corpus.encoding(), 25.0).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(&corpus.name), &file, |b, file| {
+            b.iter(|| file.get_subtitle_entries().unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("to_data");
+    for corpus in &corpora {
+        let file = parse_bytes(corpus.format, &corpus.data, corpus.encoding(), 25.0).unwrap();
+        group.throughput(Throughput::Bytes(corpus.data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(&corpus.name), &file, |b, file| {
+            b.iter(|| file.to_data().unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse_benchmarks);
+criterion_main!(benches);
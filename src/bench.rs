@@ -0,0 +1,193 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Corpora for benchmarking the parsers, used by the benchmarks in `benches/` (`cargo bench --features bench`).
+//!
+//! The corpora are generated, so they are the same on every machine and don't have to be stored in the repository.
+//! Real files can be benchmarked with `load_dir()`.
+
+use crate::errors::*;
+use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
+use crate::{get_subtitle_format, sniff_encoding, SubtitleFileInterface, SubtitleFormat, SubtitleImage, VobFile};
+use encoding_rs::Encoding;
+use failure::ResultExt;
+use std::fmt::Write;
+use std::path::Path;
+
+/// A subtitle file for benchmarks.
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    /// The name of the corpus (like `"small_srt"` or the file name), for the benchmark ids.
+    pub name: String,
+
+    /// The format of `data`.
+    pub format: SubtitleFormat,
+
+    /// The content of the file.
+    pub data: Vec<u8>,
+}
+
+impl Corpus {
+    /// The encoding of a text file (see `sniff_encoding()`), `None` for binary formats. The benchmarks pass it to
+    /// `parse_bytes()`, so they don't measure the encoding detection.
+    pub fn encoding(&self) -> Option<&'static Encoding> {
+        match self.format {
+            SubtitleFormat::VobSubSub | SubtitleFormat::EbuStl | SubtitleFormat::BluRayPgs => None,
+            _ => Some(sniff_encoding(&self.data)),
+        }
+    }
+}
+
+/// The timespan of the `i`-th generated entry: two seconds long, one second apart.
+fn entry_timespan(i: usize) -> TimeSpan {
+    let start = TimePoint::from_msecs(i as i64 * 3000);
+    TimeSpan::new(start, start + TimeDelta::from_msecs(2000))
+}
+
+/// A `.srt` file with `entry_count` entries of two lines, some of them with formatting.
+pub fn srt_corpus(entry_count: usize) -> Corpus {
+    let mut s = String::new();
+    for i in 0..entry_count {
+        let timespan = entry_timespan(i);
+        let line = if i % 3 == 0 {
+            "<i>Where are you going?</i>"
+        } else {
+            "Where are you going?"
+        };
+        writeln!(
+            s,
+            "{}\n{} --> {}\n{}\n- Home, I guess.\n",
+            i + 1,
+            srt_time(timespan.start),
+            srt_time(timespan.end),
+            line
+        )
+        .expect("writing to a string");
+    }
+    Corpus {
+        name: "small_srt".to_string(),
+        format: SubtitleFormat::SubRip,
+        data: s.into_bytes(),
+    }
+}
+
+/// A `.ass` file with a script header, styles and `entry_count` dialog lines with override tags.
+pub fn ass_corpus(entry_count: usize) -> Corpus {
+    let mut s = String::from(
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: 1920\nPlayResY: 1080\n\n\
+         [V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, Bold, Italic, Alignment, MarginL, MarginR, MarginV\n\
+         Style: Default,Arial,48,&H00FFFFFF,0,0,2,10,10,10\nStyle: Sign,Arial,40,&H0000FFFF,1,0,8,10,10,10\n\n\
+         [Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    for i in 0..entry_count {
+        let timespan = entry_timespan(i);
+        let (style, text) = if i % 4 == 0 {
+            ("Sign", "{\\pos(960,100)\\fad(150,150)\\blur0.5}The station")
+        } else {
+            ("Default", "{\\i1}Where are you going?{\\i0}\\N- Home, I guess.")
+        };
+        writeln!(
+            s,
+            "Dialogue: 0,{},{},{},,0,0,0,,{}",
+            ass_time(timespan.start),
+            ass_time(timespan.end),
+            style,
+            text
+        )
+        .expect("writing to a string");
+    }
+    Corpus {
+        name: "huge_ass".to_string(),
+        format: SubtitleFormat::SubStationAlpha,
+        data: s.into_bytes(),
+    }
+}
+
+/// A `.sub` (VobSub) file and the matching `.idx` file with `entry_count` images of a white bar with a black border.
+pub fn vobsub_corpora(entry_count: usize) -> Result<(Corpus, Corpus)> {
+    let (width, height) = (200, 40);
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let border = x < 2 || y < 2 || x >= width - 2 || y >= height - 2;
+            data.extend_from_slice(if border { &[0, 0, 0, 255] } else { &[255, 255, 255, 255] });
+        }
+    }
+    let image = SubtitleImage {
+        x: 260,
+        y: 400,
+        width: width as u32,
+        height: height as u32,
+        data,
+    };
+
+    let images = (0..entry_count).map(|i| (entry_timespan(i), image.clone())).collect();
+    let (idx_file, vob_file) = VobFile::create(images, 720, 480)?;
+    Ok((
+        Corpus {
+            name: "vobsub_idx".to_string(),
+            format: SubtitleFormat::VobSubIdx,
+            data: idx_file.to_data()?,
+        },
+        Corpus {
+            name: "vobsub_sub".to_string(),
+            format: SubtitleFormat::VobSubSub,
+            data: vob_file.to_data()?,
+        },
+    ))
+}
+
+/// The default corpora: a small `.srt` file, a huge `.ass` file and a VobSub `.idx`/`.sub` pair.
+pub fn default_corpora() -> Result<Vec<Corpus>> {
+    let (idx, sub) = vobsub_corpora(200)?;
+    Ok(vec![srt_corpus(200), ass_corpus(50_000), idx, sub])
+}
+
+/// Load all subtitle files in the directory `dir` (not recursive). Files with an unknown format are skipped.
+pub fn load_dir(dir: &Path) -> Result<Vec<Corpus>> {
+    let mut result = Vec::new();
+    for dir_entry in std::fs::read_dir(dir).context(ErrorKind::IoError)? {
+        let path = dir_entry.context(ErrorKind::IoError)?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let data = std::fs::read(&path).context(ErrorKind::IoError)?;
+        if let Some(format) = get_subtitle_format(path.extension(), &data) {
+            result.push(Corpus {
+                name: path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                format,
+                data,
+            });
+        }
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
+
+fn srt_time(t: TimePoint) -> String {
+    format!("{:02}:{:02}:{:02},{:03}", t.hours(), t.mins_comp(), t.secs_comp(), t.msecs_comp())
+}
+
+fn ass_time(t: TimePoint) -> String {
+    format!("{}:{:02}:{:02}.{:02}", t.hours(), t.mins_comp(), t.secs_comp(), t.csecs_comp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_bytes;
+
+    #[test]
+    fn corpora_test() {
+        let (idx, sub) = vobsub_corpora(3).unwrap();
+        assert_eq!(sub.encoding(), None);
+        for corpus in [srt_corpus(3), ass_corpus(3), idx, sub] {
+            let file = parse_bytes(corpus.format, &corpus.data, corpus.encoding(), 25.0).unwrap();
+            let entries = file.get_subtitle_entries().unwrap();
+            assert_eq!(entries.len(), 3, "{}", corpus.name);
+            // `.idx` files only store the start times
+            assert_eq!(entries[2].timespan.start, entry_timespan(2).start, "{}", corpus.name);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::timetypes::{TimeDelta, TimeSpan};
+use crate::{SubtitleEntry, SubtitleFile};
+
+/// A subtitle file which records its changes, so they can be undone and redone.
+///
+/// Only the changed entries are stored for every edit, so editors don't have to keep copies of whole files.
+#[derive(Debug, Clone)]
+pub struct EditableFile {
+    file: SubtitleFile,
+
+    /// The edits which can be undone, the last edit is at the end.
+    undo_stack: Vec<Edit>,
+
+    /// The undone edits which can be redone, the last undone edit is at the end.
+    redo_stack: Vec<Edit>,
+}
+
+/// A reversible change of the entries: `(index, old entry, new entry)` for every changed entry (the number of
+/// entries can't be changed by `update_subtitle_entries()`).
+#[derive(Debug, Clone)]
+struct Edit(Vec<(usize, SubtitleEntry, SubtitleEntry)>);
+
+impl Edit {
+    /// The edit between two states of the same entries, `None` if nothing was changed.
+    fn between(old: Vec<SubtitleEntry>, new: Vec<SubtitleEntry>) -> Option<Edit> {
+        let changes: Vec<(usize, SubtitleEntry, SubtitleEntry)> = old
+            .into_iter()
+            .zip(new)
+            .enumerate()
+            .filter(|(_, (old_entry, new_entry))| old_entry != new_entry)
+            .map(|(i, (old_entry, new_entry))| (i, old_entry, new_entry))
+            .collect();
+
+        if changes.is_empty() {
+            None
+        } else {
+            Some(Edit(changes))
+        }
+    }
+
+    /// Apply the edit (`undo == false`) or reverse it (`undo == true`) to the file.
+    fn apply(&self, file: &mut SubtitleFile, undo: bool) -> Result<()> {
+        let mut entries = file.get_subtitle_entries()?;
+        for (i, old, new) in &self.0 {
+            entries[*i] = if undo { old.clone() } else { new.clone() };
+        }
+        file.update_subtitle_entries(&entries)
+    }
+}
+
+impl EditableFile {
+    /// Start recording the changes of `file`.
+    pub fn new(file: SubtitleFile) -> EditableFile {
+        EditableFile {
+            file,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// The file with all changes which were not undone.
+    pub fn file(&self) -> &SubtitleFile {
+        &self.file
+    }
+
+    /// Stop recording and return the file.
+    pub fn into_file(self) -> SubtitleFile {
+        self.file
+    }
+
+    /// The entries of the file (see `SubtitleFile::get_subtitle_entries()`).
+    pub fn get_subtitle_entries(&self) -> Result<Vec<SubtitleEntry>> {
+        self.file.get_subtitle_entries()
+    }
+
+    /// Update the entries of the file (see `SubtitleFile::update_subtitle_entries()`) and record the change.
+    ///
+    /// The redo history is discarded. Nothing is recorded if no entry changed.
+    pub fn update_subtitle_entries(&mut self, entries: &[SubtitleEntry]) -> Result<()> {
+        let old = self.file.get_subtitle_entries()?;
+        self.file.update_subtitle_entries(entries)?;
+
+        // the file can normalize the entries, so the new state is read back
+        if let Some(edit) = Edit::between(old, self.file.get_subtitle_entries()?) {
+            self.undo_stack.push(edit);
+            self.redo_stack.clear();
+        }
+        Ok(())
+    }
+
+    /// Shift all entries by `delta` and record the change.
+    pub fn shift(&mut self, delta: TimeDelta) -> Result<()> {
+        self.retime(|timespan| timespan + delta)
+    }
+
+    /// Change the timespans of all entries with `f` and record the change.
+    pub fn retime<F: FnMut(TimeSpan) -> TimeSpan>(&mut self, mut f: F) -> Result<()> {
+        let mut entries = self.file.get_subtitle_entries()?;
+        for entry in &mut entries {
+            entry.timespan = f(entry.timespan);
+        }
+        self.update_subtitle_entries(&entries)
+    }
+
+    /// Whether there is a change which can be undone.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Whether there is an undone change which can be redone.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Undo the last change. Returns `false` if there is no change to undo.
+    ///
+    /// If the change can't be undone, the error is returned and the file and the history are unchanged.
+    pub fn undo(&mut self) -> Result<bool> {
+        match self.undo_stack.last() {
+            Some(edit) => {
+                edit.apply(&mut self.file, true)?;
+                self.redo_stack.extend(self.undo_stack.pop());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Redo the last undone change. Returns `false` if there is no change to redo.
+    ///
+    /// If the change can't be redone, the error is returned and the file and the history are unchanged.
+    pub fn redo(&mut self) -> Result<bool> {
+        match self.redo_stack.last() {
+            Some(edit) => {
+                edit.apply(&mut self.file, false)?;
+                self.undo_stack.extend(self.redo_stack.pop());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Forget all recorded changes (for example after saving the file).
+    pub fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimePoint;
+    use crate::{parse_str, SubtitleFormat};
+
+    #[test]
+    fn undo_redo_test() {
+        let data = "1\n00:00:01,000 --> 00:00:02,000\nA\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n";
+        let file = parse_str(SubtitleFormat::SubRip, data, 25.0).unwrap();
+        let original = file.to_data().unwrap();
+        let mut editable = EditableFile::new(file);
+        assert!(!editable.undo().unwrap());
+
+        let mut entries = editable.get_subtitle_entries().unwrap();
+        entries[1].line = Some("C".to_string());
+        editable.update_subtitle_entries(&entries).unwrap();
+        editable.shift(TimeDelta::from_msecs(500)).unwrap();
+        let edited = editable.file().to_data().unwrap();
+
+        // nothing is recorded for updates without changes
+        editable.update_subtitle_entries(&editable.get_subtitle_entries().unwrap()).unwrap();
+
+        assert!(editable.undo().unwrap());
+        let entries = editable.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].timespan.start, TimePoint::from_msecs(1000));
+        assert_eq!(entries[1].line.as_deref(), Some("C"));
+
+        assert!(editable.undo().unwrap());
+        assert!(!editable.can_undo());
+        assert_eq!(editable.file().to_data().unwrap(), original);
+
+        assert!(editable.redo().unwrap());
+        assert!(editable.redo().unwrap());
+        assert!(!editable.redo().unwrap());
+        assert_eq!(editable.file().to_data().unwrap(), edited);
+
+        // a new change discards the undone changes
+        editable.undo().unwrap();
+        editable.shift(TimeDelta::from_msecs(-500)).unwrap();
+        assert!(!editable.can_redo());
+    }
+
+    #[test]
+    fn failed_undo_test() {
+        use crate::timetypes::TimeSpan;
+        use crate::{SubtitleImage, VobFile};
+
+        // the entries of `.sub` files can't be updated, so the recorded change can't be undone
+        let image = SubtitleImage {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        };
+        let timespan = TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2000));
+        let (_, vob_file) = VobFile::create(vec![(timespan, image)], 720, 576).unwrap();
+        let file = SubtitleFile::VobSubSubFile(vob_file);
+        let entries = file.get_subtitle_entries().unwrap();
+        let mut shifted = entries[0].clone();
+        shifted.timespan += TimeDelta::from_msecs(500);
+        let mut editable = EditableFile::new(file);
+        editable.undo_stack.push(Edit(vec![(0, shifted, entries[0].clone())]));
+
+        assert!(editable.undo().is_err());
+        assert!(editable.can_undo());
+        assert!(!editable.can_redo());
+        assert_eq!(editable.get_subtitle_entries().unwrap()[0].timespan, entries[0].timespan);
+    }
+}
@@ -10,9 +10,15 @@ use std::fmt;
 
 pub use crate::formats::idx::errors as idx_errors;
 pub use crate::formats::microdvd::errors as mdvd_errors;
+pub use crate::formats::sbv::errors as sbv_errors;
+pub use crate::formats::scc::errors as scc_errors;
 
+pub use crate::formats::mpl2::errors as mpl2_errors;
+pub use crate::formats::pgs::errors as pgs_errors;
 pub use crate::formats::srt::errors as srt_errors;
 pub use crate::formats::ssa::errors as ssa_errors;
+pub use crate::formats::stl::errors as stl_errors;
+pub use crate::formats::tmp::errors as tmp_errors;
 pub use crate::formats::vobsub::errors as vob_errors;
 
 /// A result type that can be used wide for error handling.
@@ -26,6 +32,7 @@ pub struct Error {
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
 /// Error kind for a crate-wide error.
+#[non_exhaustive]
 pub enum ErrorKind {
     /// Parsing error
     ParsingError,
@@ -42,11 +49,35 @@ pub enum ErrorKind {
     /// The attempted operation does not work on binary subtitle formats.
     TextFormatOnly,
 
+    /// Reading or writing a file failed.
+    IoError,
+
+    /// Encoding or decoding an image failed.
+    ImageError,
+
+    /// Creating the subtitle data (for example with `to_data()`) failed.
+    SerializingError,
+
     /// The attempted operation does not work on this format (not supported in this version of this library).
     UpdatingEntriesNotSupported {
         /// The format for which updating the subtitle entries is not supported.
         format: SubtitleFormat,
     },
+
+    /// Files of this format can't be created from the entries of other files (see `SubtitleFile::convert_to()`).
+    ConversionNotSupported {
+        /// The format into which the entries should be converted.
+        format: SubtitleFormat,
+    },
+
+    /// The number of given subtitle entries does not match the number of entries in the file.
+    EntryCountMismatch {
+        /// The number of entries in the file.
+        expected: usize,
+
+        /// The number of given entries.
+        found: usize,
+    },
 }
 
 impl fmt::Display for ErrorKind {
@@ -58,13 +89,45 @@ impl fmt::Display for ErrorKind {
                 "unknown file format, only SubRip (.srt), SubStationAlpha (.ssa/.ass) and VobSub (.idx and .sub) are supported at the moment"
             ),
             ErrorKind::DecodingError => write!(f, "error while decoding subtitle from bytes to string (wrong charset encoding?)"),
-            ErrorKind::EncodingDetectionError => write!(f, "could not determine character encoding from byte array (manually supply character encoding?)"),
+            ErrorKind::EncodingDetectionError => write!(
+                f,
+                "could not determine character encoding from byte array (manually supply character encoding?)"
+            ),
             ErrorKind::TextFormatOnly => write!(f, "operation does not work on binary subtitle formats (only text formats)"),
+            ErrorKind::IoError => write!(f, "reading or writing a file failed"),
+            ErrorKind::ImageError => write!(f, "encoding or decoding an image failed"),
+            ErrorKind::SerializingError => write!(f, "creating the subtitle data failed"),
             ErrorKind::UpdatingEntriesNotSupported { format } => write!(
                 f,
                 "updating subtitles is not implemented or supported by the `subparse` library for this format: {}",
                 format.get_name()
             ),
+            ErrorKind::ConversionNotSupported { format } => {
+                write!(f, "converting subtitles into this format is not supported: {}", format.get_name())
+            }
+            ErrorKind::EntryCountMismatch { expected, found } => {
+                write!(f, "the file has {} subtitle entries, but {} entries were given", expected, found)
+            }
+        }
+    }
+}
+
+impl ErrorKind {
+    /// A stable identifier of the error (like `"error-parsing"`), so frontends can show their own (translated)
+    /// messages. The errors of the formats (the causes of `ErrorKind::ParsingError`) have their own identifiers.
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            ErrorKind::ParsingError => "error-parsing",
+            ErrorKind::UnknownFileFormat => "error-unknown-file-format",
+            ErrorKind::DecodingError => "error-decoding",
+            ErrorKind::EncodingDetectionError => "error-encoding-detection",
+            ErrorKind::TextFormatOnly => "error-text-format-only",
+            ErrorKind::IoError => "error-io",
+            ErrorKind::ImageError => "error-image",
+            ErrorKind::SerializingError => "error-serializing",
+            ErrorKind::UpdatingEntriesNotSupported { .. } => "error-updating-entries-not-supported",
+            ErrorKind::ConversionNotSupported { .. } => "error-conversion-not-supported",
+            ErrorKind::EntryCountMismatch { .. } => "error-entry-count-mismatch",
         }
     }
 }
@@ -90,6 +153,187 @@ impl Error {
     pub fn kind(&self) -> ErrorKind {
         *self.inner.get_context()
     }
+
+    /// The identifiers (see `ErrorKind::message_id()`) of this error and of the format errors which caused it,
+    /// starting with this error.
+    pub fn message_ids(&self) -> Vec<&'static str> {
+        let mut result = vec![self.kind().message_id()];
+        let fail: &dyn Fail = self;
+        for cause in fail.iter_causes() {
+            let message_id = if let Some(e) = cause.downcast_ref::<srt_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<ssa_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<idx_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<mdvd_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<vob_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<sbv_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<stl_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<pgs_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<mpl2_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<tmp_errors::Error>() {
+                e.kind().message_id()
+            } else if let Some(e) = cause.downcast_ref::<scc_errors::Error>() {
+                e.kind().message_id()
+            } else {
+                continue;
+            };
+            result.push(message_id);
+        }
+        result
+    }
+
+    /// The (zero-based) line of the parsed input at which parsing failed, if the format error has one (like
+    /// `srt_errors::ErrorKind::ErrorAtLine`).
+    pub fn line_num(&self) -> Option<usize> {
+        let fail: &dyn Fail = self;
+        fail.iter_causes().find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<srt_errors::Error>() {
+                match e.kind() {
+                    srt_errors::ErrorKind::ErrorAtLine { line_num } => Some(*line_num),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<ssa_errors::Error>() {
+                use ssa_errors::ErrorKind::*;
+                match e.kind() {
+                    SsaFieldsInfoNotFound => None,
+                    SsaMissingField { line_num, .. }
+                    | SsaDuplicateField { line_num, .. }
+                    | SsaTextFieldNotLast { line_num }
+                    | SsaIncorrectNumberOfFields { line_num }
+                    | SsaWrongTimepointFormat { line_num, .. }
+                    | SsaDialogLineParseError { line_num, .. }
+                    | SsaLineParseError { line_num, .. } => Some(*line_num),
+                }
+            } else if let Some(e) = cause.downcast_ref::<idx_errors::Error>() {
+                match e.kind() {
+                    idx_errors::ErrorKind::IdxLineParseError { line_num, .. } => Some(*line_num),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<mdvd_errors::Error>() {
+                match e.kind() {
+                    mdvd_errors::ErrorKind::ErrorAtLine { line_num } => Some(*line_num),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<sbv_errors::Error>() {
+                match e.kind() {
+                    sbv_errors::ErrorKind::ErrorAtLine { line_num } => Some(*line_num),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<mpl2_errors::Error>() {
+                match e.kind() {
+                    mpl2_errors::ErrorKind::ErrorAtLine { line_num } => Some(*line_num),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<tmp_errors::Error>() {
+                match e.kind() {
+                    tmp_errors::ErrorKind::ErrorAtLine { line_num } => Some(*line_num),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<scc_errors::Error>() {
+                match e.kind() {
+                    scc_errors::ErrorKind::ErrorAtLine { line_num } => Some(*line_num),
+                    _ => None,
+                }
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The part of the line which could not be parsed, if the format error has it (like the line of
+    /// `srt_errors::ErrorKind::ExpectedTimestampLine`).
+    fn offending_text(&self) -> Option<&str> {
+        let fail: &dyn Fail = self;
+        fail.iter_causes().find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<srt_errors::Error>() {
+                match e.kind() {
+                    srt_errors::ErrorKind::ExpectedIndexLine { line } | srt_errors::ErrorKind::ExpectedTimestampLine { line } => Some(line.as_str()),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<mdvd_errors::Error>() {
+                match e.kind() {
+                    mdvd_errors::ErrorKind::ExpectedSubtitleLine { line } => Some(line.as_str()),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<sbv_errors::Error>() {
+                match e.kind() {
+                    sbv_errors::ErrorKind::ExpectedTimestampLine { line } => Some(line.as_str()),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<mpl2_errors::Error>() {
+                match e.kind() {
+                    mpl2_errors::ErrorKind::ExpectedSubtitleLine { line } => Some(line.as_str()),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<tmp_errors::Error>() {
+                match e.kind() {
+                    tmp_errors::ErrorKind::ExpectedSubtitleLine { line } => Some(line.as_str()),
+                    _ => None,
+                }
+            } else if let Some(e) = cause.downcast_ref::<scc_errors::Error>() {
+                match e.kind() {
+                    scc_errors::ErrorKind::ExpectedSccLine { line } => Some(line.as_str()),
+                    _ => None,
+                }
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Show an error of parsing `source` like rustc does: the message of the most specific cause and, if the error
+/// has a line (see `Error::line_num()`), the line with carets under the part which could not be parsed.
+///
+/// ```text
+/// error[srt-expected-timestamp-line]: expected SubRip timespan line, found '00:00:01,000 -> 00:00:02,000'
+///  --> line 2
+///   |
+/// 2 | 00:00:01,000 -> 00:00:02,000
+///   | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+/// ```
+pub fn render_diagnostic(error: &Error, source: &str) -> String {
+    let fail: &dyn Fail = error;
+    let message = fail.iter_chain().last().map(|cause| cause.to_string()).unwrap_or_default();
+    let message_id = error.message_ids().last().cloned().unwrap_or_default();
+    let mut result = format!("error[{}]: {}\n", message_id, message);
+
+    let line_num = match error.line_num() {
+        Some(line_num) if line_num <= source.lines().count() => line_num,
+        _ => return result,
+    };
+    let line = source.lines().nth(line_num).unwrap_or_default();
+    let line = line.strip_prefix('\u{feff}').unwrap_or(line);
+
+    // the offending text, the whole line (without indentation) or one caret after the end of an empty line
+    let (start, len) = match error
+        .offending_text()
+        .filter(|text| !text.is_empty())
+        .and_then(|text| Some((line.find(text)?, text)))
+    {
+        Some((start, text)) => (start, text.chars().count()),
+        None => {
+            let start = line.len() - line.trim_start().len();
+            (start, line.trim().chars().count().max(1))
+        }
+    };
+    let column = line[..start].chars().count();
+
+    let line_label = (line_num + 1).to_string();
+    let gutter = " ".repeat(line_label.len());
+    result.push_str(&format!("{}--> line {}\n", gutter, line_label));
+    result.push_str(&format!("{} |\n", gutter));
+    result.push_str(&format!("{} | {}\n", line_label, line));
+    result.push_str(&format!("{} | {}{}\n", gutter, " ".repeat(column), "^".repeat(len)));
+    result
 }
 
 impl From<ErrorKind> for Error {
@@ -100,7 +344,7 @@ impl From<ErrorKind> for Error {
 
 impl From<Context<ErrorKind>> for Error {
     fn from(inner: Context<ErrorKind>) -> Error {
-        Error { inner: inner }
+        Error { inner }
     }
 }
 
@@ -160,7 +404,6 @@ error_chain! {
     }
 }*/
 
-#[macro_use]
 /// Creates the `Error`-context type for an ErrorKind and associated conversion methods.
 macro_rules! define_error {
     ($error:ident, $kind:ident) => {
@@ -205,8 +448,43 @@ macro_rules! define_error {
 
         impl From<Context<$kind>> for $error {
             fn from(inner: Context<$kind>) -> $error {
-                $error { inner: inner }
+                $error { inner }
             }
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::render_diagnostic;
+    use crate::{parse_str, SubtitleFormat};
+
+    #[test]
+    fn message_ids_test() {
+        let error = parse_str(SubtitleFormat::SubStationAlpha, "[Script Info]\n", 25.0).unwrap_err();
+        assert_eq!(error.message_ids(), vec!["error-parsing", "ssa-fields-info-not-found"]);
+    }
+
+    #[test]
+    fn render_diagnostic_test() {
+        let source = "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,000 -> 00:00:04,000\nWorld\n";
+        let error = parse_str(SubtitleFormat::SubRip, source, 25.0).unwrap_err();
+        assert_eq!(error.line_num(), Some(5));
+        assert_eq!(
+            render_diagnostic(&error, source),
+            "error[srt-expected-timestamp-line]: expected SubRip timespan line, found '00:00:03,000 -> 00:00:04,000'\n \
+             --> line 6\n  |\n6 | 00:00:03,000 -> 00:00:04,000\n  | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^\n"
+        );
+
+        let source = "[Events]\nFormat: Start, End, Text\nDialogue: 0:00:01.00,0:00:0x.00,Hello\n";
+        let error = parse_str(SubtitleFormat::SubStationAlpha, source, 25.0).unwrap_err();
+        let expected = format!("3 | Dialogue: 0:00:01.00,0:00:0x.00,Hello\n  | {}\n", "^".repeat(37));
+        assert!(render_diagnostic(&error, source).ends_with(&expected));
+
+        let error = parse_str(SubtitleFormat::SubStationAlpha, "[Script Info]\n", 25.0).unwrap_err();
+        assert_eq!(
+            render_diagnostic(&error, "[Script Info]\n"),
+            "error[ssa-fields-info-not-found]: .ssa/.ass file did not have a line beginning with `Format: ` in a `[Events]` section\n"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The functions convert plain text (with `\n` as line break) to the text of a format and back. Formatting
+//! (like `{\i1}` or `<i>`) is not recognized: escaping plain text makes sure it is shown as it is, and unescaping
+//! keeps the formatting.
+//!
+//! The entries of parsed files keep the texts in the syntax of their format (with formatting and escapes), so
+//! the parsers only share the line breaks of this module. The functions are used where plain text is written into
+//! a format or read from it: the texts of `MdvdFile::create()`, `Mpl2File::create()` and `TmpFile::create()` are
+//! escaped and the HTML export unescapes `.ssa`/`.ass` texts.
+
+use crate::SubtitleFormat;
+
+/// The hard line break in `.ssa`/`.ass` texts.
+pub const SSA_LINE_BREAK: &str = "\\N";
+
+/// The line break in `MicroDVD`, `MPL2` and TMPlayer texts.
+pub const MDVD_LINE_BREAK: &str = "|";
+
+/// The string which separates the lines of the text of a subtitle entry in the given format (`None` for binary formats).
+pub fn line_break(format: SubtitleFormat) -> Option<&'static str> {
+    match format {
+        SubtitleFormat::SubRip | SubtitleFormat::YouTubeSbv | SubtitleFormat::EbuStl | SubtitleFormat::Lrc | SubtitleFormat::Scc => Some("\n"),
+        SubtitleFormat::SubStationAlpha => Some(SSA_LINE_BREAK),
+        SubtitleFormat::MicroDVD | SubtitleFormat::Mpl2 | SubtitleFormat::TmPlayer => Some(MDVD_LINE_BREAK),
+        SubtitleFormat::VobSubIdx | SubtitleFormat::VobSubSub | SubtitleFormat::BluRayPgs => None,
+    }
+}
+
+/// Escape plain text for `.ssa`/`.ass` files: line breaks become `\N`, non-breaking spaces become `\h` and
+/// braces become `\{` and `\}` (so they don't start override blocks).
+pub fn ssa_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => result.push_str(SSA_LINE_BREAK),
+            '\u{a0}' => result.push_str("\\h"),
+            '{' => result.push_str("\\{"),
+            '}' => result.push_str("\\}"),
+            '\r' => {}
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Reverse `ssa_escape()`. The soft line break `\n` is also converted to a line break.
+pub fn ssa_unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let unescaped = match (c, chars.peek()) {
+            ('\\', Some('N')) | ('\\', Some('n')) => '\n',
+            ('\\', Some('h')) => '\u{a0}',
+            ('\\', Some('{')) => '{',
+            ('\\', Some('}')) => '}',
+            _ => {
+                result.push(c);
+                continue;
+            }
+        };
+        chars.next();
+        result.push(unescaped);
+    }
+    result
+}
+
+/// Escape plain text for WebVTT cues: `&`, `<` and `>` become character references.
+pub fn webvtt_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '\u{a0}' => result.push_str("&nbsp;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Reverse `webvtt_escape()` (also for the `&lrm;` and `&rlm;` references). Unknown references are kept.
+pub fn webvtt_unescape(s: &str) -> String {
+    const REFERENCES: [(&str, char); 6] = [
+        ("&amp;", '&'),
+        ("&lt;", '<'),
+        ("&gt;", '>'),
+        ("&nbsp;", '\u{a0}'),
+        ("&lrm;", '\u{200e}'),
+        ("&rlm;", '\u{200f}'),
+    ];
+
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        match REFERENCES.iter().find(|(reference, _)| rest.starts_with(reference)) {
+            Some(&(reference, c)) => {
+                result.push(c);
+                rest = &rest[reference.len()..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Escape plain text for `MicroDVD` files: line breaks become `|`. There is no escape for `|` itself, so it is
+/// replaced by the similar-looking `¦`.
+pub fn mdvd_escape(s: &str) -> String {
+    s.replace('\r', "").replace('|', "¦").replace('\n', MDVD_LINE_BREAK)
+}
+
+/// Reverse `mdvd_escape()` (`|` becomes a line break).
+pub fn mdvd_unescape(s: &str) -> String {
+    s.replace(MDVD_LINE_BREAK, "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping_test() {
+        let plain = "{Hello}\nto\u{a0}you | & <me>";
+
+        assert_eq!(ssa_escape(plain), "\\{Hello\\}\\Nto\\hyou | & <me>");
+        assert_eq!(ssa_unescape(&ssa_escape(plain)), plain);
+        assert_eq!(ssa_unescape("{\\i1}a\\nb"), "{\\i1}a\nb");
+
+        assert_eq!(webvtt_escape(plain), "{Hello}\nto&nbsp;you | &amp; &lt;me&gt;");
+        assert_eq!(webvtt_unescape(&webvtt_escape(plain)), plain);
+        assert_eq!(webvtt_unescape("a &foo; &lrm;"), "a &foo; \u{200e}");
+
+        assert_eq!(mdvd_escape(plain), "{Hello}|to\u{a0}you ¦ & <me>");
+        assert_eq!(mdvd_unescape("a|b"), "a\nb");
+    }
+}
@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::{SsaFile, SubtitleEntry, SubtitleFile, SubtitleFileInterface};
+use std::path::Path;
+
+/// What is needed to burn subtitles into a video with ffmpeg (see `burn_plan()`).
+#[derive(Debug, Clone)]
+pub struct FfmpegBurnPlan {
+    /// The subtitles as `.ass` file, which has to be written to the path given to `burn_plan()` before running ffmpeg.
+    pub ass_data: Vec<u8>,
+
+    /// The filter for ffmpeg's `-vf` option, like `subtitles=filename=subs.ass:force_style=FontSize\\=24`.
+    pub filter: String,
+}
+
+/// Create a plan to burn the subtitles of `file` into a video with ffmpeg's `subtitles` filter.
+///
+/// The subtitles are converted to an `.ass` file (which ffmpeg renders most faithfully), which has to be written
+/// to `ass_path`. `force_style` overrides fields of the styles, like `[("FontName", "Arial"), ("FontSize", "24")]`.
+///
+/// VobSub files can not be converted and return `ErrorKind::TextFormatOnly`.
+pub fn burn_plan(file: &SubtitleFile, ass_path: &Path, force_style: &[(&str, &str)]) -> Result<FfmpegBurnPlan> {
+    let ass_data = match file {
+        SubtitleFile::SubStationAlpha(f) => f.to_data()?,
+        SubtitleFile::SubRipFile(_)
+        | SubtitleFile::MicroDVDFile(_)
+        | SubtitleFile::YouTubeSbvFile(_)
+        | SubtitleFile::EbuStlFile(_)
+        | SubtitleFile::Mpl2File(_)
+        | SubtitleFile::TmPlayerFile(_)
+        | SubtitleFile::LrcFile(_)
+        | SubtitleFile::SccFile(_) => {
+            let entries: Vec<SubtitleEntry> = file
+                .get_subtitle_entries()?
+                .into_iter()
+                .map(|entry| SubtitleEntry {
+                    line: entry.line.map(|line| html_tags_to_ssa(&line)),
+                    ..entry
+                })
+                .collect();
+            SsaFile::create_dual(&entries, &[])?.to_data()?
+        }
+        SubtitleFile::VobSubIdxFile(_) | SubtitleFile::VobSubSubFile(_) | SubtitleFile::BluRayPgsFile(_) => {
+            return Err(ErrorKind::TextFormatOnly.into())
+        }
+    };
+
+    let mut filter = format!("subtitles=filename={}", escape_filter_value(&ass_path.to_string_lossy()));
+    if !force_style.is_empty() {
+        let style = force_style
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        filter.push_str(":force_style=");
+        filter.push_str(&escape_filter_value(&style));
+    }
+
+    Ok(FfmpegBurnPlan { ass_data, filter })
+}
+
+/// Convert the `<i>`, `<b>`, `<u>` and `<s>` tags of `.srt` files to `.ass` override tags.
+fn html_tags_to_ssa(s: &str) -> String {
+    let mut result = s.to_string();
+    for tag in &["i", "b", "u", "s", "I", "B", "U", "S"] {
+        let ssa_tag = tag.to_lowercase();
+        result = result
+            .replace(&format!("<{}>", tag), &format!("{{\\{}1}}", ssa_tag))
+            .replace(&format!("</{}>", tag), &format!("{{\\{}0}}", ssa_tag));
+    }
+    result
+}
+
+/// Escape an option value of a filter: once for the option (`\`, `'`, `:` and `=`) and once more for the filtergraph
+/// (`\`, `'`, `[`, `]`, `,` and `;`).
+fn escape_filter_value(s: &str) -> String {
+    let escape = |s: &str, special: &[char]| -> String {
+        let mut result = String::with_capacity(s.len());
+        for c in s.chars() {
+            if special.contains(&c) {
+                result.push('\\');
+            }
+            result.push(c);
+        }
+        result
+    };
+
+    let option_value = escape(s, &['\\', '\'', ':', '=']);
+    escape(&option_value, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, SubtitleFormat};
+
+    #[test]
+    fn burn_plan_test() {
+        let file = parse_str(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,500\n<i>Hello</i>\nworld\n", 25.0).unwrap();
+        let plan = burn_plan(&file, Path::new("/tmp/sub's.ass"), &[("FontName", "Arial"), ("FontSize", "24")]).unwrap();
+
+        let ass = String::from_utf8(plan.ass_data).unwrap();
+        assert!(ass.ends_with("Dialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,{\\i1}Hello{\\i0}\\Nworld\n"));
+        assert_eq!(
+            plan.filter,
+            "subtitles=filename=/tmp/sub\\\\\\'s.ass:force_style=FontName\\\\=Arial\\,FontSize\\\\=24"
+        );
+
+        let vob_file = SubtitleFile::VobSubSubFile(crate::VobFile::parse(&[]).unwrap());
+        assert_eq!(
+            burn_plan(&vob_file, Path::new("a.ass"), &[]).unwrap_err().kind(),
+            ErrorKind::TextFormatOnly
+        );
+    }
+}
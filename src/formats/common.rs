@@ -2,44 +2,67 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
 use combine::char::*;
 use combine::combinator::*;
 use combine::primitives::{ParseError, ParseResult, Parser, Stream};
+use memchr::memchr2;
 use std::fmt::Display;
+use std::ops::Range;
 use std::str::FromStr;
 
 type CustomCharParser<I> = Expected<Satisfy<I, fn(char) -> bool>>;
 
 /// Returns the string without BOMs. Unchanged if string does not start with one.
 pub fn split_bom(s: &str) -> (&str, &str) {
-    if s.as_bytes().iter().take(3).eq([0xEF, 0xBB, 0xBF].iter()) {
-        s.split_at(3)
-    } else if s.as_bytes().iter().take(2).eq([0xFE, 0xFF].iter()) {
-        s.split_at(2)
+    s.split_at(bom_len(s.as_bytes()))
+}
+
+/// The length of the UTF-8 or UTF-16 BOM at the start of `bytes` (zero if there is none).
+fn bom_len(bytes: &[u8]) -> usize {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        3
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        2
     } else {
-        ("", s)
+        0
     }
 }
 
 #[test]
-#[allow(unsafe_code)]
 fn test_split_bom() {
-    let bom1_vec = &[0xEF, 0xBB, 0xBF];
-    let bom2_vec = &[0xFE, 0xFF];
-    let bom1 = unsafe { ::std::str::from_utf8_unchecked(bom1_vec) };
-    let bom2 = unsafe { ::std::str::from_utf8_unchecked(bom2_vec) };
-
-    // Rust doesn't seem to let us create a BOM as str in a safe way.
-    assert_eq!(
-        split_bom(unsafe { ::std::str::from_utf8_unchecked(&[0xEF, 0xBB, 0xBF, 'a' as u8, 'b' as u8, 'c' as u8]) }),
-        (bom1, "abc")
-    );
-    assert_eq!(
-        split_bom(unsafe { ::std::str::from_utf8_unchecked(&[0xFE, 0xFF, 'd' as u8, 'e' as u8, 'g' as u8]) }),
-        (bom2, "deg")
-    );
+    assert_eq!(split_bom("\u{FEFF}abc"), ("\u{FEFF}", "abc"));
     assert_eq!(split_bom("bla"), ("", "bla"));
     assert_eq!(split_bom(""), ("", ""));
+
+    // the UTF-16 BOM is not valid UTF-8, so it can't be at the start of a `&str`
+    assert_eq!(bom_len(&[0xEF, 0xBB, 0xBF, b'a', b'b', b'c']), 3);
+    assert_eq!(bom_len(&[0xFE, 0xFF, b'd', b'e', b'g']), 2);
+    assert_eq!(bom_len(b"bla"), 0);
+}
+
+/// Fails with `ErrorKind::EntryCountMismatch` if `found` entries are given to update a file with `expected` entries.
+pub fn check_entry_count(expected: usize, found: usize) -> crate::errors::Result<()> {
+    if expected != found {
+        return Err(crate::errors::ErrorKind::EntryCountMismatch { expected, found }.into());
+    }
+    Ok(())
+}
+
+/// The timespans of subtitles which are shown until the next subtitle starts (for formats like `.idx` which only
+/// store the starts). Mpv shows the last subtitle for exactly one minute.
+pub fn timespans_until_next(starts: &[TimePoint]) -> Vec<TimeSpan> {
+    let next_starts = starts
+        .iter()
+        .skip(1)
+        .cloned()
+        .chain(starts.last().map(|&last| last + TimeDelta::from_mins(1)));
+    starts
+        .iter()
+        .cloned()
+        .zip(next_starts)
+        .map(|(start, end)| TimeSpan::new(start, end))
+        .collect()
 }
 
 /// Parses whitespaces and tabs.
@@ -130,8 +153,9 @@ pub fn get_lines_non_destructive(s: &str) -> Vec<SplittedLine> {
             return result;
         }
 
-        match rest.char_indices().find(|&(_, c)| c == '\r' || c == '\n') {
-            Some((idx, _)) => {
+        // `\r` and `\n` are ASCII, so the byte index is always at a char boundary
+        match memchr2(b'\r', b'\n', rest.as_bytes()) {
+            Some(idx) => {
                 let (line_str, new_rest) = rest.split_at(idx);
                 rest = new_rest;
 
@@ -168,6 +192,22 @@ fn get_lines_non_destructive_test0() {
     }
 }
 
+#[test]
+fn get_lines_non_destructive_test1() {
+    let line = |s: &str, newl: &str| (s.to_string(), newl.to_string());
+    assert_eq!(
+        get_lines_non_destructive("äb\r\nc\rd\n\ne"),
+        vec![line("äb", "\r\n"), line("c", "\r"), line("d", "\n"), line("", "\n"), line("e", "")]
+    );
+    assert_eq!(get_lines_non_destructive("a\n"), vec![line("a", "\n")]);
+}
+
+/// The byte range of `s` without leading and trailing whitespace, where `s` starts at byte `offset` of the input.
+pub fn trimmed_range(s: &str, offset: usize) -> Range<usize> {
+    let start = offset + (s.len() - s.trim_start().len());
+    start..start + s.trim().len()
+}
+
 /// Trim a string left and right, but also preserve the white-space characters. The
 /// seconds element in the returned tuple contains the non-whitespace string.
 pub fn trim_non_destructive(s: &str) -> (String, String, String) {
@@ -184,3 +224,28 @@ fn trim_left(s: &str) -> (String, String) {
         .expect("the trim parser should accept any input")
         .0
 }
+
+/// Join user tags with `separator` for writing them into a file (the separator is replaced by a space in the tags).
+pub fn join_tags(tags: &[String], separator: char) -> String {
+    tags.iter()
+        .map(|tag| tag.replace(separator, " ").trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>()
+        .join(&separator.to_string())
+}
+
+/// Split a tag list from `join_tags()` (empty tags are ignored).
+pub fn split_tags(s: &str, separator: char) -> Vec<String> {
+    s.split(separator)
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_join_split_tags() {
+    let tags = vec!["needs-review".to_string(), "sign, top".to_string(), " ".to_string()];
+    assert_eq!(join_tags(&tags, ','), "needs-review,sign  top");
+    assert_eq!(split_tags(" needs-review, ,sign  top", ','), vec!["needs-review", "sign  top"]);
+}
@@ -5,54 +5,118 @@
 use self::errors::ErrorKind::*; // the crate wide error type (we use a custom error type here)
 use self::errors::*;
 use super::common::*;
-use crate::{SubtitleEntry, SubtitleFileInterface};
+use crate::{SerializeOptions, SubtitleEntry, SubtitleFileInterface, VobFile, VobPalette, DEFAULT_VOB_PALETTE};
 
 use crate::errors::Result as SubtitleParserResult;
+use crate::formats::MemoryFootprint;
 use combine::char::*;
 use combine::combinator::*;
 use combine::primitives::Parser;
 
 use failure::ResultExt;
 
-use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
-use std::iter::once;
+use crate::timetypes::{TimeDelta, TimePoint};
 
 /// `.idx`-parser-specific errors
-#[allow(missing_docs)]
+#[allow(missing_docs, non_local_definitions)]
 pub mod errors {
     pub type Result<T> = std::result::Result<T, Error>;
 
     define_error!(Error, ErrorKind);
 
     #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
     pub enum ErrorKind {
         #[fail(display = "parsing the line `{}` failed because of `{}`", line_num, msg)]
         IdxLineParseError { line_num: usize, msg: String },
+        #[fail(display = "entry `{}` has the same timestamp as the previous entry", entry_idx)]
+        DuplicateTimestamp { entry_idx: usize },
+        #[fail(display = "`{}` is not a two-letter language code", language)]
+        InvalidLanguage { language: String },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::IdxLineParseError { .. } => "idx-line-parse-error",
+                ErrorKind::DuplicateTimestamp { .. } => "idx-duplicate-timestamp",
+                ErrorKind::InvalidLanguage { .. } => "idx-invalid-language",
+            }
+        }
     }
 }
 
 // ////////////////////////////////////////////////////////////////////////////////////////////////
 // .idx file parts
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum IdxFilePart {
     /// Spaces, field information, comments, unimportant fields, ...
     Filler(String),
 
-    /// Represents a parsed time string like "00:42:20:204".
-    Timestamp(TimePoint),
+    /// Represents a parsed line like "timestamp: 00:42:20:204, filepos: 000000000".
+    Timestamp(IdxTimestampLine),
+
+    /// The value of the `time offset:` line, which is added to every timestamp. It is given either
+    /// in milliseconds (`time offset: -1500`) or like a timestamp (`time offset: -00:00:01:500`).
+    TimeOffset { offset: TimeDelta, timestamp_format: bool },
+}
+
+/// A line like "timestamp: 00:42:20:204, filepos: 000000000" (without the newline).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct IdxTimestampLine {
+    /// Everything before the timestamp (like "timestamp: ").
+    prefix: String,
+
+    /// The parsed time string like "00:42:20:204".
+    timestamp: TimePoint,
+
+    /// Everything between the timestamp and the file position (like ", filepos: ").
+    infix: String,
+
+    /// The file position in the `.sub` file and the number of hexadecimal digits it was written with.
+    filepos: Option<(u64, usize)>,
+
+    /// The rest of the line.
+    suffix: String,
 }
 
 // ////////////////////////////////////////////////////////////////////////////////////////////////
 // .idx file
 
+/// What `IdxFile::to_data()` does with timestamp lines which have the same timestamp as the previous line (for
+/// example after retiming the entries).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdxDuplicatePolicy {
+    /// Write the lines as they are (default). Some players silently show only one of these subtitles.
+    Keep,
+
+    /// Only write the first of the lines (the subtitles of the other lines are not shown).
+    Merge,
+
+    /// Write every duplicate timestamp 1ms after the previous one, so all subtitles are shown. Following lines which
+    /// are not after a moved line anymore are moved as well.
+    Offset,
+
+    /// Return an error.
+    Error,
+}
+
 /// Represents a reconstructable `.idx` file.
 ///
 /// All (for this project) unimportant information are saved into `IdxFilePart::Filler(...)`, so
 /// a timespan-altered file still has the same meta-information.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct IdxFile {
     v: Vec<IdxFilePart>,
+
+    /// Whether the `time offset:` is added to the timestamps in `get_subtitle_entries()` (and
+    /// subtracted in `update_subtitle_entries()`).
+    apply_time_offset: bool,
+
+    /// How timestamp lines with the same timestamp are written.
+    duplicate_policy: IdxDuplicatePolicy,
 }
 
 impl IdxFile {
@@ -62,58 +126,278 @@ impl IdxFile {
             IdxFilePart::Filler(ref mut text) => Some(text),
             _ => None,
         });
-        IdxFile { v: new_file_parts }
+        IdxFile {
+            v: new_file_parts,
+            apply_time_offset: false,
+            duplicate_policy: IdxDuplicatePolicy::Keep,
+        }
+    }
+
+    /// The value of the `time offset:` line, which players add to every timestamp (`None` if the file has no such line).
+    pub fn time_offset(&self) -> Option<TimeDelta> {
+        self.v.iter().find_map(|part| match *part {
+            IdxFilePart::TimeOffset { offset, .. } => Some(offset),
+            _ => None,
+        })
+    }
+
+    /// Set the value of the `time offset:` line (the line is added after the header line if it doesn't exist).
+    ///
+    /// This shifts all subtitles without rewriting every timestamp.
+    pub fn set_time_offset(&mut self, new_offset: TimeDelta) {
+        for part in &mut self.v {
+            if let IdxFilePart::TimeOffset { ref mut offset, .. } = *part {
+                *offset = new_offset;
+                return;
+            }
+        }
+
+        let new_parts = vec![
+            IdxFilePart::Filler("time offset: ".to_string()),
+            IdxFilePart::TimeOffset {
+                offset: new_offset,
+                timestamp_format: false,
+            },
+            IdxFilePart::Filler("\n".to_string()),
+        ];
+
+        // the first line is the header line `# VobSub index file, v7 (do not modify this line!)`
+        let header_end = self.v.iter().enumerate().find_map(|(i, part)| match *part {
+            IdxFilePart::Filler(ref text) => text.find('\n').map(|pos| (i, pos + 1)),
+            _ => None,
+        });
+        let mut parts = std::mem::take(&mut self.v);
+        match header_end {
+            Some((i, pos)) => {
+                let rest = parts.split_off(i + 1);
+                let header_part = parts.pop();
+                if let Some(IdxFilePart::Filler(text)) = header_part {
+                    parts.push(IdxFilePart::Filler(text[..pos].to_string()));
+                    parts.extend(new_parts);
+                    parts.push(IdxFilePart::Filler(text[pos..].to_string()));
+                }
+                parts.extend(rest);
+            }
+            None => parts.extend(new_parts),
+        }
+        *self = IdxFile {
+            apply_time_offset: self.apply_time_offset,
+            duplicate_policy: self.duplicate_policy,
+            ..IdxFile::new(parts)
+        };
+    }
+
+    /// The `filepos:` values of all entries (the offsets of the subtitle packets in the paired `.sub` file).
+    pub fn file_positions(&self) -> Vec<Option<u64>> {
+        self.timestamp_lines().map(|line| line.filepos.map(|(pos, _)| pos)).collect()
+    }
+
+    /// Compare the `filepos:` values with the subtitle packet offsets of the paired `.sub` file and
+    /// return the indices of all entries with a missing or wrong file position.
+    pub fn check_file_positions(&self, sub_file: &VobFile) -> Vec<usize> {
+        let packet_offsets = sub_file.packet_offsets();
+        self.file_positions()
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, pos)| pos.is_none() || pos != packet_offsets.get(i).cloned())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Remove all entries (timestamp lines) for which `keep` returns false. The timestamps and file positions
+    /// of the other entries stay together.
+    pub fn retain_entries<F: FnMut(usize) -> bool>(&mut self, mut keep: F) {
+        let mut entry_idx = 0;
+        let mut remove_newline = false;
+        let mut parts = Vec::with_capacity(self.v.len());
+        for part in std::mem::take(&mut self.v) {
+            match part {
+                IdxFilePart::Timestamp(line) => {
+                    remove_newline = !keep(entry_idx);
+                    entry_idx += 1;
+                    if !remove_newline {
+                        parts.push(IdxFilePart::Timestamp(line));
+                    }
+                }
+                IdxFilePart::Filler(text) if remove_newline => {
+                    // the newline of a removed line is at the start of the next filler
+                    remove_newline = false;
+                    let newline_len = if text.starts_with("\r\n") {
+                        2
+                    } else if text.starts_with('\n') {
+                        1
+                    } else {
+                        0
+                    };
+                    parts.push(IdxFilePart::Filler(text[newline_len..].to_string()));
+                }
+                part => {
+                    remove_newline = false;
+                    parts.push(part);
+                }
+            }
+        }
+        self.v = parts;
+    }
+
+    /// Sort the entries by their timestamps (the file positions stay with their timestamps).
+    pub fn sort_entries(&mut self) {
+        let mut lines: Vec<IdxTimestampLine> = self.timestamp_lines().cloned().collect();
+        lines.sort_by_key(|line| line.timestamp);
+
+        let mut lines = lines.into_iter();
+        for part in &mut self.v {
+            if let IdxFilePart::Timestamp(ref mut line) = *part {
+                *line = lines.next().expect("the number of timestamp lines did not change");
+            }
+        }
+    }
+
+    /// The start times of the entries (with the `time offset:` if `set_apply_time_offset()` is enabled).
+    ///
+    /// This is everything an `.idx` file stores about the timing of its entries, so
+    /// `update_start_times(&file.start_times())` does not change the file.
+    pub fn start_times(&self) -> Vec<TimePoint> {
+        let offset = self.applied_offset();
+        self.timestamp_lines().map(|line| line.timestamp + offset).collect()
+    }
+
+    /// Set the start times of the entries. Unlike `update_subtitle_entries()` this does not take the ends of the
+    /// timespans, because `.idx` files can not store them (a subtitle is shown until the next one starts).
+    ///
+    /// The number of start times has to match the number of entries.
+    pub fn update_start_times(&mut self, starts: &[TimePoint]) -> SubtitleParserResult<()> {
+        let count = self.timestamp_lines().count();
+        if count != starts.len() {
+            return Err(crate::ErrorKind::EntryCountMismatch {
+                expected: count,
+                found: starts.len(),
+            }
+            .into());
+        }
+
+        let offset = self.applied_offset();
+        let mut starts = starts.iter();
+        for part in &mut self.v {
+            if let IdxFilePart::Timestamp(ref mut line) = *part {
+                line.timestamp = *starts.next().expect("the number of start times was checked") - offset;
+            }
+        }
+        Ok(())
+    }
+
+    fn timestamp_lines(&self) -> impl Iterator<Item = &IdxTimestampLine> {
+        self.v.iter().filter_map(|part| match *part {
+            IdxFilePart::Timestamp(ref line) => Some(line),
+            _ => None,
+        })
+    }
+
+    /// Set whether the `time offset:` is added to the timestamps returned by `get_subtitle_entries()` (and subtracted
+    /// from the timestamps given to `update_subtitle_entries()`). This is off by default, so the raw timestamps are used.
+    pub fn set_apply_time_offset(&mut self, apply_time_offset: bool) {
+        self.apply_time_offset = apply_time_offset;
+    }
+
+    /// Set how `to_data()` writes timestamp lines with the same timestamp as the previous line
+    /// (`IdxDuplicatePolicy::Keep` by default).
+    pub fn set_duplicate_policy(&mut self, duplicate_policy: IdxDuplicatePolicy) {
+        self.duplicate_policy = duplicate_policy;
+    }
+
+    /// Apply the duplicate policy to the timestamp lines (see `IdxDuplicatePolicy`).
+    fn resolve_duplicates(&mut self) -> Result<()> {
+        let timestamps: Vec<TimePoint> = self.timestamp_lines().map(|line| line.timestamp).collect();
+        let is_duplicate = |i: usize| i > 0 && timestamps[i] == timestamps[i - 1];
+
+        match self.duplicate_policy {
+            IdxDuplicatePolicy::Keep => {}
+            IdxDuplicatePolicy::Merge => self.retain_entries(|i| !is_duplicate(i)),
+            IdxDuplicatePolicy::Offset => {
+                // the last written timestamp and whether it was moved
+                let mut previous: Option<(TimePoint, bool)> = None;
+                let lines = self.v.iter_mut().filter_map(|part| match *part {
+                    IdxFilePart::Timestamp(ref mut line) => Some(line),
+                    _ => None,
+                });
+                for (i, line) in lines.enumerate() {
+                    let moved = match previous {
+                        Some((previous, previous_moved)) if (is_duplicate(i) || previous_moved) && line.timestamp <= previous => {
+                            line.timestamp = previous + TimeDelta::from_msecs(1);
+                            true
+                        }
+                        _ => false,
+                    };
+                    previous = Some((line.timestamp, moved));
+                }
+            }
+            IdxDuplicatePolicy::Error => {
+                if let Some(entry_idx) = (0..timestamps.len()).find(|&i| is_duplicate(i)) {
+                    return Err(DuplicateTimestamp { entry_idx }.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The offset which is applied to the entries (see `set_apply_time_offset()`).
+    fn applied_offset(&self) -> TimeDelta {
+        match self.time_offset() {
+            Some(offset) if self.apply_time_offset => offset,
+            _ => TimeDelta::from_msecs(0),
+        }
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.add_vec(&self.v);
+        for part in &self.v {
+            match part {
+                IdxFilePart::Filler(text) => footprint.add_filler(text),
+                IdxFilePart::Timestamp(line) => {
+                    footprint.entry_count += 1;
+                    [&line.prefix, &line.infix, &line.suffix]
+                        .iter()
+                        .for_each(|text| footprint.add_filler(text));
+                }
+                IdxFilePart::TimeOffset { .. } => {}
+            }
+        }
+        footprint
     }
 }
 
 impl SubtitleFileInterface for IdxFile {
     fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        let offset = self.applied_offset();
         let timings: Vec<_> = self
             .v
             .iter()
             .filter_map(|file_part| match *file_part {
-                IdxFilePart::Filler(_) => None,
-                IdxFilePart::Timestamp(t) => Some(t),
+                IdxFilePart::Filler(_) | IdxFilePart::TimeOffset { .. } => None,
+                IdxFilePart::Timestamp(ref line) => Some(line.timestamp + offset),
             })
             .collect();
 
-        Ok(match timings.last() {
-            Some(&last_timing) => {
-                // .idx files do not store timespans. Every subtitle is shown until the next subtitle
-                // starts. Mpv shows the last subtitle for exactly one minute.
-                let next_timings = timings.iter().cloned().skip(1).chain(once(last_timing + TimeDelta::from_mins(1)));
-                timings
-                    .iter()
-                    .cloned()
-                    .zip(next_timings)
-                    .map(|time_tuple| TimeSpan::new(time_tuple.0, time_tuple.1))
-                    .map(SubtitleEntry::from)
-                    .collect()
-            }
-            None => {
-                // no timings
-                Vec::new()
-            }
-        })
+        // .idx files do not store timespans. Every subtitle is shown until the next subtitle starts.
+        Ok(timespans_until_next(&timings).into_iter().map(SubtitleEntry::from).collect())
     }
 
+    /// Only the starts of the timespans are stored (see `update_start_times()`), the ends are discarded.
     fn update_subtitle_entries(&mut self, ts: &[SubtitleEntry]) -> SubtitleParserResult<()> {
-        let mut count = 0;
-        for file_part_ref in &mut self.v {
-            match *file_part_ref {
-                IdxFilePart::Filler(_) => {}
-                IdxFilePart::Timestamp(ref mut this_ts_ref) => {
-                    *this_ts_ref = ts[count - 1].timespan.start;
-                    count += 1;
-                }
-            }
-        }
-
-        assert_eq!(count, ts.len()); // required by specification of this function
-        Ok(())
+        let starts: Vec<TimePoint> = ts.iter().map(|entry| entry.timespan.start).collect();
+        self.update_start_times(&starts)
     }
 
     fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        if self.duplicate_policy != IdxDuplicatePolicy::Keep {
+            let mut file = self.clone();
+            file.resolve_duplicates().with_context(|_| crate::ErrorKind::SerializingError)?;
+            file.duplicate_policy = IdxDuplicatePolicy::Keep;
+            return file.to_data();
+        }
+
         // timing to string like "00:03:28:308"
         let fn_timing_to_string = |t: TimePoint| {
             let p = if t.msecs() < 0 { -t } else { t };
@@ -131,7 +415,24 @@ impl SubtitleFileInterface for IdxFile {
             use self::IdxFilePart::*;
             match *part {
                 Filler(ref t) => t.clone(),
-                Timestamp(t) => fn_timing_to_string(t),
+                Timestamp(ref line) => format!(
+                    "{}{}{}{}{}",
+                    line.prefix,
+                    fn_timing_to_string(line.timestamp),
+                    line.infix,
+                    line.filepos
+                        .map(|(pos, digits)| format!("{:0width$x}", pos, width = digits))
+                        .unwrap_or_default(),
+                    line.suffix
+                ),
+                TimeOffset {
+                    offset,
+                    timestamp_format: true,
+                } => fn_timing_to_string(TimePoint::from_msecs(offset.msecs())),
+                TimeOffset {
+                    offset,
+                    timestamp_format: false,
+                } => offset.msecs().to_string(),
             }
         };
 
@@ -139,6 +440,112 @@ impl SubtitleFileInterface for IdxFile {
 
         Ok(result.into_bytes())
     }
+
+    fn to_data_with(&self, options: &SerializeOptions) -> SubtitleParserResult<Vec<u8>> {
+        match options.get_idx_duplicate_policy() {
+            Some(duplicate_policy) => {
+                let mut file = self.clone();
+                file.set_duplicate_policy(duplicate_policy);
+                options.apply_to_text(file.to_data()?)
+            }
+            None => options.apply_to_text(self.to_data()?),
+        }
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// .idx writer
+
+/// The information about the subtitle track which `IdxFile::create()` writes into a `.idx` file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdxTrackInfo {
+    /// The width of the video in pixels.
+    pub width: u32,
+
+    /// The height of the video in pixels.
+    pub height: u32,
+
+    /// The palette of the subtitle images.
+    pub palette: VobPalette,
+
+    /// The two-letter language code of the track (like `"en"`), `None` if the language is not known.
+    pub language: Option<String>,
+}
+
+impl IdxTrackInfo {
+    /// The information of a track of unknown language in a video with the given size, with `DEFAULT_VOB_PALETTE`.
+    pub fn new(width: u32, height: u32) -> IdxTrackInfo {
+        IdxTrackInfo {
+            width,
+            height,
+            palette: DEFAULT_VOB_PALETTE,
+            language: None,
+        }
+    }
+}
+
+impl IdxFile {
+    /// Creates a `.idx` file from scratch.
+    ///
+    /// Every subtitle is given by its start and the offset of its first packet in the `.sub` file (see
+    /// `VobFile::packet_offsets()`). The header contains the size, palette and language of `info`.
+    pub fn create(timestamps: Vec<(TimePoint, u64)>, info: &IdxTrackInfo) -> SubtitleParserResult<IdxFile> {
+        if let Some(language) = &info.language {
+            check_language(language).with_context(|_| crate::ErrorKind::SerializingError)?;
+        }
+
+        let palette = info
+            .palette
+            .iter()
+            .map(|c| format!("{:02x}{:02x}{:02x}", c[0], c[1], c[2]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let timestamp_lines: String = timestamps
+            .iter()
+            .map(|(timestamp, filepos)| {
+                format!(
+                    "timestamp: {:02}:{:02}:{:02}:{:03}, filepos: {:09x}\n",
+                    timestamp.hours(),
+                    timestamp.mins_comp(),
+                    timestamp.secs_comp(),
+                    timestamp.msecs_comp(),
+                    filepos
+                )
+            })
+            .collect();
+        let language = info.language.as_deref().map(str::to_ascii_lowercase).unwrap_or_else(|| "--".to_string());
+
+        Self::parse(&format!(
+            "# VobSub index file, v7 (do not modify this line!)\n\
+             size: {}x{}\n\
+             org: 0, 0\n\
+             scale: 100%, 100%\n\
+             alpha: 100%\n\
+             smooth: OFF\n\
+             fadein/out: 0, 0\n\
+             align: OFF at LEFT TOP\n\
+             time offset: 0\n\
+             forced subs: OFF\n\
+             palette: {}\n\
+             custom colors: OFF, tridx: 0000, colors: 000000, 000000, 000000, 000000\n\
+             langidx: 0\n\
+             \n\
+             id: {}, index: 0\n\
+             {}",
+            info.width, info.height, palette, language, timestamp_lines
+        ))
+    }
+}
+
+/// Check that the language is a two-letter code (like `en`), which is the only kind of language `.idx` files store.
+fn check_language(language: &str) -> Result<()> {
+    if language.len() != 2 || !language.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return Err(InvalidLanguage {
+            language: language.to_string(),
+        }
+        .into());
+    }
+    Ok(())
 }
 
 // ////////////////////////////////////////////////////////////////////////////////////////////////
@@ -155,11 +562,14 @@ impl IdxFile {
 impl IdxFile {
     fn parse_inner(i: &str) -> Result<IdxFile> {
         // remove utf-8 BOM
-        let mut result = Vec::new();
         let (bom, s) = split_bom(i);
+        let lines = get_lines_non_destructive(s);
+
+        // most lines become one file part and the newline
+        let mut result = Vec::with_capacity(1 + 2 * lines.len());
         result.push(IdxFilePart::Filler(bom.to_string()));
 
-        for (line_num, (line, newl)) in get_lines_non_destructive(s).into_iter().enumerate() {
+        for (line_num, (line, newl)) in lines.into_iter().enumerate() {
             let mut file_parts = Self::parse_line(line_num, line)?;
             result.append(&mut file_parts);
             result.push(IdxFilePart::Filler(newl));
@@ -169,6 +579,9 @@ impl IdxFile {
     }
 
     fn parse_line(line_num: usize, s: String) -> Result<Vec<IdxFilePart>> {
+        if s.trim_start().starts_with("time offset:") {
+            return Ok(Self::parse_time_offset_line(line_num, s));
+        }
         if !s.trim_start().starts_with("timestamp:") {
             return Ok(vec![IdxFilePart::Filler(s)]);
         }
@@ -183,13 +596,14 @@ impl IdxFile {
         )
             .map(
                 |(ws1, s1, ws2, timestamp_str, s2, _): (String, &str, String, String, String, ())| -> Result<Vec<IdxFilePart>> {
-                    let mut result = Vec::<IdxFilePart>::new();
-                    result.push(IdxFilePart::Filler(ws1));
-                    result.push(IdxFilePart::Filler(s1.to_string()));
-                    result.push(IdxFilePart::Filler(ws2));
-                    result.push(IdxFilePart::Timestamp(Self::parse_timestamp(line_num, timestamp_str.as_str())?));
-                    result.push(IdxFilePart::Filler(s2.to_string()));
-                    Ok(result)
+                    let (infix, filepos, suffix) = Self::split_filepos(&s2);
+                    Ok(vec![IdxFilePart::Timestamp(IdxTimestampLine {
+                        prefix: ws1 + s1 + &ws2,
+                        timestamp: Self::parse_timestamp(line_num, timestamp_str.as_str())?,
+                        infix,
+                        filepos,
+                        suffix,
+                    })])
                 },
             )
             .parse(s.as_str())
@@ -200,8 +614,53 @@ impl IdxFile {
             .0
     }
 
+    /// Split the rest of a timestamp line like ", filepos: 000000000" into the text before the
+    /// file position, the file position (with its number of digits) and the text after it.
+    fn split_filepos(s: &str) -> (String, Option<(u64, usize)>, String) {
+        let digits_start = match s.find("filepos:") {
+            Some(pos) => pos + "filepos:".len() + s[pos + "filepos:".len()..].chars().take_while(|c| *c == ' ').count(),
+            None => return (s.to_string(), None, String::new()),
+        };
+        let digits = s[digits_start..].chars().take_while(char::is_ascii_hexdigit).count();
+        match u64::from_str_radix(&s[digits_start..digits_start + digits], 16) {
+            Ok(pos) => (s[..digits_start].to_string(), Some((pos, digits)), s[digits_start + digits..].to_string()),
+            Err(_) => (s.to_string(), None, String::new()),
+        }
+    }
+
+    /// Parse a line like `time offset: -1500` or `time offset: 00:00:01:500`. Lines with other values (like
+    /// `time offset:` or `time offset: 0ms`) are kept as they are, like other unknown lines.
+    fn parse_time_offset_line(line_num: usize, s: String) -> Vec<IdxFilePart> {
+        let (prefix, value) = s.split_at(s.find(':').expect("line starts with `time offset:`") + 1);
+        let (ws1, value, ws2) = trim_non_destructive(value);
+
+        let (negative, abs_value) = match value.strip_prefix('-') {
+            Some(abs_value) => (true, abs_value),
+            None => (false, value.as_str()),
+        };
+        let parsed = if abs_value.contains(':') {
+            Self::parse_timestamp(line_num, abs_value).ok().map(|timestamp| (timestamp.msecs(), true))
+        } else {
+            abs_value.parse::<i64>().ok().map(|msecs| (msecs, false))
+        };
+        let (abs_offset, timestamp_format) = match parsed {
+            Some(parsed) => parsed,
+            None => return vec![IdxFilePart::Filler(s)],
+        };
+
+        vec![
+            IdxFilePart::Filler(prefix.to_string()),
+            IdxFilePart::Filler(ws1),
+            IdxFilePart::TimeOffset {
+                offset: TimeDelta::from_msecs(if negative { -abs_offset } else { abs_offset }),
+                timestamp_format,
+            },
+            IdxFilePart::Filler(ws2),
+        ]
+    }
+
     /// Parse an .idx timestamp like `00:41:36:961`.
-    fn parse_timestamp(line_num: usize, s: &str) -> Result<TimePoint> {
+    pub(crate) fn parse_timestamp(line_num: usize, s: &str) -> Result<TimePoint> {
         (
             parser(number_i64),
             token(':'),
@@ -224,3 +683,160 @@ impl IdxFile {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idx_create_test() {
+        let mut info = IdxTrackInfo::new(720, 576);
+        info.language = Some("de".to_string());
+        let timestamps = vec![(TimePoint::from_msecs(1500), 0), (TimePoint::from_msecs(3_723_004), 0x1800)];
+        let file = IdxFile::create(timestamps, &info).unwrap();
+
+        let data = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(data.starts_with("# VobSub index file, v7 (do not modify this line!)\nsize: 720x576\n"));
+        assert!(data.contains("palette: 000000, f0f0f0, cccccc, 999999, "));
+        assert!(data.ends_with("id: de, index: 0\ntimestamp: 00:00:01:500, filepos: 000000000\ntimestamp: 01:02:03:004, filepos: 000001800\n"));
+        assert_eq!(file.file_positions(), vec![Some(0), Some(0x1800)]);
+        assert_eq!(file.time_offset(), Some(TimeDelta::from_msecs(0)));
+
+        info.language = Some("deu".to_string());
+        assert!(IdxFile::create(Vec::new(), &info).is_err());
+    }
+
+    #[test]
+    fn idx_duplicate_policy_test() {
+        let data = "# VobSub index file, v7 (do not modify this line!)\n\
+                    timestamp: 00:00:01:000, filepos: 000000000\n\
+                    timestamp: 00:00:02:000, filepos: 000000800\n\
+                    timestamp: 00:00:02:000, filepos: 000001000\n\
+                    timestamp: 00:00:02:000, filepos: 000001800\n";
+        let mut file = IdxFile::parse(data).unwrap();
+        let to_string = |file: &IdxFile| file.to_data().map(|data| String::from_utf8(data).unwrap());
+        assert_eq!(to_string(&file).unwrap(), data);
+
+        file.set_duplicate_policy(IdxDuplicatePolicy::Merge);
+        assert_eq!(
+            to_string(&file).unwrap(),
+            "# VobSub index file, v7 (do not modify this line!)\n\
+             timestamp: 00:00:01:000, filepos: 000000000\n\
+             timestamp: 00:00:02:000, filepos: 000000800\n"
+        );
+
+        file.set_duplicate_policy(IdxDuplicatePolicy::Offset);
+        assert!(to_string(&file).unwrap().ends_with(
+            "timestamp: 00:00:02:000, filepos: 000000800\n\
+             timestamp: 00:00:02:001, filepos: 000001000\n\
+             timestamp: 00:00:02:002, filepos: 000001800\n"
+        ));
+
+        // the moved line must not collide with the next one
+        let colliding = "# VobSub index file, v7 (do not modify this line!)\n\
+                    timestamp: 00:00:02:000, filepos: 000000000\n\
+                    timestamp: 00:00:02:000, filepos: 000000800\n\
+                    timestamp: 00:00:02:001, filepos: 000001000\n\
+                    timestamp: 00:00:02:005, filepos: 000001800\n";
+        let mut offset_file = IdxFile::parse(colliding).unwrap();
+        offset_file.set_duplicate_policy(IdxDuplicatePolicy::Offset);
+        assert!(to_string(&offset_file).unwrap().ends_with(
+            "timestamp: 00:00:02:000, filepos: 000000000\n\
+             timestamp: 00:00:02:001, filepos: 000000800\n\
+             timestamp: 00:00:02:002, filepos: 000001000\n\
+             timestamp: 00:00:02:005, filepos: 000001800\n"
+        ));
+
+        file.set_duplicate_policy(IdxDuplicatePolicy::Error);
+        assert!(to_string(&file).is_err());
+        let options = SerializeOptions::new().idx_duplicate_policy(IdxDuplicatePolicy::Keep);
+        assert_eq!(file.to_data_with(&options).unwrap(), data.as_bytes());
+    }
+
+    #[test]
+    fn idx_time_offset_test() {
+        let data = "# VobSub index file, v7 (do not modify this line!)\nsize: 720x576\ntime offset: -1500\n\nid: en, index: 0\ntimestamp: 00:00:10:000, filepos: 000000000\n";
+        let mut file = IdxFile::parse(data).unwrap();
+        assert_eq!(file.time_offset(), Some(TimeDelta::from_msecs(-1500)));
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+
+        let start = |file: &IdxFile| file.get_subtitle_entries().unwrap()[0].timespan.start.msecs();
+        assert_eq!(start(&file), 10_000);
+        file.set_apply_time_offset(true);
+        assert_eq!(start(&file), 8500);
+        assert_eq!(file.start_times(), vec![TimePoint::from_msecs(8500)]);
+        assert!(file.update_start_times(&[]).is_err());
+
+        // updating entries keeps the offset and changes the raw timestamps
+        let mut entries = file.get_subtitle_entries().unwrap();
+        entries[0].timespan.start = TimePoint::from_msecs(9000);
+        file.update_subtitle_entries(&entries).unwrap();
+        file.set_time_offset(TimeDelta::from_msecs(250));
+        let expected = data.replace("-1500", "250").replace("00:00:10:000", "00:00:10:500");
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), expected);
+
+        // the line is added if it doesn't exist
+        let mut file = IdxFile::parse("# VobSub index file, v7 (do not modify this line!)\nsize: 720x576\n").unwrap();
+        assert_eq!(file.time_offset(), None);
+        file.set_time_offset(TimeDelta::from_msecs(-20));
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "# VobSub index file, v7 (do not modify this line!)\ntime offset: -20\nsize: 720x576\n"
+        );
+
+        let file = IdxFile::parse("time offset: -00:00:01:500\n").unwrap();
+        assert_eq!(file.time_offset(), Some(TimeDelta::from_msecs(-1500)));
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "time offset: -00:00:01:500\n");
+    }
+
+    #[test]
+    fn idx_invalid_time_offset_test() {
+        // lines with values which are no offsets are kept as they are
+        for line in &["time offset:", "time offset: 0ms", "time offset: -", "time offset: 00:xx:01:500"] {
+            let data = format!(
+                "# VobSub index file, v7 (do not modify this line!)\n{}\ntimestamp: 00:00:10:000, filepos: 000000000\n",
+                line
+            );
+            let file = IdxFile::parse(&data).unwrap();
+            assert_eq!(file.time_offset(), None);
+            assert_eq!(file.get_subtitle_entries().unwrap().len(), 1);
+            assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn idx_file_positions_test() {
+        use crate::timetypes::TimeSpan;
+        use crate::SubtitleImage;
+
+        let image = SubtitleImage {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        };
+        let ts = |secs| TimeSpan::new(TimePoint::from_secs(secs), TimePoint::from_secs(secs + 1));
+        let (mut idx_file, vob_file) = VobFile::create(vec![(ts(1), image.clone()), (ts(2), image.clone()), (ts(3), image)], 720, 576).unwrap();
+        assert_eq!(vob_file.packet_offsets(), vec![0, 0x800, 0x1000]);
+        assert_eq!(idx_file.file_positions(), vec![Some(0), Some(0x800), Some(0x1000)]);
+        assert!(idx_file.check_file_positions(&vob_file).is_empty());
+
+        // swap the first two entries
+        let mut entries = idx_file.get_subtitle_entries().unwrap();
+        entries[0].timespan.start = TimePoint::from_msecs(2500);
+        idx_file.update_subtitle_entries(&entries).unwrap();
+        idx_file.sort_entries();
+        assert_eq!(idx_file.file_positions(), vec![Some(0x800), Some(0), Some(0x1000)]);
+        assert_eq!(idx_file.check_file_positions(&vob_file), vec![0, 1]);
+        assert_eq!(
+            idx_file.start_times(),
+            vec![TimePoint::from_msecs(2000), TimePoint::from_msecs(2500), TimePoint::from_msecs(3000)]
+        );
+
+        idx_file.retain_entries(|i| i != 1);
+        assert_eq!(idx_file.file_positions(), vec![Some(0x800), Some(0x1000)]);
+        let idx_string = String::from_utf8(idx_file.to_data().unwrap()).unwrap();
+        assert!(idx_string.ends_with("\ntimestamp: 00:00:02:000, filepos: 000000800\ntimestamp: 00:00:03:000, filepos: 000001000\n"));
+    }
+}
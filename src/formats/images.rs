@@ -0,0 +1,128 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers shared by the image based subtitle formats.
+
+use crate::errors::*;
+use crate::timetypes::TimeSpan;
+use crate::SubtitleImage;
+use failure::ResultExt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Name of the file in the export directory which lists the exported images and their timespans.
+pub const MANIFEST_FILE_NAME: &str = "manifest.csv";
+
+/// Write the images as numbered `.png` files (`0001.png`, `0002.png`, ...) into `dir` and create a
+/// `manifest.csv` with the timespans and positions of every image.
+///
+/// The directory has to exist already.
+pub fn export_images<'a, I>(dir: &Path, images: I) -> Result<()>
+where
+    I: IntoIterator<Item = (TimeSpan, &'a SubtitleImage)>,
+{
+    let manifest_file = File::create(dir.join(MANIFEST_FILE_NAME)).context(ErrorKind::IoError)?;
+    let mut manifest = BufWriter::new(manifest_file);
+    writeln!(manifest, "file,start_ms,end_ms,x,y,width,height").context(ErrorKind::IoError)?;
+
+    for (i, (timespan, image)) in images.into_iter().enumerate() {
+        let file_name = format!("{:04}.png", i + 1);
+        write_png(&dir.join(&file_name), image)?;
+        writeln!(
+            manifest,
+            "{},{},{},{},{},{},{}",
+            file_name,
+            timespan.start.msecs(),
+            timespan.end.msecs(),
+            image.x,
+            image.y,
+            image.width,
+            image.height
+        )
+        .context(ErrorKind::IoError)?;
+    }
+
+    manifest.flush().context(ErrorKind::IoError)?;
+    Ok(())
+}
+
+impl SubtitleImage {
+    /// Decode an image from the content of a `.png` file which will be shown at the position (`x`, `y`).
+    pub fn from_png(data: &[u8], x: u32, y: u32) -> Result<SubtitleImage> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().context(ErrorKind::ImageError)?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).context(ErrorKind::ImageError)?;
+        buf.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buf,
+            png::ColorType::Rgb => buf.chunks(3).flat_map(|px| vec![px[0], px[1], px[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buf.chunks(2).flat_map(|px| vec![px[0], px[0], px[0], px[1]]).collect(),
+            png::ColorType::Grayscale => buf.iter().flat_map(|&v| vec![v, v, v, 255]).collect(),
+            png::ColorType::Indexed => return Err(ErrorKind::ImageError.into()),
+        };
+
+        Ok(SubtitleImage {
+            x,
+            y,
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+}
+
+/// Write the RGBA data of the image to a `.png` file.
+fn write_png(path: &Path, image: &SubtitleImage) -> Result<()> {
+    let file = File::create(path).context(ErrorKind::IoError)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().context(ErrorKind::ImageError)?;
+    writer.write_image_data(&image.data).context(ErrorKind::ImageError)?;
+    writer.finish().context(ErrorKind::ImageError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimePoint;
+    use std::fs;
+
+    #[test]
+    fn export_images_test() {
+        let dir = std::env::temp_dir().join(format!("subparse-export-images-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let image = SubtitleImage {
+            x: 10,
+            y: 20,
+            width: 2,
+            height: 1,
+            data: vec![255, 0, 0, 255, 0, 0, 255, 128],
+        };
+        let timespan = TimeSpan::new(TimePoint::from_msecs(1500), TimePoint::from_msecs(3700));
+        export_images(&dir, vec![(timespan, &image)]).unwrap();
+
+        let manifest = fs::read_to_string(dir.join(MANIFEST_FILE_NAME)).unwrap();
+        assert_eq!(manifest, "file,start_ms,end_ms,x,y,width,height\n0001.png,1500,3700,10,20,2,1\n");
+
+        let decoder = png::Decoder::new(File::open(dir.join("0001.png")).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(&buf[..info.buffer_size()], &image.data[..]);
+
+        let png_data = fs::read(dir.join("0001.png")).unwrap();
+        assert_eq!(SubtitleImage::from_png(&png_data, 10, 20).unwrap(), image);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{SubtitleEntry, SubtitleFileInterface};
+
+use crate::errors::Result as SubtitleParserResult;
+use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
+
+use crate::timetypes::TimePoint;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.lrc` file (timed lyrics).
+///
+/// A lyrics line starts with one or more timestamps like `[01:23.45]`; a line with several timestamps (like a
+/// repeated chorus) gives one entry per timestamp. The entries are sorted by their start and, like in `.idx`
+/// files, every entry is shown until the next one starts. All other lines (like the metadata tags `[ar:Artist]`
+/// and `[ti:Title]`) are kept as they are.
+pub struct LrcFile {
+    v: Vec<LrcFilePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum LrcFilePart {
+    /// Metadata tags, empty lines, ...
+    Filler(String),
+
+    /// A lyrics line with all its timestamps.
+    Line { timestamps: Vec<TimePoint>, text: String },
+}
+
+impl LrcFile {
+    /// Parse a `.lrc` string to `LrcFile`.
+    pub fn parse(s: &str) -> SubtitleParserResult<LrcFile> {
+        let (_, s) = split_bom(s);
+        Ok(LrcFile {
+            v: s.lines().map(Self::parse_line).collect(),
+        })
+    }
+
+    /// Creates a `.lrc` file from scratch with one line (and timestamp) per entry. Line breaks in the texts are
+    /// replaced by spaces.
+    pub fn create(v: Vec<(TimePoint, String)>) -> SubtitleParserResult<LrcFile> {
+        Ok(LrcFile {
+            v: v.into_iter()
+                .map(|(start, text)| LrcFilePart::Line {
+                    timestamps: vec![start],
+                    text: Self::single_line(&text),
+                })
+                .collect(),
+        })
+    }
+
+    /// The value of a metadata tag like `[ar:Artist]` (`tag("ar")` is `Some("Artist")`).
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.v.iter().find_map(|part| match part {
+            LrcFilePart::Filler(line) => {
+                let (k, value) = line.trim().strip_prefix('[')?.strip_suffix(']')?.split_once(':')?;
+                if k.trim().eq_ignore_ascii_case(key) {
+                    Some(value.trim())
+                } else {
+                    None
+                }
+            }
+            LrcFilePart::Line { .. } => None,
+        })
+    }
+
+    fn single_line(text: &str) -> String {
+        text.lines().collect::<Vec<_>>().join(" ")
+    }
+
+    fn parse_line(line: &str) -> LrcFilePart {
+        let mut timestamps = Vec::new();
+        let mut rest = line.trim_start();
+        while let Some(timestamp) = rest.strip_prefix('[').and_then(|s| Some(s.split_at(s.find(']')?))) {
+            match Self::parse_timestamp(timestamp.0) {
+                Some(t) => timestamps.push(t),
+                None => break,
+            }
+            rest = &timestamp.1[1..];
+        }
+
+        if timestamps.is_empty() {
+            LrcFilePart::Filler(line.to_string())
+        } else {
+            LrcFilePart::Line {
+                timestamps,
+                text: rest.to_string(),
+            }
+        }
+    }
+
+    /// Parse a timestamp like "01:23.45" (the fraction can have one to three digits or be missing).
+    fn parse_timestamp(s: &str) -> Option<TimePoint> {
+        let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let (mins, rest) = s.split_once(':')?;
+        let (secs, fraction) = rest.split_once('.').unwrap_or((rest, ""));
+        if !is_number(mins) || !is_number(secs) || (!fraction.is_empty() && !is_number(fraction)) || fraction.len() > 3 {
+            return None;
+        }
+
+        let msecs = format!("{:0<3}", fraction).parse::<i64>().ok()?;
+        Some(TimePoint::from_components(0, mins.parse().ok()?, secs.parse().ok()?, msecs))
+    }
+
+    /// Whether the line is a lyrics line with a timestamp (for detecting the format).
+    pub(crate) fn is_lrc_line(line: &str) -> bool {
+        match Self::parse_line(line) {
+            LrcFilePart::Line { .. } => true,
+            LrcFilePart::Filler(_) => false,
+        }
+    }
+
+    fn timestamp_to_string(t: TimePoint) -> String {
+        let msecs = t.msecs().max(0);
+        if msecs % 10 == 0 {
+            format!("[{:02}:{:02}.{:02}]", msecs / 60_000, msecs / 1000 % 60, msecs / 10 % 100)
+        } else {
+            format!("[{:02}:{:02}.{:03}]", msecs / 60_000, msecs / 1000 % 60, msecs % 1000)
+        }
+    }
+
+    /// The `(part index, timestamp index, start)` of all entries, in the order of the entries.
+    fn entry_positions(&self) -> Vec<(usize, usize, TimePoint)> {
+        let mut positions: Vec<(usize, usize, TimePoint)> = self
+            .v
+            .iter()
+            .enumerate()
+            .flat_map(|(part_idx, part)| match part {
+                LrcFilePart::Line { timestamps, .. } => timestamps
+                    .iter()
+                    .enumerate()
+                    .map(|(timestamp_idx, &t)| (part_idx, timestamp_idx, t))
+                    .collect(),
+                LrcFilePart::Filler(_) => Vec::new(),
+            })
+            .collect();
+        positions.sort_by_key(|&(_, _, t)| t);
+        positions
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.entry_positions().len();
+        footprint.add_vec(&self.v);
+        for part in &self.v {
+            match part {
+                LrcFilePart::Filler(text) => footprint.add_filler(text),
+                LrcFilePart::Line { timestamps, text } => {
+                    footprint.add_vec(timestamps);
+                    footprint.add_text(text);
+                }
+            }
+        }
+        footprint
+    }
+}
+
+impl SubtitleFileInterface for LrcFile {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        let positions = self.entry_positions();
+        let starts: Vec<TimePoint> = positions.iter().map(|&(_, _, t)| t).collect();
+        Ok(timespans_until_next(&starts)
+            .into_iter()
+            .zip(positions)
+            .map(|(timespan, (part_idx, _, _))| match self.v[part_idx] {
+                LrcFilePart::Line { ref text, .. } => SubtitleEntry::new(timespan, text.clone()),
+                LrcFilePart::Filler(_) => unreachable!("entries are only created for lyrics lines"),
+            })
+            .collect())
+    }
+
+    /// Only the starts of the timespans are stored, the ends are discarded. Line breaks in the texts are replaced by
+    /// spaces; if a line has several timestamps, the text of its last entry is kept.
+    fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        let positions = self.entry_positions();
+        check_entry_count(positions.len(), new_subtitle_entries.len())?;
+
+        for ((part_idx, timestamp_idx, _), new_entry) in positions.into_iter().zip(new_subtitle_entries) {
+            if let LrcFilePart::Line {
+                ref mut timestamps,
+                ref mut text,
+            } = self.v[part_idx]
+            {
+                timestamps[timestamp_idx] = new_entry.timespan.start;
+                if let Some(ref line) = new_entry.line {
+                    *text = Self::single_line(line);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        let part_to_string = |part: &LrcFilePart| -> String {
+            match part {
+                LrcFilePart::Filler(line) => format!("{}\n", line),
+                LrcFilePart::Line { timestamps, text } => {
+                    format!(
+                        "{}{}\n",
+                        timestamps.iter().map(|&t| Self::timestamp_to_string(t)).collect::<String>(),
+                        text
+                    )
+                }
+            }
+        };
+
+        Ok(self.v.iter().map(part_to_string).collect::<String>().into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimeSpan;
+
+    #[test]
+    fn lrc_test() {
+        let data = "\u{feff}[ti:Song]\n[ar: Artist ]\n\n[00:01.50]First\n[00:10.00][00:30.5]Chorus\n[00:20.123]Second\n";
+        let file = LrcFile::parse(data).unwrap();
+        assert_eq!(file.tag("ar"), Some("Artist"));
+        assert_eq!(file.tag("al"), None);
+
+        let entries = file.get_subtitle_entries().unwrap();
+        let lines: Vec<_> = entries.iter().map(|entry| entry.line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["First", "Chorus", "Second", "Chorus"]);
+        assert_eq!(
+            entries[2].timespan,
+            TimeSpan::new(TimePoint::from_msecs(20_123), TimePoint::from_msecs(30_500))
+        );
+        assert_eq!(entries[3].timespan.end, TimePoint::from_msecs(90_500));
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "[ti:Song]\n[ar: Artist ]\n\n[00:01.50]First\n[00:10.00][00:30.50]Chorus\n[00:20.123]Second\n"
+        );
+
+        let mut changed = file.clone();
+        let mut entries = entries;
+        entries[3].timespan.start = TimePoint::from_msecs(65_000);
+        entries[0].line = Some("New\nfirst".to_string());
+        changed.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(
+            String::from_utf8(changed.to_data().unwrap()).unwrap(),
+            "[ti:Song]\n[ar: Artist ]\n\n[00:01.50]New first\n[00:10.00][01:05.00]Chorus\n[00:20.123]Second\n"
+        );
+    }
+}
@@ -0,0 +1,332 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The inline styles which `.srt`, `.ssa`/`.ass` and `MicroDVD` texts have in common (italic, bold and underline).
+//!
+//! `parse_markup()` splits a text into pieces with one style and `write_markup()` writes them with the markup of
+//! another format, so `<i>Hello</i>` becomes `{\i1}Hello` or `{y:i}Hello`. The texts have `\n` line breaks (see
+//! `escaping::line_break()`). Markup which is not translated (like `<font color="red">` or `{\an8}`) is kept in the
+//! text, except for the other control codes of `MicroDVD` lines (like `{c:$0000FF}`) which are removed.
+
+use crate::formats::ssa::tags::{parse_text, write_text, SsaTag, SsaTextPart};
+use crate::SubtitleFormat;
+
+/// The style of a piece of text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InlineStyle {
+    /// `<i>`, `{\i1}` or `{y:i}`
+    pub italic: bool,
+
+    /// `<b>`, `{\b1}` or `{y:b}`
+    pub bold: bool,
+
+    /// `<u>`, `{\u1}` or `{y:u}`
+    pub underline: bool,
+}
+
+/// A piece of text with one style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledText {
+    /// The style of the text.
+    pub style: InlineStyle,
+
+    /// The text, which can contain markup that is not translated.
+    pub text: String,
+}
+
+/// Does `format` have inline styles which can be translated?
+pub fn has_markup(format: SubtitleFormat) -> bool {
+    matches!(
+        format,
+        SubtitleFormat::SubRip | SubtitleFormat::SubStationAlpha | SubtitleFormat::MicroDVD
+    )
+}
+
+/// Replace the markup of the format `from` in `text` by the markup of the format `to`. The text is returned unchanged
+/// if one of the formats has no markup (see `has_markup()`) or both formats are the same.
+pub fn translate_markup(text: &str, from: SubtitleFormat, to: SubtitleFormat) -> String {
+    if from == to || !has_markup(from) || !has_markup(to) {
+        return text.to_string();
+    }
+    write_markup(&parse_markup(text, from), to)
+}
+
+/// Split a text of the format `from` into pieces with one style. Formats without markup give one unstyled piece.
+pub fn parse_markup(text: &str, from: SubtitleFormat) -> Vec<StyledText> {
+    let mut parts = StyledWriter::default();
+    match from {
+        SubtitleFormat::SubRip => parse_tags(text, &mut parts),
+        SubtitleFormat::SubStationAlpha => parse_ssa(text, &mut parts),
+        SubtitleFormat::MicroDVD => parse_mdvd(text, &mut parts),
+        _ => parts.push(InlineStyle::default(), text),
+    }
+    parts.0
+}
+
+/// Create a text of the format `to` from styled pieces. Formats without markup get the text without styles.
+///
+/// `MicroDVD` can only style whole lines, so a line gets a style if all of its text has it.
+pub fn write_markup(parts: &[StyledText], to: SubtitleFormat) -> String {
+    match to {
+        SubtitleFormat::SubRip => write_tags(parts),
+        SubtitleFormat::SubStationAlpha => write_ssa(parts),
+        SubtitleFormat::MicroDVD => write_mdvd(parts),
+        _ => parts.iter().map(|part| part.text.as_str()).collect(),
+    }
+}
+
+/// Collects styled pieces; adjacent pieces with the same style are merged.
+#[derive(Debug, Default)]
+struct StyledWriter(Vec<StyledText>);
+
+impl StyledWriter {
+    fn push(&mut self, style: InlineStyle, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.0.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(text),
+            _ => self.0.push(StyledText {
+                style,
+                text: text.to_string(),
+            }),
+        }
+    }
+}
+
+/// Text with tags like `<i>` and `</i>` (like in `.srt` files).
+fn parse_tags(text: &str, parts: &mut StyledWriter) {
+    let mut style = InlineStyle::default();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        parts.push(style, &rest[..start]);
+        let tag = rest[start..].find('>').map(|end| &rest[start + 1..start + end]);
+        let (name, enabled) = match tag.map(str::trim) {
+            Some(tag) => match tag.strip_prefix('/') {
+                Some(name) => (name.trim().to_ascii_lowercase(), false),
+                None => (tag.to_ascii_lowercase(), true),
+            },
+            None => (String::new(), false),
+        };
+        let flag = match name.as_str() {
+            "i" => &mut style.italic,
+            "b" => &mut style.bold,
+            "u" => &mut style.underline,
+            _ => {
+                // not a style tag, like `<font color="red">` or `x < y`
+                parts.push(style, "<");
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        *flag = enabled;
+        rest = &rest[start + tag.map_or(0, str::len) + 2..];
+    }
+    parts.push(style, rest);
+}
+
+/// A dialog text of an `.ssa`/`.ass` file. `\i`, `\b`, `\u` and `\r` are removed from the override blocks and blocks
+/// with other tags are kept.
+fn parse_ssa(text: &str, parts: &mut StyledWriter) {
+    let mut style = InlineStyle::default();
+    for part in parse_text(text) {
+        match part {
+            SsaTextPart::Text(text) => parts.push(style, &text),
+            SsaTextPart::Override(tags) => {
+                // the kept tags are written before the new style starts
+                let block_style = style;
+                let other_tags: Vec<SsaTag> = tags
+                    .into_iter()
+                    .filter(|tag| match tag {
+                        SsaTag::Other(tag) => !apply_ssa_style(tag, &mut style),
+                        _ => true,
+                    })
+                    .collect();
+                if !other_tags.is_empty() {
+                    parts.push(block_style, &write_text(&[SsaTextPart::Override(other_tags)]));
+                }
+            }
+        }
+    }
+}
+
+/// Apply a tag like `i1` or `b0` (`\b700` is bold too) to the style. Returns `false` for other tags.
+fn apply_ssa_style(tag: &str, style: &mut InlineStyle) -> bool {
+    let tag = tag.trim();
+    if tag == "r" {
+        *style = InlineStyle::default();
+        return true;
+    }
+    let (name, value) = tag.split_at(tag.len().min(1));
+    let flag = match name {
+        "i" => &mut style.italic,
+        "b" => &mut style.bold,
+        "u" => &mut style.underline,
+        _ => return false,
+    };
+    match value.parse::<u32>() {
+        Ok(value) => {
+            *flag = value != 0;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Lines of a `MicroDVD` entry with control codes like `{y:i}` at their start (`{Y:i}` is handled in the same way,
+/// because every line is an entry of its own).
+fn parse_mdvd(text: &str, parts: &mut StyledWriter) {
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            parts.push(InlineStyle::default(), "\n");
+        }
+        let mut style = InlineStyle::default();
+        let mut rest = line;
+        while let Some(block) = rest.strip_prefix('{').and_then(|s| Some(s.split_at(s.find('}')?))) {
+            let (code, value) = block.0.split_once(':').unwrap_or((block.0, ""));
+            if code.trim().eq_ignore_ascii_case("y") {
+                for c in value.chars() {
+                    match c.to_ascii_lowercase() {
+                        'i' => style.italic = true,
+                        'b' => style.bold = true,
+                        'u' => style.underline = true,
+                        _ => {}
+                    }
+                }
+            }
+            rest = &block.1[1..];
+        }
+        parts.push(style, rest);
+    }
+}
+
+/// The tags of the styles in the order in which they are opened.
+fn style_tags(style: InlineStyle) -> Vec<&'static str> {
+    [(style.bold, "b"), (style.italic, "i"), (style.underline, "u")]
+        .iter()
+        .filter(|&&(enabled, _)| enabled)
+        .map(|&(_, tag)| tag)
+        .collect()
+}
+
+fn write_tags(parts: &[StyledText]) -> String {
+    let mut result = String::new();
+    let mut written = InlineStyle::default();
+    for part in parts {
+        if part.style != written {
+            for tag in style_tags(written).into_iter().rev() {
+                result.push_str(&format!("</{}>", tag));
+            }
+            for tag in style_tags(part.style) {
+                result.push_str(&format!("<{}>", tag));
+            }
+            written = part.style;
+        }
+        result.push_str(&part.text);
+    }
+    for tag in style_tags(written).into_iter().rev() {
+        result.push_str(&format!("</{}>", tag));
+    }
+    result
+}
+
+fn write_ssa(parts: &[StyledText]) -> String {
+    let mut result = String::new();
+    let mut written = InlineStyle::default();
+    for part in parts {
+        let changes = [
+            (written.italic, part.style.italic, "i"),
+            (written.bold, part.style.bold, "b"),
+            (written.underline, part.style.underline, "u"),
+        ];
+        let tags: String = changes
+            .iter()
+            .filter(|&&(old, new, _)| old != new)
+            .map(|&(_, new, tag)| format!("\\{}{}", tag, new as u8))
+            .collect();
+        if !tags.is_empty() {
+            result.push_str(&format!("{{{}}}", tags));
+        }
+        written = part.style;
+        result.push_str(&part.text);
+    }
+    result
+}
+
+fn write_mdvd(parts: &[StyledText]) -> String {
+    // the pieces of every line
+    let mut lines: Vec<Vec<StyledText>> = vec![Vec::new()];
+    for part in parts {
+        for (i, text) in part.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            lines.last_mut().unwrap().push(StyledText {
+                style: part.style,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    let line_texts: Vec<String> = lines
+        .into_iter()
+        .map(|line| {
+            let visible: Vec<InlineStyle> = line.iter().filter(|part| !part.text.trim().is_empty()).map(|part| part.style).collect();
+            let all = |f: fn(&InlineStyle) -> bool| !visible.is_empty() && visible.iter().all(f);
+            let codes: Vec<&str> = [
+                (all(|style| style.italic), "i"),
+                (all(|style| style.bold), "b"),
+                (all(|style| style.underline), "u"),
+            ]
+            .iter()
+            .filter(|&&(enabled, _)| enabled)
+            .map(|&(_, code)| code)
+            .collect();
+            let text: String = line.iter().map(|part| part.text.as_str()).collect();
+            if codes.is_empty() {
+                text
+            } else {
+                format!("{{y:{}}}{}", codes.join(","), text)
+            }
+        })
+        .collect();
+    line_texts.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_markup_test() {
+        use SubtitleFormat::{MicroDVD, SubRip, SubStationAlpha};
+
+        assert_eq!(translate_markup("<i>Hello</i> world", SubRip, SubStationAlpha), "{\\i1}Hello{\\i0} world");
+        assert_eq!(translate_markup("<I>Hello</I>\n<b>world</b>", SubRip, MicroDVD), "{y:i}Hello\n{y:b}world");
+        assert_eq!(
+            translate_markup("<font color=\"red\">x < y</font>", SubRip, SubStationAlpha),
+            "<font color=\"red\">x < y</font>"
+        );
+
+        assert_eq!(
+            translate_markup("{\\an8\\i1}Hello{\\i0\\b700} world", SubStationAlpha, SubRip),
+            "{\\an8}<i>Hello</i><b> world</b>"
+        );
+        assert_eq!(
+            translate_markup("{\\bord2\\blur1}Hello", SubStationAlpha, SubRip),
+            "{\\bord2\\blur1}Hello"
+        );
+        assert_eq!(
+            translate_markup("{\\i1}Hello\n{\\r}world", SubStationAlpha, MicroDVD),
+            "{y:i}Hello\nworld"
+        );
+
+        assert_eq!(
+            translate_markup("{y:i,b}{c:$0000FF}Hello\nworld", MicroDVD, SubRip),
+            "<b><i>Hello</i></b>\nworld"
+        );
+        assert_eq!(translate_markup("{Y:u}Hello", MicroDVD, SubStationAlpha), "{\\u1}Hello");
+        assert_eq!(translate_markup("<i>Hello</i>", SubRip, SubtitleFormat::Mpl2), "<i>Hello</i>");
+    }
+}
@@ -4,36 +4,57 @@
 
 use self::errors::ErrorKind::*;
 use self::errors::*;
-use crate::{SubtitleEntry, SubtitleFileInterface};
+use crate::{SerializeOptions, SubtitleEntry, SubtitleEntryFrames, SubtitleFileInterface};
 
 use crate::errors::Result as SubtitleParserResult;
+use crate::escaping::{mdvd_escape, MDVD_LINE_BREAK};
 use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
 use combine::char::char;
 use combine::combinator::{eof, many, parser as p, satisfy, sep_by};
 use combine::primitives::Parser;
 
 use itertools::Itertools;
 use std::borrow::Cow;
-use std::collections::HashSet;
 
 use failure::ResultExt;
 
 use crate::timetypes::{TimePoint, TimeSpan};
+use crate::transform::LineBreakStyle;
+use crate::warnings::{collect_warnings, ParseWarning, ParseWarningKind};
 use std::collections::LinkedList;
+use std::hash::{Hash, Hasher};
 
 /// Errors specific to `.sub`(`MicroDVD`)-parsing.
-#[allow(missing_docs)]
+#[allow(missing_docs, non_local_definitions)]
 pub mod errors {
     pub type Result<T> = std::result::Result<T, Error>;
 
     define_error!(Error, ErrorKind);
 
     #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
     pub enum ErrorKind {
         #[fail(display = "expected subtittle line, found `{}`", line)]
         ExpectedSubtitleLine { line: String },
         #[fail(display = "parse error at line `{}`", line_num)]
         ErrorAtLine { line_num: usize },
+        #[fail(display = "multiple lines have the frame range `{{{}}}{{{}}}`", start_frame, end_frame)]
+        DuplicateFrameRange { start_frame: i64, end_frame: i64 },
+        #[fail(display = "the frame `{}` has no valid time at {} frames per second", frame, fps)]
+        InvalidFrameTime { frame: i64, fps: f64 },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::ExpectedSubtitleLine { .. } => "mdvd-expected-subtitle-line",
+                ErrorKind::ErrorAtLine { .. } => "mdvd-error-at-line",
+                ErrorKind::DuplicateFrameRange { .. } => "mdvd-duplicate-frame-range",
+                ErrorKind::InvalidFrameTime { .. } => "mdvd-invalid-frame-time",
+            }
+        }
     }
 }
 
@@ -55,7 +76,7 @@ impl From<String> for MdvdFormatting {
 impl MdvdFormatting {
     /// Is this a single line formatting (e.g. `y:i`) or a multi-line formatting (e.g `Y:i`)?
     fn is_container_line_formatting(f: &str) -> bool {
-        f.chars().next().and_then(|c| Some(c.is_uppercase())).unwrap_or(false)
+        f.chars().next().map(|c| c.is_uppercase()).unwrap_or(false)
     }
 
     /// Applies `to_lowercase()` to first char, leaves the rest of the characters untouched.
@@ -93,6 +114,38 @@ impl MdvdFormatting {
     }
 }
 
+/// What `MdvdFile::to_data()` does with lines that have the same start and end frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MdvdDuplicatePolicy {
+    /// Write the lines into one line like `{0}{25}Text1|Text2` (default).
+    Merge,
+
+    /// Write every line on its own, like `{0}{25}Text1` and `{0}{25}Text2`.
+    KeepSeparate,
+
+    /// Return an error.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Options for `MdvdFile::parse_with_options()`.
+pub struct MdvdParseOptions {
+    /// Repair sloppy lines instead of failing with `ExpectedSubtitleLine`: missing closing braces (like
+    /// `{0}{25Text`), spaces around and between the frame numbers (like `{0} {25}`) and trailing whitespace or
+    /// carriage returns are accepted, and lines with only whitespace are skipped. Every repaired line is reported
+    /// with a `ParseWarningKind::MalformedLineRepaired` warning (see `MdvdFile::entry_warnings()`).
+    pub repair_malformed_lines: bool,
+}
+
+impl MdvdParseOptions {
+    /// Options which read sloppy files like most players do (all repairs are enabled).
+    pub fn lenient() -> MdvdParseOptions {
+        MdvdParseOptions {
+            repair_malformed_lines: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Represents a reconstructable `.sub`(`MicroDVD`) file.
 pub struct MdvdFile {
@@ -102,10 +155,19 @@ pub struct MdvdFile {
 
     /// all lines and multilines
     v: Vec<MdvdLine>,
+
+    /// How lines with identical frame ranges are written.
+    duplicate_policy: MdvdDuplicatePolicy,
+
+    /// Problems that were found while parsing.
+    warnings: Vec<ParseWarning>,
+
+    /// A line like "{DEFAULT}{}{}{c:$0000ff}" which sets the formatting of all lines (written unchanged)
+    default_line: Option<String>,
 }
 
 /// Holds the description of a line like.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct MdvdLine {
     /// The start frame.
     start_frame: i64,
@@ -120,41 +182,276 @@ struct MdvdLine {
     text: String,
 }
 
+// `f64` is neither `Eq` nor `Hash`, so the files are compared by the bits of `fps`; the warnings are no content
+impl PartialEq for MdvdFile {
+    fn eq(&self, other: &MdvdFile) -> bool {
+        self.fps.to_bits() == other.fps.to_bits()
+            && self.v == other.v
+            && self.duplicate_policy == other.duplicate_policy
+            && self.default_line == other.default_line
+    }
+}
+
+impl Eq for MdvdFile {}
+
+impl Hash for MdvdFile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fps.to_bits().hash(state);
+        self.v.hash(state);
+        self.duplicate_policy.hash(state);
+        self.default_line.hash(state);
+    }
+}
+
 impl MdvdLine {
-    fn to_subtitle_entry(&self, fps: f64) -> SubtitleEntry {
-        SubtitleEntry {
-            timespan: TimeSpan::new(
-                TimePoint::from_msecs((self.start_frame as f64 * 1000.0 / fps) as i64),
-                TimePoint::from_msecs((self.end_frame as f64 * 1000.0 / fps) as i64),
-            ),
+    fn to_subtitle_entry(&self, fps: f64) -> Result<SubtitleEntry> {
+        let time = |frame: i64| TimePoint::from_secs_f64(frame as f64 / fps).map_err(|_| Error::from(InvalidFrameTime { frame, fps }));
+        Ok(SubtitleEntry {
+            timespan: TimeSpan::new(time(self.start_frame)?, time(self.end_frame)?),
             line: Some(self.text.clone()),
-        }
+            image: None,
+            position: None,
+            speaker: None,
+            index: None,
+            tags: Vec::new(),
+        })
     }
 }
 
 impl MdvdFile {
+    /// Whether `fps` is the frame rate of the file. Frame rates which are written with fewer digits (like `23.976` for
+    /// `24000/1001`) count as the same.
+    fn has_fps(&self, fps: f64) -> bool {
+        (fps - self.fps).abs() < 1e-3
+    }
+
     /// Parse a `MicroDVD` `.sub` subtitle string to `MdvdFile`.
     pub fn parse(s: &str, fps: f64) -> SubtitleParserResult<MdvdFile> {
-        Ok(Self::parse_file(s, fps).with_context(|_| crate::ErrorKind::ParsingError)?)
+        Self::parse_with_options(s, fps, MdvdParseOptions::default())
+    }
+
+    /// Parse a `MicroDVD` `.sub` subtitle string to `MdvdFile` with non-default options.
+    pub fn parse_with_options(s: &str, fps: f64, options: MdvdParseOptions) -> SubtitleParserResult<MdvdFile> {
+        Ok(Self::parse_file(s, fps, options).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    /// The line like `{DEFAULT}{}{}{c:$0000ff}` at the start of the file, which sets the formatting of all lines
+    /// in some players (it is written unchanged, but its formatting is lost when the file is converted).
+    pub fn default_line(&self) -> Option<&str> {
+        self.default_line.as_deref()
+    }
+
+    /// Parse a `MicroDVD` `.sub` subtitle string to `MdvdFile` and return the problems which did not prevent parsing:
+    /// the `warnings()` of the file and the ones of `warnings::check_entries()`.
+    pub fn parse_with_warnings(s: &str, fps: f64, options: MdvdParseOptions) -> SubtitleParserResult<(MdvdFile, Vec<ParseWarning>)> {
+        let file = Self::parse_with_options(s, fps, options)?;
+        // the positions of the entries are not kept, so the line numbers of the entry warnings are `0`
+        let warnings = collect_warnings(&file.warnings, &file.get_subtitle_entries()?, s, |_| None);
+        Ok((file, warnings))
+    }
+
+    /// Problems (like repaired lines) that were found while parsing the file.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// The problems that were found in the entry with the index `entry_idx` while parsing the file.
+    ///
+    /// Every part of a line (separated by `|`) is its own entry, so a repaired line is reported for its first part.
+    pub fn entry_warnings(&self, entry_idx: usize) -> impl Iterator<Item = &ParseWarning> {
+        self.warnings.iter().filter(move |warning| warning.entry_idx == Some(entry_idx))
+    }
+
+    /// The number of frames per second of the video, which is used to convert the frames to times.
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    /// The texts of the entries with their control codes like `{y:i}` at the start, which the texts of
+    /// `get_subtitle_entries()` don't have.
+    pub fn formatted_texts(&self) -> Vec<String> {
+        self.v
+            .iter()
+            .map(|line| {
+                let codes: String = line
+                    .formatting
+                    .iter()
+                    .map(|formatting| format!("{{{}}}", formatting.to_formatting_string(false)))
+                    .collect();
+                codes + &line.text
+            })
+            .collect()
+    }
+
+    /// Set how `to_data()` writes lines with identical start and end frames (`MdvdDuplicatePolicy::Merge` by default).
+    ///
+    /// The lines are always sorted by their frame ranges; lines with identical frame ranges keep their relative order.
+    pub fn set_duplicate_policy(&mut self, duplicate_policy: MdvdDuplicatePolicy) {
+        self.duplicate_policy = duplicate_policy;
+    }
+
+    /// Merge or split the lines of all subtitles (see `LineBreakStyle`).
+    ///
+    /// In `.sub` files the lines of a subtitle are separated by `|`, so every line is its own entry with the frame
+    /// range of the subtitle. Merged lines only keep the formatting which all lines have in common.
+    pub fn convert_line_breaks(&mut self, style: LineBreakStyle) {
+        let groups: Vec<Vec<MdvdLine>> = std::mem::take(&mut self.v)
+            .into_iter()
+            .group_by(|line| (line.start_frame, line.end_frame))
+            .into_iter()
+            .map(|(_, group_iter)| group_iter.collect())
+            .collect();
+
+        for group in groups {
+            let (start_frame, end_frame) = (group[0].start_frame, group[0].end_frame);
+            let formatting: Vec<MdvdFormatting> = group[0]
+                .formatting
+                .iter()
+                .filter(|formatting| group.iter().all(|line| line.formatting.contains(formatting)))
+                .cloned()
+                .collect();
+
+            let texts: Vec<String> = group.iter().map(|line| line.text.clone()).collect();
+            let new_texts = style.apply(texts.clone());
+            if new_texts == texts {
+                self.v.extend(group);
+                continue;
+            }
+
+            self.v.extend(new_texts.into_iter().map(|text| MdvdLine {
+                start_frame,
+                end_frame,
+                formatting: formatting.clone(),
+                text,
+            }));
+        }
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.v.len();
+        footprint.add_vec(&self.v);
+        footprint.add_vec(&self.warnings);
+        for line in &self.v {
+            footprint.add_text(&line.text);
+            footprint.add_vec(&line.formatting);
+            for formatting in &line.formatting {
+                match formatting {
+                    MdvdFormatting::Unknown(text) => footprint.add_filler(text),
+                }
+            }
+        }
+        if let Some(default_line) = &self.default_line {
+            footprint.add_filler(default_line);
+        }
+        footprint
     }
 }
 
 /// Implements parse functions.
 impl MdvdFile {
-    fn parse_file(i: &str, fps: f64) -> Result<MdvdFile> {
+    fn parse_file(i: &str, fps: f64, options: MdvdParseOptions) -> Result<MdvdFile> {
         let mut result: Vec<MdvdLine> = Vec::new();
+        let mut warnings: Vec<ParseWarning> = Vec::new();
+        let mut default_line: Option<String> = None;
 
         // remove utf-8 bom
         let (_, s) = split_bom(i);
 
         for (line_num, line) in s.lines().enumerate() {
+            if default_line.is_none() && result.is_empty() && line.trim_start().starts_with("{DEFAULT}") {
+                default_line = Some(line.trim().to_string());
+                continue;
+            }
+
+            let repaired;
+            let line = if options.repair_malformed_lines {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                repaired = Self::repair_line(line);
+                if repaired != line {
+                    warnings.push(ParseWarning {
+                        line_num,
+                        entry_idx: Some(result.len()),
+                        kind: ParseWarningKind::MalformedLineRepaired,
+                    });
+                }
+                repaired.as_str()
+            } else {
+                line
+            };
+
             // a line looks like "{0}{25}{c:$0000ff}{y:b,u}{f:DeJaVuSans}{s:12}Hello!|{y:i}Hello2!" where
             // 0 and 25 are the start and end frames and the other information is the formatting.
             let mut lines: Vec<MdvdLine> = Self::parse_line(line_num, line)?;
             result.append(&mut lines);
         }
 
-        Ok(MdvdFile { fps: fps, v: result })
+        Ok(MdvdFile {
+            fps,
+            v: result,
+            duplicate_policy: MdvdDuplicatePolicy::Merge,
+            warnings,
+            default_line,
+        })
+    }
+
+    /// Rewrite a sloppy line like " { 0 } {25 Hello!|{y:i Hello2!\r" to "{0}{25}Hello!|Hello2!" (see
+    /// `MdvdParseOptions::repair_malformed_lines`). Lines which can't be repaired are returned unchanged (except
+    /// for the trailing whitespace), so `parse_line()` reports them.
+    fn repair_line(line: &str) -> String {
+        let line = line.trim_end();
+        let mut rest = line.trim_start();
+        let mut frames: Vec<&str> = Vec::new();
+
+        // the two frame numbers, each with an opening brace and an optional closing brace
+        for _ in 0..2 {
+            let after_brace = match rest.strip_prefix('{') {
+                Some(after_brace) => after_brace.trim_start(),
+                None => return line.to_string(),
+            };
+            let number_len = after_brace
+                .char_indices()
+                .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+                .map_or(after_brace.len(), |(i, _)| i);
+            if after_brace[..number_len].trim_start_matches('-').is_empty() {
+                return line.to_string();
+            }
+            frames.push(&after_brace[..number_len]);
+
+            let after_number = after_brace[number_len..].trim_start();
+            rest = after_number.strip_prefix('}').unwrap_or(after_number);
+            if frames.len() == 1 {
+                rest = rest.trim_start();
+            }
+        }
+
+        // formatting blocks without a closing brace (which would swallow the rest of the line) are removed
+        let text = rest
+            .split(MDVD_LINE_BREAK)
+            .map(|single_line| {
+                let mut single_line = single_line;
+                let mut repaired = String::new();
+                while let Some(after_brace) = single_line.strip_prefix('{') {
+                    match after_brace.find('}') {
+                        Some(end) => {
+                            repaired.push_str(&single_line[..end + 2]);
+                            single_line = &after_brace[end + 1..];
+                        }
+                        None => {
+                            let info_len = after_brace.find(char::is_whitespace).unwrap_or(after_brace.len());
+                            single_line = after_brace[info_len..].trim_start();
+                        }
+                    }
+                }
+                repaired + single_line
+            })
+            .join(MDVD_LINE_BREAK);
+
+        format!("{{{}}}{{{}}}{}", frames[0], frames[1], text)
     }
 
     // Parses something like "{0}{25}{C:$0000ff}{y:b,u}{f:DeJaVuSans}{s:12}Hello!|{s:15}Hello2!"
@@ -211,10 +508,10 @@ impl MdvdFile {
         fmts_and_lines
             .into_iter()
             .map(|(sline_fmts, text)| MdvdLine {
-                start_frame: start_frame,
-                end_frame: end_frame,
-                text: text,
-                formatting: cline_fmts.clone().into_iter().chain(sline_fmts.into_iter()).collect(),
+                start_frame,
+                end_frame,
+                text,
+                formatting: cline_fmts.clone().into_iter().chain(sline_fmts).collect(),
             })
             .collect()
     }
@@ -235,18 +532,23 @@ impl MdvdFile {
 
 impl SubtitleFileInterface for MdvdFile {
     fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
-        Ok(self.v.iter().map(|line| line.to_subtitle_entry(self.fps)).collect())
+        Ok(self
+            .v
+            .iter()
+            .map(|line| line.to_subtitle_entry(self.fps))
+            .collect::<Result<Vec<SubtitleEntry>>>()
+            .with_context(|_| crate::ErrorKind::ParsingError)?)
     }
 
     fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
-        assert_eq!(new_subtitle_entries.len(), self.v.len());
+        check_entry_count(self.v.len(), new_subtitle_entries.len())?;
 
         let mut iter = new_subtitle_entries.iter().peekable();
         for line in &mut self.v {
             let peeked = iter.next().unwrap();
 
-            line.start_frame = (peeked.timespan.start.secs_f64() * self.fps) as i64;
-            line.end_frame = (peeked.timespan.end.secs_f64() * self.fps) as i64;
+            line.start_frame = peeked.timespan.start.to_frame(self.fps);
+            line.end_frame = peeked.timespan.end.to_frame(self.fps);
 
             if let Some(ref text) = peeked.line {
                 line.text = text.clone();
@@ -256,50 +558,172 @@ impl SubtitleFileInterface for MdvdFile {
         Ok(())
     }
 
+    fn get_subtitle_entries_frames(&self, fps: f64) -> SubtitleParserResult<Vec<SubtitleEntryFrames>> {
+        if !self.has_fps(fps) {
+            return Ok(self
+                .get_subtitle_entries()?
+                .into_iter()
+                .map(|entry| SubtitleEntryFrames::from_entry(entry, fps))
+                .collect());
+        }
+
+        // the frame numbers are stored in the file, so no conversion is necessary
+        Ok(self
+            .v
+            .iter()
+            .map(|line| SubtitleEntryFrames {
+                start_frame: line.start_frame,
+                end_frame: line.end_frame,
+                line: Some(line.text.clone()),
+                image: None,
+                position: None,
+                speaker: None,
+                index: None,
+                tags: Vec::new(),
+            })
+            .collect())
+    }
+
+    fn update_subtitle_entries_frames(&mut self, new_subtitle_entries: &[SubtitleEntryFrames], fps: f64) -> SubtitleParserResult<()> {
+        if !self.has_fps(fps) {
+            let entries: Vec<SubtitleEntry> = new_subtitle_entries.iter().map(|entry| entry.to_entry(fps)).collect();
+            return self.update_subtitle_entries(&entries);
+        }
+
+        check_entry_count(self.v.len(), new_subtitle_entries.len())?;
+
+        for (line, new_entry) in self.v.iter_mut().zip(new_subtitle_entries) {
+            line.start_frame = new_entry.start_frame;
+            line.end_frame = new_entry.end_frame;
+
+            if let Some(ref text) = new_entry.line {
+                line.text = text.clone();
+            }
+        }
+
+        Ok(())
+    }
+
     fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        Ok(self.to_data_inner().with_context(|_| crate::ErrorKind::SerializingError)?)
+    }
+
+    fn to_data_with(&self, options: &SerializeOptions) -> SubtitleParserResult<Vec<u8>> {
+        match options.get_mdvd_duplicate_policy() {
+            Some(duplicate_policy) => {
+                let mut file = self.clone();
+                file.set_duplicate_policy(duplicate_policy);
+                options.apply_to_text(file.to_data()?)
+            }
+            None => options.apply_to_text(self.to_data()?),
+        }
+    }
+}
+
+impl MdvdFile {
+    /// Creates a `MicroDVD` `.sub` file from scratch.
+    ///
+    /// The timespans are converted to the nearest frames of a video with `fps` frames per second. Every line of a
+    /// text (separated by a newline) is its own entry, like in parsed files; the lines are written with `|`. Control
+    /// codes like `{y:i}` at the start of a line are read as its formatting.
+    pub fn create(lines: Vec<(TimeSpan, String)>, fps: f64) -> SubtitleParserResult<MdvdFile> {
+        let v = lines
+            .into_iter()
+            .flat_map(|(timespan, text)| {
+                let (start_frame, end_frame) = (timespan.start.to_frame(fps), timespan.end.to_frame(fps));
+                let texts: Vec<String> = if text.is_empty() {
+                    vec![String::new()]
+                } else {
+                    text.lines().map(str::to_string).collect()
+                };
+                texts.into_iter().map(move |text| {
+                    let (formatting, text) = Self::split_formatting(&text);
+                    MdvdLine {
+                        start_frame,
+                        end_frame,
+                        formatting,
+                        text: mdvd_escape(text),
+                    }
+                })
+            })
+            .collect();
+
+        Ok(MdvdFile {
+            fps,
+            v,
+            duplicate_policy: MdvdDuplicatePolicy::Merge,
+            warnings: Vec::new(),
+            default_line: None,
+        })
+    }
+
+    /// Split a line like `{y:i}{c:$0000FF}Hello` into its control codes and its text.
+    fn split_formatting(line: &str) -> (Vec<MdvdFormatting>, &str) {
+        let mut formatting = Vec::new();
+        let mut rest = line;
+        while let Some((code, after)) = rest.strip_prefix('{').and_then(|s| s.split_once('}')) {
+            formatting.push(MdvdFormatting::from(code.to_string()));
+            rest = after;
+        }
+        (formatting, rest)
+    }
+
+    fn to_data_inner(&self) -> Result<Vec<u8>> {
+        // the sort is stable, so lines with the same frame range keep their order
         let mut sorted_list = self.v.clone();
         sorted_list.sort_by_key(|line| (line.start_frame, line.end_frame));
 
+        let groups: Vec<Vec<MdvdLine>> = match self.duplicate_policy {
+            MdvdDuplicatePolicy::KeepSeparate => sorted_list.into_iter().map(|line| vec![line]).collect(),
+            MdvdDuplicatePolicy::Merge | MdvdDuplicatePolicy::Error => sorted_list
+                .into_iter()
+                .group_by(|line| (line.start_frame, line.end_frame))
+                .into_iter()
+                .map(|(_, group_iter)| group_iter.collect())
+                .collect(),
+        };
+
         let mut result: LinkedList<Cow<'static, str>> = LinkedList::new();
+        if let Some(default_line) = &self.default_line {
+            result.push_back(default_line.clone().into());
+        }
 
-        for (gi, group_iter) in sorted_list
-            .into_iter()
-            .group_by(|line| (line.start_frame, line.end_frame))
-            .into_iter()
-            .enumerate()
-        {
-            if gi != 0 {
+        for (gi, group) in groups.into_iter().enumerate() {
+            if gi != 0 || self.default_line.is_some() {
                 result.push_back("\n".into());
             }
 
-            let group: Vec<MdvdLine> = group_iter.1.collect();
             let group_len = group.len();
+            let (start_frame, end_frame) = (group[0].start_frame, group[0].end_frame);
+            if group_len > 1 && self.duplicate_policy == MdvdDuplicatePolicy::Error {
+                return Err(DuplicateFrameRange { start_frame, end_frame }.into());
+            }
 
-            let (start_frame, end_frame) = group_iter.0;
-            let (formattings, texts): (Vec<HashSet<MdvdFormatting>>, Vec<String>) =
-                group.into_iter().map(|line| (line.formatting.into_iter().collect(), line.text)).unzip();
+            // the formattings are kept in their original order (and not in a set), so the output is deterministic
+            let (formattings, texts): (Vec<Vec<MdvdFormatting>>, Vec<String>) = group
+                .into_iter()
+                .map(|line| (line.formatting.into_iter().unique().collect(), line.text))
+                .unzip();
 
             // all single lines in the container line "cline" have the same start and end time
             //  -> the .sub file format let's them be on the same line with "{0}{1000}Text1|Text2"
 
             // find common formatting in all lines
-            let common_formatting = if group_len == 1 {
+            let common_formatting: Vec<MdvdFormatting> = if group_len == 1 {
                 // if this "group" only has a single line, let's say that every formatting is individual
-                HashSet::new()
+                Vec::new()
             } else {
-                formattings
+                formattings[0]
                     .iter()
-                    .fold(None, |acc, set| match acc {
-                        None => Some(set.clone()),
-                        Some(acc_set) => Some(acc_set.intersection(set).cloned().collect()),
-                    })
-                    .unwrap()
+                    .filter(|formatting| formattings.iter().all(|fmts| fmts.contains(formatting)))
+                    .cloned()
+                    .collect()
             };
 
             let individual_formattings = formattings
                 .into_iter()
-                .map(|formatting| formatting.difference(&common_formatting).cloned().collect())
-                .collect::<Vec<HashSet<MdvdFormatting>>>();
+                .map(|formatting| formatting.into_iter().filter(|f| !common_formatting.contains(f)).collect())
+                .collect::<Vec<Vec<MdvdFormatting>>>();
 
             result.push_back("{".into());
             result.push_back(start_frame.to_string().into());
@@ -315,9 +739,9 @@ impl SubtitleFileInterface for MdvdFile {
                 result.push_back("}".into());
             }
 
-            for (i, (individual_formatting, text)) in individual_formattings.into_iter().zip(texts.into_iter()).enumerate() {
+            for (i, (individual_formatting, text)) in individual_formattings.into_iter().zip(texts).enumerate() {
                 if i != 0 {
-                    result.push_back("|".into());
+                    result.push_back(MDVD_LINE_BREAK.into());
                 }
 
                 for formatting in individual_formatting {
@@ -374,4 +798,114 @@ mod tests {
         // these can't be condensed, because the lines have different times
         test_mdvd("{0}{25}{y:i}Text1\n{0}{26}{y:i}Text2", "{0}{25}{y:i}Text1\n{0}{26}{y:i}Text2");
     }
+
+    #[test]
+    fn mdvd_repair_malformed_lines_test() {
+        let data = "{0}{25}Fine\n{30} {50}Space\n{60}{75 Missing brace\r\r\n \n{ 80 }{90}{y:i Unclosed|{y:b}Bold  \n";
+        assert!(MdvdFile::parse(data, 25.0).is_err());
+
+        let file = MdvdFile::parse_with_options(data, 25.0, MdvdParseOptions::lenient()).unwrap();
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "{0}{25}Fine\n{30}{50}Space\n{60}{75}Missing brace\n{80}{90}Unclosed|{y:b}Bold"
+        );
+
+        let warned_entries: Vec<_> = file.warnings().iter().map(|warning| (warning.line_num, warning.entry_idx)).collect();
+        assert_eq!(warned_entries, vec![(1, Some(1)), (2, Some(2)), (4, Some(3))]);
+        assert_eq!(file.entry_warnings(0).count(), 0);
+        assert_eq!(file.entry_warnings(3).next().unwrap().kind, ParseWarningKind::MalformedLineRepaired);
+
+        // lines without frame numbers are still errors
+        assert!(MdvdFile::parse_with_options("{0}{25}Fine\nHello", 25.0, MdvdParseOptions::lenient()).is_err());
+    }
+
+    #[test]
+    fn mdvd_convert_line_breaks_test() {
+        let mut file = MdvdFile::parse("{0}{25}{y:i}Text1|{y:i}{y:b}Text2\n{30}{50}A long line of text", 25.0).unwrap();
+        file.convert_line_breaks(LineBreakStyle::SingleLine);
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "{0}{25}{y:i}Text1 Text2\n{30}{50}A long line of text"
+        );
+
+        file.convert_line_breaks(LineBreakStyle::TwoLines { max_line_len: 12 });
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "{0}{25}{y:i}Text1 Text2\n{30}{50}A long line|of text"
+        );
+    }
+
+    #[test]
+    fn mdvd_test_frames() {
+        let mut file = MdvdFile::parse("{2}{25}Text1\n{30}{50}Text2", 23.976).unwrap();
+
+        let mut entries = file.get_subtitle_entries_frames(23.976).unwrap();
+        assert_eq!((entries[0].start_frame, entries[0].end_frame), (2, 25));
+        assert_eq!((entries[1].start_frame, entries[1].end_frame), (30, 50));
+
+        entries[0].start_frame = 3;
+        file.update_subtitle_entries_frames(&entries, 23.976).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "{3}{25}Text1\n{30}{50}Text2");
+
+        // other frame rates are converted through the timestamps
+        let entries = file.get_subtitle_entries_frames(47.952).unwrap();
+        assert_eq!((entries[0].start_frame, entries[0].end_frame), (6, 50));
+
+        // the same frame rate with more digits uses the frame numbers of the file
+        let file = MdvdFile::parse("{2000000}{2000001}Text", 23.976).unwrap();
+        let entries = file.get_subtitle_entries_frames(24000.0 / 1001.0).unwrap();
+        assert_eq!((entries[0].start_frame, entries[0].end_frame), (2000000, 2000001));
+    }
+
+    #[test]
+    fn mdvd_test_update_round_trip() {
+        // an unchanged get/update round trip keeps the frames (they were truncated before)
+        let data = "{1001}{1100}Text1\n{2}{3}Text2";
+        let mut file = MdvdFile::parse(data, 23.976).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        file.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "{2}{3}Text2\n{1001}{1100}Text1");
+    }
+
+    #[test]
+    fn mdvd_test_duplicate_policy() {
+        let mut file = MdvdFile::parse("{0}{25}{y:u}{y:b}{y:i}Text1\n{30}{50}Text2\n{0}{25}{y:i}{y:b}Text3", 25.0).unwrap();
+        let to_string = |file: &MdvdFile| file.to_data().map(|data| String::from_utf8(data).unwrap());
+
+        // the common formattings are written in the order of the first line
+        assert_eq!(to_string(&file).unwrap(), "{0}{25}{Y:b}{Y:i}{y:u}Text1|Text3\n{30}{50}Text2");
+
+        file.set_duplicate_policy(MdvdDuplicatePolicy::KeepSeparate);
+        assert_eq!(
+            to_string(&file).unwrap(),
+            "{0}{25}{y:u}{y:b}{y:i}Text1\n{0}{25}{y:i}{y:b}Text3\n{30}{50}Text2"
+        );
+
+        file.set_duplicate_policy(MdvdDuplicatePolicy::Error);
+        assert!(to_string(&file).is_err());
+    }
+
+    #[test]
+    fn mdvd_create_test() {
+        let span = |start, end| TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end));
+        let file = MdvdFile::create(
+            vec![(span(1000, 2000), "Hello\nworld".to_string()), (span(2500, 4000), "Bye".to_string())],
+            23.976,
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "{24}{48}Hello|world\n{60}{96}Bye");
+
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].timespan, span(2503, 4004));
+
+        let file = MdvdFile::create(vec![(span(0, 1000), "{y:i}Hello\n{y:i}{c:$0000FF}world".to_string())], 25.0).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "{0}{25}{Y:i}Hello|{c:$0000FF}world");
+        assert_eq!(file.formatted_texts(), vec!["{y:i}Hello", "{y:i}{c:$0000FF}world"]);
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].line.as_deref(), Some("Hello"));
+
+        // a `|` in the text would be a line break
+        let file = MdvdFile::create(vec![(span(0, 1000), "A | B".to_string())], 25.0).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "{0}{25}A ¦ B");
+    }
 }
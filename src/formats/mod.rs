@@ -4,20 +4,45 @@
 
 pub mod common;
 pub mod idx;
+pub mod images;
+pub mod lrc;
+pub mod markup;
 pub mod microdvd;
+pub mod mpl2;
+pub mod pgs;
+pub mod sbv;
+pub mod scc;
 pub mod srt;
 pub mod ssa;
+pub mod stl;
+pub mod tmp;
 pub mod vobsub;
 
 use crate::errors::*;
+use crate::escaping::line_break;
+use crate::html::HtmlOptions;
+use crate::timetypes::{TimeDelta, TimeSpan};
+use crate::transcript::TranscriptOptions;
+use crate::transform::{shift_matching, LineBreakStyle};
+use crate::warnings::{collect_warnings, ParseWarning};
+use crate::EntryProvenance;
+use crate::SerializeOptions;
+use crate::SpeakerPolicy;
 use crate::SubtitleEntry;
+use crate::SubtitleEntryFrames;
 use crate::SubtitleFileInterface;
-use encoding_rs::Encoding;
-use std::ffi::OsStr;
+use crate::SubtitleImage;
+use crate::SubtitleSourceRanges;
 use chardet::{charset2encoding, detect};
+use encoding_rs::{DecoderResult, Encoding};
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsStr;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-/// All formats which are supported by this library.
+/// All formats which are supported by this library (more can be added in new versions).
+#[non_exhaustive]
 pub enum SubtitleFormat {
     /// .srt file
     SubRip,
@@ -33,10 +58,49 @@ pub enum SubtitleFormat {
 
     /// .sub file (`MicroDVD`/text)
     MicroDVD,
+
+    /// .sbv file (YouTube)
+    YouTubeSbv,
+
+    /// .stl file (EBU-STL/binary)
+    EbuStl,
+
+    /// .sup file (Blu-ray PGS/binary)
+    BluRayPgs,
+
+    /// .txt file (`MPL2`/text)
+    Mpl2,
+
+    /// .txt file (TMPlayer/text)
+    TmPlayer,
+
+    /// .lrc file (timed lyrics)
+    Lrc,
+
+    /// .scc file (Scenarist/CEA-608 captions)
+    Scc,
 }
 
-#[derive(Clone, Debug)]
-/// Unified wrapper around the all individual subtitle file types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// A variant of a subtitle format with features which are lost when the file is converted (see
+/// `SubtitleFile::dialect()`).
+pub enum SubtitleDialect {
+    /// .srt file with coordinates like `X1:100 X2:600 Y1:400 Y2:450` after the timespans
+    SrtWithCoordinates,
+
+    /// .ssa file (SubStation Alpha v4 with a `[V4 Styles]` section)
+    SsaV4,
+
+    /// .ass file (Advanced SubStation Alpha v4+ with a `[V4+ Styles]` section)
+    AssV4Plus,
+
+    /// .sub file (`MicroDVD`) with a `{DEFAULT}{}{}` line which sets the formatting of all lines
+    MdvdWithDefaultLine,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// Unified wrapper around the all individual subtitle file types (more can be added in new versions).
+#[non_exhaustive]
 pub enum SubtitleFile {
     /// .srt file
     SubRipFile(srt::SrtFile),
@@ -52,9 +116,152 @@ pub enum SubtitleFile {
 
     /// .sub file (`MicroDVD`/text)
     MicroDVDFile(microdvd::MdvdFile),
+
+    /// .sbv file (YouTube)
+    YouTubeSbvFile(sbv::SbvFile),
+
+    /// .stl file (EBU-STL/binary)
+    EbuStlFile(stl::StlFile),
+
+    /// .sup file (Blu-ray PGS/binary)
+    BluRayPgsFile(pgs::PgsFile),
+
+    /// .txt file (`MPL2`/text)
+    Mpl2File(mpl2::Mpl2File),
+
+    /// .txt file (TMPlayer/text)
+    TmPlayerFile(tmp::TmpFile),
+
+    /// .lrc file (timed lyrics)
+    LrcFile(lrc::LrcFile),
+
+    /// .scc file (Scenarist/CEA-608 captions)
+    SccFile(scc::SccFile),
 }
 
 impl SubtitleFile {
+    /// The format of the file.
+    pub fn format(&self) -> SubtitleFormat {
+        match self {
+            SubtitleFile::SubRipFile(_) => SubtitleFormat::SubRip,
+            SubtitleFile::SubStationAlpha(_) => SubtitleFormat::SubStationAlpha,
+            SubtitleFile::VobSubIdxFile(_) => SubtitleFormat::VobSubIdx,
+            SubtitleFile::VobSubSubFile(_) => SubtitleFormat::VobSubSub,
+            SubtitleFile::MicroDVDFile(_) => SubtitleFormat::MicroDVD,
+            SubtitleFile::YouTubeSbvFile(_) => SubtitleFormat::YouTubeSbv,
+            SubtitleFile::EbuStlFile(_) => SubtitleFormat::EbuStl,
+            SubtitleFile::BluRayPgsFile(_) => SubtitleFormat::BluRayPgs,
+            SubtitleFile::Mpl2File(_) => SubtitleFormat::Mpl2,
+            SubtitleFile::TmPlayerFile(_) => SubtitleFormat::TmPlayer,
+            SubtitleFile::LrcFile(_) => SubtitleFormat::Lrc,
+            SubtitleFile::SccFile(_) => SubtitleFormat::Scc,
+        }
+    }
+
+    /// The variant of the format, if the file uses one with features that other formats can't represent (the
+    /// application can warn the user before converting the file). `.ssa`/`.ass` files always have a dialect.
+    pub fn dialect(&self) -> Option<SubtitleDialect> {
+        match self {
+            SubtitleFile::SubRipFile(f) if f.has_coordinates() => Some(SubtitleDialect::SrtWithCoordinates),
+            SubtitleFile::SubStationAlpha(f) if f.is_legacy_ssa() => Some(SubtitleDialect::SsaV4),
+            SubtitleFile::SubStationAlpha(_) => Some(SubtitleDialect::AssV4Plus),
+            SubtitleFile::MicroDVDFile(f) if f.default_line().is_some() => Some(SubtitleDialect::MdvdWithDefaultLine),
+            _ => None,
+        }
+    }
+
+    /// The warnings of the parser and of `warnings::check_entries()` for the file parsed from `content`.
+    fn collect_warnings(&self, content: &str) -> Result<Vec<ParseWarning>> {
+        let stored = match self {
+            SubtitleFile::SubRipFile(f) => f.warnings().to_vec(),
+            SubtitleFile::MicroDVDFile(f) => f.warnings().to_vec(),
+            SubtitleFile::VobSubSubFile(f) => f.image_warnings(),
+            _ => Vec::new(),
+        };
+        Ok(collect_warnings(&stored, &self.get_subtitle_entries()?, content, |entry_idx| {
+            self.source_ranges(entry_idx).map(|ranges| ranges.start.start)
+        }))
+    }
+
+    /// Create a file of the format `format` with the entries of this file. This is the conversion which all other
+    /// conversion functions use: `convert_to()` calls it with the default options, `convert_to_with_provenance()`
+    /// adds where the entries come from and `convert()` writes the new file. `convert_entries()` is only needed for
+    /// entries which don't belong to a file.
+    ///
+    /// The line breaks and the styles are converted like with `convert_entries()` (which also lists the formats that
+    /// can be created), the formatting of `MicroDVD` lines is kept. `MicroDVD` files are created with the frame rate
+    /// of the options, of a `MicroDVD` source file or with 25 frames per second.
+    ///
+    /// The images of VobSub (`.sub`) and Blu-ray PGS files can be converted into each other (see `VobFile::create()`
+    /// and `PgsFile::create()` for how the colors are reduced); converting them into text formats returns
+    /// `ErrorKind::TextFormatOnly`.
+    pub fn convert_to_with(&self, format: SubtitleFormat, options: &ConvertOptions) -> Result<SubtitleFile> {
+        if !self.format().capabilities().text {
+            return self.convert_images_to(format, options);
+        }
+        let fps = match (options.fps, self) {
+            (Some(fps), _) => fps,
+            (None, SubtitleFile::MicroDVDFile(f)) => f.fps(),
+            (None, _) => 25.0,
+        };
+        let mut entries = self.get_subtitle_entries()?;
+        if let SubtitleFile::MicroDVDFile(f) = self {
+            // the formatting of `MicroDVD` lines is not part of their entries
+            for (entry, text) in entries.iter_mut().zip(f.formatted_texts()) {
+                entry.line = Some(text);
+            }
+        }
+        convert_entries_with_fps(&entries, self.format(), format, fps)
+    }
+
+    /// The conversion of `convert_to_with()` for files with images.
+    fn convert_images_to(&self, format: SubtitleFormat, options: &ConvertOptions) -> Result<SubtitleFile> {
+        let images: Vec<(TimeSpan, SubtitleImage)> = self
+            .get_subtitle_entries()?
+            .into_iter()
+            .filter_map(|entry| Some((entry.timespan, entry.image?)))
+            .collect();
+        let (width, height) = match (options.screen_size, self) {
+            (Some(size), _) => size,
+            (None, SubtitleFile::BluRayPgsFile(f)) if f.screen_size().is_some() => f.screen_size().unwrap(),
+            (None, _) => images.iter().fold((720, 576), |(width, height), (_, image)| {
+                (
+                    width.max(image.x.saturating_add(image.width)),
+                    height.max(image.y.saturating_add(image.height)),
+                )
+            }),
+        };
+
+        match format {
+            SubtitleFormat::BluRayPgs => Ok(SubtitleFile::BluRayPgsFile(pgs::PgsFile::create(images, width, height)?)),
+            SubtitleFormat::VobSubSub => Ok(SubtitleFile::VobSubSubFile(vobsub::VobFile::create(images, width, height)?.1)),
+            SubtitleFormat::VobSubIdx => Ok(SubtitleFile::VobSubIdxFile(vobsub::VobFile::create(images, width, height)?.0)),
+            _ => Err(ErrorKind::TextFormatOnly.into()),
+        }
+    }
+
+    /// Works like `convert_to_with()` with the default options.
+    pub fn convert_to(&self, format: SubtitleFormat) -> Result<SubtitleFile> {
+        self.convert_to_with(format, &ConvertOptions::new())
+    }
+
+    /// Works like `convert_to()`, but also returns where every entry of the new file comes from (in the order of the
+    /// entries), so problems in the new file can be traced back to the source file.
+    pub fn convert_to_with_provenance(&self, format: SubtitleFormat) -> Result<(SubtitleFile, Vec<EntryProvenance>)> {
+        let provenance = self
+            .get_subtitle_entries()?
+            .iter()
+            .enumerate()
+            .map(|(entry_idx, entry)| EntryProvenance {
+                format: self.format(),
+                entry_idx,
+                index: entry.index,
+                source: self.source_ranges(entry_idx),
+            })
+            .collect();
+        Ok((self.convert_to(format)?, provenance))
+    }
+
     /// The subtitle entries can be changed by calling `update_subtitle_entries()`.
     pub fn get_subtitle_entries(&self) -> Result<Vec<SubtitleEntry>> {
         match self {
@@ -63,14 +270,22 @@ impl SubtitleFile {
             SubtitleFile::VobSubIdxFile(f) => f.get_subtitle_entries(),
             SubtitleFile::VobSubSubFile(f) => f.get_subtitle_entries(),
             SubtitleFile::MicroDVDFile(f) => f.get_subtitle_entries(),
+            SubtitleFile::YouTubeSbvFile(f) => f.get_subtitle_entries(),
+            SubtitleFile::EbuStlFile(f) => f.get_subtitle_entries(),
+            SubtitleFile::BluRayPgsFile(f) => f.get_subtitle_entries(),
+            SubtitleFile::Mpl2File(f) => f.get_subtitle_entries(),
+            SubtitleFile::TmPlayerFile(f) => f.get_subtitle_entries(),
+            SubtitleFile::LrcFile(f) => f.get_subtitle_entries(),
+            SubtitleFile::SccFile(f) => f.get_subtitle_entries(),
         }
     }
 
     /// Set the entries from the subtitle entries from the `get_subtitle_entries()`.
     ///
-    /// The length of the given input slice should always match the length of the vector length from
-    /// `get_subtitle_entries()`. This function can not delete/create new entries, but preserves
-    /// everything else in the file (formatting, authors, ...).
+    /// The length of the given input slice has to match the length of the vector length from
+    /// `get_subtitle_entries()` (otherwise `ErrorKind::EntryCountMismatch` is returned and the file is not
+    /// changed). This function can not delete/create new entries, but preserves everything else in the file
+    /// (formatting, authors, ...).
     ///
     /// If the input entry has `entry.line == None`, the line will not be overwritten.
     ///
@@ -84,6 +299,199 @@ impl SubtitleFile {
             SubtitleFile::VobSubIdxFile(f) => f.update_subtitle_entries(i),
             SubtitleFile::VobSubSubFile(f) => f.update_subtitle_entries(i),
             SubtitleFile::MicroDVDFile(f) => f.update_subtitle_entries(i),
+            SubtitleFile::YouTubeSbvFile(f) => f.update_subtitle_entries(i),
+            SubtitleFile::EbuStlFile(f) => f.update_subtitle_entries(i),
+            SubtitleFile::BluRayPgsFile(f) => f.update_subtitle_entries(i),
+            SubtitleFile::Mpl2File(f) => f.update_subtitle_entries(i),
+            SubtitleFile::TmPlayerFile(f) => f.update_subtitle_entries(i),
+            SubtitleFile::LrcFile(f) => f.update_subtitle_entries(i),
+            SubtitleFile::SccFile(f) => f.update_subtitle_entries(i),
+        }
+    }
+
+    /// Works like `update_subtitle_entries()`, but the number of entries is checked for all formats
+    /// (`ErrorKind::EntryCountMismatch`) before the file is changed.
+    ///
+    /// Formats that can not be updated are rejected before any entries are read
+    /// (`ErrorKind::UpdatingEntriesNotSupported`).
+    pub fn try_update_subtitle_entries(&mut self, i: &[SubtitleEntry]) -> Result<()> {
+        if let SubtitleFile::VobSubSubFile(_) | SubtitleFile::BluRayPgsFile(_) = self {
+            return Err(ErrorKind::UpdatingEntriesNotSupported { format: self.format() }.into());
+        }
+
+        let expected = self.get_subtitle_entries()?.len();
+        if expected != i.len() {
+            return Err(ErrorKind::EntryCountMismatch { expected, found: i.len() }.into());
+        }
+
+        self.update_subtitle_entries(i)
+    }
+
+    /// Works like `update_subtitle_entries()`, but the speakers of the entries are written
+    /// according to `policy`.
+    ///
+    /// With `SpeakerPolicy::FormatDefault` the `Name` field of `.ssa`/`.ass` files is set for every entry, so the
+    /// field is cleared for entries without a speaker (`update_subtitle_entries()` keeps it). With
+    /// `SpeakerPolicy::Dash` and `SpeakerPolicy::NamePrefix` the speakers are written into the
+    /// text (so entries without a `line` only get a speaker if the format supports it natively).
+    pub fn update_subtitle_entries_with_speakers(&mut self, i: &[SubtitleEntry], policy: SpeakerPolicy) -> Result<()> {
+        let policy = match (policy, &*self) {
+            (SpeakerPolicy::FormatDefault, SubtitleFile::SubStationAlpha(_)) => {
+                let entries: Vec<SubtitleEntry> = i
+                    .iter()
+                    .map(|entry| SubtitleEntry {
+                        speaker: Some(entry.speaker.clone().unwrap_or_default()),
+                        ..entry.clone()
+                    })
+                    .collect();
+                return self.update_subtitle_entries(&entries);
+            }
+            (SpeakerPolicy::FormatDefault, _) => SpeakerPolicy::Dash,
+            (policy, _) => policy,
+        };
+
+        let separator = line_break(self.format()).unwrap_or("\n");
+        let entries: Vec<SubtitleEntry> = i
+            .iter()
+            .map(|entry| {
+                let line = match (policy, entry.speaker.as_deref(), &entry.line) {
+                    (SpeakerPolicy::Dash, Some(speaker), Some(line)) if speaker.split('/').filter(|name| !name.trim().is_empty()).count() > 1 => {
+                        Some(
+                            line.split(separator)
+                                .map(|line| format!("- {}", line))
+                                .collect::<Vec<_>>()
+                                .join(separator),
+                        )
+                    }
+                    (SpeakerPolicy::NamePrefix, Some(speaker), Some(line)) => Some(format!("{}: {}", speaker, line)),
+                    (_, _, line) => line.clone(),
+                };
+
+                SubtitleEntry {
+                    line,
+                    speaker: None,
+                    ..entry.clone()
+                }
+            })
+            .collect();
+
+        self.update_subtitle_entries(&entries)
+    }
+
+    /// A hash of the content of the file, for example as key for caching the results of transformations.
+    ///
+    /// This does not serialize the file. The value is only stable within one build of the program, so it should not
+    /// be saved to disk.
+    pub fn content_fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Where the timestamps and the text of the entry with index `entry_idx` are in the parsed input as byte
+    /// ranges. This can be used to edit the input in place or to highlight an entry in an editor.
+    ///
+    /// Returns `None` for formats other than `.srt` and `.ssa`/`.ass`, for created files and if there is no such entry.
+    pub fn source_ranges(&self, entry_idx: usize) -> Option<SubtitleSourceRanges> {
+        match self {
+            SubtitleFile::SubRipFile(f) => f.source_ranges(entry_idx),
+            SubtitleFile::SubStationAlpha(f) => f.source_ranges(entry_idx),
+            SubtitleFile::VobSubIdxFile(_)
+            | SubtitleFile::VobSubSubFile(_)
+            | SubtitleFile::MicroDVDFile(_)
+            | SubtitleFile::YouTubeSbvFile(_)
+            | SubtitleFile::EbuStlFile(_)
+            | SubtitleFile::BluRayPgsFile(_)
+            | SubtitleFile::Mpl2File(_)
+            | SubtitleFile::TmPlayerFile(_)
+            | SubtitleFile::LrcFile(_)
+            | SubtitleFile::SccFile(_) => None,
+        }
+    }
+
+    /// Merge or split the lines of all entries (see `LineBreakStyle`).
+    ///
+    /// The line breaks are written with the markers of the format (a newline in `.srt`, `.sbv`, `.stl`, `.lrc` and `.scc` files, `\N` in
+    /// `.ssa`/`.ass` files and `|` in `MicroDVD`, `MPL2` and TMPlayer files). Binary formats return `ErrorKind::TextFormatOnly`.
+    pub fn convert_line_breaks(&mut self, style: LineBreakStyle) -> Result<()> {
+        let format = match self {
+            SubtitleFile::SubRipFile(_) => SubtitleFormat::SubRip,
+            SubtitleFile::SubStationAlpha(_) => SubtitleFormat::SubStationAlpha,
+            SubtitleFile::YouTubeSbvFile(_) => SubtitleFormat::YouTubeSbv,
+            SubtitleFile::EbuStlFile(_) => SubtitleFormat::EbuStl,
+            SubtitleFile::Mpl2File(_) => SubtitleFormat::Mpl2,
+            SubtitleFile::TmPlayerFile(_) => SubtitleFormat::TmPlayer,
+            SubtitleFile::LrcFile(_) => SubtitleFormat::Lrc,
+            SubtitleFile::SccFile(_) => SubtitleFormat::Scc,
+            SubtitleFile::MicroDVDFile(f) => {
+                f.convert_line_breaks(style);
+                return Ok(());
+            }
+            SubtitleFile::VobSubIdxFile(_) | SubtitleFile::VobSubSubFile(_) | SubtitleFile::BluRayPgsFile(_) => {
+                return Err(ErrorKind::TextFormatOnly.into())
+            }
+        };
+        let separator = line_break(format).expect("text formats have line breaks");
+
+        let mut entries = self.get_subtitle_entries()?;
+        for line in entries.iter_mut().filter_map(|entry| entry.line.as_mut()) {
+            let lines = line.split(separator).map(str::to_string).collect();
+            *line = style.apply(lines).join(separator);
+        }
+        self.update_subtitle_entries(&entries)
+    }
+
+    /// Shift only the entries for which `predicate` returns `true` by `delta` (see `transform::shift_matching()`).
+    /// Returns the number of shifted entries.
+    pub fn shift_matching<F: FnMut(&SubtitleEntry) -> bool>(&mut self, delta: TimeDelta, predicate: F) -> Result<usize> {
+        let mut entries = self.get_subtitle_entries()?;
+        let shifted = shift_matching(&mut entries, delta, predicate);
+        if shifted > 0 {
+            self.try_update_subtitle_entries(&entries)?;
+        }
+        Ok(shifted)
+    }
+
+    /// A view of the file in which all entries are shifted by `delta`, without changing or cloning the file.
+    pub fn shifted(&self, delta: TimeDelta) -> ShiftedView<'_> {
+        ShiftedView { file: self, delta }
+    }
+
+    /// Works like `get_subtitle_entries()`, but the timespans are given as frame numbers for a video
+    /// with `fps` frames per second.
+    pub fn get_subtitle_entries_frames(&self, fps: f64) -> Result<Vec<SubtitleEntryFrames>> {
+        match self {
+            SubtitleFile::SubRipFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::SubStationAlpha(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::VobSubIdxFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::VobSubSubFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::MicroDVDFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::YouTubeSbvFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::EbuStlFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::BluRayPgsFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::Mpl2File(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::TmPlayerFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::LrcFile(f) => f.get_subtitle_entries_frames(fps),
+            SubtitleFile::SccFile(f) => f.get_subtitle_entries_frames(fps),
+        }
+    }
+
+    /// Works like `update_subtitle_entries()`, but the timespans are given as frame numbers for a
+    /// video with `fps` frames per second.
+    pub fn update_subtitle_entries_frames(&mut self, i: &[SubtitleEntryFrames], fps: f64) -> Result<()> {
+        match self {
+            SubtitleFile::SubRipFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::SubStationAlpha(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::VobSubIdxFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::VobSubSubFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::MicroDVDFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::YouTubeSbvFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::EbuStlFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::BluRayPgsFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::Mpl2File(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::TmPlayerFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::LrcFile(f) => f.update_subtitle_entries_frames(i, fps),
+            SubtitleFile::SccFile(f) => f.update_subtitle_entries_frames(i, fps),
         }
     }
 
@@ -96,10 +504,213 @@ impl SubtitleFile {
             SubtitleFile::VobSubIdxFile(f) => f.to_data(),
             SubtitleFile::VobSubSubFile(f) => f.to_data(),
             SubtitleFile::MicroDVDFile(f) => f.to_data(),
+            SubtitleFile::YouTubeSbvFile(f) => f.to_data(),
+            SubtitleFile::EbuStlFile(f) => f.to_data(),
+            SubtitleFile::BluRayPgsFile(f) => f.to_data(),
+            SubtitleFile::Mpl2File(f) => f.to_data(),
+            SubtitleFile::TmPlayerFile(f) => f.to_data(),
+            SubtitleFile::LrcFile(f) => f.to_data(),
+            SubtitleFile::SccFile(f) => f.to_data(),
+        }
+    }
+
+    /// An estimate of the memory which this file uses (see `MemoryFootprint`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        match self {
+            SubtitleFile::SubRipFile(f) => f.memory_footprint(),
+            SubtitleFile::SubStationAlpha(f) => f.memory_footprint(),
+            SubtitleFile::VobSubIdxFile(f) => f.memory_footprint(),
+            SubtitleFile::VobSubSubFile(f) => f.memory_footprint(),
+            SubtitleFile::MicroDVDFile(f) => f.memory_footprint(),
+            SubtitleFile::YouTubeSbvFile(f) => f.memory_footprint(),
+            SubtitleFile::EbuStlFile(f) => f.memory_footprint(),
+            SubtitleFile::BluRayPgsFile(f) => f.memory_footprint(),
+            SubtitleFile::Mpl2File(f) => f.memory_footprint(),
+            SubtitleFile::TmPlayerFile(f) => f.memory_footprint(),
+            SubtitleFile::LrcFile(f) => f.memory_footprint(),
+            SubtitleFile::SccFile(f) => f.memory_footprint(),
+        }
+    }
+
+    /// Works like `to_data()`, but the line endings, byte order mark, character encoding etc.
+    /// can be chosen with the `options`.
+    pub fn to_data_with(&self, options: &SerializeOptions) -> Result<Vec<u8>> {
+        match self {
+            SubtitleFile::SubRipFile(f) => f.to_data_with(options),
+            SubtitleFile::SubStationAlpha(f) => f.to_data_with(options),
+            SubtitleFile::VobSubIdxFile(f) => f.to_data_with(options),
+            SubtitleFile::VobSubSubFile(f) => f.to_data_with(options),
+            SubtitleFile::MicroDVDFile(f) => f.to_data_with(options),
+            SubtitleFile::YouTubeSbvFile(f) => f.to_data_with(options),
+            SubtitleFile::EbuStlFile(f) => f.to_data_with(options),
+            SubtitleFile::BluRayPgsFile(f) => f.to_data_with(options),
+            SubtitleFile::Mpl2File(f) => f.to_data_with(options),
+            SubtitleFile::TmPlayerFile(f) => f.to_data_with(options),
+            SubtitleFile::LrcFile(f) => f.to_data_with(options),
+            SubtitleFile::SccFile(f) => f.to_data_with(options),
+        }
+    }
+
+    /// A plain text or Markdown transcript of the entries (see `transcript::transcript()`).
+    pub fn to_transcript(&self, options: &TranscriptOptions) -> Result<String> {
+        Ok(crate::transcript::transcript(&self.get_subtitle_entries()?, self.format(), options))
+    }
+
+    /// An HTML page or fragment of the entries (see `html::to_html()`).
+    pub fn to_html(&self, options: &HtmlOptions) -> Result<String> {
+        Ok(crate::html::to_html(&self.get_subtitle_entries()?, self.format(), options))
+    }
+
+    /// The entries as a JSON document (see the `json` module for the schema). Requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String> {
+        crate::json::JsonSubtitles::from_file(self)?.to_json()
+    }
+
+    /// Create a file from a JSON document of `to_json()`, in the format of the document. Only the formats of
+    /// `convert_entries()` can be created. Requires the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn from_json(s: &str) -> Result<SubtitleFile> {
+        crate::json::JsonSubtitles::from_json(s)?.to_file()
+    }
+}
+
+/// Create a file of the format `to` from entries which were read from a file of the format `from` (files are converted
+/// with `SubtitleFile::convert_to_with()`, which also keeps the formatting of `MicroDVD` lines).
+///
+/// The line breaks are converted and between `.srt`, `.ssa`/`.ass` and `MicroDVD` files italic, bold and underlined
+/// text is translated (like `<i>` to `{\\i1}`, see the `markup` module); other formatting is kept as it is. Speakers and tags
+/// are kept if the new format supports them. Only `.srt`, `.ssa`/`.ass`, `.sbv`, `.stl`, `MicroDVD`, `MPL2`, TMPlayer,
+/// `.lrc` and `.scc` files can be created this way; other formats return `ErrorKind::ConversionNotSupported`.
+///
+/// `MicroDVD` files are created for a video with 25 frames per second, use `convert_entries_with_fps()` for other
+/// frame rates.
+pub fn convert_entries(entries: &[SubtitleEntry], from: SubtitleFormat, to: SubtitleFormat) -> Result<SubtitleFile> {
+    convert_entries_with_fps(entries, from, to, 25.0)
+}
+
+/// Works like `convert_entries()`, but `MicroDVD` files are created for a video with `fps` frames per second.
+pub fn convert_entries_with_fps(entries: &[SubtitleEntry], from: SubtitleFormat, to: SubtitleFormat, fps: f64) -> Result<SubtitleFile> {
+    let entries: Vec<SubtitleEntry> = entries
+        .iter()
+        .map(|entry| SubtitleEntry {
+            line: entry.line.as_ref().map(|line| {
+                let line = match line_break(from) {
+                    Some(separator) => line.split(separator).collect::<Vec<_>>().join("\n"),
+                    None => line.clone(),
+                };
+                markup::translate_markup(&line, from, to)
+            }),
+            ..entry.clone()
+        })
+        .collect();
+
+    match to {
+        SubtitleFormat::SubRip => {
+            let mut file = srt::SrtFile::create(
+                entries
+                    .iter()
+                    .map(|entry| (entry.timespan, entry.line.clone().unwrap_or_default()))
+                    .collect(),
+            )?;
+            // the texts were already written, this keeps the tags (and the numbers of `.srt` entries)
+            let entries: Vec<SubtitleEntry> = entries.into_iter().map(|entry| SubtitleEntry { line: None, ..entry }).collect();
+            file.update_subtitle_entries(&entries)?;
+            Ok(SubtitleFile::SubRipFile(file))
+        }
+        SubtitleFormat::SubStationAlpha => Ok(SubtitleFile::SubStationAlpha(ssa::SsaFile::create(&entries)?)),
+        SubtitleFormat::YouTubeSbv => Ok(SubtitleFile::YouTubeSbvFile(sbv::SbvFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?)),
+        SubtitleFormat::EbuStl => Ok(SubtitleFile::EbuStlFile(stl::StlFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?)),
+        SubtitleFormat::Mpl2 => Ok(SubtitleFile::Mpl2File(mpl2::Mpl2File::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?)),
+        SubtitleFormat::TmPlayer => Ok(SubtitleFile::TmPlayerFile(tmp::TmpFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan.start, entry.line.unwrap_or_default()))
+                .collect(),
+        )?)),
+        SubtitleFormat::Lrc => Ok(SubtitleFile::LrcFile(lrc::LrcFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan.start, entry.line.unwrap_or_default()))
+                .collect(),
+        )?)),
+        SubtitleFormat::Scc => Ok(SubtitleFile::SccFile(scc::SccFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?)),
+        SubtitleFormat::MicroDVD => Ok(SubtitleFile::MicroDVDFile(microdvd::MdvdFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+            fps,
+        )?)),
+        SubtitleFormat::VobSubIdx | SubtitleFormat::VobSubSub | SubtitleFormat::BluRayPgs => {
+            Err(ErrorKind::ConversionNotSupported { format: to }.into())
         }
     }
 }
 
+/// The options of `SubtitleFile::convert_to_with()` and `convert()`, which are created like
+/// `ConvertOptions::new().fps(23.976)`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConvertOptions {
+    fps: Option<f64>,
+    screen_size: Option<(u32, u32)>,
+    serialize_options: SerializeOptions,
+}
+
+impl ConvertOptions {
+    /// The default options: `MicroDVD` files are created with the frame rate of a `MicroDVD` source file (or 25
+    /// frames per second) and the data is written like with `to_data()`.
+    pub fn new() -> ConvertOptions {
+        ConvertOptions::default()
+    }
+
+    /// Create `MicroDVD` files for a video with `fps` frames per second.
+    pub fn fps(mut self, fps: f64) -> ConvertOptions {
+        self.fps = Some(fps);
+        self
+    }
+
+    /// Create VobSub and Blu-ray PGS files for a screen with the given width and height. By default the screen size
+    /// of a PGS source file is used, and VobSub images are placed on a DVD screen (720x576 pixels, enlarged if the
+    /// images don't fit). The images are not scaled.
+    pub fn screen_size(mut self, width: u32, height: u32) -> ConvertOptions {
+        self.screen_size = Some((width, height));
+        self
+    }
+
+    /// Write the new file with these options (see `SubtitleFile::to_data_with()`).
+    pub fn serialize_options(mut self, serialize_options: SerializeOptions) -> ConvertOptions {
+        self.serialize_options = serialize_options;
+        self
+    }
+}
+
+/// Write the entries of `file` as a file of the format `target` (see `SubtitleFile::convert_to_with()`), with the
+/// serialize options of `options`.
+pub fn convert(file: &SubtitleFile, target: SubtitleFormat, options: &ConvertOptions) -> Result<Vec<u8>> {
+    file.convert_to_with(target, options)?.to_data_with(&options.serialize_options)
+}
+
 impl From<srt::SrtFile> for SubtitleFile {
     fn from(f: srt::SrtFile) -> SubtitleFile {
         SubtitleFile::SubRipFile(f)
@@ -130,7 +741,124 @@ impl From<microdvd::MdvdFile> for SubtitleFile {
     }
 }
 
+impl From<sbv::SbvFile> for SubtitleFile {
+    fn from(f: sbv::SbvFile) -> SubtitleFile {
+        SubtitleFile::YouTubeSbvFile(f)
+    }
+}
+
+impl From<stl::StlFile> for SubtitleFile {
+    fn from(f: stl::StlFile) -> SubtitleFile {
+        SubtitleFile::EbuStlFile(f)
+    }
+}
+
+impl From<pgs::PgsFile> for SubtitleFile {
+    fn from(f: pgs::PgsFile) -> SubtitleFile {
+        SubtitleFile::BluRayPgsFile(f)
+    }
+}
+
+impl From<mpl2::Mpl2File> for SubtitleFile {
+    fn from(f: mpl2::Mpl2File) -> SubtitleFile {
+        SubtitleFile::Mpl2File(f)
+    }
+}
+
+impl From<tmp::TmpFile> for SubtitleFile {
+    fn from(f: tmp::TmpFile) -> SubtitleFile {
+        SubtitleFile::TmPlayerFile(f)
+    }
+}
+
+impl From<lrc::LrcFile> for SubtitleFile {
+    fn from(f: lrc::LrcFile) -> SubtitleFile {
+        SubtitleFile::LrcFile(f)
+    }
+}
+
+impl From<scc::SccFile> for SubtitleFile {
+    fn from(f: scc::SccFile) -> SubtitleFile {
+        SubtitleFile::SccFile(f)
+    }
+}
+
+/// A file whose entries are read with a time shift (see `SubtitleFile::shifted()`).
+///
+/// The shift is applied when the entries are read, so previewing a shift is cheap. Use `to_file()` to get a
+/// shifted copy of the file.
+#[derive(Debug, Clone, Copy)]
+pub struct ShiftedView<'a> {
+    file: &'a SubtitleFile,
+    delta: TimeDelta,
+}
+
+impl<'a> ShiftedView<'a> {
+    /// The underlying (unchanged) file.
+    pub fn file(&self) -> &'a SubtitleFile {
+        self.file
+    }
+
+    /// The time shift of the entries.
+    pub fn delta(&self) -> TimeDelta {
+        self.delta
+    }
+
+    /// A view which is shifted by `delta` in addition to the shift of this view.
+    pub fn shifted(&self, delta: TimeDelta) -> ShiftedView<'a> {
+        ShiftedView {
+            file: self.file,
+            delta: self.delta + delta,
+        }
+    }
+
+    /// The entries of the file with shifted timespans.
+    pub fn get_subtitle_entries(&self) -> Result<Vec<SubtitleEntry>> {
+        let mut entries = self.file.get_subtitle_entries()?;
+        for entry in &mut entries {
+            entry.timespan += self.delta;
+        }
+        Ok(entries)
+    }
+
+    /// Works like `get_subtitle_entries()`, but the timespans are given as frame numbers for a video
+    /// with `fps` frames per second.
+    pub fn get_subtitle_entries_frames(&self, fps: f64) -> Result<Vec<SubtitleEntryFrames>> {
+        Ok(self
+            .get_subtitle_entries()?
+            .into_iter()
+            .map(|entry| SubtitleEntryFrames::from_entry(entry, fps))
+            .collect())
+    }
+
+    /// A copy of the file with the shift applied. This fails for formats whose entries can't be updated (like
+    /// VobSub `.sub` files).
+    pub fn to_file(&self) -> Result<SubtitleFile> {
+        let mut file = self.file.clone();
+        if self.delta != TimeDelta::from_msecs(0) {
+            file.update_subtitle_entries(&self.get_subtitle_entries()?)?;
+        }
+        Ok(file)
+    }
+}
+
 impl SubtitleFormat {
+    /// All formats, in the order of their declaration.
+    pub const ALL: [SubtitleFormat; 12] = [
+        SubtitleFormat::SubRip,
+        SubtitleFormat::SubStationAlpha,
+        SubtitleFormat::VobSubIdx,
+        SubtitleFormat::VobSubSub,
+        SubtitleFormat::MicroDVD,
+        SubtitleFormat::YouTubeSbv,
+        SubtitleFormat::EbuStl,
+        SubtitleFormat::BluRayPgs,
+        SubtitleFormat::Mpl2,
+        SubtitleFormat::TmPlayer,
+        SubtitleFormat::Lrc,
+        SubtitleFormat::Scc,
+    ];
+
     /// Get a descriptive string for the format like `".srt (SubRip)"`.
     pub fn get_name(&self) -> &'static str {
         match *self {
@@ -139,14 +867,125 @@ impl SubtitleFormat {
             SubtitleFormat::VobSubIdx => ".idx (VobSub)",
             SubtitleFormat::VobSubSub => ".sub (VobSub)",
             SubtitleFormat::MicroDVD => ".sub (MicroDVD)",
+            SubtitleFormat::YouTubeSbv => ".sbv (YouTube)",
+            SubtitleFormat::EbuStl => ".stl (EBU-STL)",
+            SubtitleFormat::BluRayPgs => ".sup (Blu-ray PGS)",
+            SubtitleFormat::Mpl2 => ".txt (MPL2)",
+            SubtitleFormat::TmPlayer => ".txt (TMPlayer)",
+            SubtitleFormat::Lrc => ".lrc (LRC lyrics)",
+            SubtitleFormat::Scc => ".scc (Scenarist/CEA-608)",
+        }
+    }
+
+    /// Get the operations which are supported by this format.
+    ///
+    /// Generic tools can use this to disable operations for a file type before running into errors.
+    pub fn capabilities(&self) -> SubtitleFormatCapabilities {
+        let (text, styling, images, entry_insertion, timespan_end, creation_from_scratch, entry_tags) = match *self {
+            SubtitleFormat::SubRip => (true, true, false, false, true, true, true),
+            SubtitleFormat::SubStationAlpha => (true, true, false, false, true, true, true),
+            SubtitleFormat::VobSubIdx => (false, false, false, false, false, true, false),
+            SubtitleFormat::VobSubSub => (false, false, true, false, true, true, false),
+            SubtitleFormat::MicroDVD => (true, true, false, false, true, true, false),
+            SubtitleFormat::YouTubeSbv => (true, false, false, false, true, true, false),
+            SubtitleFormat::EbuStl => (true, true, false, false, true, true, false),
+            SubtitleFormat::BluRayPgs => (false, false, true, false, true, true, false),
+            SubtitleFormat::Mpl2 => (true, false, false, false, true, true, false),
+            SubtitleFormat::TmPlayer => (true, false, false, false, false, true, false),
+            SubtitleFormat::Lrc => (true, false, false, false, false, true, false),
+            SubtitleFormat::Scc => (true, false, false, false, true, true, false),
+        };
+
+        SubtitleFormatCapabilities {
+            text,
+            styling,
+            images,
+            entry_insertion,
+            timespan_end,
+            creation_from_scratch,
+            entry_tags,
         }
     }
 }
 
+/// The operations which are supported by a subtitle format (see `SubtitleFormat::capabilities()`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubtitleFormatCapabilities {
+    /// The entries contain text (`SubtitleEntry::line`), which can be read and changed.
+    pub text: bool,
+
+    /// The text can be styled (italics, colors, ...).
+    pub styling: bool,
+
+    /// The entries contain images (`SubtitleEntry::image`).
+    pub images: bool,
+
+    /// New entries can be inserted between the existing ones with `update_subtitle_entries()`. No format supports this
+    /// yet: the entries are updated in place, so their number has to stay the same (`ErrorKind::EntryCountMismatch`).
+    /// Files with other entries can be created from scratch instead (see `creation_from_scratch`).
+    pub entry_insertion: bool,
+
+    /// The end of the timespans is stored. In `.idx` files a subtitle is shown until the next timestamp.
+    pub timespan_end: bool,
+
+    /// Files can be created from scratch (like `SrtFile::create()` or `VobFile::create()`).
+    pub creation_from_scratch: bool,
+
+    /// The tags of the entries (`SubtitleEntry::tags`) are kept and can be written with `SerializeOptions::write_tags()`.
+    /// All other formats return entries without tags.
+    pub entry_tags: bool,
+}
+
+#[test]
+fn test_subtitle_format_capabilities() {
+    assert!(SubtitleFormat::SubRip.capabilities().text);
+    assert!(!SubtitleFormat::SubRip.capabilities().images);
+    assert!(SubtitleFormat::VobSubSub.capabilities().images);
+    assert!(!SubtitleFormat::VobSubIdx.capabilities().timespan_end);
+    assert!(SubtitleFormat::MicroDVD.capabilities().creation_from_scratch);
+    assert!(SubtitleFormat::BluRayPgs.capabilities().creation_from_scratch);
+    assert!(SubtitleFormat::SubStationAlpha.capabilities().entry_tags);
+
+    // the flag matches whether a file accepts an additional entry
+    let files = [
+        (SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000\nA\n"),
+        (
+            SubtitleFormat::SubStationAlpha,
+            "[Events]\nFormat: Start, End, Text\nDialogue: 0:00:01.00,0:00:02.00,A\n",
+        ),
+        (SubtitleFormat::MicroDVD, "{0}{25}A\n"),
+        (SubtitleFormat::YouTubeSbv, "0:00:01.000,0:00:02.000\nA\n"),
+        (SubtitleFormat::Mpl2, "[10][20]A\n"),
+        (SubtitleFormat::TmPlayer, "00:00:01:A\n"),
+        (SubtitleFormat::Lrc, "[00:01.00]A\n"),
+    ];
+    for &(format, data) in &files {
+        let mut file = parse_str(format, data, 25.0).unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+        entries.push(entries[0].clone());
+        assert_eq!(file.update_subtitle_entries(&entries).is_ok(), format.capabilities().entry_insertion);
+    }
+}
+
 #[test]
 fn test_subtitle_format_by_extension() {
     // this shows how the input paramter can be crated from scratch
     assert_eq!(get_subtitle_format_by_extension(Some(OsStr::new("srt"))), Some(SubtitleFormat::SubRip));
+    assert_eq!(
+        get_subtitle_format_by_extension(Some(OsStr::new("sbv"))),
+        Some(SubtitleFormat::YouTubeSbv)
+    );
+    assert_eq!(get_subtitle_format_by_extension(Some(OsStr::new("txt"))), None);
+    assert_eq!(get_subtitle_format_by_extension(Some(OsStr::new("lrc"))), Some(SubtitleFormat::Lrc));
+    assert_eq!(
+        get_subtitle_format(Some(OsStr::new("txt")), b"\n[10][25]Hello"),
+        Some(SubtitleFormat::Mpl2)
+    );
+    assert_eq!(
+        get_subtitle_format(Some(OsStr::new("txt")), b"00:01:23:Hello"),
+        Some(SubtitleFormat::TmPlayer)
+    );
+    assert_eq!(get_subtitle_format(Some(OsStr::new("txt")), b"Hello"), None);
 }
 
 /// Returns the subtitle format by the file extension.
@@ -155,19 +994,27 @@ fn test_subtitle_format_by_extension() {
 /// both work. Returns `None` if subtitle format could not be recognized.
 ///
 /// Because the `.sub` file extension is ambiguous (both `MicroDVD` and `VobSub` use that extension) the
-/// function will return `None` in that case. Instead, use the content-aware `get_subtitle_format`
+/// function will return `None` in that case (the same goes for `.txt` files, which may be `MPL2` or TMPlayer files). Instead, use the content-aware `get_subtitle_format`
 /// to handle this case correctly.
 ///
 /// `Option` is used to simplify handling with `PathBuf::extension()`.
 pub fn get_subtitle_format_by_extension(extension: Option<&OsStr>) -> Option<SubtitleFormat> {
-    let _ext_opt: Option<&OsStr> = extension.into();
-
-    if _ext_opt == Some(OsStr::new("srt")) {
+    if extension == Some(OsStr::new("srt")) {
         Some(SubtitleFormat::SubRip)
-    } else if _ext_opt == Some(OsStr::new("ssa")) || _ext_opt == Some(OsStr::new("ass")) {
+    } else if extension == Some(OsStr::new("ssa")) || extension == Some(OsStr::new("ass")) {
         Some(SubtitleFormat::SubStationAlpha)
-    } else if _ext_opt == Some(OsStr::new("idx")) {
+    } else if extension == Some(OsStr::new("idx")) {
         Some(SubtitleFormat::VobSubIdx)
+    } else if extension == Some(OsStr::new("sbv")) {
+        Some(SubtitleFormat::YouTubeSbv)
+    } else if extension == Some(OsStr::new("stl")) {
+        Some(SubtitleFormat::EbuStl)
+    } else if extension == Some(OsStr::new("sup")) {
+        Some(SubtitleFormat::BluRayPgs)
+    } else if extension == Some(OsStr::new("lrc")) {
+        Some(SubtitleFormat::Lrc)
+    } else if extension == Some(OsStr::new("scc")) {
+        Some(SubtitleFormat::Scc)
     } else {
         None
     }
@@ -183,6 +1030,12 @@ pub fn is_valid_extension_for_subtitle_format(extension: Option<&OsStr>, format:
         SubtitleFormat::VobSubIdx => extension == Some(OsStr::new("idx")),
         SubtitleFormat::VobSubSub => extension == Some(OsStr::new("sub")),
         SubtitleFormat::MicroDVD => extension == Some(OsStr::new("sub")),
+        SubtitleFormat::YouTubeSbv => extension == Some(OsStr::new("sbv")),
+        SubtitleFormat::EbuStl => extension == Some(OsStr::new("stl")),
+        SubtitleFormat::BluRayPgs => extension == Some(OsStr::new("sup")),
+        SubtitleFormat::Mpl2 | SubtitleFormat::TmPlayer => extension == Some(OsStr::new("txt")),
+        SubtitleFormat::Lrc => extension == Some(OsStr::new("lrc")),
+        SubtitleFormat::Scc => extension == Some(OsStr::new("scc")),
     }
 }
 
@@ -213,6 +1066,19 @@ pub fn get_subtitle_format(extension: Option<&OsStr>, content: &[u8]) -> Option<
         } else {
             Some(SubtitleFormat::MicroDVD)
         }
+    } else if extension == Some(OsStr::new("txt")) {
+        let text = String::from_utf8_lossy(content);
+        let first_line = text
+            .lines()
+            .map(|line| line.trim_start_matches('\u{feff}'))
+            .find(|line| !line.trim().is_empty());
+        if first_line.map_or(false, mpl2::Mpl2File::is_mpl2_line) {
+            Some(SubtitleFormat::Mpl2)
+        } else if first_line.map_or(false, tmp::TmpFile::is_tmp_line) {
+            Some(SubtitleFormat::TmPlayer)
+        } else {
+            None
+        }
     } else {
         get_subtitle_format_by_extension(extension)
     }
@@ -238,21 +1104,59 @@ pub fn parse_str(format: SubtitleFormat, content: &str, fps: f64) -> Result<Subt
         SubtitleFormat::SubRip => Ok(srt::SrtFile::parse(content)?.into()),
         SubtitleFormat::SubStationAlpha => Ok(ssa::SsaFile::parse(content)?.into()),
         SubtitleFormat::VobSubIdx => Ok(idx::IdxFile::parse(content)?.into()),
-        SubtitleFormat::VobSubSub => Err(ErrorKind::TextFormatOnly.into()),
+        SubtitleFormat::VobSubSub | SubtitleFormat::EbuStl | SubtitleFormat::BluRayPgs => Err(ErrorKind::TextFormatOnly.into()),
         SubtitleFormat::MicroDVD => Ok(microdvd::MdvdFile::parse(content, fps)?.into()),
+        SubtitleFormat::YouTubeSbv => Ok(sbv::SbvFile::parse(content)?.into()),
+        SubtitleFormat::Mpl2 => Ok(mpl2::Mpl2File::parse(content)?.into()),
+        SubtitleFormat::TmPlayer => Ok(tmp::TmpFile::parse(content)?.into()),
+        SubtitleFormat::Lrc => Ok(lrc::LrcFile::parse(content)?.into()),
+        SubtitleFormat::Scc => Ok(scc::SccFile::parse(content)?.into()),
     }
 }
 
-/// Helper function for text subtitles for byte-to-text decoding (use `None` for automatic detection).
-fn decode_bytes_to_string(content: &[u8], encoding: Option<&'static Encoding>) -> Result<String> {
-    let det_encoding = match encoding {
-        Some(encoding) => encoding,
+/// Parse text subtitles like `parse_str()` and return the problems which did not prevent parsing: the warnings of
+/// the parser (only `.srt` and `MicroDVD` files have them, see `SrtFile::warnings()`) and the ones of
+/// `warnings::check_entries()`, ordered by their entries.
+pub fn parse_str_with_warnings(format: SubtitleFormat, content: &str, fps: f64) -> Result<(SubtitleFile, Vec<ParseWarning>)> {
+    let file = parse_str(format, content, fps)?;
+    let warnings = file.collect_warnings(content)?;
+    Ok((file, warnings))
+}
+
+/// Parse all subtitle formats like `parse_bytes()` and return the problems which did not prevent parsing (see
+/// `parse_str_with_warnings()`, `VobSub` files also report images which can not be decoded). The line numbers of the
+/// warnings of binary formats are `0`.
+pub fn parse_bytes_with_warnings(
+    format: SubtitleFormat,
+    content: &[u8],
+    encoding: Option<&'static Encoding>,
+    fps: f64,
+) -> Result<(SubtitleFile, Vec<ParseWarning>)> {
+    match format {
+        SubtitleFormat::VobSubSub | SubtitleFormat::EbuStl | SubtitleFormat::BluRayPgs => {
+            let file = parse_bytes(format, content, encoding, fps)?;
+            let warnings = file.collect_warnings("")?;
+            Ok((file, warnings))
+        }
+        _ => parse_str_with_warnings(format, &decode_bytes_to_string(content, encoding)?, fps),
+    }
+}
+
+/// Returns the given encoding or detects it with `chardet` (for `None`).
+pub(crate) fn detect_encoding(content: &[u8], encoding: Option<&'static Encoding>) -> Result<&'static Encoding> {
+    match encoding {
+        Some(encoding) => Ok(encoding),
         None => {
             let (charset, _, _) = detect(content);
             let encoding_name = charset2encoding(&charset);
-            Encoding::for_label_no_replacement(encoding_name.as_bytes()).ok_or(ErrorKind::EncodingDetectionError)?
+            Ok(Encoding::for_label_no_replacement(encoding_name.as_bytes()).ok_or(ErrorKind::EncodingDetectionError)?)
         }
-    };
+    }
+}
+
+/// Helper function for text subtitles for byte-to-text decoding (use `None` for automatic detection).
+fn decode_bytes_to_string(content: &[u8], encoding: Option<&'static Encoding>) -> Result<String> {
+    let det_encoding = detect_encoding(content, encoding)?;
 
     let (decoded, _, replaced) = det_encoding.decode(content);
     if replaced {
@@ -270,7 +1174,8 @@ fn decode_bytes_to_string(content: &[u8], encoding: Option<&'static Encoding>) -
 /// a specific format that has no additional parameters, you can use the `parse` function of
 /// the respective `***File` struct.
 ///
-/// `encoding`: to parse a text-based subtitle format, a character encoding is needed (use `None` for auto-detection by `chardet`)
+/// `encoding`: to parse a text-based subtitle format, a character encoding is needed (use `None` for auto-detection by `chardet`).
+/// `parse_bytes_auto()` detects the encoding more reliably and returns it.
 ///
 /// `fps`: this parameter is used for `MicroDVD` `.sub` files. These files do not store timestamps in
 /// seconds/minutes/... but in frame numbers. So the timing `0 to 30` means "show subtitle for one second"
@@ -283,5 +1188,639 @@ pub fn parse_bytes(format: SubtitleFormat, content: &[u8], encoding: Option<&'st
         SubtitleFormat::VobSubIdx => Ok(idx::IdxFile::parse(&decode_bytes_to_string(content, encoding)?)?.into()),
         SubtitleFormat::VobSubSub => Ok(vobsub::VobFile::parse(content)?.into()),
         SubtitleFormat::MicroDVD => Ok(microdvd::MdvdFile::parse(&decode_bytes_to_string(content, encoding)?, fps)?.into()),
+        SubtitleFormat::YouTubeSbv => Ok(sbv::SbvFile::parse(&decode_bytes_to_string(content, encoding)?)?.into()),
+        SubtitleFormat::EbuStl => Ok(stl::StlFile::parse(content)?.into()),
+        SubtitleFormat::BluRayPgs => Ok(pgs::PgsFile::parse(content)?.into()),
+        SubtitleFormat::Mpl2 => Ok(mpl2::Mpl2File::parse(&decode_bytes_to_string(content, encoding)?)?.into()),
+        SubtitleFormat::TmPlayer => Ok(tmp::TmpFile::parse(&decode_bytes_to_string(content, encoding)?)?.into()),
+        SubtitleFormat::Lrc => Ok(lrc::LrcFile::parse(&decode_bytes_to_string(content, encoding)?)?.into()),
+        SubtitleFormat::Scc => Ok(scc::SccFile::parse(&decode_bytes_to_string(content, encoding)?)?.into()),
+    }
+}
+
+/// An estimate of the memory which a file uses (see `SubtitleFile::memory_footprint()`), for example to limit the
+/// files a server keeps in memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MemoryFootprint {
+    /// The number of subtitle entries.
+    pub entry_count: usize,
+
+    /// The bytes of the texts of the entries (with speakers and tags).
+    pub text_bytes: usize,
+
+    /// The bytes of the parts which are only kept to write the file again, like headers, styles, comments and
+    /// whitespace.
+    pub filler_bytes: usize,
+
+    /// The bytes of the image data of `VobSub` and Blu-ray PGS files. Clones of a file share this data.
+    pub image_bytes: usize,
+
+    /// All bytes of the file, with the bytes above, the unused capacity of the buffers and the bookkeeping of the
+    /// file (like timestamps).
+    pub total_bytes: usize,
+}
+
+impl MemoryFootprint {
+    /// The footprint of a file without its buffers.
+    pub(crate) fn of<T>(_file: &T) -> MemoryFootprint {
+        MemoryFootprint {
+            total_bytes: size_of::<T>(),
+            ..MemoryFootprint::default()
+        }
+    }
+
+    /// Add the buffer of a vector (but not the buffers of its elements).
+    pub(crate) fn add_vec<T>(&mut self, v: &Vec<T>) {
+        self.total_bytes += v.capacity() * size_of::<T>();
+    }
+
+    /// Add a text of an entry.
+    pub(crate) fn add_text(&mut self, text: &String) {
+        self.text_bytes += text.len();
+        self.total_bytes += text.capacity();
+    }
+
+    /// Add a part which is only kept to write the file again.
+    pub(crate) fn add_filler(&mut self, filler: &String) {
+        self.filler_bytes += filler.len();
+        self.total_bytes += filler.capacity();
+    }
+
+    /// Add image data.
+    pub(crate) fn add_image(&mut self, data: &[u8]) {
+        self.image_bytes += data.len();
+        self.total_bytes += data.len();
+    }
+}
+
+/// Information about the decoding in `parse_bytes_lossy()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodingReport {
+    /// The encoding which was used to decode the content (`None` for binary formats).
+    pub encoding: Option<&'static Encoding>,
+
+    /// The byte positions (in the content) of the malformed sequences, which were replaced by `U+FFFD`.
+    pub replaced: Vec<usize>,
+}
+
+impl DecodingReport {
+    /// The number of replaced characters.
+    pub fn replaced_count(&self) -> usize {
+        self.replaced.len()
+    }
+}
+
+/// Decode the bytes and replace malformed sequences with `U+FFFD`. Returns the decoded text and the byte
+/// positions of the malformed sequences.
+pub(crate) fn decode_bytes_to_string_lossy(content: &[u8], encoding: &'static Encoding) -> (String, Vec<usize>) {
+    let mut decoder = encoding.new_decoder();
+    let mut decoded = String::with_capacity(decoder.max_utf8_buffer_length_without_replacement(content.len()).unwrap_or(content.len()));
+    let mut replaced = Vec::new();
+    let mut read_total = 0;
+    loop {
+        let (result, read) = decoder.decode_to_string_without_replacement(&content[read_total..], &mut decoded, true);
+        read_total += read;
+        match result {
+            DecoderResult::InputEmpty => return (decoded, replaced),
+            DecoderResult::OutputFull => decoded.reserve(content.len() - read_total + 16),
+            DecoderResult::Malformed(bad_len, after_len) => {
+                replaced.push(read_total - after_len as usize - bad_len as usize);
+                decoded.push('\u{FFFD}');
+            }
+        }
+    }
+}
+
+/// Works like `parse_bytes`, but malformed byte sequences are replaced by `U+FFFD` instead of returning
+/// `ErrorKind::DecodingError`. The returned report contains the positions of the replaced characters.
+pub fn parse_bytes_lossy(
+    format: SubtitleFormat,
+    content: &[u8],
+    encoding: Option<&'static Encoding>,
+    fps: f64,
+) -> Result<(SubtitleFile, DecodingReport)> {
+    if format == SubtitleFormat::VobSubSub || format == SubtitleFormat::EbuStl || format == SubtitleFormat::BluRayPgs {
+        let report = DecodingReport {
+            encoding: None,
+            replaced: Vec::new(),
+        };
+        return Ok((parse_bytes(format, content, None, fps)?, report));
+    }
+
+    let encoding = detect_encoding(content, encoding)?;
+    let (decoded, replaced) = decode_bytes_to_string_lossy(content, encoding);
+    let report = DecodingReport {
+        encoding: Some(encoding),
+        replaced,
+    };
+    Ok((parse_str(format, &decoded, fps)?, report))
+}
+
+/// Works like `parse_bytes`, but tries the `encodings` in the given order (like UTF-8, then Windows-1252) until
+/// the content can be decoded without errors. Returns the encoding which succeeded (`None` for binary formats).
+///
+/// Only decoding errors lead to the next encoding; if the decoded text can not be parsed, the parse error is
+/// returned. If no encoding works, `ErrorKind::DecodingError` is returned.
+pub fn parse_bytes_with_fallbacks(
+    format: SubtitleFormat,
+    content: &[u8],
+    encodings: &[&'static Encoding],
+    fps: f64,
+) -> Result<(SubtitleFile, Option<&'static Encoding>)> {
+    if format == SubtitleFormat::VobSubSub || format == SubtitleFormat::EbuStl || format == SubtitleFormat::BluRayPgs {
+        return Ok((parse_bytes(format, content, None, fps)?, None));
+    }
+
+    for &encoding in encodings {
+        match decode_bytes_to_string(content, Some(encoding)) {
+            Ok(decoded) => return Ok((parse_str(format, &decoded, fps)?, Some(encoding))),
+            Err(ref e) if e.kind() == ErrorKind::DecodingError => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(ErrorKind::DecodingError.into())
+}
+
+/// Works like `parse_bytes`, but the encoding is detected from the content (see `sniff_encoding()`) and returned
+/// (`None` for binary formats).
+pub fn parse_bytes_auto(format: SubtitleFormat, content: &[u8], fps: f64) -> Result<(SubtitleFile, Option<&'static Encoding>)> {
+    if format == SubtitleFormat::VobSubSub || format == SubtitleFormat::EbuStl || format == SubtitleFormat::BluRayPgs {
+        return Ok((parse_bytes(format, content, None, fps)?, None));
+    }
+
+    let encoding = sniff_encoding(content);
+    Ok((parse_str(format, &decode_bytes_to_string(content, Some(encoding))?, fps)?, Some(encoding)))
+}
+
+/// Guess the encoding of a text. The checks are tried in this order:
+///
+/// 1. a byte order mark (UTF-8, UTF-16LE or UTF-16BE)
+/// 2. UTF-16 without byte order mark (many zero bytes at either the odd or the even positions, which would be valid
+///    UTF-8 too)
+/// 3. valid UTF-8
+/// 4. the guess of `chardet`, if it is not a Latin encoding (like Windows-1251 or Shift_JIS) and the content can be
+///    decoded with it
+/// 5. Windows-1250 (Central European) or Windows-1252 (Western European), whichever gives more letters inside of
+///    words (like `ł` and `ś` in `łódź`), Windows-1252 if there is no difference
+pub fn sniff_encoding(content: &[u8]) -> &'static Encoding {
+    use encoding_rs::{UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1252};
+
+    if let Some((encoding, _)) = Encoding::for_bom(content) {
+        return encoding;
+    }
+    let zeros = |parity: usize| content.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let half = content.len() / 2;
+    if half > 0 && zeros(1) * 2 > half && zeros(0) == 0 {
+        return UTF_16LE;
+    }
+    if half > 0 && zeros(0) * 2 > half && zeros(1) == 0 {
+        return UTF_16BE;
+    }
+    if std::str::from_utf8(content).is_ok() {
+        return UTF_8;
+    }
+
+    let (charset, _, _) = detect(content);
+    if let Some(encoding) = Encoding::for_label_no_replacement(charset2encoding(&charset).as_bytes()) {
+        // `chardet` often confuses the Latin encodings, they are handled below
+        let latin = [
+            UTF_8,
+            WINDOWS_1250,
+            WINDOWS_1252,
+            encoding_rs::WINDOWS_1254,
+            encoding_rs::WINDOWS_1257,
+            encoding_rs::WINDOWS_1258,
+            encoding_rs::ISO_8859_2,
+            encoding_rs::ISO_8859_3,
+            encoding_rs::ISO_8859_4,
+            encoding_rs::ISO_8859_10,
+            encoding_rs::ISO_8859_13,
+            encoding_rs::ISO_8859_14,
+            encoding_rs::ISO_8859_15,
+            encoding_rs::ISO_8859_16,
+        ];
+        if !latin.contains(&encoding) && encoding.decode_without_bom_handling_and_without_replacement(content).is_some() {
+            return encoding;
+        }
+    }
+
+    // the number of non-ASCII letters between other letters
+    let word_letters = |encoding: &'static Encoding| {
+        let (decoded, _) = encoding.decode_without_bom_handling(content);
+        let chars: Vec<char> = decoded.chars().collect();
+        chars
+            .windows(3)
+            .filter(|w| !w[1].is_ascii() && w[1].is_alphabetic() && (w[0].is_alphabetic() || w[2].is_alphabetic()))
+            .count()
+    };
+    if word_letters(WINDOWS_1250) > word_letters(WINDOWS_1252) {
+        WINDOWS_1250
+    } else {
+        WINDOWS_1252
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimePoint;
+
+    #[test]
+    fn convert_to_test() {
+        let data = "[Events]\nFormat: Start, End, Name, Text\nDialogue: 0:00:01.00,0:00:02.00,Alice,Hello\\NWorld\n";
+        let file = parse_str(SubtitleFormat::SubStationAlpha, data, 25.0).unwrap();
+        let srt_file = file.convert_to(SubtitleFormat::SubRip).unwrap();
+        assert_eq!(srt_file.format(), SubtitleFormat::SubRip);
+        assert_eq!(
+            String::from_utf8(srt_file.to_data().unwrap()).unwrap(),
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\nWorld\n\n"
+        );
+
+        let ssa_file = srt_file.convert_to(SubtitleFormat::SubStationAlpha).unwrap();
+        let data_string = String::from_utf8(ssa_file.to_data().unwrap()).unwrap();
+        assert!(data_string.ends_with("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\\NWorld\n"));
+
+        let mdvd_file = convert_entries_with_fps(&file.get_subtitle_entries().unwrap(), file.format(), SubtitleFormat::MicroDVD, 50.0).unwrap();
+        assert_eq!(String::from_utf8(mdvd_file.to_data().unwrap()).unwrap(), "{50}{100}Hello|World");
+        let options = ConvertOptions::new().fps(50.0);
+        assert_eq!(file.convert_to_with(SubtitleFormat::MicroDVD, &options).unwrap(), mdvd_file);
+        assert!(file.convert_to(SubtitleFormat::BluRayPgs).is_err());
+
+        // `convert_to()` keeps the frame rate and the formatting of `MicroDVD` files like `convert()`
+        let mdvd = parse_str(SubtitleFormat::MicroDVD, "{50}{100}{y:i}Hello|World", 50.0).unwrap();
+        for &format in &[SubtitleFormat::MicroDVD, SubtitleFormat::SubRip, SubtitleFormat::SubStationAlpha] {
+            assert_eq!(
+                mdvd.convert_to(format).unwrap().to_data().unwrap(),
+                convert(&mdvd, format, &ConvertOptions::new()).unwrap()
+            );
+        }
+        assert_eq!(
+            String::from_utf8(mdvd.convert_to(SubtitleFormat::MicroDVD).unwrap().to_data().unwrap()).unwrap(),
+            "{50}{100}{y:i}Hello|World"
+        );
+    }
+
+    #[test]
+    fn convert_test() {
+        let data = "[Events]\nFormat: Start, End, Name, Text\nDialogue: 0:00:01.00,0:00:02.00,Alice,Hello\\NWorld\n";
+        let file = parse_str(SubtitleFormat::SubStationAlpha, data, 25.0).unwrap();
+        let to_string = |data: Vec<u8>| String::from_utf8(data).unwrap();
+
+        let srt = convert(&file, SubtitleFormat::SubRip, &ConvertOptions::new()).unwrap();
+        assert_eq!(to_string(srt), "1\n00:00:01,000 --> 00:00:02,000\nHello\nWorld\n\n");
+
+        let options = ConvertOptions::new().serialize_options(SerializeOptions::new().newline(crate::serialize::NewlineStyle::CrLf));
+        let mpl2 = convert(&file, SubtitleFormat::Mpl2, &options).unwrap();
+        assert_eq!(to_string(mpl2), "[10][20]Hello|World\r\n");
+
+        let mdvd = parse_str(SubtitleFormat::MicroDVD, "{50}{100}Hello|World", 50.0).unwrap();
+        assert_eq!(
+            to_string(convert(&mdvd, SubtitleFormat::MicroDVD, &ConvertOptions::new()).unwrap()),
+            "{50}{100}Hello|World"
+        );
+        // every line of a `MicroDVD` subtitle is its own entry
+        let ssa = convert(&mdvd, SubtitleFormat::SubStationAlpha, &ConvertOptions::new()).unwrap();
+        assert!(to_string(ssa).ends_with("0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,World\n"));
+
+        // the styles are translated
+        let mdvd_italic = parse_str(SubtitleFormat::MicroDVD, "{50}{100}{y:i}Hello|World", 50.0).unwrap();
+        let srt = convert(&mdvd_italic, SubtitleFormat::SubRip, &ConvertOptions::new()).unwrap();
+        assert_eq!(
+            to_string(srt),
+            "1\n00:00:01,000 --> 00:00:02,000\n<i>Hello</i>\n\n2\n00:00:01,000 --> 00:00:02,000\nWorld\n\n"
+        );
+        let srt = parse_str(
+            SubtitleFormat::SubRip,
+            "1\n00:00:01,000 --> 00:00:02,000\n<b>Hello</b>\n<i>World</i>\n",
+            25.0,
+        )
+        .unwrap();
+        let mdvd = convert(&srt, SubtitleFormat::MicroDVD, &ConvertOptions::new()).unwrap();
+        assert_eq!(to_string(mdvd), "{25}{50}{y:b}Hello|{y:i}World");
+        let ssa = convert(&srt, SubtitleFormat::SubStationAlpha, &ConvertOptions::new()).unwrap();
+        assert!(to_string(ssa).ends_with(",{\\b1}Hello{\\b0}\\N{\\i1}World\n"));
+
+        let vob_file = SubtitleFile::VobSubSubFile(vobsub::VobFile::parse(&[]).unwrap());
+        let err = convert(&vob_file, SubtitleFormat::SubRip, &ConvertOptions::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TextFormatOnly);
+    }
+
+    #[test]
+    fn convert_images_test() {
+        // a 4x2 image with white and transparent pixels
+        let white = [255, 255, 255, 255];
+        let data: Vec<u8> = [white, [0; 4], white, [0; 4], white, white, [0; 4], [0; 4]].concat();
+        let image = SubtitleImage {
+            x: 10,
+            y: 500,
+            width: 4,
+            height: 2,
+            data,
+        };
+        let timespan = TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2000));
+        let (_, vob_file) = vobsub::VobFile::create(vec![(timespan, image.clone())], 720, 576).unwrap();
+        let vob_file = SubtitleFile::VobSubSubFile(vob_file);
+
+        let pgs_file = vob_file.convert_to(SubtitleFormat::BluRayPgs).unwrap();
+        match &pgs_file {
+            SubtitleFile::BluRayPgsFile(f) => assert_eq!(f.screen_size(), Some((720, 576))),
+            _ => panic!("expected a PGS file"),
+        }
+        let entries = pgs_file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timespan.start, timespan.start);
+        assert_eq!(entries[0].image, Some(image.clone()));
+
+        let options = ConvertOptions::new().screen_size(1920, 1080);
+        let pgs_file = vob_file.convert_to_with(SubtitleFormat::BluRayPgs, &options).unwrap();
+        let vob_file = pgs_file.convert_to(SubtitleFormat::VobSubSub).unwrap();
+        assert_eq!(vob_file.get_subtitle_entries().unwrap()[0].image, Some(image));
+        let idx_file = pgs_file.convert_to(SubtitleFormat::VobSubIdx).unwrap();
+        assert!(String::from_utf8(idx_file.to_data().unwrap()).unwrap().contains("size: 1920x1080"));
+
+        let err = pgs_file.convert_to(SubtitleFormat::SubRip).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TextFormatOnly);
+    }
+
+    #[test]
+    fn dialect_test() {
+        let dialect = |format, data: &str| parse_str(format, data, 25.0).unwrap().dialect();
+
+        let srt = "1\n00:00:01,000 --> 00:00:02,000 X1:100 X2:600 Y1:400 Y2:450\nA\n\n";
+        assert_eq!(dialect(SubtitleFormat::SubRip, srt), Some(SubtitleDialect::SrtWithCoordinates));
+        let file = parse_str(SubtitleFormat::SubRip, srt, 25.0).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), srt);
+        assert_eq!(dialect(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000\nA\n"), None);
+        assert!(parse_str(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000 X1:100\nA\n", 25.0).is_err());
+
+        let events = "[Events]\nFormat: Start, End, Text\nDialogue: 0:00:01.00,0:00:02.00,A\n";
+        let ssa = format!("[Script Info]\nScriptType: v4.00\n\n{}", events);
+        assert_eq!(dialect(SubtitleFormat::SubStationAlpha, &ssa), Some(SubtitleDialect::SsaV4));
+        let ass = format!("[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n{}", events);
+        assert_eq!(dialect(SubtitleFormat::SubStationAlpha, &ass), Some(SubtitleDialect::AssV4Plus));
+
+        let mdvd = "{DEFAULT}{}{}{c:$0000ff}\n{0}{25}A";
+        assert_eq!(dialect(SubtitleFormat::MicroDVD, mdvd), Some(SubtitleDialect::MdvdWithDefaultLine));
+        let file = parse_str(SubtitleFormat::MicroDVD, mdvd, 25.0).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap().len(), 1);
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), mdvd);
+        assert_eq!(dialect(SubtitleFormat::MicroDVD, "{0}{25}A"), None);
+    }
+
+    #[test]
+    fn parse_with_warnings_test() {
+        use crate::warnings::ParseWarningKind;
+
+        let data = "1\n00:00:01,000 --> 00:00:03,000\nA\n\n2\n00:00:02,000 --> 00:00:04,000\nB\n\n3\n00:00:05,000 --> 25:00:00,000\nC\n";
+        let (file, warnings) = parse_bytes_with_warnings(SubtitleFormat::SubRip, data.as_bytes(), Some(encoding_rs::UTF_8), 25.0).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap().len(), 3);
+        let found: Vec<_> = warnings
+            .iter()
+            .map(|warning| (warning.line_num, warning.entry_idx, warning.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (5, Some(1), ParseWarningKind::OverlappingEntries),
+                (9, Some(2), ParseWarningKind::SuspiciousTimestamp),
+            ]
+        );
+
+        let (_, warnings) = parse_str_with_warnings(SubtitleFormat::MicroDVD, "{0}{25}A\n{50}{75}B", 25.0).unwrap();
+        assert!(warnings.is_empty());
+        assert!(parse_str_with_warnings(SubtitleFormat::SubRip, "A\n", 25.0).is_err());
+    }
+
+    #[test]
+    fn shifted_view_test() {
+        let file = parse_str(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000\nA\n", 25.0).unwrap();
+        let view = file.shifted(TimeDelta::from_msecs(500)).shifted(TimeDelta::from_msecs(-100));
+        assert_eq!(view.delta(), TimeDelta::from_msecs(400));
+
+        let entries = view.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].timespan.start, TimePoint::from_msecs(1400));
+        assert_eq!(view.get_subtitle_entries_frames(25.0).unwrap()[0].end_frame, 60);
+
+        // the file itself is not changed
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].timespan.start, TimePoint::from_msecs(1000));
+        assert_eq!(
+            view.to_file().unwrap().to_data().unwrap(),
+            b"1\n00:00:01,400 --> 00:00:02,400\nA\n\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn content_fingerprint_test() {
+        let data = "1\n00:00:01,000 --> 00:00:02,000\nA\n";
+        let mut file = parse_str(SubtitleFormat::SubRip, data, 25.0).unwrap();
+        let file2 = parse_str(SubtitleFormat::SubRip, data, 25.0).unwrap();
+        assert_eq!(file, file2);
+        assert_eq!(file.content_fingerprint(), file2.content_fingerprint());
+
+        let mut entries = file.get_subtitle_entries().unwrap();
+        entries[0].line = Some("B".to_string());
+        file.update_subtitle_entries(&entries).unwrap();
+        assert_ne!(file, file2);
+        assert_ne!(file.content_fingerprint(), file2.content_fingerprint());
+
+        let mdvd = parse_str(SubtitleFormat::MicroDVD, "{0}{25}A", 25.0).unwrap();
+        assert_eq!(mdvd, parse_str(SubtitleFormat::MicroDVD, "{0}{25}A", 25.0).unwrap());
+        assert_ne!(mdvd, parse_str(SubtitleFormat::MicroDVD, "{0}{25}A", 30.0).unwrap());
+
+        // where the entries were in the input is no content
+        let crlf = parse_str(SubtitleFormat::SubRip, &data.replace('\n', "\r\n"), 25.0).unwrap();
+        assert_eq!(crlf, file2);
+        assert_eq!(crlf.content_fingerprint(), file2.content_fingerprint());
+        let timespan = TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2000));
+        let created = SubtitleFile::SubRipFile(srt::SrtFile::create(vec![(timespan, "A".to_string())]).unwrap());
+        assert_eq!(created, file2);
+        assert_eq!(created.content_fingerprint(), file2.content_fingerprint());
+    }
+
+    #[test]
+    fn send_sync_test() {
+        // files can be shared with worker threads
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SubtitleFile>();
+        assert_send_sync::<srt::SrtFile>();
+        assert_send_sync::<ssa::SsaFile>();
+        assert_send_sync::<idx::IdxFile>();
+        assert_send_sync::<vobsub::VobFile>();
+        assert_send_sync::<microdvd::MdvdFile>();
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn parse_bytes_lossy_test() {
+        let content = b"1\n00:00:01,000 --> 00:00:02,000\nCaf\xe9 \xff!\n";
+        assert!(parse_bytes(SubtitleFormat::SubRip, content, Some(encoding_rs::UTF_8), 25.0).is_err());
+
+        let (file, report) = parse_bytes_lossy(SubtitleFormat::SubRip, content, Some(encoding_rs::UTF_8), 25.0).unwrap();
+        assert_eq!(report.replaced, vec![35, 37]);
+        assert_eq!(report.replaced_count(), 2);
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].line, Some("Caf\u{FFFD} \u{FFFD}!".to_string()));
+    }
+
+    #[test]
+    fn parse_bytes_with_fallbacks_test() {
+        use encoding_rs::{UTF_8, WINDOWS_1252};
+
+        // "Café" in Windows-1252 is not valid UTF-8
+        let content = b"1\n00:00:01,000 --> 00:00:02,000\nCaf\xe9\n";
+        let (file, encoding) = parse_bytes_with_fallbacks(SubtitleFormat::SubRip, content, &[UTF_8, WINDOWS_1252], 25.0).unwrap();
+        assert_eq!(encoding, Some(WINDOWS_1252));
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].line, Some("Café".to_string()));
+
+        let err = parse_bytes_with_fallbacks(SubtitleFormat::SubRip, content, &[UTF_8], 25.0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DecodingError);
+    }
+
+    #[test]
+    fn memory_footprint_test() {
+        let srt = parse_str(
+            SubtitleFormat::SubRip,
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\nWorld\n\n2\n00:00:03,000 --> 00:00:04,000\nBye\n",
+            25.0,
+        )
+        .unwrap();
+        let footprint = srt.memory_footprint();
+        assert_eq!(footprint.entry_count, 2);
+        assert_eq!(footprint.text_bytes, 13);
+        assert_eq!(footprint.filler_bytes, 0);
+        assert_eq!(footprint.image_bytes, 0);
+        assert!(footprint.total_bytes > footprint.text_bytes);
+
+        let ssa = parse_str(
+            SubtitleFormat::SubStationAlpha,
+            "[Events]\nFormat: Start, End, Text\nDialogue: 0:00:01.00,0:00:02.00,Hello\n",
+            25.0,
+        )
+        .unwrap();
+        let footprint = ssa.memory_footprint();
+        assert_eq!((footprint.entry_count, footprint.text_bytes), (1, 5));
+        assert!(footprint.filler_bytes >= "[Events]\nFormat: Start, End, Text\nDialogue: ".len());
+
+        let vob_file = SubtitleFile::VobSubSubFile(vobsub::VobFile::parse(&[]).unwrap());
+        assert_eq!((vob_file.memory_footprint().entry_count, vob_file.memory_footprint().image_bytes), (0, 0));
+    }
+
+    #[test]
+    fn parse_bytes_auto_test() {
+        use encoding_rs::{UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252};
+
+        let srt = |text: &[u8]| [&b"1\n00:00:01,000 --> 00:00:02,000\n"[..], text, b"\n"].concat();
+        let auto = |content: &[u8]| {
+            let (file, encoding) = parse_bytes_auto(SubtitleFormat::SubRip, content, 25.0).unwrap();
+            (file.get_subtitle_entries().unwrap()[0].line.clone().unwrap(), encoding.unwrap())
+        };
+
+        assert_eq!(auto(&srt("Café".as_bytes())), ("Café".to_string(), UTF_8));
+        assert_eq!(
+            auto(&srt(b"Caf\xe9 cr\xe8me br\xfbl\xe9e")),
+            ("Café crème brûlée".to_string(), WINDOWS_1252)
+        );
+        assert_eq!(
+            auto(&srt(b"\xa3\xf3d\x9f i Krak\xf3w, \x9cwi\xeaty")),
+            ("Łódź i Kraków, święty".to_string(), WINDOWS_1250)
+        );
+        let russian = "Привет, как дела? Всё хорошо, спасибо.";
+        let (windows_1251, _, _) = WINDOWS_1251.encode(russian);
+        assert_eq!(auto(&srt(&windows_1251)), (russian.to_string(), WINDOWS_1251));
+
+        let utf16: Vec<u8> = "1\n00:00:01,000 --> 00:00:02,000\nHello\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(auto(&utf16), ("Hello".to_string(), UTF_16LE));
+        assert_eq!(auto(&[&[0xFF, 0xFE][..], &utf16].concat()), ("Hello".to_string(), UTF_16LE));
+    }
+
+    #[test]
+    fn try_update_subtitle_entries_test() {
+        let mut file = parse_str(
+            SubtitleFormat::SubRip,
+            "1\n00:00:01,000 --> 00:00:02,000\nA\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n",
+            25.0,
+        )
+        .unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+
+        let err = file.try_update_subtitle_entries(&entries[..1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EntryCountMismatch { expected: 2, found: 1 });
+        let err = file.update_subtitle_entries(&entries[..1]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::EntryCountMismatch { expected: 2, found: 1 });
+
+        entries[1].line = Some("C".to_string());
+        file.try_update_subtitle_entries(&entries).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap()[1].line, Some("C".to_string()));
+
+        let mut vob_file = SubtitleFile::VobSubSubFile(vobsub::VobFile::parse(&[]).unwrap());
+        let err = vob_file.try_update_subtitle_entries(&[]).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::UpdatingEntriesNotSupported {
+                format: SubtitleFormat::VobSubSub
+            }
+        );
+    }
+
+    #[test]
+    fn source_ranges_test() {
+        let data = "\u{FEFF}1\r\n00:00:01,000 --> 00:00:02,000\r\n Hello\r\nworld\r\n";
+        let file = parse_str(SubtitleFormat::SubRip, data, 25.0).unwrap();
+        let ranges = file.source_ranges(0).unwrap();
+        assert_eq!(&data[ranges.start], "00:00:01,000");
+        assert_eq!(&data[ranges.end], "00:00:02,000");
+        assert_eq!(
+            ranges.text.iter().map(|range| &data[range.clone()]).collect::<Vec<_>>(),
+            vec!["Hello", "world"]
+        );
+        assert_eq!(file.source_ranges(1), None);
+
+        let data = "[Events]\r\nFormat: Layer, Start, End, Style, Text\r\nDialogue: 0, 0:00:01.00,0:00:02.00 ,Default,Hello, world\r\n";
+        let file = parse_str(SubtitleFormat::SubStationAlpha, data, 25.0).unwrap();
+        let ranges = file.source_ranges(0).unwrap();
+        assert_eq!(&data[ranges.start], "0:00:01.00");
+        assert_eq!(&data[ranges.end], "0:00:02.00");
+        assert_eq!(&data[ranges.text[0].clone()], "Hello, world");
+
+        // a dialog line with fewer fields than the format line
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.60,0:00:05.08,Default,,0,0,0,";
+        let file = parse_str(SubtitleFormat::SubStationAlpha, data, 25.0).unwrap();
+        assert_eq!(file.source_ranges(0), None);
+    }
+
+    #[test]
+    fn convert_to_with_provenance_test() {
+        let data = "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n5\n00:00:03,000 --> 00:00:04,000\nWorld\n";
+        let file = parse_str(SubtitleFormat::SubRip, data, 25.0).unwrap();
+        let (converted, provenance) = file.convert_to_with_provenance(SubtitleFormat::SubStationAlpha).unwrap();
+        assert_eq!(converted.get_subtitle_entries().unwrap().len(), provenance.len());
+        assert_eq!(provenance[1].format, SubtitleFormat::SubRip);
+        assert_eq!(provenance[1].entry_idx, 1);
+        assert_eq!(provenance[1].index, Some(5));
+        assert_eq!(provenance[1].line_num(data), Some(5));
+
+        let (_, provenance) = converted.convert_to_with_provenance(SubtitleFormat::SubRip).unwrap();
+        assert_eq!(provenance[0].format, SubtitleFormat::SubStationAlpha);
+        assert_eq!(provenance[0].line_num(data), None);
+    }
+
+    #[test]
+    fn convert_line_breaks_test() {
+        let mut file = parse_str(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000\nHello\nworld\n", 25.0).unwrap();
+        file.convert_line_breaks(LineBreakStyle::SingleLine).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].line, Some("Hello world".to_string()));
+        file.convert_line_breaks(LineBreakStyle::TwoLines { max_line_len: 8 }).unwrap();
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\nworld\n\n"
+        );
+
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Name, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,Hello\\Nworld\n";
+        let mut file = parse_str(SubtitleFormat::SubStationAlpha, data, 25.0).unwrap();
+        file.convert_line_breaks(LineBreakStyle::SingleLine).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].line, Some("Hello world".to_string()));
     }
 }
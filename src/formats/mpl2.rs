@@ -0,0 +1,257 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use self::errors::ErrorKind::*;
+use self::errors::*;
+use crate::{SubtitleEntry, SubtitleFileInterface};
+
+use crate::errors::Result as SubtitleParserResult;
+use crate::escaping::{mdvd_escape, MDVD_LINE_BREAK};
+use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
+use combine::char::char;
+use combine::combinator::{many, optional, parser as p, satisfy};
+use combine::primitives::{ParseResult, Parser, Stream};
+
+use failure::ResultExt;
+
+use crate::timetypes::{TimePoint, TimeSpan};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Errors specific to `.txt` (`MPL2`) parsing.
+#[allow(missing_docs, non_local_definitions)]
+pub mod errors {
+
+    define_error!(Error, ErrorKind);
+
+    #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        #[fail(display = "expected MPL2 line like `[10][25]Text`, found '{}'", line)]
+        ExpectedSubtitleLine { line: String },
+
+        #[fail(display = "parse error at line `{}`", line_num)]
+        ErrorAtLine { line_num: usize },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::ExpectedSubtitleLine { .. } => "mpl2-expected-subtitle-line",
+                ErrorKind::ErrorAtLine { .. } => "mpl2-error-at-line",
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.txt` file in the `MPL2` format.
+///
+/// Every line is an entry like `[123][456]Line|Line2`, where the timestamps are in deciseconds and the lines of
+/// the text are separated by `|`. Timestamps are rounded to deciseconds when the entries are changed.
+pub struct Mpl2File {
+    v: Vec<Mpl2Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// One entry of a `MPL2` file.
+struct Mpl2Line {
+    /// start time in deciseconds
+    start: i64,
+
+    /// end time in deciseconds
+    end: i64,
+
+    /// the dialog/text lines of the entry
+    texts: Vec<String>,
+}
+
+impl Mpl2Line {
+    fn new(timespan: TimeSpan, text: &str) -> Mpl2Line {
+        Mpl2Line {
+            start: to_deciseconds(timespan.start),
+            end: to_deciseconds(timespan.end),
+            texts: text.lines().map(mdvd_escape).collect(),
+        }
+    }
+}
+
+fn to_deciseconds(t: TimePoint) -> i64 {
+    (t.msecs() as f64 / 100.0).round() as i64
+}
+
+impl Mpl2File {
+    /// Parse a `MPL2` subtitle string to `Mpl2File`.
+    pub fn parse(s: &str) -> SubtitleParserResult<Mpl2File> {
+        Ok(Self::parse_file(s).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    /// Creates a `MPL2` file from scratch.
+    pub fn create(v: Vec<(TimeSpan, String)>) -> SubtitleParserResult<Mpl2File> {
+        Ok(Mpl2File {
+            v: v.into_iter().map(|(timespan, text)| Mpl2Line::new(timespan, &text)).collect(),
+        })
+    }
+
+    fn parse_file(i: &str) -> Result<Mpl2File> {
+        let (_, s) = split_bom(i);
+
+        let v = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line_num, line)| Self::parse_line(line_num, line))
+            .collect::<Result<Vec<Mpl2Line>>>()?;
+
+        Ok(Mpl2File { v })
+    }
+
+    /// Matches a timestamp like "[123]" (in deciseconds).
+    fn timestamp<I>(input: I) -> ParseResult<i64, I>
+    where
+        I: Stream<Item = char>,
+    {
+        (char('['), optional(char('-')), p(number_i64), char(']'))
+            .map(|t| if t.1.is_some() { -t.2 } else { t.2 })
+            .parse_stream(input)
+    }
+
+    /// Matches a line like "[123][456]Line|Line2".
+    fn parse_line(line_num: usize, line: &str) -> Result<Mpl2Line> {
+        let (start, end, text) = (p(Self::timestamp), p(Self::timestamp), many(satisfy(|_| true)))
+            .map(|t: (i64, i64, String)| t)
+            .parse(line.trim())
+            .map(|x| x.0)
+            .map_err(|_| Error::from(ExpectedSubtitleLine { line: line.to_string() }))
+            .with_context(|_| ErrorAtLine { line_num })?;
+
+        Ok(Mpl2Line {
+            start,
+            end,
+            texts: text.split(MDVD_LINE_BREAK).map(|s| s.trim().to_string()).collect(),
+        })
+    }
+
+    /// Whether the line starts like a `MPL2` entry (for detecting the format of `.txt` files).
+    pub(crate) fn is_mpl2_line(line: &str) -> bool {
+        Self::parse_line(0, line).is_ok()
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.v.len();
+        footprint.add_vec(&self.v);
+        for line in &self.v {
+            footprint.add_vec(&line.texts);
+            line.texts.iter().for_each(|text| footprint.add_text(text));
+        }
+        footprint
+    }
+}
+
+impl SubtitleFileInterface for Mpl2File {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        Ok(self
+            .v
+            .iter()
+            .map(|line| {
+                let timespan = TimeSpan::new(TimePoint::from_msecs(line.start * 100), TimePoint::from_msecs(line.end * 100));
+                SubtitleEntry::new(timespan, line.texts.join("\n"))
+            })
+            .collect())
+    }
+
+    fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        check_entry_count(self.v.len(), new_subtitle_entries.len())?;
+
+        for (line_ref, new_entry_ref) in self.v.iter_mut().zip(new_subtitle_entries) {
+            line_ref.start = to_deciseconds(new_entry_ref.timespan.start);
+            line_ref.end = to_deciseconds(new_entry_ref.timespan.end);
+            if let Some(ref text) = new_entry_ref.line {
+                line_ref.texts = text.lines().map(mdvd_escape).collect();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        Ok(self
+            .v
+            .iter()
+            .map(|line| format!("[{}][{}]{}\n", line.start, line.end, line.texts.join(MDVD_LINE_BREAK)))
+            .collect::<String>()
+            .into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mpl2_parse_test() {
+        let data = "\u{feff}[123][456]Hello|World\r\n\r\n[4567][4600] Bye \n[-5][0]Negative";
+        let entries = Mpl2File::parse(data).unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].timespan,
+            TimeSpan::new(TimePoint::from_msecs(12300), TimePoint::from_msecs(45600))
+        );
+        assert_eq!(entries[0].line.as_deref(), Some("Hello\nWorld"));
+        assert_eq!(entries[1].line.as_deref(), Some("Bye"));
+        assert_eq!(entries[2].timespan, TimeSpan::new(TimePoint::from_msecs(-500), TimePoint::from_msecs(0)));
+    }
+
+    #[test]
+    fn mpl2_italics_test() {
+        // a leading `/` marks a line as italic; it is kept as part of the text and written back unchanged
+        let data = "[10][20]/Hello|World\n[30][40]/Both|/Lines\n";
+        let file = Mpl2File::parse(data).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].line.as_deref(), Some("/Hello\nWorld"));
+        assert_eq!(entries[1].line.as_deref(), Some("/Both\n/Lines"));
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn mpl2_decisecond_rounding_test() {
+        let created = Mpl2File::create(vec![
+            (
+                TimeSpan::new(TimePoint::from_msecs(1249), TimePoint::from_msecs(2250)),
+                "A|1\nB".to_string(),
+            ),
+            (TimeSpan::new(TimePoint::from_msecs(-149), TimePoint::from_msecs(-150)), "C".to_string()),
+        ])
+        .unwrap();
+        let created_data = String::from_utf8(created.to_data().unwrap()).unwrap();
+        assert_eq!(created_data, "[12][23]A¦1|B\n[-1][-2]C\n");
+        assert_eq!(Mpl2File::parse(&created_data).unwrap(), created);
+
+        let mut file = Mpl2File::parse("[10][20]Hello\n").unwrap();
+        file.update_subtitle_entries(&[SubtitleEntry::new(
+            TimeSpan::new(TimePoint::from_msecs(3049), TimePoint::from_msecs(3050)),
+            "A|1\nB".to_string(),
+        )])
+        .unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "[30][31]A¦1|B\n");
+    }
+
+    #[test]
+    fn mpl2_invalid_line_test() {
+        for line in &["{1}{2}Hello", "[1]Hello", "[1][2", "[a][2]Hello", "Hello [1][2]"] {
+            let err = Mpl2File::parse(&format!("[1][2]A\n\n{}\n", line)).unwrap_err();
+            assert_eq!(
+                err.message_ids(),
+                ["error-parsing", "mpl2-error-at-line", "mpl2-expected-subtitle-line"],
+                "{}",
+                line
+            );
+            assert_eq!(err.line_num(), Some(2), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,748 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use self::errors::ErrorKind::*;
+use self::errors::*;
+use crate::errors::Result as SubtitleParserResult;
+use crate::formats::images;
+use crate::formats::MemoryFootprint;
+use crate::timetypes::{TimePoint, TimeSpan};
+use crate::{
+    SerializeOptions, SubtitleAlignment, SubtitleCoordinates, SubtitleEntry, SubtitleFileInterface, SubtitleFormat, SubtitleImage, SubtitlePosition,
+};
+use failure::ResultExt;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Errors specific to Blu-ray `.sup` (PGS) parsing.
+#[allow(missing_docs, non_local_definitions)]
+pub mod errors {
+
+    define_error!(Error, ErrorKind);
+
+    #[derive(Clone, Copy, PartialEq, Debug, Fail)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        #[fail(display = "expected PGS segment (starting with `PG`) at byte {}", offset)]
+        MissingMagic { offset: usize },
+
+        #[fail(display = "PGS segment at byte {} is cut off", offset)]
+        TruncatedSegment { offset: usize },
+
+        #[fail(display = "PGS segment of type 0x{:02x} at byte {} is too short", segment_type, offset)]
+        InvalidSegment { offset: usize, segment_type: u8 },
+
+        /// A subtitle image can not be stored in a `.sup` file.
+        #[fail(display = "invalid subtitle image: {}", msg)]
+        InvalidImage { msg: &'static str },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::MissingMagic { .. } => "pgs-missing-magic",
+                ErrorKind::TruncatedSegment { .. } => "pgs-truncated-segment",
+                ErrorKind::InvalidSegment { .. } => "pgs-invalid-segment",
+                ErrorKind::InvalidImage { .. } => "pgs-invalid-image",
+            }
+        }
+    }
+}
+
+/// Palette definition segment.
+const SEGMENT_PDS: u8 = 0x14;
+/// Object definition segment (a fragment of an RLE compressed bitmap).
+const SEGMENT_ODS: u8 = 0x15;
+/// Presentation composition segment (starts a display set).
+const SEGMENT_PCS: u8 = 0x16;
+/// Window definition segment (the area of the screen which is updated).
+const SEGMENT_WDS: u8 = 0x17;
+/// End of display set segment.
+const SEGMENT_END: u8 = 0x80;
+
+/// Size of the segment header: `PG`, presentation and decoding timestamp, type and size.
+const SEGMENT_HEADER_SIZE: usize = 13;
+
+/// Composition state of a presentation composition which starts a new epoch (all objects and palettes are reset).
+const EPOCH_START: u8 = 0x80;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.sup` file (Blu-ray Presentation Graphic Stream, PGS).
+///
+/// A subtitle is shown from the display set which composes its objects until the next display set. The (big)
+/// file data is shared between clones and the images are only decoded by `images()` and
+/// `get_subtitle_entries()`, like for `VobFile`.
+pub struct PgsFile {
+    /// Saves the file data (shared between clones, because it is never changed).
+    data: Arc<[u8]>,
+
+    /// The timespans of the subtitles.
+    timespans: Vec<TimeSpan>,
+
+    /// The video width and height of the first presentation composition.
+    screen_size: Option<(u32, u32)>,
+}
+
+impl PgsFile {
+    /// Parse the contents of a Blu-ray `.sup` file to `PgsFile`.
+    pub fn parse(b: &[u8]) -> SubtitleParserResult<Self> {
+        let stream = read_stream(b, false).with_context(|_| crate::ErrorKind::ParsingError)?;
+        Ok(PgsFile {
+            data: b.into(),
+            timespans: stream.subtitles.into_iter().map(|(timespan, _)| timespan).collect(),
+            screen_size: stream.screen_size,
+        })
+    }
+
+    /// The timespans of the subtitles, which are read from the segment headers and presentation compositions only
+    /// (see `scan_metadata()`).
+    pub(crate) fn scan_timespans(b: &[u8]) -> SubtitleParserResult<Vec<TimeSpan>> {
+        let stream = read_stream(b, false).with_context(|_| crate::ErrorKind::ParsingError)?;
+        Ok(stream.subtitles.into_iter().map(|(timespan, _)| timespan).collect())
+    }
+
+    /// The video width and height the subtitles were made for (`None` if the file has no presentation composition).
+    pub fn screen_size(&self) -> Option<(u32, u32)> {
+        self.screen_size
+    }
+
+    /// Decode the subtitle images.
+    ///
+    /// The images are in the same order as the entries from `get_subtitle_entries()`. If a display set composes
+    /// several objects, they are combined into one image.
+    pub fn images(&self) -> SubtitleParserResult<Vec<SubtitleImage>> {
+        let stream = read_stream(&self.data, true).with_context(|_| crate::ErrorKind::ParsingError)?;
+        Ok(stream.subtitles.into_iter().filter_map(|(_, image)| image).collect())
+    }
+
+    /// Write every subtitle image as numbered `.png` file into the (existing) directory `dir`, together
+    /// with a `manifest.csv` (see `VobFile::export_images()`).
+    pub fn export_images(&self, dir: &Path) -> SubtitleParserResult<()> {
+        let images = self.images()?;
+        images::export_images(dir, self.timespans.iter().cloned().zip(&images))
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.timespans.len();
+        footprint.add_vec(&self.timespans);
+        footprint.add_image(&self.data);
+        footprint
+    }
+}
+
+/// The result of reading all segments of a stream.
+#[derive(Debug, Default)]
+struct PgsStream {
+    /// The timespans of the subtitles with the images (if they were decoded).
+    subtitles: Vec<(TimeSpan, Option<SubtitleImage>)>,
+    screen_size: Option<(u32, u32)>,
+}
+
+/// A bitmap from (possibly several) object definition segments.
+#[derive(Debug, Default)]
+struct PgsObject {
+    width: usize,
+    height: usize,
+    rle: Vec<u8>,
+}
+
+/// A reference to an object in a presentation composition.
+#[derive(Debug)]
+struct CompositionObject {
+    object_id: u16,
+    x: usize,
+    y: usize,
+
+    /// The shown part of the object as (x, y, width, height).
+    crop: Option<(usize, usize, usize, usize)>,
+}
+
+/// A presentation composition which is shown once its display set ends.
+#[derive(Debug)]
+struct Composition {
+    start: TimePoint,
+    palette_id: u8,
+    objects: Vec<CompositionObject>,
+}
+
+fn read_u16(b: &[u8], offset: usize) -> usize {
+    (b[offset] as usize) << 8 | b[offset + 1] as usize
+}
+
+/// Read all display sets. Without `with_images` the palettes and objects are skipped.
+fn read_stream(data: &[u8], with_images: bool) -> Result<PgsStream> {
+    let mut stream = PgsStream::default();
+    let mut palettes: HashMap<u8, [[u8; 4]; 256]> = HashMap::new();
+    let mut objects: HashMap<u16, PgsObject> = HashMap::new();
+    let mut composition: Option<Composition> = None;
+    let mut shown: Option<(TimePoint, Option<SubtitleImage>)> = None;
+    let mut last_time = TimePoint::from_msecs(0);
+
+    let mut offset = 0;
+    while offset < data.len() {
+        if !data[offset..].starts_with(b"PG") {
+            return Err(MissingMagic { offset }.into());
+        }
+        let header = data.get(offset..offset + SEGMENT_HEADER_SIZE).ok_or(TruncatedSegment { offset })?;
+        // the presentation timestamp is given in 1/90000 seconds
+        let pts = u64::from(header[2]) << 24 | u64::from(header[3]) << 16 | u64::from(header[4]) << 8 | u64::from(header[5]);
+        let time = TimePoint::from_msecs(((pts + 45) / 90) as i64);
+        let segment_type = header[10];
+        let payload_start = offset + SEGMENT_HEADER_SIZE;
+        let payload = data
+            .get(payload_start..payload_start + read_u16(header, 11))
+            .ok_or(TruncatedSegment { offset })?;
+        let invalid = || Error::from(InvalidSegment { offset, segment_type });
+        last_time = time;
+
+        match segment_type {
+            SEGMENT_PCS => {
+                if payload.len() < 11 {
+                    return Err(invalid());
+                }
+                if stream.screen_size.is_none() {
+                    stream.screen_size = Some((read_u16(payload, 0) as u32, read_u16(payload, 2) as u32));
+                }
+                if payload[7] == EPOCH_START {
+                    palettes.clear();
+                    objects.clear();
+                }
+
+                let mut composition_objects = Vec::new();
+                let mut pos = 11;
+                for _ in 0..payload[10] {
+                    let object = payload.get(pos..pos + 8).ok_or_else(invalid)?;
+                    let cropped = object[3] & 0x80 != 0;
+                    let crop = if cropped {
+                        let crop = payload.get(pos + 8..pos + 16).ok_or_else(invalid)?;
+                        Some((read_u16(crop, 0), read_u16(crop, 2), read_u16(crop, 4), read_u16(crop, 6)))
+                    } else {
+                        None
+                    };
+                    composition_objects.push(CompositionObject {
+                        object_id: read_u16(object, 0) as u16,
+                        x: read_u16(object, 4),
+                        y: read_u16(object, 6),
+                        crop,
+                    });
+                    pos += if cropped { 16 } else { 8 };
+                }
+
+                composition = Some(Composition {
+                    start: time,
+                    palette_id: payload[9],
+                    objects: composition_objects,
+                });
+            }
+            SEGMENT_PDS if with_images => {
+                if payload.len() < 2 {
+                    return Err(invalid());
+                }
+                let palette = palettes.entry(payload[0]).or_insert([[0; 4]; 256]);
+                for entry in payload[2..].chunks_exact(5) {
+                    let rgb = ycrcb_to_rgb(entry[1], entry[2], entry[3]);
+                    palette[entry[0] as usize] = [rgb[0], rgb[1], rgb[2], entry[4]];
+                }
+            }
+            SEGMENT_ODS if with_images => {
+                if payload.len() < 4 {
+                    return Err(invalid());
+                }
+                let object_id = read_u16(payload, 0) as u16;
+                if payload[3] & 0x80 != 0 {
+                    // the first fragment has the (24 bit) data length and the size of the bitmap
+                    if payload.len() < 11 {
+                        return Err(invalid());
+                    }
+                    objects.insert(
+                        object_id,
+                        PgsObject {
+                            width: read_u16(payload, 7),
+                            height: read_u16(payload, 9),
+                            rle: payload[11..].to_vec(),
+                        },
+                    );
+                } else {
+                    objects.entry(object_id).or_default().rle.extend_from_slice(&payload[4..]);
+                }
+            }
+            SEGMENT_END => {
+                if let Some(composition) = composition.take() {
+                    if let Some((start, image)) = shown.take() {
+                        stream.subtitles.push((TimeSpan::new(start, composition.start), image));
+                    }
+                    if !composition.objects.is_empty() {
+                        let image = if with_images {
+                            Some(compose_image(&composition, &objects, palettes.get(&composition.palette_id)))
+                        } else {
+                            None
+                        };
+                        shown = Some((composition.start, image));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        offset = payload_start + payload.len();
+    }
+
+    // a subtitle which is not cleared is shown until the end of the stream
+    if let Some((start, image)) = shown {
+        stream.subtitles.push((TimeSpan::new(start, last_time.max(start)), image));
+    }
+
+    Ok(stream)
+}
+
+/// Combine the objects of a composition into one RGBA image (the pixels which are not covered are transparent).
+fn compose_image(composition: &Composition, objects: &HashMap<u16, PgsObject>, palette: Option<&[[u8; 4]; 256]>) -> SubtitleImage {
+    // the shown parts of the objects as (composition object, object, x, y, width, height)
+    let parts: Vec<_> = composition
+        .objects
+        .iter()
+        .filter_map(|c| {
+            let object = objects.get(&c.object_id)?;
+            let (crop_x, crop_y, width, height) = c.crop.unwrap_or((0, 0, object.width, object.height));
+            let width = width.min(object.width.saturating_sub(crop_x));
+            let height = height.min(object.height.saturating_sub(crop_y));
+            Some((c, object, crop_x, crop_y, width, height))
+        })
+        .collect();
+
+    let left = parts.iter().map(|p| p.0.x).min().unwrap_or(0);
+    let top = parts.iter().map(|p| p.0.y).min().unwrap_or(0);
+    let right = parts.iter().map(|p| p.0.x + p.4).max().unwrap_or(left);
+    let bottom = parts.iter().map(|p| p.0.y + p.5).max().unwrap_or(top);
+    let (width, height) = (right - left, bottom - top);
+
+    let mut data = vec![0u8; width * height * 4];
+    for (c, object, crop_x, crop_y, part_width, part_height) in parts {
+        let indices = decode_rle(&object.rle, object.width, object.height);
+        for y in 0..part_height {
+            for x in 0..part_width {
+                let color = palette.map_or([0; 4], |palette| palette[indices[(crop_y + y) * object.width + crop_x + x] as usize]);
+                let pos = ((c.y - top + y) * width + c.x - left + x) * 4;
+                data[pos..pos + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    SubtitleImage {
+        x: left as u32,
+        y: top as u32,
+        width: width as u32,
+        height: height as u32,
+        data,
+    }
+}
+
+/// Decode the run length encoded palette indices of an object. Missing pixels (in broken files) have index 0.
+fn decode_rle(rle: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut indices = vec![0u8; width * height];
+    let (mut x, mut y) = (0, 0);
+    let mut bytes = rle.iter().cloned();
+
+    while let Some(b) = bytes.next() {
+        let (length, color) = if b != 0 {
+            (1, b)
+        } else {
+            match bytes.next() {
+                None => break,
+                Some(0) => {
+                    // end of line
+                    x = 0;
+                    y += 1;
+                    continue;
+                }
+                Some(flags) => {
+                    let mut length = (flags & 0x3F) as usize;
+                    if flags & 0x40 != 0 {
+                        length = length << 8 | bytes.next().unwrap_or(0) as usize;
+                    }
+                    let color = if flags & 0x80 != 0 { bytes.next().unwrap_or(0) } else { 0 };
+                    (length, color)
+                }
+            }
+        };
+
+        if y < height {
+            let end = (x + length).min(width);
+            if x < end {
+                indices[y * width + x..y * width + end].iter_mut().for_each(|index| *index = color);
+            }
+        }
+        x += length;
+    }
+
+    indices
+}
+
+/// Convert a (ITU-R BT.709, studio range) YCrCb color to RGB.
+fn ycrcb_to_rgb(y: u8, cr: u8, cb: u8) -> [u8; 3] {
+    let (y, cr, cb) = (1.164 * (f64::from(y) - 16.0), f64::from(cr) - 128.0, f64::from(cb) - 128.0);
+    let clamp = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+    [clamp(y + 1.793 * cr), clamp(y - 0.533 * cr - 0.213 * cb), clamp(y + 2.112 * cb)]
+}
+
+impl SubtitleFileInterface for PgsFile {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        Ok(self
+            .timespans
+            .iter()
+            .zip(self.images()?)
+            .map(|(&timespan, image)| SubtitleEntry {
+                timespan,
+                line: None,
+                position: Some(SubtitlePosition {
+                    alignment: SubtitleAlignment::TopLeft,
+                    coordinates: Some(SubtitleCoordinates::Pixels {
+                        x: f64::from(image.x),
+                        y: f64::from(image.y),
+                    }),
+                }),
+                image: Some(image),
+                speaker: None,
+                index: None,
+                tags: Vec::new(),
+            })
+            .collect())
+    }
+
+    fn update_subtitle_entries(&mut self, _: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        Err(crate::errors::ErrorKind::UpdatingEntriesNotSupported {
+            format: SubtitleFormat::BluRayPgs,
+        }
+        .into())
+    }
+
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        Ok(self.data.to_vec())
+    }
+
+    fn to_data_with(&self, options: &SerializeOptions) -> SubtitleParserResult<Vec<u8>> {
+        if options.changes_text() {
+            return Err(crate::errors::ErrorKind::TextFormatOnly.into());
+        }
+        self.to_data()
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// PGS writer
+
+impl PgsFile {
+    /// Creates a `.sup` file from scratch.
+    ///
+    /// Every image is shown during its timespan at its position on a screen with the given width and height. The
+    /// entries should be sorted by their start times; a subtitle which is still shown when the next one starts is
+    /// replaced by it.
+    ///
+    /// Every subtitle has its own palette with up to 255 colors and a transparent background (pixels with an alpha
+    /// value of 0). Images with more colors are reduced to fewer shades of every color channel.
+    pub fn create(v: Vec<(TimeSpan, SubtitleImage)>, width: u32, height: u32) -> SubtitleParserResult<PgsFile> {
+        let data = Self::create_inner(v, width, height).with_context(|_| crate::errors::ErrorKind::SerializingError)?;
+        Self::parse(&data)
+    }
+
+    fn create_inner(v: Vec<(TimeSpan, SubtitleImage)>, width: u32, height: u32) -> Result<Vec<u8>> {
+        if width > 0xFFFF || height > 0xFFFF {
+            return Err(InvalidImage {
+                msg: "the screen size is too large",
+            }
+            .into());
+        }
+
+        let mut data = Vec::new();
+        let mut composition_number: u16 = 0;
+        for (i, (timespan, image)) in v.iter().enumerate() {
+            if image.data.len() != image.width as usize * image.height as usize * 4 {
+                return Err(InvalidImage {
+                    msg: "the length of the RGBA data does not match the image dimensions",
+                }
+                .into());
+            }
+            if image.width == 0 || image.height == 0 || image.width > 4096 || image.height > 4096 {
+                return Err(InvalidImage {
+                    msg: "the image width and height have to be between 1 and 4096",
+                }
+                .into());
+            }
+            let (x, y) = match (image.x.checked_add(image.width), image.y.checked_add(image.height)) {
+                (Some(right), Some(bottom)) if right <= width && bottom <= height => (image.x as u16, image.y as u16),
+                _ => {
+                    return Err(InvalidImage {
+                        msg: "the image is not on the screen",
+                    }
+                    .into())
+                }
+            };
+            let window = [0, x, y, image.width as u16, image.height as u16];
+            let pts = timespan.start.msecs().max(0) as u64 * 90;
+
+            // the display set which shows the image starts a new epoch, so it has its own palette and object
+            let mut pcs = vec![];
+            push_u16s(&mut pcs, &[width as u16, height as u16]);
+            pcs.push(0x10);
+            push_u16s(&mut pcs, &[composition_number]);
+            pcs.extend_from_slice(&[EPOCH_START, 0, 0, 1]);
+            push_u16s(&mut pcs, &[0]);
+            pcs.extend_from_slice(&[0, 0]);
+            push_u16s(&mut pcs, &[x, y]);
+            write_segment(&mut data, pts, SEGMENT_PCS, &pcs);
+            write_window(&mut data, pts, window);
+
+            let (palette, indices) = palette_indices(image);
+            let mut pds = vec![0, 0];
+            for (index, &[r, g, b, a]) in palette.iter().enumerate() {
+                let (y, cr, cb) = rgb_to_ycrcb(r, g, b);
+                pds.extend_from_slice(&[index as u8, y, cr, cb, a]);
+            }
+            write_segment(&mut data, pts, SEGMENT_PDS, &pds);
+
+            let rle = encode_rle(&indices, image.width as usize);
+            // the object data starts with its width and height, it is split into segments with at most 65535 bytes
+            let object_len = rle.len() + 4;
+            if object_len > 0xFF_FFFF {
+                return Err(InvalidImage {
+                    msg: "the image data is too large",
+                }
+                .into());
+            }
+            let first_len = (0xFFFF - 11).min(rle.len());
+            let mut fragments = vec![&rle[..first_len]];
+            fragments.extend(rle[first_len..].chunks(0xFFFF - 4));
+            for (fragment_idx, fragment) in fragments.iter().enumerate() {
+                let mut ods = vec![];
+                push_u16s(&mut ods, &[0]);
+                ods.push(0);
+                let first = fragment_idx == 0;
+                let last = fragment_idx + 1 == fragments.len();
+                ods.push(if first { 0x80 } else { 0 } | if last { 0x40 } else { 0 });
+                if first {
+                    ods.extend_from_slice(&(object_len as u32).to_be_bytes()[1..]);
+                    push_u16s(&mut ods, &[image.width as u16, image.height as u16]);
+                }
+                ods.extend_from_slice(fragment);
+                write_segment(&mut data, pts, SEGMENT_ODS, &ods);
+            }
+            write_segment(&mut data, pts, SEGMENT_END, &[]);
+            composition_number = composition_number.wrapping_add(1);
+
+            // the display set which clears the screen (not needed if the next subtitle replaces this one)
+            let replaced = v.get(i + 1).map_or(false, |(next, _)| next.start <= timespan.end);
+            if !replaced {
+                let pts = timespan.end.msecs().max(0) as u64 * 90;
+                let mut pcs = vec![];
+                push_u16s(&mut pcs, &[width as u16, height as u16]);
+                pcs.push(0x10);
+                push_u16s(&mut pcs, &[composition_number]);
+                pcs.extend_from_slice(&[0, 0, 0, 0]);
+                write_segment(&mut data, pts, SEGMENT_PCS, &pcs);
+                write_window(&mut data, pts, window);
+                write_segment(&mut data, pts, SEGMENT_END, &[]);
+                composition_number = composition_number.wrapping_add(1);
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+fn push_u16s(v: &mut Vec<u8>, values: &[u16]) {
+    for value in values {
+        v.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Write a segment with the presentation timestamp `pts` (in 1/90000 seconds, the decoding timestamp is 0).
+fn write_segment(data: &mut Vec<u8>, pts: u64, segment_type: u8, payload: &[u8]) {
+    data.extend_from_slice(b"PG");
+    data.extend_from_slice(&(pts as u32).to_be_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.push(segment_type);
+    push_u16s(data, &[payload.len() as u16]);
+    data.extend_from_slice(payload);
+}
+
+/// Write a window definition segment with one window (id, x, y, width and height).
+fn write_window(data: &mut Vec<u8>, pts: u64, window: [u16; 5]) {
+    let mut wds = vec![1, window[0] as u8];
+    push_u16s(&mut wds, &window[1..]);
+    write_segment(data, pts, SEGMENT_WDS, &wds);
+}
+
+/// The palette (RGBA colors, the first one is transparent) and the palette indices of the pixels of an image.
+fn palette_indices(image: &SubtitleImage) -> (Vec<[u8; 4]>, Vec<u8>) {
+    // the number of the low bits of the color channels which are dropped
+    let mut shift = 0;
+    loop {
+        let mut palette = vec![[0u8; 4]];
+        let mut known: HashMap<[u8; 4], u8> = HashMap::new();
+        let indices: Option<Vec<u8>> = image
+            .data
+            .chunks(4)
+            .map(|px| {
+                if px[3] == 0 {
+                    return Some(0);
+                }
+                let reduce = |c: u8| c >> shift << shift;
+                let color = [reduce(px[0]), reduce(px[1]), reduce(px[2]), reduce(px[3]).max(1)];
+                if let Some(&index) = known.get(&color) {
+                    return Some(index);
+                }
+                if palette.len() == 256 {
+                    return None;
+                }
+                let index = palette.len() as u8;
+                palette.push(color);
+                known.insert(color, index);
+                Some(index)
+            })
+            .collect();
+        if let Some(indices) = indices {
+            return (palette, indices);
+        }
+        shift += 1;
+    }
+}
+
+/// Run length encode the palette indices of an image with `width` pixels per line (see `decode_rle()`).
+fn encode_rle(indices: &[u8], width: usize) -> Vec<u8> {
+    let mut rle = Vec::new();
+    for line in indices.chunks(width) {
+        let mut pos = 0;
+        while pos < line.len() {
+            let color = line[pos];
+            let length = line[pos..].iter().take(0x3FFF).take_while(|&&c| c == color).count();
+            match (color, length) {
+                (0, _) if length < 64 => rle.extend_from_slice(&[0, length as u8]),
+                (0, _) => rle.extend_from_slice(&[0, 0x40 | (length >> 8) as u8, length as u8]),
+                (_, 1..=2) => rle.extend(std::iter::repeat(color).take(length)),
+                (_, _) if length < 64 => rle.extend_from_slice(&[0, 0x80 | length as u8, color]),
+                (_, _) => rle.extend_from_slice(&[0, 0xC0 | (length >> 8) as u8, length as u8, color]),
+            }
+            pos += length;
+        }
+        rle.extend_from_slice(&[0, 0]);
+    }
+    rle
+}
+
+/// Convert an RGB color to (ITU-R BT.709, studio range) YCrCb (see `ycrcb_to_rgb()`).
+fn rgb_to_ycrcb(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+    let clamp = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+    (
+        clamp(16.0 + 0.1826 * r + 0.6142 * g + 0.0620 * b),
+        clamp(128.0 + 0.4392 * r - 0.3989 * g - 0.0403 * b),
+        clamp(128.0 - 0.1006 * r - 0.3386 * g + 0.4392 * b),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(pts: u32, segment_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut result = b"PG".to_vec();
+        result.extend_from_slice(&pts.to_be_bytes());
+        result.extend_from_slice(&[0; 4]);
+        result.push(segment_type);
+        result.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        result.extend_from_slice(payload);
+        result
+    }
+
+    #[test]
+    fn pgs_test() {
+        let mut data = Vec::new();
+        // 1920x1080, epoch start, palette 0, one object at (100, 900)
+        data.extend(segment(
+            90_000,
+            SEGMENT_PCS,
+            &[7, 128, 4, 56, 0x10, 0, 0, 0x80, 0, 0, 1, 0, 1, 0, 0, 0, 100, 3, 132],
+        ));
+        data.extend(segment(90_000, SEGMENT_PDS, &[0, 0, 1, 235, 128, 128, 255, 2, 16, 128, 128, 128]));
+        // a 3x2 bitmap: one white pixel and two half transparent black pixels, then three transparent pixels
+        data.extend(segment(90_000, SEGMENT_ODS, &[0, 1, 0, 0x80, 0, 0, 13, 0, 3, 0, 2, 1, 0, 0x82, 2, 0, 0]));
+        data.extend(segment(90_000, SEGMENT_ODS, &[0, 1, 0, 0x40, 0, 3, 0, 0]));
+        data.extend(segment(90_000, SEGMENT_END, &[]));
+        // clear the screen after 2.5 seconds
+        data.extend(segment(225_000, SEGMENT_PCS, &[7, 128, 4, 56, 0x10, 0, 1, 0, 0, 0, 0]));
+        data.extend(segment(225_000, SEGMENT_END, &[]));
+
+        let file = PgsFile::parse(&data).unwrap();
+        assert_eq!(file.screen_size(), Some((1920, 1080)));
+
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].timespan,
+            TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2500))
+        );
+        let image = entries[0].image.as_ref().unwrap();
+        assert_eq!((image.x, image.y, image.width, image.height), (100, 900, 3, 2));
+        assert_eq!(&image.data[..12], &[255, 255, 255, 255, 0, 0, 0, 128, 0, 0, 0, 128]);
+        assert!(image.data[12..].iter().all(|&b| b == 0));
+        assert_eq!(file.to_data().unwrap(), data);
+
+        let err = PgsFile::parse(&data[..data.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::ParsingError);
+        assert!(PgsFile::parse(b"no PGS").is_err());
+    }
+
+    #[test]
+    fn create_pgs_test() {
+        // a 70x2 image: a transparent pixel, 68 white pixels and a half transparent red pixel in every line
+        let mut data = Vec::new();
+        for _ in 0..2 {
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            for _ in 0..68 {
+                data.extend_from_slice(&[255, 255, 255, 255]);
+            }
+            data.extend_from_slice(&[255, 0, 0, 128]);
+        }
+        let image = SubtitleImage {
+            x: 100,
+            y: 900,
+            width: 70,
+            height: 2,
+            data,
+        };
+        let timespan = |start, end| TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end));
+        let lines = vec![
+            (timespan(1000, 2500), image.clone()),
+            (timespan(2000, 3000), image.clone()),
+            (timespan(4000, 5000), image.clone()),
+        ];
+        let file = PgsFile::create(lines, 1920, 1080).unwrap();
+        assert_eq!(file.screen_size(), Some((1920, 1080)));
+
+        // the second subtitle replaces the first one
+        let entries = file.get_subtitle_entries().unwrap();
+        let timespans: Vec<TimeSpan> = entries.iter().map(|entry| entry.timespan).collect();
+        assert_eq!(timespans, vec![timespan(1000, 2000), timespan(2000, 3000), timespan(4000, 5000)]);
+        for entry in &entries {
+            let decoded = entry.image.as_ref().unwrap();
+            assert_eq!((decoded.x, decoded.y, decoded.width, decoded.height), (100, 900, 70, 2));
+            // the colors can be off by one after the conversion to YCrCb
+            assert!(decoded
+                .data
+                .iter()
+                .zip(&image.data)
+                .all(|(&a, &b)| (i32::from(a) - i32::from(b)).abs() <= 1));
+        }
+
+        let outside = (timespan(0, 1000), SubtitleImage { x: 1900, ..image });
+        let err = PgsFile::create(vec![outside], 1920, 1080).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::SerializingError);
+    }
+}
@@ -0,0 +1,286 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use self::errors::ErrorKind::*;
+use self::errors::*;
+use crate::{SubtitleEntry, SubtitleFileInterface};
+
+use crate::errors::Result as SubtitleParserResult;
+use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
+use combine::char::char;
+use combine::combinator::{eof, optional, parser as p, skip_many};
+use combine::primitives::{ParseResult, Parser, Stream};
+
+use failure::ResultExt;
+
+use crate::timetypes::{TimePoint, TimeSpan};
+use std::iter::once;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Errors specific to `.sbv`-parsing.
+#[allow(missing_docs, non_local_definitions)]
+pub mod errors {
+
+    define_error!(Error, ErrorKind);
+
+    #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        #[fail(display = "expected SBV timespan line, found '{}'", line)]
+        ExpectedTimestampLine { line: String },
+
+        #[fail(display = "parse error at line `{}`", line_num)]
+        ErrorAtLine { line_num: usize },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::ExpectedTimestampLine { .. } => "sbv-expected-timestamp-line",
+                ErrorKind::ErrorAtLine { .. } => "sbv-error-at-line",
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.sbv` file (the format of YouTube's caption editor).
+///
+/// An entry is a timespan line like `0:00:01.500,0:00:04.000` followed by the lines of the text. The entries are
+/// separated by empty lines.
+pub struct SbvFile {
+    v: Vec<SbvLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// One entry of a `.sbv` file.
+struct SbvLine {
+    /// start and end time of subtitle
+    timespan: TimeSpan,
+
+    /// the dialog/text lines of the entry
+    texts: Vec<String>,
+}
+
+impl SbvFile {
+    /// Parse a `.sbv` subtitle string to `SbvFile`.
+    pub fn parse(s: &str) -> SubtitleParserResult<SbvFile> {
+        Ok(Self::parse_file(s).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    /// Creates a .sbv file from scratch.
+    pub fn create(v: Vec<(TimeSpan, String)>) -> SubtitleParserResult<SbvFile> {
+        Ok(SbvFile {
+            v: v.into_iter()
+                .map(|(timespan, text)| SbvLine {
+                    timespan,
+                    texts: text.lines().map(str::to_string).collect(),
+                })
+                .collect(),
+        })
+    }
+
+    fn parse_file(i: &str) -> Result<SbvFile> {
+        let (_, s) = split_bom(i);
+
+        let mut result: Vec<SbvLine> = Vec::new();
+        let mut current: Option<SbvLine> = None;
+
+        // the `once("")` is there so no last entry gets ignored
+        for (line_num, line) in s.lines().chain(once("")).enumerate() {
+            current = match current {
+                None if line.trim().is_empty() => None,
+                None => Some(SbvLine {
+                    timespan: Self::parse_timespan_line(line_num, line)?,
+                    texts: Vec::new(),
+                }),
+                Some(entry) if line.trim().is_empty() => {
+                    result.push(entry);
+                    None
+                }
+                Some(mut entry) => {
+                    entry.texts.push(line.trim().to_string());
+                    Some(entry)
+                }
+            };
+        }
+
+        Ok(SbvFile { v: result })
+    }
+
+    /// Matches a `.sbv` timestamp like "0:00:01.500" (the hours can have any number of digits).
+    fn timestamp<I>(input: I) -> ParseResult<TimePoint, I>
+    where
+        I: Stream<Item = char>,
+    {
+        (
+            optional(char('-')),
+            p(number_i64),
+            char(':'),
+            p(number_i64),
+            char(':'),
+            p(number_i64),
+            char('.'),
+            p(number_i64),
+        )
+            .map(|t| {
+                let timepoint = TimePoint::from_components(t.1, t.3, t.5, t.7);
+                if t.0.is_some() {
+                    -timepoint
+                } else {
+                    timepoint
+                }
+            })
+            .parse_stream(input)
+    }
+
+    /// Matches a `.sbv` timespan like "0:00:01.500,0:00:04.000".
+    pub(crate) fn parse_timespan_line(line_num: usize, line: &str) -> Result<TimeSpan> {
+        let result = (skip_many(ws()), p(Self::timestamp), char(','), p(Self::timestamp), skip_many(ws()), eof())
+            .map(|t| TimeSpan::new(t.1, t.3))
+            .parse(line)
+            .map(|x| x.0)
+            .map_err(|_| Error::from(ExpectedTimestampLine { line: line.to_string() }))
+            .with_context(|_| ErrorAtLine { line_num })?;
+
+        Ok(result)
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.v.len();
+        footprint.add_vec(&self.v);
+        for line in &self.v {
+            footprint.add_vec(&line.texts);
+            line.texts.iter().for_each(|text| footprint.add_text(text));
+        }
+        footprint
+    }
+}
+
+impl SubtitleFileInterface for SbvFile {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        Ok(self
+            .v
+            .iter()
+            .map(|line| SubtitleEntry::new(line.timespan, line.texts.join("\n")))
+            .collect())
+    }
+
+    fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        check_entry_count(self.v.len(), new_subtitle_entries.len())?;
+
+        for (line_ref, new_entry_ref) in self.v.iter_mut().zip(new_subtitle_entries) {
+            line_ref.timespan = new_entry_ref.timespan;
+            if let Some(ref text) = new_entry_ref.line {
+                line_ref.texts = text.lines().map(str::to_string).collect();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        let timepoint_to_str = |t: TimePoint| -> String {
+            let p = if t.msecs() < 0 { -t } else { t };
+            format!(
+                "{}{}:{:02}:{:02}.{:03}",
+                if t.msecs() < 0 { "-" } else { "" },
+                p.hours(),
+                p.mins_comp(),
+                p.secs_comp(),
+                p.msecs_comp()
+            )
+        };
+        let line_to_str = |line: &SbvLine| -> String {
+            format!(
+                "{},{}\n{}\n\n",
+                timepoint_to_str(line.timespan.start),
+                timepoint_to_str(line.timespan.end),
+                line.texts.join("\n")
+            )
+        };
+
+        Ok(self.v.iter().map(line_to_str).collect::<String>().into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sbv_parse_test() {
+        let data = "\u{feff}0:00:01.500,0:00:04.000\r\nHello\r\nWorld\r\n\r\n\r\n1:02:03.004,1:02:05.000\nBye\n";
+        let entries = SbvFile::parse(data).unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].timespan,
+            TimeSpan::new(TimePoint::from_msecs(1500), TimePoint::from_msecs(4000))
+        );
+        assert_eq!(entries[0].line.as_deref(), Some("Hello\nWorld"));
+        assert_eq!(entries[1].timespan.start, TimePoint::from_components(1, 2, 3, 4));
+        assert_eq!(entries[1].line.as_deref(), Some("Bye"));
+
+        // the hours can have more than one digit and the last entry needs no trailing empty line
+        let entries = SbvFile::parse("100:00:00.000,100:00:01.000\nA").unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timespan.end, TimePoint::from_components(100, 0, 1, 0));
+        assert_eq!(entries[0].line.as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn sbv_serialize_test() {
+        let file = SbvFile::parse("0:00:01.500,0:00:04.000\r\nHello\r\nWorld\r\n\r\n\r\n1:02:03.004,1:02:05.000\nBye\n").unwrap();
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            "0:00:01.500,0:00:04.000\nHello\nWorld\n\n1:02:03.004,1:02:05.000\nBye\n\n"
+        );
+
+        let created = SbvFile::create(vec![(
+            TimeSpan::new(TimePoint::from_msecs(-500), TimePoint::from_msecs(500)),
+            "A".to_string(),
+        )])
+        .unwrap();
+        let created_data = String::from_utf8(created.to_data().unwrap()).unwrap();
+        assert_eq!(created_data, "-0:00:00.500,0:00:00.500\nA\n\n");
+        assert_eq!(SbvFile::parse(&created_data).unwrap(), created);
+    }
+
+    #[test]
+    fn sbv_bad_timestamp_line_test() {
+        let bad_lines = [
+            "Hello",
+            "0:00:01.500",
+            "0:00:01.500,",
+            "0:00:01,500,0:00:04.000",
+            "0:00:01.500 --> 0:00:04.000",
+            "0:00:01.500,0:00:04.000 Hello",
+        ];
+        for line in &bad_lines {
+            let err = SbvFile::parse(&format!("0:00:00.000,0:00:01.000\nA\n\n{}\nB\n", line)).unwrap_err();
+            assert_eq!(
+                err.message_ids(),
+                ["error-parsing", "sbv-error-at-line", "sbv-expected-timestamp-line"],
+                "{}",
+                line
+            );
+            assert_eq!(err.line_num(), Some(3), "{}", line);
+        }
+    }
+
+    #[test]
+    fn sbv_missing_timestamp_line_test() {
+        // an empty line ends the entry, so the text after it needs its own timespan line
+        let err = SbvFile::parse("0:00:00.000,0:00:01.000\nA\n\nB\n").unwrap_err();
+        assert_eq!(err.line_num(), Some(3));
+
+        assert_eq!(SbvFile::parse("").unwrap().get_subtitle_entries().unwrap(), []);
+        assert_eq!(SbvFile::parse("\n\n").unwrap().get_subtitle_entries().unwrap(), []);
+    }
+}
@@ -0,0 +1,613 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use self::errors::ErrorKind::*;
+use self::errors::*;
+use crate::{SubtitleEntry, SubtitleFileInterface};
+
+use crate::errors::Result as SubtitleParserResult;
+use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
+
+use failure::ResultExt;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+use crate::timetypes::{TimePoint, TimeSpan};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Errors specific to `.scc` parsing.
+#[allow(missing_docs, non_local_definitions)]
+pub mod errors {
+
+    define_error!(Error, ErrorKind);
+
+    #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        #[fail(display = "expected `Scenarist_SCC V1.0` header")]
+        ExpectedHeader,
+
+        #[fail(display = "expected SCC line like `00:00:01;02\t9420 9420`, found '{}'", line)]
+        ExpectedSccLine { line: String },
+
+        #[fail(display = "parse error at line `{}`", line_num)]
+        ErrorAtLine { line_num: usize },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::ExpectedHeader => "scc-expected-header",
+                ErrorKind::ExpectedSccLine { .. } => "scc-expected-scc-line",
+                ErrorKind::ErrorAtLine { .. } => "scc-error-at-line",
+            }
+        }
+    }
+}
+
+const HEADER: &str = "Scenarist_SCC V1.0";
+
+/// The characters of the basic set which differ from ASCII.
+const BASIC_CHARS: [(u8, char); 10] = [
+    (0x2a, 'á'),
+    (0x5c, 'é'),
+    (0x5e, 'í'),
+    (0x5f, 'ó'),
+    (0x60, 'ú'),
+    (0x7b, 'ç'),
+    (0x7c, '÷'),
+    (0x7d, 'Ñ'),
+    (0x7e, 'ñ'),
+    (0x7f, '█'),
+];
+
+/// The special characters (`0x11 0x30` to `0x11 0x3f`, `0x39` is the transparent space).
+const SPECIAL_CHARS: &str = "®°½¿™¢£♪à\u{a0}èâêîôû";
+
+/// The extended characters (`0x12 0x20` to `0x12 0x3f` and `0x13 0x20` to `0x13 0x3f`), which replace the
+/// previous character.
+const EXTENDED_CHARS: [&str; 2] = ["ÁÉÓÚÜü‘¡*'—©℠•“”ÀÂÇÈÊËëÎÏïÔÙùÛ«»", "ÃãÍÌìÒòÕõ{}\\^_|~ÄäÖöß¥¤¦ÅåØø┌┐└┘"];
+
+/// The rows of the preamble address codes (by the first byte and the `0x20` bit of the second byte).
+const PAC_ROWS: [(u8, u8, u8); 15] = [
+    (0x11, 0x00, 1),
+    (0x11, 0x20, 2),
+    (0x12, 0x00, 3),
+    (0x12, 0x20, 4),
+    (0x15, 0x00, 5),
+    (0x15, 0x20, 6),
+    (0x16, 0x00, 7),
+    (0x16, 0x20, 8),
+    (0x17, 0x00, 9),
+    (0x17, 0x20, 10),
+    (0x10, 0x00, 11),
+    (0x13, 0x00, 12),
+    (0x13, 0x20, 13),
+    (0x14, 0x00, 14),
+    (0x14, 0x20, 15),
+];
+
+/// Resume caption loading (pop-on captions).
+const RCL: u8 = 0x20;
+/// Backspace.
+const BS: u8 = 0x21;
+/// Roll-up captions with two, three or four rows.
+const RU2: u8 = 0x25;
+const RU4: u8 = 0x27;
+/// Resume direct captioning (paint-on captions).
+const RDC: u8 = 0x29;
+/// Erase displayed memory.
+const EDM: u8 = 0x2c;
+/// Carriage return.
+const CR: u8 = 0x2d;
+/// Erase non-displayed memory.
+const ENM: u8 = 0x2e;
+/// End of caption (swaps the displayed and the non-displayed memory).
+const EOC: u8 = 0x2f;
+
+/// The number of frames a caption which is never erased is shown (one minute, like the last entry of `.idx` files).
+const LAST_CAPTION_FRAMES: i64 = 1800;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.scc` file (Scenarist captions with CEA-608 data).
+///
+/// The control codes of the first caption channel are decoded into text entries: pop-on captions are shown from
+/// their "end of caption" code until they are erased or replaced, roll-up captions from their first character
+/// until the next carriage return. Positions and styles are not kept.
+///
+/// `to_data()` encodes the entries as pop-on captions. The times are stored as frames of 29.97 fps video, lines
+/// longer than 32 characters are not split.
+pub struct SccFile {
+    /// Whether the timecodes are written as drop-frame timecodes (like `00:01:00;02`).
+    drop_frame: bool,
+
+    v: Vec<SccCaption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// One caption of a `.scc` file.
+struct SccCaption {
+    /// start frame
+    start: i64,
+
+    /// end frame
+    end: i64,
+
+    /// the rows of the caption, separated by `\n`
+    text: String,
+}
+
+fn frames_to_timepoint(frames: i64) -> TimePoint {
+    TimePoint::from_msecs((frames * 1001 + 15) / 30)
+}
+
+fn timepoint_to_frames(t: TimePoint) -> i64 {
+    (t.msecs().max(0) * 30 + 500) / 1001
+}
+
+/// The frame number of a timecode like `01:02:03;04` (`drop_frame` for timecodes which skip the frame numbers 0 and
+/// 1 in every minute not divisible by ten).
+fn timecode_to_frames(hours: i64, mins: i64, secs: i64, frames: i64, drop_frame: bool) -> i64 {
+    let total_mins = 60 * hours + mins;
+    let frame = (60 * total_mins + secs) * 30 + frames;
+    if drop_frame {
+        frame - 2 * (total_mins - total_mins / 10)
+    } else {
+        frame
+    }
+}
+
+fn frames_to_timecode(frame: i64, drop_frame: bool) -> String {
+    let mut frame = frame.max(0);
+    if drop_frame {
+        let (tens_of_mins, rest) = (frame / 17982, frame % 17982);
+        frame += 18 * tens_of_mins + if rest >= 2 { 2 * ((rest - 2) / 1798) } else { 0 };
+    }
+    format!(
+        "{:02}:{:02}:{:02}{}{:02}",
+        frame / 108_000,
+        frame / 1800 % 60,
+        frame / 30 % 60,
+        if drop_frame { ';' } else { ':' },
+        frame % 30
+    )
+}
+
+/// Add the odd parity bit to a seven bit character.
+fn with_parity(b: u8) -> u8 {
+    if b.count_ones() % 2 == 0 {
+        b | 0x80
+    } else {
+        b
+    }
+}
+
+/// The caption memory (the displayed or the non-displayed one).
+#[derive(Debug)]
+struct Memory {
+    rows: BTreeMap<u8, String>,
+    row: u8,
+}
+
+impl Memory {
+    fn new() -> Memory {
+        Memory {
+            rows: BTreeMap::new(),
+            row: 15,
+        }
+    }
+
+    fn set_row(&mut self, row: u8) {
+        self.row = row;
+        self.rows.entry(row).or_default();
+    }
+
+    fn push(&mut self, c: char) {
+        self.rows.entry(self.row).or_default().push(c);
+    }
+
+    fn backspace(&mut self) {
+        if let Some(row) = self.rows.get_mut(&self.row) {
+            row.pop();
+        }
+    }
+
+    fn text(&self) -> String {
+        self.rows.values().join("\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaptionMode {
+    PopOn,
+    RollUp,
+    PaintOn,
+}
+
+/// Decodes the caption data of the first channel into captions.
+struct Decoder {
+    mode: CaptionMode,
+    displayed: Memory,
+    non_displayed: Memory,
+
+    /// the frame at which the displayed memory was shown
+    shown_since: Option<i64>,
+
+    /// whether the characters belong to the first channel (changed by the control codes)
+    first_channel: bool,
+
+    /// the previous word if it is a control code (control codes are sent twice)
+    last_control: Option<[u8; 2]>,
+
+    captions: Vec<SccCaption>,
+}
+
+impl Decoder {
+    fn new() -> Decoder {
+        Decoder {
+            mode: CaptionMode::PopOn,
+            displayed: Memory::new(),
+            non_displayed: Memory::new(),
+            shown_since: None,
+            first_channel: true,
+            last_control: None,
+            captions: Vec::new(),
+        }
+    }
+
+    /// The memory the characters are written into.
+    fn target(&mut self, frame: i64) -> &mut Memory {
+        if self.mode == CaptionMode::PopOn {
+            &mut self.non_displayed
+        } else {
+            self.shown_since.get_or_insert(frame);
+            &mut self.displayed
+        }
+    }
+
+    /// End the displayed caption at `frame`.
+    fn hide(&mut self, frame: i64) {
+        if let Some(start) = self.shown_since.take() {
+            self.captions.push(SccCaption {
+                start,
+                end: frame,
+                text: self.displayed.text(),
+            });
+        }
+    }
+
+    fn push_char(&mut self, frame: i64, c: char) {
+        if self.first_channel {
+            self.target(frame).push(c);
+        }
+    }
+
+    fn decode_word(&mut self, frame: i64, word: [u8; 2]) {
+        let (b1, b2) = (word[0] & 0x7f, word[1] & 0x7f);
+        if !(0x10..0x20).contains(&b1) {
+            self.last_control = None;
+            for &b in &[b1, b2] {
+                if b >= 0x20 {
+                    let c = BASIC_CHARS.iter().find(|&&(code, _)| code == b).map_or(b as char, |&(_, c)| c);
+                    self.push_char(frame, c);
+                }
+            }
+            return;
+        }
+
+        // the second control code of a pair is ignored
+        if self.last_control == Some([b1, b2]) {
+            self.last_control = None;
+            return;
+        }
+        self.last_control = Some([b1, b2]);
+
+        self.first_channel = b1 & 0x08 == 0;
+        if !self.first_channel {
+            return;
+        }
+        let b1 = b1 & !0x08;
+
+        match (b1, b2) {
+            (0x14, RCL) => self.mode = CaptionMode::PopOn,
+            (0x14, RU2..=RU4) => self.mode = CaptionMode::RollUp,
+            (0x14, RDC) => self.mode = CaptionMode::PaintOn,
+            (0x14, BS) => self.target(frame).backspace(),
+            (0x14, EDM) => {
+                self.hide(frame);
+                self.displayed = Memory::new();
+            }
+            (0x14, ENM) => self.non_displayed = Memory::new(),
+            (0x14, CR) if self.mode == CaptionMode::RollUp => {
+                self.hide(frame);
+                self.displayed = Memory::new();
+            }
+            (0x14, EOC) => {
+                self.hide(frame);
+                std::mem::swap(&mut self.displayed, &mut self.non_displayed);
+                if !self.displayed.rows.is_empty() {
+                    self.shown_since = Some(frame);
+                }
+            }
+            // mid-row codes (color and italics) are shown as spaces
+            (0x11, 0x20..=0x2f) => self.push_char(frame, ' '),
+            (0x11, 0x30..=0x3f) => {
+                let c = SPECIAL_CHARS.chars().nth(usize::from(b2 - 0x30)).expect("16 special characters");
+                self.push_char(frame, c);
+            }
+            (0x12..=0x13, 0x20..=0x3f) => {
+                let c = EXTENDED_CHARS[usize::from(b1 - 0x12)]
+                    .chars()
+                    .nth(usize::from(b2 - 0x20))
+                    .expect("32 extended characters");
+                self.target(frame).backspace();
+                self.push_char(frame, c);
+            }
+            (_, 0x40..=0x7f) => {
+                if let Some(&(_, _, row)) = PAC_ROWS.iter().find(|&&(pac_b1, bit, _)| pac_b1 == b1 && bit == b2 & 0x20) {
+                    self.target(frame).set_row(row);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self, last_frame: i64) -> Vec<SccCaption> {
+        if let Some(start) = self.shown_since {
+            self.hide(last_frame.max(start + LAST_CAPTION_FRAMES));
+        }
+        self.captions
+    }
+}
+
+/// Encodes captions into the words of the caption data.
+#[derive(Default)]
+struct Encoder {
+    words: Vec<[u8; 2]>,
+
+    /// a character which waits for the second byte of its word
+    pending: Option<u8>,
+}
+
+impl Encoder {
+    fn flush(&mut self) {
+        if let Some(b) = self.pending.take() {
+            self.words.push([b, 0x00]);
+        }
+    }
+
+    fn control(&mut self, b1: u8, b2: u8) {
+        self.flush();
+        self.words.push([b1, b2]);
+        self.words.push([b1, b2]);
+    }
+
+    fn basic(&mut self, b: u8) {
+        match self.pending.take() {
+            Some(pending) => self.words.push([pending, b]),
+            None => self.pending = Some(b),
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        if let Some(&(b, _)) = BASIC_CHARS.iter().find(|&&(_, basic_c)| basic_c == c) {
+            self.basic(b);
+        } else if c.is_ascii() && !c.is_ascii_control() && BASIC_CHARS.iter().all(|&(b, _)| b != c as u8) {
+            self.basic(c as u8);
+        } else if let Some(i) = SPECIAL_CHARS.chars().position(|special_c| special_c == c) {
+            self.control(0x11, 0x30 + i as u8);
+        } else if let Some((set, i)) = (0..2).find_map(|set| Some((set, EXTENDED_CHARS[set].chars().position(|ext_c| ext_c == c)?))) {
+            // decoders without the extended characters show the space, the others replace it
+            self.basic(b' ');
+            self.control(0x12 + set as u8, 0x20 + i as u8);
+        } else {
+            self.basic(b'?');
+        }
+    }
+
+    /// The words of a pop-on caption, which is shown at the time of the line.
+    fn pop_on(text: &str) -> Vec<[u8; 2]> {
+        let mut encoder = Encoder::default();
+        encoder.control(0x14, RCL);
+        encoder.control(0x14, ENM);
+
+        let lines: Vec<&str> = if text.is_empty() { vec![""] } else { text.lines().collect() };
+        let first_row = 16 - lines.len().min(15) as u8;
+        for (i, line) in lines.into_iter().enumerate() {
+            let row = (first_row + i as u8).min(15);
+            let &(b1, bit, _) = PAC_ROWS.iter().find(|&&(_, _, pac_row)| pac_row == row).expect("rows 1 to 15");
+            encoder.control(b1, 0x40 | bit);
+            line.chars().for_each(|c| encoder.push_char(c));
+        }
+
+        encoder.control(0x14, EOC);
+        encoder.words
+    }
+}
+
+fn words_to_string(words: &[[u8; 2]]) -> String {
+    words
+        .iter()
+        .map(|word| format!("{:02x}{:02x}", with_parity(word[0]), with_parity(word[1])))
+        .join(" ")
+}
+
+impl SccFile {
+    /// Parse a `.scc` string to `SccFile`.
+    pub fn parse(s: &str) -> SubtitleParserResult<SccFile> {
+        Ok(Self::parse_file(s).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    /// Creates a `.scc` file with pop-on captions from scratch (the times are rounded to frames).
+    pub fn create(v: Vec<(TimeSpan, String)>) -> SubtitleParserResult<SccFile> {
+        Ok(SccFile {
+            drop_frame: true,
+            v: v.into_iter()
+                .map(|(timespan, text)| SccCaption {
+                    start: timepoint_to_frames(timespan.start),
+                    end: timepoint_to_frames(timespan.end),
+                    text,
+                })
+                .collect(),
+        })
+    }
+
+    fn parse_file(i: &str) -> Result<SccFile> {
+        let (_, s) = split_bom(i);
+        let mut lines = s.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        if lines.next().map_or(true, |(_, line)| line.trim() != HEADER) {
+            return Err(ExpectedHeader.into());
+        }
+
+        let mut decoder = Decoder::new();
+        let mut drop_frame = true;
+        let mut last_frame = 0;
+        for (line_num, line) in lines {
+            let (frame, line_drop_frame, words) = Self::parse_line(line)
+                .ok_or_else(|| Error::from(ExpectedSccLine { line: line.to_string() }))
+                .with_context(|_| ErrorAtLine { line_num })?;
+            drop_frame = line_drop_frame;
+            last_frame = frame;
+            for word in words {
+                decoder.decode_word(frame, word);
+            }
+        }
+
+        Ok(SccFile {
+            drop_frame,
+            v: decoder.finish(last_frame),
+        })
+    }
+
+    /// Matches a line like "00:00:01;02 9420 9420 94ae 94ae" (with a tab after the timecode).
+    fn parse_line(line: &str) -> Option<(i64, bool, Vec<[u8; 2]>)> {
+        let mut parts = line.split_whitespace();
+        let timecode = parts.next()?;
+        let drop_frame = timecode.contains(';') || timecode.contains('.');
+        let numbers = timecode
+            .split([':', ';', '.'])
+            .map(|n| {
+                if n.bytes().all(|b| b.is_ascii_digit()) {
+                    n.parse::<i64>().ok()
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<Vec<i64>>>()?;
+        let frame = match numbers[..] {
+            [hours, mins, secs, frames] => timecode_to_frames(hours, mins, secs, frames, drop_frame),
+            _ => return None,
+        };
+
+        let words = parts
+            .map(|word| {
+                if word.len() != 4 || !word.is_char_boundary(2) {
+                    return None;
+                }
+                Some([u8::from_str_radix(&word[..2], 16).ok()?, u8::from_str_radix(&word[2..], 16).ok()?])
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some((frame, drop_frame, words))
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.v.len();
+        footprint.add_vec(&self.v);
+        self.v.iter().for_each(|caption| footprint.add_text(&caption.text));
+        footprint
+    }
+}
+
+impl SubtitleFileInterface for SccFile {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        Ok(self
+            .v
+            .iter()
+            .map(|caption| {
+                let timespan = TimeSpan::new(frames_to_timepoint(caption.start), frames_to_timepoint(caption.end));
+                SubtitleEntry::new(timespan, caption.text.clone())
+            })
+            .collect())
+    }
+
+    fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        check_entry_count(self.v.len(), new_subtitle_entries.len())?;
+
+        for (caption_ref, new_entry_ref) in self.v.iter_mut().zip(new_subtitle_entries) {
+            caption_ref.start = timepoint_to_frames(new_entry_ref.timespan.start);
+            caption_ref.end = timepoint_to_frames(new_entry_ref.timespan.end);
+            if let Some(ref text) = new_entry_ref.line {
+                caption_ref.text = text.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every caption is loaded and shown at its start and erased at its end (unless the next caption replaces it).
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        let mut result = format!("{}\n", HEADER);
+        for (i, caption) in self.v.iter().enumerate() {
+            let start_words = words_to_string(&Encoder::pop_on(&caption.text));
+            result.push_str(&format!("\n{}\t{}\n", frames_to_timecode(caption.start, self.drop_frame), start_words));
+
+            if self.v.get(i + 1).map_or(true, |next| next.start > caption.end) {
+                let end_words = words_to_string(&[[0x14, EDM], [0x14, EDM]]);
+                result.push_str(&format!("\n{}\t{}\n", frames_to_timecode(caption.end, self.drop_frame), end_words));
+            }
+        }
+
+        Ok(result.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scc_test() {
+        assert_eq!(frames_to_timecode(1800, true), "00:01:00;02");
+        assert_eq!(frames_to_timecode(17982, true), "00:10:00;00");
+        assert_eq!(timecode_to_frames(0, 1, 0, 2, true), 1800);
+        assert_eq!(frames_to_timecode(1800, false), "00:01:00:00");
+
+        // "HELLO" with a PAC for row 15, "über" in roll-up mode on the second channel is ignored
+        let data = "Scenarist_SCC V1.0\n\n\
+                    00:00:00;00\t9420 9420 94ae 94ae 94e0 94e0 c845 4c4c 4f80 942f 942f\n\n\
+                    00:00:01;00\t1c25 1c25 7562 e5f2\n\n\
+                    00:00:02;00\t942c 942c\n";
+        let file = SccFile::parse(data).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].line.as_deref(), Some("HELLO"));
+        assert_eq!(entries[0].timespan, TimeSpan::new(TimePoint::from_msecs(0), TimePoint::from_msecs(2002)));
+
+        let created = SccFile::create(vec![
+            (TimeSpan::new(TimePoint::from_secs(1), TimePoint::from_secs(2)), "Ärger *\nõ♪".to_string()),
+            (TimeSpan::new(TimePoint::from_secs(2), TimePoint::from_secs(3)), "I".to_string()),
+        ])
+        .unwrap();
+        let created_data = String::from_utf8(created.to_data().unwrap()).unwrap();
+        assert_eq!(
+            created_data,
+            "Scenarist_SCC V1.0\n\n\
+             00:00:01;00\t9420 9420 94ae 94ae 9440 9440 2080 13b0 13b0 f267 e5f2 2020 92a8 92a8 94e0 94e0 2080 13a8 13a8 9137 9137 942f 942f\n\n\
+             00:00:02;00\t9420 9420 94ae 94ae 94e0 94e0 4980 942f 942f\n\n\
+             00:00:03;00\t942c 942c\n"
+        );
+        let parsed = SccFile::parse(&created_data).unwrap();
+        assert_eq!(parsed, created);
+        assert_eq!(parsed.get_subtitle_entries().unwrap()[0].line.as_deref(), Some("Ärger *\nõ♪"));
+
+        assert!(SccFile::parse("00:00:00;00\t9420\n").is_err());
+        assert!(SccFile::parse("Scenarist_SCC V1.0\n\n00:00:00;00\t94\n").is_err());
+    }
+}
@@ -4,30 +4,37 @@
 
 use self::errors::ErrorKind::*;
 use self::errors::*;
-use crate::{SubtitleEntry, SubtitleFileInterface};
+use crate::serialize::NewlineStyle;
+use crate::template::FileTemplate;
+use crate::{SerializeOptions, SubtitleEntry, SubtitleFileInterface, SubtitleSourceRanges};
 
 use crate::errors::Result as SubtitleParserResult;
 use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
 use combine::char::{char, string};
-use combine::combinator::{eof, parser as p, skip_many};
-use combine::primitives::Parser;
+use combine::combinator::{any, eof, many, or, parser as p, skip_many};
+use combine::primitives::{ParseResult, Parser, Stream};
 
 use failure::ResultExt;
 
 use itertools::Itertools;
 
-use crate::timetypes::{TimePoint, TimeSpan};
+use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
+use crate::warnings::{collect_warnings, ParseWarning, ParseWarningKind};
+use std::hash::{Hash, Hasher};
 use std::iter::once;
+use std::num::IntErrorKind;
 
 type Result<T> = std::result::Result<T, Error>;
 
 /// Errors specific to `.srt`-parsing.
-#[allow(missing_docs)]
+#[allow(missing_docs, non_local_definitions)]
 pub mod errors {
 
     define_error!(Error, ErrorKind);
 
     #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
     pub enum ErrorKind {
         #[fail(display = "expected SubRip index line, found '{}'", line)]
         ExpectedIndexLine { line: String },
@@ -38,6 +45,17 @@ pub mod errors {
         #[fail(display = "parse error at line `{}`", line_num)]
         ErrorAtLine { line_num: usize },
     }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::ExpectedIndexLine { .. } => "srt-expected-index-line",
+                ErrorKind::ExpectedTimestampLine { .. } => "srt-expected-timestamp-line",
+                ErrorKind::ErrorAtLine { .. } => "srt-error-at-line",
+            }
+        }
+    }
 }
 
 /// The parsing works as a finite state machine. These are the states in it.
@@ -45,20 +63,153 @@ enum SrtParserState {
     // emptyline or index follows
     Emptyline,
 
-    /// timing line follows
-    Index(i64),
+    /// timing line follows (the original index line is kept in case this is not an entry)
+    Index(i64, String),
+
+    /// lines of a block that is not an entry (credits, comments, ...); emptyline or more lines follow
+    NonEntryBlock(Vec<String>),
+
+    /// stray lines which are folded into the previous entry; emptyline or more lines follow
+    StrayLines,
 
     /// dialog or emptyline follows
-    Timing(i64, TimeSpan),
+    Timing(i64, TimeSpan, SubtitleSourceRanges),
 
     /// emptyline follows
-    Dialog(i64, TimeSpan, Vec<String>),
+    Dialog(i64, TimeSpan, Vec<String>, SubtitleSourceRanges),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Options for `SrtFile::parse_with_options()`.
+pub struct SrtParseOptions {
+    /// Keep blocks which are not subtitle entries (like a credit block before the first entry or
+    /// `NOTE` comments) instead of failing with `ExpectedIndexLine`. These blocks are written
+    /// unchanged by `to_data()`.
+    pub preserve_non_entry_blocks: bool,
+
+    /// Add blocks which don't start with an index line to the text of the previous entry (for example a line
+    /// that is separated from its entry by an empty line). Every folded block is reported with a
+    /// `ParseWarningKind::StrayLinesFolded` warning (see `SrtFile::entry_warnings()`).
+    pub fold_stray_lines: bool,
+
+    /// Treat a line with only a number as dialog unless it is followed by a timespan line (like ffmpeg does). A
+    /// number after the empty line at the end of an entry is then added to the text of the entry (reported with a
+    /// `ParseWarningKind::StrayLinesFolded` warning), and an index line with a timespan line directly after the
+    /// text starts a new entry. Without this option, a number after an empty line is always an index and a number
+    /// directly after the text is always text.
+    pub numeric_dialog_lines: bool,
+
+    /// Start an entry at a timespan line without an index line before it. The entry gets the index after the
+    /// previous entry (or 1), which is reported with a `ParseWarningKind::IndexFixed` warning.
+    pub missing_index_lines: bool,
+
+    /// Read the tags comments which `SerializeOptions::write_tags()` writes after the entries (like
+    /// `NOTE tags: needs-review, sign`) into the tags of the entries (see `SubtitleEntry::tags`). Without this
+    /// option, the tags comments are kept as blocks which are not entries. Both ways, `to_data()` writes them back.
+    pub read_tags_comments: bool,
+}
+
+impl SrtParseOptions {
+    /// Options which read malformed files like ffmpeg and pysubs2 do: blocks before the first entry are kept, stray
+    /// lines are folded into the previous entry, lines with only a number can be dialog and index lines can be
+    /// missing.
+    pub fn lenient() -> SrtParseOptions {
+        SrtParseOptions {
+            preserve_non_entry_blocks: true,
+            fold_stray_lines: true,
+            numeric_dialog_lines: true,
+            missing_index_lines: true,
+            ..SrtParseOptions::default()
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 /// Represents a `.srt` file.
 pub struct SrtFile {
-    v: Vec<SrtLine>,
+    v: Vec<SrtFilePart>,
+
+    /// How `<i>`-tags are handled when entry texts are split into lines and merged again.
+    italic_normalization: SrtItalicNormalization,
+
+    /// Problems that were found while parsing.
+    warnings: Vec<ParseWarning>,
+
+    /// The character between the seconds and milliseconds of the written timestamps.
+    millisecond_separator: SrtMillisecondSeparator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The character between the seconds and the milliseconds of `.srt` timestamps.
+pub enum SrtMillisecondSeparator {
+    /// Timestamps like `00:00:01,500`, as defined by the format (default).
+    Comma,
+
+    /// Timestamps like `00:00:01.500`, which many files in the wild use.
+    Dot,
+}
+
+impl SrtMillisecondSeparator {
+    fn to_char(self) -> char {
+        match self {
+            SrtMillisecondSeparator::Comma => ',',
+            SrtMillisecondSeparator::Dot => '.',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Defines how `SrtFile::fix_timespans()` repairs entries with zero-length or negative timespans.
+pub enum SrtTimespanFix {
+    /// Swap start and end of negative timespans (zero-length timespans are not changed).
+    Swap,
+
+    /// Set the end of the timespan to its start plus this duration.
+    SetDuration(TimeDelta),
+
+    /// Set the end of the timespan to the start of the next entry, but show the subtitle for at most
+    /// this duration (the last entry is always shown for this duration).
+    ExtendToNext(TimeDelta),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Defines how `<i>`-tags are treated when the text of a multi-line entry is split into lines
+/// (`update_subtitle_entries()`) or merged into a single string (`get_subtitle_entries()`).
+pub enum SrtItalicNormalization {
+    /// Leave the `<i>`-tags as they are.
+    Keep,
+
+    /// Every line gets its own balanced `<i>...</i>` pair: an italic span which continues over a line break is
+    /// closed at the end of the line and re-opened at the start of the next line (as many players expect).
+    /// When merging lines, these per-line pairs are joined to a single span again.
+    BalancePerLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Adjustments for the quirks of players when writing `.srt` files (see `SerializeOptions::srt_profile()`).
+///
+/// Both profiles number the entries consecutively from 1 (or from 0 if the first entry has the index 0), write
+/// negative times as zero and remove empty lines from the texts (which would end the entry).
+pub enum SrtProfile {
+    /// For players which only know the original format: only `<i>`, `<b>` and `<u>` tags are kept, blocks which
+    /// are not entries, coordinates after the timespans (and the tags of `SerializeOptions::write_tags()`) are
+    /// removed, the milliseconds are separated by commas and the lines end with `\r\n` (unless other line endings
+    /// are set).
+    Strict,
+
+    /// For VLC: `<font>` tags and `{\anN}` alignment tags are kept as well, other `{...}` override blocks
+    /// (which VLC shows as text) are removed.
+    Vlc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A block of a `.srt` file.
+enum SrtFilePart {
+    /// A block which is not a subtitle entry (preserved with `SrtParseOptions::preserve_non_entry_blocks`).
+    Filler(String),
+
+    /// A subtitle entry.
+    Entry(SrtLine),
 }
 
 #[derive(Debug, Clone)]
@@ -72,165 +223,1246 @@ struct SrtLine {
 
     /// the dialog/text lines of the `SrtLine`
     texts: Vec<String>,
+
+    /// where the timestamps and text lines are in the parsed input (`None` for created files)
+    source: Option<SubtitleSourceRanges>,
+
+    /// the user tags of the entry (see `SubtitleEntry::tags`)
+    tags: Vec<String>,
+
+    /// the rectangle like "X1:100 X2:600 Y1:400 Y2:450" after the timespan (an extension of some rippers)
+    coordinates: Option<String>,
+
+    /// the tags comment after the entry in the parsed input (see `SrtParseOptions::read_tags_comments`)
+    tags_comment: Option<String>,
+}
+
+// the files are compared by their content: the warnings and where the entries were in the parsed input are left out,
+// so a parsed file equals a created one (or one with other line endings)
+impl PartialEq for SrtFile {
+    fn eq(&self, other: &SrtFile) -> bool {
+        self.v == other.v && self.italic_normalization == other.italic_normalization && self.millisecond_separator == other.millisecond_separator
+    }
+}
+
+impl Eq for SrtFile {}
+
+impl Hash for SrtFile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.v.hash(state);
+        self.italic_normalization.hash(state);
+        self.millisecond_separator.hash(state);
+    }
+}
+
+impl PartialEq for SrtLine {
+    fn eq(&self, other: &SrtLine) -> bool {
+        self.timespan == other.timespan
+            && self.index == other.index
+            && self.texts == other.texts
+            && self.tags == other.tags
+            && self.coordinates == other.coordinates
+    }
 }
 
+impl Eq for SrtLine {}
+
+impl Hash for SrtLine {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.timespan.hash(state);
+        self.index.hash(state);
+        self.texts.hash(state);
+        self.tags.hash(state);
+        self.coordinates.hash(state);
+    }
+}
+
+/// The start of the comment blocks in which `SerializeOptions::write_tags()` stores the tags of the previous entry.
+const TAGS_COMMENT_PREFIX: &str = "NOTE tags:";
+
 impl SrtFile {
     /// Parse a `.srt` subtitle string to `SrtFile`.
     pub fn parse(s: &str) -> SubtitleParserResult<SrtFile> {
-        Ok(Self::parse_file(s).with_context(|_| crate::ErrorKind::ParsingError)?)
+        Self::parse_with_options(s, SrtParseOptions::default())
+    }
+
+    /// Parse a `.srt` subtitle string to `SrtFile` with non-default options.
+    pub fn parse_with_options(s: &str, options: SrtParseOptions) -> SubtitleParserResult<SrtFile> {
+        Ok(Self::parse_file(s, options).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    /// Parse a `.srt` subtitle string to `SrtFile` and return the problems which did not prevent parsing: the
+    /// `warnings()` of the file and the ones of `warnings::check_entries()`.
+    pub fn parse_with_warnings(s: &str, options: SrtParseOptions) -> SubtitleParserResult<(SrtFile, Vec<ParseWarning>)> {
+        let file = Self::parse_with_options(s, options)?;
+        let warnings = collect_warnings(&file.warnings, &file.get_subtitle_entries()?, s, |entry_idx| {
+            file.source_ranges(entry_idx).map(|ranges| ranges.start.start)
+        });
+        Ok((file, warnings))
+    }
+
+    /// Where the timestamps and text lines of an entry are in the parsed input (`None` if the file was created
+    /// and not parsed, or if there is no such entry).
+    pub fn source_ranges(&self, entry_idx: usize) -> Option<SubtitleSourceRanges> {
+        self.lines().nth(entry_idx).and_then(|line| line.source.clone())
+    }
+
+    /// All subtitle entries (without the preserved non-entry blocks).
+    fn lines(&self) -> impl Iterator<Item = &SrtLine> {
+        self.v.iter().filter_map(|part| match part {
+            SrtFilePart::Entry(line) => Some(line),
+            SrtFilePart::Filler(_) => None,
+        })
+    }
+
+    /// All subtitle entries (without the preserved non-entry blocks).
+    fn lines_mut(&mut self) -> impl Iterator<Item = &mut SrtLine> {
+        self.v.iter_mut().filter_map(|part| match part {
+            SrtFilePart::Entry(line) => Some(line),
+            SrtFilePart::Filler(_) => None,
+        })
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.add_vec(&self.v);
+        footprint.add_vec(&self.warnings);
+        for part in &self.v {
+            match part {
+                SrtFilePart::Filler(text) => footprint.add_filler(text),
+                SrtFilePart::Entry(line) => {
+                    footprint.entry_count += 1;
+                    footprint.add_vec(&line.texts);
+                    footprint.add_vec(&line.tags);
+                    line.texts
+                        .iter()
+                        .chain(&line.tags)
+                        .chain(&line.tags_comment)
+                        .for_each(|text| footprint.add_text(text));
+                    if let Some(source) = &line.source {
+                        footprint.add_vec(&source.text);
+                    }
+                }
+            }
+        }
+        footprint
     }
 }
 
 /// Implements parse functions.
 impl SrtFile {
-    fn parse_file(i: &str) -> Result<SrtFile> {
+    fn parse_file(i: &str, options: SrtParseOptions) -> Result<SrtFile> {
         use self::SrtParserState::*;
 
-        let mut result: Vec<SrtLine> = Vec::new();
+        let mut result: Vec<SrtFilePart> = Vec::new();
+        let mut warnings: Vec<ParseWarning> = Vec::new();
+
+        // the number of entries in `result`, which is the index of the next entry for the warnings
+        let mut entry_count = 0;
 
         // remove utf-8 bom
-        let (_, s) = split_bom(i);
+        let (bom, s) = split_bom(i);
+
+        // the byte position of every line in the input (`lines()` and `split_inclusive()` return the same number of lines)
+        let line_starts = s.split_inclusive('\n').scan(bom.len(), |pos, line| {
+            let start = *pos;
+            *pos += line.len();
+            Some(start)
+        });
 
         let mut state: SrtParserState = Emptyline; // expect emptyline or index
 
         // the `once("")` is there so no last entry gets ignored
-        for (line_num, line) in s.lines().chain(once("")).enumerate() {
+        let mut lines = s.lines().zip(line_starts).chain(once(("", i.len()))).enumerate().peekable();
+        while let Some((line_num, (line, line_start))) = lines.next() {
+            // every line adds at most one file part
+            let part_count = result.len();
+            // is this a line with only a number which does not start an entry (see `SrtParseOptions::numeric_dialog_lines`)?
+            let numeric_dialog = options.numeric_dialog_lines
+                && Self::parse_index_line(line_num, line).is_ok()
+                && lines
+                    .peek()
+                    .map_or(true, |&(_, (next, _))| Self::parse_timespan_line(line_num + 1, next).is_err());
+
             state = match state {
                 Emptyline => {
+                    // tags comments (see `TAGS_COMMENT_PREFIX`) belong to the previous entry
+                    if line.trim().is_empty() || (options.read_tags_comments && Self::attach_tags_comment(line, &mut result)) {
+                        Emptyline
+                    } else if Self::is_tags_comment(line, &result) {
+                        NonEntryBlock(vec![line.to_string()])
+                    } else {
+                        match Self::parse_index_line(line_num, line) {
+                            Ok(_) if numeric_dialog && Self::fold_stray_line(line, line_start, &mut result) => {
+                                warnings.push(ParseWarning {
+                                    line_num,
+                                    entry_idx: Some(entry_count - 1),
+                                    kind: ParseWarningKind::StrayLinesFolded,
+                                });
+                                StrayLines
+                            }
+                            Ok(index) => Index(index, line.to_string()),
+                            Err(e) => {
+                                let timespan = if options.missing_index_lines {
+                                    Self::parse_timespan_line(line_num, line).ok()
+                                } else {
+                                    None
+                                };
+                                match timespan {
+                                    // the index line is missing, so the entry gets the index after the previous one
+                                    Some(timespan) => {
+                                        let index = Self::last_index(&result).map_or(1, |index| index.saturating_add(1));
+                                        warnings.push(ParseWarning {
+                                            line_num,
+                                            entry_idx: Some(entry_count),
+                                            kind: ParseWarningKind::IndexFixed,
+                                        });
+                                        Self::state_timing(line_num, line, line_start, entry_count, &mut warnings, index, timespan)
+                                    }
+                                    None if options.fold_stray_lines && Self::fold_stray_line(line, line_start, &mut result) => {
+                                        warnings.push(ParseWarning {
+                                            line_num,
+                                            entry_idx: Some(entry_count - 1),
+                                            kind: ParseWarningKind::StrayLinesFolded,
+                                        });
+                                        StrayLines
+                                    }
+                                    None if options.preserve_non_entry_blocks => NonEntryBlock(vec![line.to_string()]),
+                                    None => return Err(e),
+                                }
+                            }
+                        }
+                    }
+                }
+                Index(index, index_line) => match Self::parse_timespan_line(line_num, line) {
+                    Ok(timespan) => Self::state_timing(line_num, line, line_start, entry_count, &mut warnings, index, timespan),
+                    Err(_) if options.preserve_non_entry_blocks => Self::state_expect_non_entry_line(line, &mut result, vec![index_line]),
+                    Err(e) => return Err(e),
+                },
+                NonEntryBlock(lines) => Self::state_expect_non_entry_line(line, &mut result, lines),
+                StrayLines => {
                     if line.trim().is_empty() {
                         Emptyline
                     } else {
-                        Index(Self::parse_index_line(line_num, line)?)
+                        Self::fold_stray_line(line, line_start, &mut result);
+                        StrayLines
                     }
                 }
-                Index(index) => Timing(index, Self::parse_timespan_line(line_num, line)?),
-                Timing(index, timespan) => Self::state_expect_dialog(line, &mut result, index, timespan, Vec::new()),
-                Dialog(index, timespan, texts) => Self::state_expect_dialog(line, &mut result, index, timespan, texts),
+                Timing(index, timespan, source) => Self::state_expect_dialog(line, line_start, &mut result, index, timespan, Vec::new(), source),
+                Dialog(index, timespan, texts, source) => match Self::parse_index_line(line_num, line) {
+                    Ok(next_index) if options.numeric_dialog_lines && !numeric_dialog => {
+                        // the empty line between the entries is missing
+                        Self::state_expect_dialog("", line_start, &mut result, index, timespan, texts, source);
+                        Index(next_index, line.to_string())
+                    }
+                    _ => Self::state_expect_dialog(line, line_start, &mut result, index, timespan, texts, source),
+                },
             };
+            if result.len() > part_count && matches!(result.last(), Some(SrtFilePart::Entry(_))) {
+                entry_count += 1;
+            }
         }
 
-        Ok(SrtFile { v: result })
+        // the coordinates are after the end timestamp (the timespan line was checked by `parse_timespan_line()`)
+        for part in &mut result {
+            if let SrtFilePart::Entry(SrtLine {
+                source: Some(source),
+                coordinates,
+                ..
+            }) = part
+            {
+                coordinates.clone_from(&i[source.end.end..].lines().next().and_then(Self::parse_coordinates));
+            }
+        }
+
+        // files which only use dots are written with dots again
+        let sources: Vec<&SubtitleSourceRanges> = result
+            .iter()
+            .filter_map(|part| match part {
+                SrtFilePart::Entry(line) => line.source.as_ref(),
+                SrtFilePart::Filler(_) => None,
+            })
+            .collect();
+        let millisecond_separator = if !sources.is_empty()
+            && sources
+                .iter()
+                .all(|source| i[source.start.clone()].contains('.') && i[source.end.clone()].contains('.'))
+        {
+            SrtMillisecondSeparator::Dot
+        } else {
+            SrtMillisecondSeparator::Comma
+        };
+
+        Ok(SrtFile {
+            v: result,
+            italic_normalization: SrtItalicNormalization::Keep,
+            warnings,
+            millisecond_separator,
+        })
     }
 
-    fn state_expect_dialog(line: &str, result: &mut Vec<SrtLine>, index: i64, timespan: TimeSpan, mut texts: Vec<String>) -> SrtParserState {
-        if line.trim().is_empty() {
-            result.push(SrtLine {
-                index: index,
-                timespan: timespan,
-                texts: texts,
+    /// The index of the last entry in the parsed file parts.
+    fn last_index(result: &[SrtFilePart]) -> Option<i64> {
+        result.iter().rev().find_map(|part| match part {
+            SrtFilePart::Entry(entry) => Some(entry.index),
+            SrtFilePart::Filler(_) => None,
+        })
+    }
+
+    /// Start the entry `entry_idx` with the parsed timespan `line` (and warn about zero-length and negative timespans).
+    fn state_timing(
+        line_num: usize,
+        line: &str,
+        line_start: usize,
+        entry_idx: usize,
+        warnings: &mut Vec<ParseWarning>,
+        index: i64,
+        timespan: TimeSpan,
+    ) -> SrtParserState {
+        if timespan.end == timespan.start {
+            warnings.push(ParseWarning {
+                line_num,
+                entry_idx: Some(entry_idx),
+                kind: ParseWarningKind::ZeroLengthTimespan,
+            });
+        } else if timespan.end < timespan.start {
+            warnings.push(ParseWarning {
+                line_num,
+                entry_idx: Some(entry_idx),
+                kind: ParseWarningKind::NegativeTimespan,
             });
+        }
+        let arrow_pos = line.find("-->").expect("the timespan line was parsed");
+        // the end timestamp can be followed by coordinates
+        let after_arrow = &line[arrow_pos + 3..];
+        let leading_ws = after_arrow.len() - after_arrow.trim_start().len();
+        let end_len = after_arrow[leading_ws..]
+            .find(char::is_whitespace)
+            .unwrap_or(after_arrow.len() - leading_ws);
+        let source = SubtitleSourceRanges {
+            start: trimmed_range(&line[..arrow_pos], line_start),
+            end: trimmed_range(&after_arrow[..leading_ws + end_len], line_start + arrow_pos + 3),
+            text: Vec::new(),
+        };
+        SrtParserState::Timing(index, timespan, source)
+    }
+
+    /// Add a stray line to the text of the previous entry. Returns `false` if the previous block is not an entry.
+    fn fold_stray_line(line: &str, line_start: usize, result: &mut [SrtFilePart]) -> bool {
+        match result.last_mut() {
+            Some(SrtFilePart::Entry(entry)) => {
+                entry.texts.push(line.trim().to_string());
+                if let Some(ref mut source) = entry.source {
+                    source.text.push(trimmed_range(line, line_start));
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Is the line a tags comment (see `TAGS_COMMENT_PREFIX`) after an entry?
+    fn is_tags_comment(line: &str, result: &[SrtFilePart]) -> bool {
+        line.trim().starts_with(TAGS_COMMENT_PREFIX) && matches!(result.last(), Some(SrtFilePart::Entry(_)))
+    }
+
+    /// Set the tags of the previous entry from a tags comment (see `TAGS_COMMENT_PREFIX`). Returns `false` if the
+    /// line is no tags comment or the previous block is not an entry.
+    fn attach_tags_comment(line: &str, result: &mut [SrtFilePart]) -> bool {
+        match (line.trim().strip_prefix(TAGS_COMMENT_PREFIX), result.last_mut()) {
+            (Some(tags), Some(SrtFilePart::Entry(entry))) => {
+                entry.tags = split_tags(tags, ',');
+                entry.tags_comment = Some(line.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn state_expect_non_entry_line(line: &str, result: &mut Vec<SrtFilePart>, mut lines: Vec<String>) -> SrtParserState {
+        if line.trim().is_empty() {
+            result.push(SrtFilePart::Filler(lines.join("\n")));
+            SrtParserState::Emptyline
+        } else {
+            lines.push(line.to_string());
+            SrtParserState::NonEntryBlock(lines)
+        }
+    }
+
+    fn state_expect_dialog(
+        line: &str,
+        line_start: usize,
+        result: &mut Vec<SrtFilePart>,
+        index: i64,
+        timespan: TimeSpan,
+        mut texts: Vec<String>,
+        mut source: SubtitleSourceRanges,
+    ) -> SrtParserState {
+        if line.trim().is_empty() {
+            result.push(SrtFilePart::Entry(SrtLine {
+                index,
+                timespan,
+                texts,
+                source: Some(source),
+                tags: Vec::new(),
+                coordinates: None,
+                tags_comment: None,
+            }));
             SrtParserState::Emptyline
         } else {
             texts.push(line.trim().to_string());
-            SrtParserState::Dialog(index, timespan, texts)
+            source.text.push(trimmed_range(line, line_start));
+            SrtParserState::Dialog(index, timespan, texts, source)
         }
     }
 
-    /// Matches a line with a single index.
+    /// Matches a line with a single index. Numbers which don't fit into an `i64` are saturated.
     fn parse_index_line(line_num: usize, s: &str) -> Result<i64> {
-        Ok(s.trim()
-            .parse::<i64>()
+        let result = s.trim().parse::<i64>().or_else(|e| match e.kind() {
+            IntErrorKind::PosOverflow => Ok(i64::MAX),
+            IntErrorKind::NegOverflow => Ok(i64::MIN),
+            _ => Err(e),
+        });
+        Ok(result
             .with_context(|_| ExpectedIndexLine { line: s.to_string() })
             .with_context(|_| ErrorAtLine { line_num })?)
     }
 
-    /// Matches a `SubRip` timespan like "00:24:45,670 --> 00:24:45,680".
-    fn parse_timespan_line(line_num: usize, line: &str) -> Result<TimeSpan> {
-        // Matches a `SubRip` timestamp like "00:24:45,670"
-        let timestamp = |s| {
-            (
-                p(number_i64),
-                char(':'),
-                p(number_i64),
-                char(':'),
-                p(number_i64),
-                char(','),
-                p(number_i64),
-            )
-                .map(|t| TimePoint::from_components(t.0, t.2, t.4, t.6))
-                .parse_stream(s)
-        };
+    /// Matches a `SubRip` timestamp like "00:24:45,670" (or "00:24:45.670").
+    fn timestamp<I>(input: I) -> ParseResult<TimePoint, I>
+    where
+        I: Stream<Item = char>,
+    {
+        (
+            p(number_i64),
+            char(':'),
+            p(number_i64),
+            char(':'),
+            p(number_i64),
+            or(char(','), char('.')),
+            p(number_i64),
+        )
+            .map(|t| TimePoint::from_components(t.0, t.2, t.4, t.6))
+            .parse_stream(input)
+    }
 
+    /// Parse a single `SubRip` timestamp like "00:24:45,670" or "00:24:45.670" (surrounding whitespace is ignored).
+    pub(crate) fn parse_timestamp(s: &str) -> Result<TimePoint> {
+        (p(Self::timestamp), eof())
+            .map(|t| t.0)
+            .parse(s.trim())
+            .map(|x| x.0)
+            .map_err(|_| Error::from(ExpectedTimestampLine { line: s.to_string() }))
+    }
+
+    /// Matches a `SubRip` timespan like "00:24:45,670 --> 00:24:45,680" (the milliseconds can also be separated by dots).
+    fn parse_timespan_line(line_num: usize, line: &str) -> Result<TimeSpan> {
         let result = (
             skip_many(ws()),
-            p(&timestamp),
+            p(Self::timestamp),
             skip_many(ws()),
             string("-->"),
             skip_many(ws()),
-            p(&timestamp),
-            skip_many(ws()),
-            eof(),
+            p(Self::timestamp),
+            many(any()),
         )
-            .map(|t| TimeSpan::new(t.1, t.5))
+            .map(|t: (_, _, _, _, _, _, String)| (TimeSpan::new(t.1, t.5), t.6))
             .parse(line)
             .map(|x| x.0)
-            .map_err(|_| Error::from(ExpectedTimestampLine { line: line.to_string() }))
+            .ok()
+            .filter(|(_, rest)| rest.trim().is_empty() || Self::parse_coordinates(rest).is_some())
+            .map(|(timespan, _)| timespan)
+            .ok_or_else(|| Error::from(ExpectedTimestampLine { line: line.to_string() }))
             .with_context(|_| ErrorAtLine { line_num })?;
 
         Ok(result)
     }
+
+    /// Matches coordinates like " X1:100 X2:600 Y1:400 Y2:450" after a timespan.
+    fn parse_coordinates(s: &str) -> Option<String> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let valid = fields.len() == 4
+            && fields.iter().zip(&["X1:", "X2:", "Y1:", "Y2:"]).all(|(field, name)| {
+                field.len() > name.len() && field[..name.len()].eq_ignore_ascii_case(name) && field[name.len()..].parse::<i64>().is_ok()
+            });
+        if valid {
+            Some(fields.join(" "))
+        } else {
+            None
+        }
+    }
 }
 
 impl SubtitleFileInterface for SrtFile {
     fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
         let timings = self
-            .v
-            .iter()
-            .map(|line| SubtitleEntry::new(line.timespan, line.texts.iter().join("\n")))
+            .lines()
+            .map(|line| SubtitleEntry {
+                index: Some(line.index),
+                tags: line.tags.clone(),
+                ..SubtitleEntry::new(line.timespan, self.merge_lines(&line.texts))
+            })
             .collect();
 
         Ok(timings)
     }
 
     fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
-        assert_eq!(self.v.len(), new_subtitle_entries.len()); // required by specification of this function
+        check_entry_count(self.lines().count(), new_subtitle_entries.len())?;
 
-        for (line_ref, new_entry_ref) in self.v.iter_mut().zip(new_subtitle_entries) {
+        let italic_normalization = self.italic_normalization;
+        for (line_ref, new_entry_ref) in self.lines_mut().zip(new_subtitle_entries) {
             line_ref.timespan = new_entry_ref.timespan;
             if let Some(ref text) = new_entry_ref.line {
-                line_ref.texts = text.lines().map(str::to_string).collect();
+                line_ref.texts = split_lines(text, italic_normalization);
             }
+            if let Some(index) = new_entry_ref.index {
+                line_ref.index = index;
+            }
+            line_ref.tags = new_entry_ref.tags.clone();
         }
 
         Ok(())
     }
 
     fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
-        let timepoint_to_str =
-            |t: TimePoint| -> String { format!("{:02}:{:02}:{:02},{:03}", t.hours(), t.mins_comp(), t.secs_comp(), t.msecs_comp()) };
-        let line_to_str = |line: &SrtLine| -> String {
+        Ok(self.to_string_with_tags(false).into_bytes())
+    }
+
+    fn to_data_with(&self, options: &SerializeOptions) -> SubtitleParserResult<Vec<u8>> {
+        let profile = options.get_srt_profile();
+        let write_tags = options.get_write_tags() && profile != Some(SrtProfile::Strict);
+        let data = match (options.get_srt_italic_normalization(), profile) {
+            (Some(SrtItalicNormalization::Keep), None) | (None, None) => self.to_string_with_tags(write_tags),
+            (italic_normalization, profile) => {
+                let mut file = self.clone();
+                if let Some(profile) = profile {
+                    file.apply_profile(profile);
+                }
+                if italic_normalization == Some(SrtItalicNormalization::BalancePerLine) {
+                    file.normalize_italics();
+                }
+                file.to_string_with_tags(write_tags)
+            }
+        };
+
+        match profile {
+            Some(SrtProfile::Strict) => options.or_newline(NewlineStyle::CrLf).apply_to_text(data.into_bytes()),
+            Some(SrtProfile::Vlc) | None => options.apply_to_text(data.into_bytes()),
+        }
+    }
+}
+
+impl SrtFile {
+    /// Change the entries for the player quirks of the profile (the line endings are set by `to_data_with()`).
+    fn apply_profile(&mut self, profile: SrtProfile) {
+        if profile == SrtProfile::Strict {
+            self.v.retain(|part| matches!(part, SrtFilePart::Entry(_)));
+            self.millisecond_separator = SrtMillisecondSeparator::Comma;
+        }
+
+        self.reindex(if self.index_start() == Some(0) { 0 } else { 1 });
+
+        let zero = TimePoint::from_msecs(0);
+        for line in self.lines_mut() {
+            if profile == SrtProfile::Strict {
+                line.coordinates = None;
+                line.tags_comment = None;
+            }
+            line.timespan = TimeSpan::new(line.timespan.start.max(zero), line.timespan.end.max(zero));
+            line.texts = line
+                .texts
+                .iter()
+                .map(|text| filter_formatting(text, profile))
+                .filter(|text| !text.trim().is_empty())
+                .collect();
+        }
+    }
+
+    /// The content of the file, with a tags comment after every tagged entry if `write_tags` is set (and after the
+    /// entries which had one in the parsed input).
+    fn to_string_with_tags(&self, write_tags: bool) -> String {
+        let separator = self.millisecond_separator.to_char();
+        let timepoint_to_str = |t: TimePoint| -> String {
             format!(
-                "{}\n{} --> {}\n{}\n\n",
-                line.index,
-                timepoint_to_str(line.timespan.start),
-                timepoint_to_str(line.timespan.end),
-                line.texts.join("\n")
+                "{:02}:{:02}:{:02}{}{:03}",
+                t.hours(),
+                t.mins_comp(),
+                t.secs_comp(),
+                separator,
+                t.msecs_comp()
             )
         };
+        let part_to_str = |part: &SrtFilePart| -> String {
+            match part {
+                SrtFilePart::Filler(text) => format!("{}\n\n", text),
+                SrtFilePart::Entry(line) => {
+                    let mut s = format!(
+                        "{}\n{} --> {}{}\n{}\n\n",
+                        line.index,
+                        timepoint_to_str(line.timespan.start),
+                        timepoint_to_str(line.timespan.end),
+                        line.coordinates
+                            .as_ref()
+                            .map(|coordinates| format!(" {}", coordinates))
+                            .unwrap_or_default(),
+                        line.texts.join("\n")
+                    );
+                    let tags = join_tags(&line.tags, ',');
+                    match &line.tags_comment {
+                        // the comment of the input is written unchanged as long as the tags are the same
+                        Some(comment) if split_tags(&comment.trim()[TAGS_COMMENT_PREFIX.len()..], ',') == line.tags => {
+                            s.push_str(&format!("{}\n\n", comment));
+                        }
+                        tags_comment if (write_tags || tags_comment.is_some()) && !tags.is_empty() => {
+                            s.push_str(&format!("{} {}\n\n", TAGS_COMMENT_PREFIX, tags.replace(',', ", ")));
+                        }
+                        _ => {}
+                    }
+                    s
+                }
+            }
+        };
 
-        Ok(self.v.iter().map(line_to_str).collect::<String>().into_bytes())
+        self.v.iter().map(part_to_str).collect()
     }
 }
 
+impl SrtFile {
+    /// Set how `<i>`-tags are handled when entry texts are split into lines and merged again.
+    ///
+    /// This only affects subsequent calls of `get_subtitle_entries()` and `update_subtitle_entries()`. Use
+    /// `normalize_italics()` to apply `SrtItalicNormalization::BalancePerLine` to the current lines.
+    pub fn set_italic_normalization(&mut self, italic_normalization: SrtItalicNormalization) {
+        self.italic_normalization = italic_normalization;
+    }
+
+    /// The character between the seconds and the milliseconds of the timestamps written by `to_data()`.
+    ///
+    /// Parsed files in which all timestamps use dots (like `00:00:01.500`) are written with dots, all other files
+    /// with commas.
+    pub fn millisecond_separator(&self) -> SrtMillisecondSeparator {
+        self.millisecond_separator
+    }
+
+    /// Set the character between the seconds and the milliseconds of the timestamps written by `to_data()`.
+    pub fn set_millisecond_separator(&mut self, millisecond_separator: SrtMillisecondSeparator) {
+        self.millisecond_separator = millisecond_separator;
+    }
+
+    /// Whether an entry has coordinates like `X1:100 X2:600 Y1:400 Y2:450` after its timespan (which are written
+    /// by some rippers and are lost when the file is converted to other formats).
+    pub fn has_coordinates(&self) -> bool {
+        self.lines().any(|line| line.coordinates.is_some())
+    }
+
+    /// The index of the first entry (usually `1`, but some tools number the entries from `0`).
+    pub fn index_start(&self) -> Option<i64> {
+        self.lines().next().map(|line| line.index)
+    }
+
+    /// Number the entries consecutively, starting with `start_at` (like `0` for tools which expect 0-based numbers).
+    ///
+    /// Parsed files keep their numbers (also if they skip values) until this is called.
+    pub fn reindex(&mut self, start_at: i64) {
+        for (i, line) in self.lines_mut().enumerate() {
+            line.index = start_at.saturating_add(i as i64);
+        }
+    }
+
+    /// Problems (like zero-length or negative timespans) that were found while parsing the file.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// The problems that were found in the entry with the index `entry_idx` while parsing the file (including
+    /// the repairs of lenient parsing, like `ParseWarningKind::StrayLinesFolded`).
+    pub fn entry_warnings(&self, entry_idx: usize) -> impl Iterator<Item = &ParseWarning> {
+        self.warnings.iter().filter(move |warning| warning.entry_idx == Some(entry_idx))
+    }
+
+    /// Repair all entries whose timespan ends at or before its start.
+    pub fn fix_timespans(&mut self, fix: SrtTimespanFix) {
+        let next_starts: Vec<Option<TimePoint>> = self.lines().skip(1).map(|line| Some(line.timespan.start)).chain(once(None)).collect();
+
+        for (line, next_start) in self.lines_mut().zip(next_starts) {
+            let timespan = &mut line.timespan;
+            if timespan.end > timespan.start {
+                continue;
+            }
+
+            match fix {
+                SrtTimespanFix::Swap => *timespan = TimeSpan::new(timespan.end, timespan.start),
+                SrtTimespanFix::SetDuration(duration) => timespan.end = timespan.start + duration,
+                SrtTimespanFix::ExtendToNext(max_duration) => {
+                    let max_end = timespan.start + max_duration;
+                    timespan.end = match next_start {
+                        Some(next_start) if next_start > timespan.start && next_start < max_end => next_start,
+                        _ => max_end,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Balance the `<i>`-tags of every line in the file (see `SrtItalicNormalization::BalancePerLine`).
+    pub fn normalize_italics(&mut self) {
+        for line in self.lines_mut() {
+            line.texts = balance_italics_per_line(&line.texts);
+        }
+    }
+
+    fn merge_lines(&self, texts: &[String]) -> String {
+        match self.italic_normalization {
+            SrtItalicNormalization::Keep => texts.iter().join("\n"),
+            SrtItalicNormalization::BalancePerLine => merge_italics_per_line(texts),
+        }
+    }
+}
+
+/// Splits the text of an entry into its lines.
+fn split_lines(text: &str, italic_normalization: SrtItalicNormalization) -> Vec<String> {
+    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+    match italic_normalization {
+        SrtItalicNormalization::Keep => lines,
+        SrtItalicNormalization::BalancePerLine => balance_italics_per_line(&lines),
+    }
+}
+
+/// Closes italic spans at the end of each line and re-opens them at the start of the next line.
+fn balance_italics_per_line(lines: &[String]) -> Vec<String> {
+    let mut italic = false;
+    lines
+        .iter()
+        .map(|line| {
+            let mut result = String::new();
+            if italic {
+                result.push_str("<i>");
+            }
+
+            let mut rest = line.as_str();
+            while let Some(idx) = rest.find('<') {
+                let (before, tag_and_rest) = rest.split_at(idx);
+                result.push_str(before);
+                if starts_with_ignore_case(tag_and_rest, "<i>") {
+                    italic = true;
+                    result.push_str(&tag_and_rest[..3]);
+                    rest = &tag_and_rest[3..];
+                } else if starts_with_ignore_case(tag_and_rest, "</i>") {
+                    italic = false;
+                    result.push_str(&tag_and_rest[..4]);
+                    rest = &tag_and_rest[4..];
+                } else {
+                    result.push('<');
+                    rest = &tag_and_rest[1..];
+                }
+            }
+            result.push_str(rest);
+
+            if italic {
+                result.push_str("</i>");
+            }
+
+            // an opening tag directly at the end of the line (followed by its closing tag) is useless
+            if let Some(stripped) = result.strip_suffix("<i></i>") {
+                result = stripped.to_string();
+            }
+
+            result
+        })
+        .collect()
+}
+
+/// Joins the lines with `\n`; a `</i>` at the end of a line followed by `<i>` at the start of the next line is removed.
+fn merge_italics_per_line(lines: &[String]) -> String {
+    let mut result = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut line = line.as_str();
+        if i != 0 {
+            if ends_with_ignore_case(&result, "</i>") && starts_with_ignore_case(line, "<i>") {
+                result.truncate(result.len() - 4);
+                line = &line[3..];
+            }
+            result.push('\n');
+        }
+        result.push_str(line);
+    }
+    result
+}
+
+/// Remove the formatting which the players of the profile don't support (see `SrtProfile`).
+fn filter_formatting(line: &str, profile: SrtProfile) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(idx) = rest.find(['<', '{']) {
+        result.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        let closing = if rest.starts_with('<') { '>' } else { '}' };
+        let markup = match rest.find(closing) {
+            Some(end) => &rest[..=end],
+            None => {
+                // not closed, so it is no markup
+                result.push_str(&rest[..1]);
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let keep = if closing == '>' {
+            let name = markup[1..markup.len() - 1]
+                .trim_start_matches('/')
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            let name = name.to_ascii_lowercase();
+            ["i", "b", "u"].contains(&name.as_str()) || (profile == SrtProfile::Vlc && name == "font")
+        } else {
+            let alignment = markup.strip_prefix("{\\an").and_then(|s| s.strip_suffix('}'));
+            profile == SrtProfile::Vlc && alignment.map_or(false, |n| n.len() == 1 && ("1"..="9").contains(&n))
+        };
+        if keep {
+            result.push_str(markup);
+        }
+        rest = &rest[markup.len()..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len()).map(|p| p.eq_ignore_ascii_case(prefix)).unwrap_or(false)
+}
+
+fn ends_with_ignore_case(s: &str, suffix: &str) -> bool {
+    s.len() >= suffix.len() && s.get(s.len() - suffix.len()..).map(|p| p.eq_ignore_ascii_case(suffix)).unwrap_or(false)
+}
+
 impl SrtFile {
     /// Creates .srt file from scratch.
     pub fn create(v: Vec<(TimeSpan, String)>) -> SubtitleParserResult<SrtFile> {
         let file_parts = v
             .into_iter()
             .enumerate()
-            .map(|(i, (ts, text))| SrtLine {
-                index: i as i64 + 1,
-                timespan: ts,
-                texts: text.lines().map(str::to_string).collect(),
+            .map(|(i, (ts, text))| {
+                SrtFilePart::Entry(SrtLine {
+                    index: i as i64 + 1,
+                    timespan: ts,
+                    texts: text.lines().map(str::to_string).collect(),
+                    source: None,
+                    tags: Vec::new(),
+                    coordinates: None,
+                    tags_comment: None,
+                })
+            })
+            .collect();
+
+        Ok(SrtFile {
+            v: file_parts,
+            italic_normalization: SrtItalicNormalization::Keep,
+            warnings: Vec::new(),
+            millisecond_separator: SrtMillisecondSeparator::Comma,
+        })
+    }
+
+    /// Works like `create()`, but the title and comment of the template are written into a `NOTE` block at the start
+    /// of the file if the template enables `FileTemplate::srt_note_block()`.
+    ///
+    /// Parse files with that block with `SrtParseOptions::preserve_non_entry_blocks` to keep it.
+    pub fn create_with_template(v: Vec<(TimeSpan, String)>, template: &FileTemplate) -> SubtitleParserResult<SrtFile> {
+        let mut file = Self::create(v)?;
+        if !template.has_srt_note_block() {
+            return Ok(file);
+        }
+        let header: Vec<String> = template.rendered_title().into_iter().chain(template.rendered_comment_lines()).collect();
+        // empty lines would end the block
+        let lines: Vec<&str> = header.iter().map(|line| line.trim()).filter(|line| !line.is_empty()).collect();
+        if !lines.is_empty() {
+            file.v.insert(0, SrtFilePart::Filler(format!("NOTE {}", lines.join("\n"))));
+        }
+        Ok(file)
+    }
+
+    /// Creates a draft .srt file from a plain text transcript.
+    ///
+    /// The transcript is split into sentences (at line breaks and sentence endings like `.`, `!` or `?`)
+    /// which are distributed over `timespan` like in `create_proportional()`.
+    pub fn create_from_transcript(text: &str, timespan: TimeSpan) -> SubtitleParserResult<SrtFile> {
+        Self::create_proportional(split_sentences(text), timespan)
+    }
+
+    /// Creates a draft .srt file in which the sentences follow each other without gaps during `timespan`.
+    ///
+    /// The duration of every sentence is proportional to its number of (non-whitespace) characters.
+    pub fn create_proportional(sentences: Vec<String>, timespan: TimeSpan) -> SubtitleParserResult<SrtFile> {
+        let weights: Vec<i64> = sentences
+            .iter()
+            .map(|sentence| sentence.chars().filter(|c| !c.is_whitespace()).count().max(1) as i64)
+            .collect();
+        let total_weight: i64 = weights.iter().sum();
+        let total_msecs = timespan.len().msecs();
+
+        // the cumulative weights are used so that the rounding errors do not add up
+        let mut cumulative_weight = 0;
+        let mut start = timespan.start;
+        let mut entries = Vec::new();
+        for (sentence, weight) in sentences.into_iter().zip(weights) {
+            cumulative_weight += weight;
+            let end = timespan.start + TimeDelta::from_msecs(total_msecs * cumulative_weight / total_weight);
+            entries.push((TimeSpan::new(start, end), sentence));
+            start = end;
+        }
+
+        Self::create(entries)
+    }
+
+    /// Creates a draft .srt file in which the sentences with the given durations follow each other without gaps.
+    pub fn create_sequential(sentences: Vec<(String, TimeDelta)>, start: TimePoint) -> SubtitleParserResult<SrtFile> {
+        let mut start = start;
+        let entries = sentences
+            .into_iter()
+            .map(|(sentence, duration)| {
+                let timespan = TimeSpan::new(start, start + duration);
+                start = timespan.end;
+                (timespan, sentence)
             })
             .collect();
 
-        Ok(SrtFile { v: file_parts })
+        Self::create(entries)
+    }
+}
+
+/// Split a text into trimmed, non-empty sentences at line breaks and at sentence endings
+/// (`.`, `!`, `?` and `…`, including following closing quotes/brackets) which are followed by whitespace.
+fn split_sentences(text: &str) -> Vec<String> {
+    let is_sentence_end = |c: char| c == '.' || c == '!' || c == '?' || c == '…';
+    let is_closing = |c: char| c == '"' || c == '\'' || c == ')' || c == '»' || c == '”' || c == '’';
+
+    let mut sentences = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            current.push(c);
+            if !is_sentence_end(c) {
+                continue;
+            }
+
+            while let Some(&next) = chars.peek() {
+                if !is_sentence_end(next) && !is_closing(next) {
+                    break;
+                }
+                current.push(next);
+                chars.next();
+            }
+
+            if chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+                sentences.push(current.trim().to_string());
+                current.clear();
+            }
+        }
+        sentences.push(current.trim().to_string());
     }
+
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
 }
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn srt_invalid_timespans_test() {
+        use super::{SrtFile, SrtTimespanFix};
+        use crate::timetypes::TimeDelta;
+        use crate::warnings::{ParseWarning, ParseWarningKind};
+        use crate::SubtitleFileInterface;
+
+        let data = "1\n00:00:01,000 --> 00:00:01,000\nA\n\n2\n00:00:01,500 --> 00:00:01,200\nB\n\n3\n00:00:05,000 --> 00:00:05,000\nC\n\n";
+        let file = SrtFile::parse(data).unwrap();
+        assert_eq!(
+            file.warnings(),
+            &[
+                ParseWarning {
+                    line_num: 1,
+                    entry_idx: Some(0),
+                    kind: ParseWarningKind::ZeroLengthTimespan,
+                },
+                ParseWarning {
+                    line_num: 5,
+                    entry_idx: Some(1),
+                    kind: ParseWarningKind::NegativeTimespan,
+                },
+                ParseWarning {
+                    line_num: 9,
+                    entry_idx: Some(2),
+                    kind: ParseWarningKind::ZeroLengthTimespan,
+                },
+            ]
+        );
+
+        let spans = |file: &SrtFile| -> Vec<(i64, i64)> {
+            file.get_subtitle_entries()
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.timespan.start.msecs(), entry.timespan.end.msecs()))
+                .collect()
+        };
+
+        let mut swapped = file.clone();
+        swapped.fix_timespans(SrtTimespanFix::Swap);
+        assert_eq!(spans(&swapped), vec![(1000, 1000), (1200, 1500), (5000, 5000)]);
+
+        let mut extended = file.clone();
+        extended.fix_timespans(SrtTimespanFix::ExtendToNext(TimeDelta::from_secs(2)));
+        assert_eq!(spans(&extended), vec![(1000, 1500), (1500, 3500), (5000, 7000)]);
+
+        let mut fixed_duration = file;
+        fixed_duration.fix_timespans(SrtTimespanFix::SetDuration(TimeDelta::from_msecs(100)));
+        assert_eq!(spans(&fixed_duration), vec![(1000, 1100), (1500, 1600), (5000, 5100)]);
+    }
+
+    #[test]
+    fn srt_non_entry_blocks_test() {
+        use super::{SrtFile, SrtParseOptions};
+        use crate::SubtitleFileInterface;
+
+        let data = "Subtitles by someone\nwww.example.com\n\n1\n00:00:01,000 --> 00:00:02,000\nHello\n\nNOTE this is a comment\n\n2\nnot a timespan\n\n3\n00:00:03,000 --> 00:00:04,000\nWorld\n\n";
+        assert!(SrtFile::parse(data).is_err());
+
+        let options = SrtParseOptions {
+            preserve_non_entry_blocks: true,
+            ..SrtParseOptions::default()
+        };
+        let file = SrtFile::parse_with_options(data, options).unwrap();
+        let texts: Vec<_> = file
+            .get_subtitle_entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.line.unwrap())
+            .collect();
+        assert_eq!(texts, vec!["Hello", "World"]);
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn srt_fold_stray_lines_test() {
+        use super::{SrtFile, SrtParseOptions};
+        use crate::warnings::{ParseWarning, ParseWarningKind};
+        use crate::SubtitleFileInterface;
+
+        let data = "1\n00:00:01,000 --> 00:00:02,000\nHello\n\nworld\nagain\n\n2\n00:00:03,000 --> 00:00:04,000\nBye\n";
+        assert!(SrtFile::parse(data).is_err());
+
+        let options = SrtParseOptions {
+            fold_stray_lines: true,
+            ..SrtParseOptions::default()
+        };
+        let file = SrtFile::parse_with_options(data, options).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].line, Some("Hello\nworld\nagain".to_string()));
+        assert_eq!(entries[1].line, Some("Bye".to_string()));
+        assert_eq!(
+            file.entry_warnings(0).collect::<Vec<_>>(),
+            vec![&ParseWarning {
+                line_num: 4,
+                entry_idx: Some(0),
+                kind: ParseWarningKind::StrayLinesFolded,
+            }]
+        );
+        assert_eq!(file.entry_warnings(1).count(), 0);
+
+        let lenient = SrtFile::parse_with_options(&format!("Credits\n\n{}", data), SrtParseOptions::lenient()).unwrap();
+        assert_eq!(lenient.get_subtitle_entries().unwrap(), entries);
+    }
+
+    #[test]
+    fn srt_numeric_dialog_lines_test() {
+        use super::{SrtFile, SrtParseOptions};
+        use crate::SubtitleFileInterface;
+
+        let data = "1\n00:00:01,000 --> 00:00:02,000\nThe answer is\n\n42\n\n2\n00:00:03,000 --> 00:00:04,000\nCount:\n3\n3\n00:00:05,000 --> 00:00:06,000\nBye\n";
+        assert!(SrtFile::parse(data).is_err());
+
+        let options = SrtParseOptions {
+            numeric_dialog_lines: true,
+            ..SrtParseOptions::default()
+        };
+        let file = SrtFile::parse_with_options(data, options).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        let lines: Vec<_> = entries.iter().map(|entry| entry.line.as_deref().unwrap()).collect();
+        assert_eq!(lines, vec!["The answer is\n42", "Count:\n3", "Bye"]);
+        assert_eq!(entries[2].index, Some(3));
+        assert_eq!(file.entry_warnings(0).count(), 1);
+    }
+
+    #[test]
+    fn srt_index_test() {
+        use super::SrtFile;
+        use crate::SubtitleFileInterface;
+
+        let data = "7\n00:00:01,000 --> 00:00:02,000\nHello\n\n12\n00:00:03,000 --> 00:00:04,000\nWorld\n\n";
+        let mut file = SrtFile::parse(data).unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.index).collect::<Vec<_>>(), vec![Some(7), Some(12)]);
+
+        // entries without an index keep the one of the file
+        entries[0].index = None;
+        entries[1].index = Some(8);
+        file.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data.replace("12\n", "8\n"));
+    }
+
+    #[test]
+    fn srt_reindex_test() {
+        use super::{SrtFile, SrtProfile};
+        use crate::{SerializeOptions, SubtitleFileInterface};
+
+        let data = "0\n00:00:01,000 --> 00:00:02,000\nA\n\n5\n00:00:03,000 --> 00:00:04,000\nB\n\n99999999999999999999\n00:00:05,000 --> 00:00:06,000\nC\n\n";
+        let mut file = SrtFile::parse(data).unwrap();
+        assert_eq!(file.index_start(), Some(0));
+        let indices = |file: &SrtFile| file.get_subtitle_entries().unwrap().iter().map(|entry| entry.index).collect::<Vec<_>>();
+        assert_eq!(indices(&file), vec![Some(0), Some(5), Some(i64::MAX)]);
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            data.replace("99999999999999999999", &i64::MAX.to_string())
+        );
+
+        // the profiles keep 0-based numbers
+        let strict = file.to_data_with(&SerializeOptions::new().srt_profile(SrtProfile::Strict)).unwrap();
+        assert!(String::from_utf8(strict).unwrap().starts_with("0\r\n00:00:01,000"));
+
+        file.reindex(1);
+        assert_eq!(indices(&file), vec![Some(1), Some(2), Some(3)]);
+        file.reindex(i64::MAX - 1);
+        assert_eq!(indices(&file), vec![Some(i64::MAX - 1), Some(i64::MAX), Some(i64::MAX)]);
+    }
+
+    #[test]
+    fn srt_millisecond_separator_test() {
+        use super::{SrtFile, SrtMillisecondSeparator, SrtProfile};
+        use crate::timetypes::TimePoint;
+        use crate::{SerializeOptions, SubtitleFileInterface};
+
+        let data = "1\n00:00:01.500 --> 00:00:02.250\nHello\n\n2\n00:00:03.000 --> 00:00:04.000\nWorld\n\n";
+        let mut file = SrtFile::parse(data).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].timespan.start, TimePoint::from_msecs(1500));
+        assert_eq!(entries[0].timespan.end, TimePoint::from_msecs(2250));
+        assert_eq!(file.millisecond_separator(), SrtMillisecondSeparator::Dot);
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+
+        let strict = file.to_data_with(&SerializeOptions::new().srt_profile(SrtProfile::Strict)).unwrap();
+        assert!(String::from_utf8(strict).unwrap().contains("00:00:01,500 --> 00:00:02,250"));
+
+        file.set_millisecond_separator(SrtMillisecondSeparator::Comma);
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data.replace('.', ","));
+
+        // files with mixed separators are written with commas
+        let mixed = SrtFile::parse("1\n00:00:01.500 --> 00:00:02,250\nHello\n").unwrap();
+        assert_eq!(mixed.millisecond_separator(), SrtMillisecondSeparator::Comma);
+    }
+
+    #[test]
+    fn srt_missing_index_test() {
+        use super::{SrtFile, SrtParseOptions};
+        use crate::SubtitleFileInterface;
+
+        let data = "00:00:01,000 --> 00:00:02,000\nHello\n\n00:00:03,000 --> 00:00:04,000\nWorld\n\n7\n00:00:05,000 --> 00:00:06,000\nAgain\n\n00:00:07,000 --> 00:00:08,000\nBye\n";
+        assert!(SrtFile::parse(data).is_err());
+
+        let options = SrtParseOptions {
+            missing_index_lines: true,
+            ..SrtParseOptions::default()
+        };
+        let file = SrtFile::parse_with_options(data, options).unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        let indices: Vec<_> = entries.iter().map(|entry| entry.index).collect();
+        assert_eq!(indices, vec![Some(1), Some(2), Some(7), Some(8)]);
+        assert_eq!(entries[1].line, Some("World".to_string()));
+        assert_eq!(&data[file.source_ranges(3).unwrap().start], "00:00:07,000");
+
+        // the synthesized indices are written
+        let written = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(written.starts_with("1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n"));
+    }
+
+    #[test]
+    fn srt_speaker_test() {
+        use crate::timetypes::{TimePoint, TimeSpan};
+        use crate::{SpeakerPolicy, SubtitleFile};
+
+        let timespan = |start| TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(start + 1000));
+        let lines = vec![
+            (timespan(0), "Hi".to_string()),
+            (timespan(1000), "How are you?".to_string()),
+            (timespan(2000), "Fine\nAnd you?".to_string()),
+        ];
+        let mut file = SubtitleFile::SubRipFile(super::SrtFile::create(lines).unwrap());
+
+        let mut entries = file.get_subtitle_entries().unwrap();
+        for (entry, speaker) in entries.iter_mut().zip(&["Alice", "Bob", "Alice/Bob"]) {
+            entry.speaker = Some(speaker.to_string());
+        }
+
+        let texts = |file: &SubtitleFile| -> Vec<String> {
+            file.get_subtitle_entries()
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.line.unwrap())
+                .collect()
+        };
+
+        let mut dash_file = file.clone();
+        dash_file
+            .update_subtitle_entries_with_speakers(&entries, SpeakerPolicy::FormatDefault)
+            .unwrap();
+        // only cues with several speakers get dashes
+        assert_eq!(texts(&dash_file), vec!["Hi", "How are you?", "- Fine\n- And you?"]);
+
+        file.update_subtitle_entries_with_speakers(&entries, SpeakerPolicy::NamePrefix).unwrap();
+        assert_eq!(texts(&file), vec!["Alice: Hi", "Bob: How are you?", "Alice/Bob: Fine\nAnd you?"]);
+    }
+
+    #[test]
+    fn create_srt_from_transcript_test() {
+        use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
+        use crate::SubtitleFileInterface;
+
+        let text = "Hello there! How are you?\n\n\"I'm fine.\" Thanks...\nBye";
+        let file = super::SrtFile::create_from_transcript(text, TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(4000))).unwrap();
+        let spans: Vec<_> = file
+            .get_subtitle_entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.timespan.start.msecs(), entry.timespan.end.msecs(), entry.line.unwrap()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (1000, 1767, "Hello there!".to_string()),
+                (1767, 2465, "How are you?".to_string()),
+                (2465, 3162, "\"I'm fine.\"".to_string()),
+                (3162, 3790, "Thanks...".to_string()),
+                (3790, 4000, "Bye".to_string()),
+            ]
+        );
+
+        let file = super::SrtFile::create_sequential(
+            vec![
+                ("a".to_string(), TimeDelta::from_msecs(500)),
+                ("b".to_string(), TimeDelta::from_msecs(700)),
+            ],
+            TimePoint::from_msecs(100),
+        )
+        .unwrap();
+        let data_string = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert_eq!(
+            data_string,
+            "1\n00:00:00,100 --> 00:00:00,600\na\n\n2\n00:00:00,600 --> 00:00:01,300\nb\n\n"
+        );
+    }
+
     #[test]
     fn create_srt_test() {
         use crate::timetypes::{TimePoint, TimeSpan};
@@ -253,6 +1485,135 @@ mod tests {
         let expected = "1\n00:00:01,500 --> 00:00:03,700\nline1\n\n2\n00:00:04,500 --> 00:00:08,700\nline2\n\n".to_string();
         println!("\n{:?}\n{:?}", data_string, expected);
         assert_eq!(data_string, expected);
+
+        let template = crate::template::FileTemplate::new()
+            .title("Episode 1")
+            .comment("Created by {tool}")
+            .value("tool", "subsync");
+        let lines = vec![(
+            TimeSpan::new(TimePoint::from_msecs(1500), TimePoint::from_msecs(3700)),
+            "line1".to_string(),
+        )];
+
+        // without the `NOTE` block, the created file can be read by the default parser
+        let file = super::SrtFile::create_with_template(lines.clone(), &template).unwrap();
+        let data = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert_eq!(data, "1\n00:00:01,500 --> 00:00:03,700\nline1\n\n");
+        let parsed = crate::parse_str(crate::SubtitleFormat::SubRip, &data, 25.0).unwrap();
+        assert_eq!(parsed.get_subtitle_entries().unwrap().len(), 1);
+
+        let file = super::SrtFile::create_with_template(lines, &template.srt_note_block(true)).unwrap();
+        let data = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert_eq!(data, "NOTE Episode 1\nCreated by subsync\n\n1\n00:00:01,500 --> 00:00:03,700\nline1\n\n");
+        assert!(super::SrtFile::parse(&data).is_err());
+        let options = super::SrtParseOptions {
+            preserve_non_entry_blocks: true,
+            ..Default::default()
+        };
+        let parsed = super::SrtFile::parse_with_options(&data, options).unwrap();
+        assert_eq!(parsed.get_subtitle_entries().unwrap().len(), 1);
+        assert_eq!(parsed.to_data().unwrap(), data.into_bytes());
+    }
+
+    #[test]
+    fn srt_italic_normalization_test() {
+        use super::SrtItalicNormalization;
+        use crate::timetypes::{TimePoint, TimeSpan};
+        use crate::SubtitleFileInterface;
+
+        let ts = TimeSpan::new(TimePoint::from_msecs(0), TimePoint::from_msecs(1000));
+        let mut file = super::SrtFile::create(vec![(ts, "<i>line1\nline2</i>\nline3".to_string())]).unwrap();
+
+        file.normalize_italics();
+        let data_string = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert_eq!(data_string, "1\n00:00:00,000 --> 00:00:01,000\n<i>line1</i>\n<i>line2</i>\nline3\n\n");
+
+        // merging and splitting again results in the same lines
+        file.set_italic_normalization(SrtItalicNormalization::BalancePerLine);
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].line, Some("<i>line1\nline2</i>\nline3".to_string()));
+        file.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data_string);
+    }
+
+    #[test]
+    fn srt_tags_test() {
+        use crate::{SerializeOptions, SubtitleFileInterface};
+
+        let mut file = super::SrtFile::parse("1\n00:00:01,000 --> 00:00:02,000\nA\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n").unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+        entries[0].tags = vec!["needs-review".to_string(), "sign".to_string()];
+        file.update_subtitle_entries(&entries).unwrap();
+
+        // the tags are only written on request
+        let data = file.to_data().unwrap();
+        assert!(!String::from_utf8(data).unwrap().contains("NOTE"));
+        let data = String::from_utf8(file.to_data_with(&SerializeOptions::new().write_tags(true)).unwrap()).unwrap();
+        assert_eq!(
+            data,
+            "1\n00:00:01,000 --> 00:00:02,000\nA\n\nNOTE tags: needs-review, sign\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n\n"
+        );
+
+        let options = super::SrtParseOptions {
+            read_tags_comments: true,
+            ..Default::default()
+        };
+        let mut parsed = super::SrtFile::parse_with_options(&data, options).unwrap();
+        assert_eq!(parsed.get_subtitle_entries().unwrap(), file.get_subtitle_entries().unwrap());
+        assert_eq!(String::from_utf8(parsed.to_data().unwrap()).unwrap(), data);
+
+        // the changed tags are written into the comment
+        let mut entries = parsed.get_subtitle_entries().unwrap();
+        entries[0].tags = vec!["sign".to_string()];
+        parsed.update_subtitle_entries(&entries).unwrap();
+        assert!(String::from_utf8(parsed.to_data().unwrap()).unwrap().contains("\nNOTE tags: sign\n"));
+    }
+
+    #[test]
+    fn srt_tags_comment_round_trip_test() {
+        use crate::SubtitleFileInterface;
+
+        // without `read_tags_comments`, the comment is a block of its own which is written back unchanged
+        let data = "1\n00:00:01,000 --> 00:00:02,000\nA\n\nNOTE tags:needs-review,sign\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n\n";
+        let file = super::SrtFile::parse(data).unwrap();
+        assert!(file.get_subtitle_entries().unwrap().iter().all(|entry| entry.tags.is_empty()));
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+
+        let options = super::SrtParseOptions {
+            read_tags_comments: true,
+            ..Default::default()
+        };
+        let file = super::SrtFile::parse_with_options(data, options).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].tags, vec!["needs-review", "sign"]);
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn srt_profile_test() {
+        use super::{SrtParseOptions, SrtProfile};
+        use crate::{SerializeOptions, SubtitleFileInterface};
+
+        let data = "Credits\n\n5\n00:00:01,000 --> 00:00:02,000\n{\\an8}<font color=\"red\">A</font> {\\b1}<i>b</i> <c>\n\n\
+                    7\n00:00:01,000 --> 00:00:02,000\n{\\pos(1,2)}C\n";
+        let options = SrtParseOptions {
+            preserve_non_entry_blocks: true,
+            ..Default::default()
+        };
+        let mut file = super::SrtFile::parse_with_options(data, options).unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+        entries[1].timespan.start = crate::timetypes::TimePoint::from_msecs(-1000);
+        file.update_subtitle_entries(&entries).unwrap();
+
+        let to_string = |options: SerializeOptions| String::from_utf8(file.to_data_with(&options).unwrap()).unwrap();
+        assert_eq!(
+            to_string(SerializeOptions::new().srt_profile(SrtProfile::Strict)),
+            "1\r\n00:00:01,000 --> 00:00:02,000\r\nA <i>b</i> \r\n\r\n2\r\n00:00:00,000 --> 00:00:02,000\r\nC\r\n\r\n"
+        );
+        assert_eq!(
+            to_string(SerializeOptions::new().srt_profile(SrtProfile::Vlc)),
+            "Credits\n\n1\n00:00:01,000 --> 00:00:02,000\n{\\an8}<font color=\"red\">A</font> <i>b</i> \n\n\
+             2\n00:00:00,000 --> 00:00:02,000\nC\n\n"
+        );
     }
 }
 // TODO: parser tests
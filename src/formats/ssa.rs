@@ -2,31 +2,39 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::{SubtitleEntry, SubtitleFileInterface};
+use crate::template::FileTemplate;
+use crate::{SerializeOptions, SubtitleAlignment, SubtitleCoordinates, SubtitleEntry, SubtitleFileInterface, SubtitlePosition, SubtitleSourceRanges};
 
 use crate::errors::Result as SubtitleParserResult;
+use crate::escaping::SSA_LINE_BREAK;
 use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
 use combine::char::*;
 use combine::combinator::*;
 use combine::primitives::Parser;
 
-use crate::timetypes::{TimePoint, TimeSpan};
+use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
 use failure::ResultExt;
+use std::hash::{Hash, Hasher};
 use std::iter::once;
+use std::ops::{Deref, DerefMut};
 
 type Result<T> = std::result::Result<T, Error>;
 
 use self::errors::Error;
 use self::errors::ErrorKind::*;
 
+pub mod tags;
+
 // Errors specific to the '.ssa' format.
-#[allow(missing_docs)]
+#[allow(missing_docs, non_local_definitions)]
 pub mod errors {
 
     define_error!(Error, ErrorKind);
 
     /// `.ssa`-parser-specific errors
     #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
     pub enum ErrorKind {
         #[fail(display = ".ssa/.ass file did not have a line beginning with `Format: ` in a `[Events]` section")]
         SsaFieldsInfoNotFound,
@@ -52,6 +60,22 @@ pub mod errors {
         #[fail(display = "parsing the line `{}` failed because of `{}`", line_num, msg)]
         SsaLineParseError { line_num: usize, msg: String },
     }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::SsaFieldsInfoNotFound => "ssa-fields-info-not-found",
+                ErrorKind::SsaMissingField { .. } => "ssa-missing-field",
+                ErrorKind::SsaDuplicateField { .. } => "ssa-duplicate-field",
+                ErrorKind::SsaTextFieldNotLast { .. } => "ssa-text-field-not-last",
+                ErrorKind::SsaIncorrectNumberOfFields { .. } => "ssa-incorrect-number-of-fields",
+                ErrorKind::SsaWrongTimepointFormat { .. } => "ssa-wrong-timepoint-format",
+                ErrorKind::SsaDialogLineParseError { .. } => "ssa-dialog-line-parse-error",
+                ErrorKind::SsaLineParseError { .. } => "ssa-line-parse-error",
+            }
+        }
+    }
 }
 /*error_chain! {
     errors {
@@ -89,7 +113,35 @@ struct SsaFieldsInfo {
     start_field_idx: usize,
     end_field_idx: usize,
     text_field_idx: usize,
+    name_field_idx: Option<usize>,
+    effect_field_idx: Option<usize>,
     num_fields: usize,
+
+    /// The other fields which are parsed (see `SsaDialogue`)
+    other_fields: Vec<Option<SsaDialogueField>>,
+}
+
+/// A field of dialog lines which is stored as `SsaFilePart::Field`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SsaDialogueField {
+    Layer,
+    Style,
+    MarginL,
+    MarginR,
+    MarginV,
+}
+
+impl SsaDialogueField {
+    fn from_name(name: &str) -> Option<SsaDialogueField> {
+        Some(match name {
+            "Layer" => SsaDialogueField::Layer,
+            "Style" => SsaDialogueField::Style,
+            "MarginL" => SsaDialogueField::MarginL,
+            "MarginR" => SsaDialogueField::MarginR,
+            "MarginV" => SsaDialogueField::MarginV,
+            _ => return None,
+        })
+    }
 }
 
 impl SsaFieldsInfo {
@@ -100,10 +152,16 @@ impl SsaFieldsInfo {
         let mut start_field_idx: Option<usize> = None;
         let mut end_field_idx: Option<usize> = None;
         let mut text_field_idx: Option<usize> = None;
+        let mut name_field_idx: Option<usize> = None;
+        let mut effect_field_idx: Option<usize> = None;
 
-        // filter "Start" and "End" and "Text"
+        // filter "Start" and "End" and "Text" (and the optional "Name" and "Effect")
         let split_iter = field_info.split(',');
         let num_fields = split_iter.clone().count();
+        let other_fields = split_iter
+            .clone()
+            .map(|field_name| SsaDialogueField::from_name(field_name.trim()))
+            .collect();
         for (i, field_name) in split_iter.enumerate() {
             let trimmed = field_name.trim();
             if trimmed == "Start" {
@@ -121,6 +179,16 @@ impl SsaFieldsInfo {
                     return Err(SsaDuplicateField { line_num, f: "Text" })?;
                 }
                 text_field_idx = Some(i);
+            } else if trimmed == "Name" {
+                if name_field_idx.is_some() {
+                    return Err(SsaDuplicateField { line_num, f: "Name" })?;
+                }
+                name_field_idx = Some(i);
+            } else if trimmed == "Effect" {
+                if effect_field_idx.is_some() {
+                    return Err(SsaDuplicateField { line_num, f: "Effect" })?;
+                }
+                effect_field_idx = Some(i);
             }
         }
 
@@ -133,7 +201,10 @@ impl SsaFieldsInfo {
             start_field_idx: start_field_idx.ok_or_else(|| Error::from(SsaMissingField { line_num, f: "Start" }))?,
             end_field_idx: end_field_idx.ok_or_else(|| Error::from(SsaMissingField { line_num, f: "End" }))?,
             text_field_idx: text_field_idx2,
-            num_fields: num_fields,
+            name_field_idx,
+            effect_field_idx,
+            num_fields,
+            other_fields,
         })
     }
 }
@@ -146,6 +217,32 @@ impl SsaFile {
     pub fn parse(s: &str) -> SubtitleParserResult<SsaFile> {
         Ok(Self::parse_inner(s.to_string()).with_context(|_| crate::ErrorKind::ParsingError)?)
     }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.add_vec(&self.v);
+        for part in &self.v {
+            match part {
+                SsaFilePart::TimespanStart(_) => footprint.entry_count += 1,
+                SsaFilePart::TimespanEnd(_) => {}
+                SsaFilePart::Name(text) | SsaFilePart::Effect(text) | SsaFilePart::Text(text) => footprint.add_text(text),
+                SsaFilePart::Filler(text)
+                | SsaFilePart::StyleFormat { line: text, .. }
+                | SsaFilePart::Style(text)
+                | SsaFilePart::Field(_, text)
+                | SsaFilePart::Attachment { text, .. } => footprint.add_filler(text),
+            }
+        }
+        footprint.add_vec(&self.source_ranges);
+        self.source_ranges.iter().flatten().for_each(|ranges| footprint.add_vec(&ranges.text));
+        footprint.add_vec(&self.tags);
+        for tags in &self.tags {
+            footprint.add_vec(tags);
+            tags.iter().for_each(|tag| footprint.add_text(tag));
+        }
+        footprint
+    }
 }
 
 /// Implement parser helper functions.
@@ -161,8 +258,26 @@ impl SsaFile {
         let fields_info = SsaFieldsInfo::new_from_fields_info_line(line_num, field_info_line)?;
 
         // parse the dialog lines with the given format
-        file_parts.append(&mut Self::parse_dialog_lines(&fields_info, s)?);
-        Ok(SsaFile::new(file_parts))
+        let mut source_ranges = Vec::new();
+        file_parts.append(&mut Self::parse_dialog_lines(&fields_info, s, bom.len(), &mut source_ranges)?);
+
+        // timestamps with milliseconds (written by some tools) are written with milliseconds again
+        let has_millisecond_timestamps = source_ranges.iter().flatten().any(|ranges| {
+            [&ranges.start, &ranges.end]
+                .iter()
+                .any(|range| Self::timepoint_fractional_digits(&i[(*range).clone()]) >= 3)
+        });
+
+        let mut file = SsaFile::new(file_parts, source_ranges);
+        if has_millisecond_timestamps {
+            file.timestamp_precision = SsaTimestampPrecision::Milliseconds;
+        }
+        Ok(file)
+    }
+
+    /// The number of digits after the last separator of a timestamp like "0:19:41.99".
+    fn timepoint_fractional_digits(s: &str) -> usize {
+        s.rsplit(['.', ':']).next().map_or(0, str::len)
     }
 
     /// Searches and parses a format line like "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text".
@@ -193,29 +308,75 @@ impl SsaFile {
     /// ```text
     /// "Dialogue: 1,0:22:43.52,0:22:46.22,ED-Romaji,,0,0,0,,{\fad(150,150)\blur0.5\bord1}some text"
     /// ```
-    fn parse_dialog_lines(fields_info: &SsaFieldsInfo, s: &str) -> Result<Vec<SsaFilePart>> {
+    ///
+    /// The byte ranges of the fields of every dialog line are added to `source_ranges` (the input starts at byte `offset`).
+    fn parse_dialog_lines(
+        fields_info: &SsaFieldsInfo,
+        s: &str,
+        offset: usize,
+        source_ranges: &mut Vec<Option<SubtitleSourceRanges>>,
+    ) -> Result<Vec<SsaFilePart>> {
+        let lines = get_lines_non_destructive(s);
+
+        // the file parts are not reserved up front: a dialog line has dozens of them, so reserving them for every line
+        // allocates (and touches) more memory than the reallocations cost (see the `parse_by_entry_count` benchmark)
         let mut result = Vec::new();
+        source_ranges.reserve(lines.len());
         let mut section_opt: Option<String> = None;
+        let mut line_start = offset;
 
-        for (line_num, (line, newl)) in get_lines_non_destructive(s).into_iter().enumerate() {
+        for (line_num, (line, newl)) in lines.into_iter().enumerate() {
             let trimmed_line = line.trim().to_string();
+            let current_line_start = line_start;
+            line_start += line.len() + newl.len();
 
             // parse section headers like `[Events]`
             if trimmed_line.starts_with('[') && trimmed_line.ends_with(']') {
                 section_opt = Some(trimmed_line[1..trimmed_line.len() - 1].to_string());
                 result.push(SsaFilePart::Filler(line));
-                result.push(SsaFilePart::Filler("\n".to_string()));
+                result.push(SsaFilePart::Filler(newl));
+                continue;
+            }
+
+            if let Some(legacy) = section_opt.as_deref().and_then(style_section_is_legacy) {
+                let part = if trimmed_line.starts_with("Format:") {
+                    SsaFilePart::StyleFormat { line, legacy }
+                } else if trimmed_line.starts_with("Style:") {
+                    SsaFilePart::Style(line)
+                } else {
+                    SsaFilePart::Filler(line)
+                };
+                result.push(part);
+                result.push(SsaFilePart::Filler(newl));
+                continue;
+            }
+
+            if let Some(kind) = section_opt.as_deref().and_then(SsaAttachmentKind::from_section) {
+                let is_header = trimmed_line.starts_with(kind.header());
+                match result.last_mut() {
+                    // the encoded lines of the attachment
+                    Some(SsaFilePart::Attachment { text, .. }) if !is_header && !trimmed_line.is_empty() => {
+                        text.push_str(&line);
+                        text.push_str(&newl);
+                    }
+                    _ if is_header => result.push(SsaFilePart::Attachment { kind, text: line + &newl }),
+                    _ => {
+                        result.push(SsaFilePart::Filler(line));
+                        result.push(SsaFilePart::Filler(newl));
+                    }
+                }
                 continue;
             }
 
             if section_opt.is_none() || section_opt.iter().any(|s| s != "Events") || !trimmed_line.starts_with("Dialogue:") {
                 result.push(SsaFilePart::Filler(line));
-                result.push(SsaFilePart::Filler("\n".to_string()));
+                result.push(SsaFilePart::Filler(newl));
                 continue;
             }
 
-            result.append(&mut Self::parse_dialog_line(line_num, line.as_str(), fields_info)?);
+            Self::parse_dialog_line(line_num, line.as_str(), fields_info, &mut result)?;
             result.push(SsaFilePart::Filler(newl));
+            source_ranges.push(Self::dialog_line_source_ranges(&line, current_line_start, fields_info));
         }
 
         Ok(result)
@@ -226,7 +387,9 @@ impl SsaFile {
     /// ```text
     /// "Dialogue: 1,0:22:43.52,0:22:46.22,ED-Romaji,,0,0,0,,{\fad(150,150)\blur0.5\bord1}some text"
     /// ```
-    fn parse_dialog_line(line_num: usize, line: &str, fields_info: &SsaFieldsInfo) -> Result<Vec<SsaFilePart>> {
+    ///
+    /// The file parts are appended to `result`.
+    fn parse_dialog_line(line_num: usize, line: &str, fields_info: &SsaFieldsInfo, result: &mut Vec<SsaFilePart>) -> Result<()> {
         let parts_res = (
             many(ws()),
             string("Dialogue:"),
@@ -234,36 +397,57 @@ impl SsaFile {
             count(fields_info.num_fields - 1, (many(none_of(once(','))), token(','))),
             many(r#try(any())),
         )
-            .map(
-                |(ws1, dl, ws2, v, text): (String, &str, String, Vec<(String, char)>, String)| -> Result<Vec<SsaFilePart>> {
-                    let mut result: Vec<SsaFilePart> = Vec::new();
-                    result.push(SsaFilePart::Filler(ws1));
-                    result.push(SsaFilePart::Filler(dl.to_string()));
-                    result.push(SsaFilePart::Filler(ws2.to_string()));
-                    result.append(&mut Self::parse_fields(line_num, fields_info, v)?);
-                    result.push(SsaFilePart::Text(text));
-                    Ok(result)
-                },
-            )
             .parse(line);
 
-        match parts_res {
-            // Ok() means that parsing succeded, but the "map" function might created an SSA error
-            Ok((parts, _)) => Ok(parts?),
-            Err(e) => Err(SsaDialogLineParseError {
-                line_num,
-                msg: parse_error_to_string(e),
+        let ((ws1, dl, ws2, v, text), _) = match parts_res {
+            Ok(parts) => parts,
+            Err(e) => {
+                return Err(SsaDialogLineParseError {
+                    line_num,
+                    msg: parse_error_to_string(e),
+                }
+                .into())
             }
-            .into()),
-        }
+        };
+
+        result.push(SsaFilePart::Filler(ws1));
+        result.push(SsaFilePart::Filler(dl.to_string()));
+        result.push(SsaFilePart::Filler(ws2));
+        Self::parse_fields(line_num, fields_info, v, result)?;
+        result.push(SsaFilePart::Text(text));
+        Ok(())
     }
 
-    /// Parses an array of fields with the "fields info".
+    /// Find the byte ranges of the fields in a (successfully parsed) dialog line, which starts at byte `line_start`.
+    /// Returns `None` if the line has fewer fields than the format line.
+    fn dialog_line_source_ranges(line: &str, line_start: usize, fields_info: &SsaFieldsInfo) -> Option<SubtitleSourceRanges> {
+        let mut field_start = line_start + line.find("Dialogue:").expect("dialog line was parsed") + "Dialogue:".len();
+        let fields: Vec<(usize, &str)> = line[field_start - line_start..]
+            .splitn(fields_info.num_fields, ',')
+            .map(|field| {
+                let start = field_start;
+                field_start += field.len() + 1;
+                (start, field)
+            })
+            .collect();
+
+        let &(start_start, start) = fields.get(fields_info.start_field_idx)?;
+        let &(end_start, end) = fields.get(fields_info.end_field_idx)?;
+        let &(text_start, text) = fields.get(fields_info.text_field_idx)?;
+        let text_range = text_start..text_start + text.len();
+        Some(SubtitleSourceRanges {
+            start: trimmed_range(start, start_start),
+            end: trimmed_range(end, end_start),
+            text: vec![text_range],
+        })
+    }
+
+    /// Parses an array of fields with the "fields info" and appends the file parts to `result`.
     ///
     /// The fields (comma seperated information) as an array like
     // `vec!["1", "0:22:43.52", "0:22:46.22", "ED-Romaji", "", "0", "0", "0", "", "{\fad(150,150)\blur0.5\bord1}some text"]`.
-    fn parse_fields(line_num: usize, fields_info: &SsaFieldsInfo, v: Vec<(String, char)>) -> Result<Vec<SsaFilePart>> {
-        let extract_file_parts_closure = |(i, (field, sep_char)): (_, (String, char))| -> Result<Vec<SsaFilePart>> {
+    fn parse_fields(line_num: usize, fields_info: &SsaFieldsInfo, v: Vec<(String, char)>, result: &mut Vec<SsaFilePart>) -> Result<()> {
+        for (i, (field, sep_char)) in v.into_iter().enumerate() {
             let (begin, field, end) = trim_non_destructive(&field);
 
             let part = if i == fields_info.start_field_idx {
@@ -272,31 +456,27 @@ impl SsaFile {
                 SsaFilePart::TimespanEnd(Self::parse_timepoint(line_num, &field)?)
             } else if i == fields_info.text_field_idx {
                 SsaFilePart::Text(field.to_string())
+            } else if Some(i) == fields_info.name_field_idx {
+                SsaFilePart::Name(field.to_string())
+            } else if Some(i) == fields_info.effect_field_idx {
+                SsaFilePart::Effect(field.to_string())
+            } else if let Some(kind) = fields_info.other_fields[i] {
+                SsaFilePart::Field(kind, field.to_string())
             } else {
                 SsaFilePart::Filler(field.to_string())
             };
 
-            Ok(vec![
-                SsaFilePart::Filler(begin),
-                part,
-                SsaFilePart::Filler(end),
-                SsaFilePart::Filler(sep_char.to_string()),
-            ])
-        };
-
-        let result = v
-            .into_iter()
-            .enumerate()
-            .map(extract_file_parts_closure)
-            .collect::<Result<Vec<Vec<SsaFilePart>>>>()?
-            .into_iter()
-            .flat_map(|part| part)
-            .collect();
-        Ok(result)
+            result.push(SsaFilePart::Filler(begin));
+            result.push(part);
+            result.push(SsaFilePart::Filler(end));
+            result.push(SsaFilePart::Filler(sep_char.to_string()));
+        }
+        Ok(())
     }
 
-    /// Something like "0:19:41.99"
-    fn parse_timepoint(line_num: usize, s: &str) -> Result<TimePoint> {
+    /// Something like "0:19:41.99" (the fraction is scaled by its number of digits, so "0:19:41.990" and
+    /// "0:19:41.9" are the same time).
+    pub(crate) fn parse_timepoint(line_num: usize, s: &str) -> Result<TimePoint> {
         let parse_res = (
             parser(number_i64),
             token(':'),
@@ -304,10 +484,14 @@ impl SsaFile {
             token(':'),
             parser(number_i64),
             or(token('.'), token(':')),
-            parser(number_i64),
+            many1(digit()),
             eof(),
         )
-            .map(|(h, _, mm, _, ss, _, ms, _)| TimePoint::from_components(h, mm, ss, ms * 10))
+            .map(|(h, _, mm, _, ss, _, fraction, _): (_, _, _, _, _, _, String, _)| {
+                // digits after the third one are below a millisecond and are cut off
+                let ms: i64 = format!("{:0<3}", &fraction[..fraction.len().min(3)]).parse().unwrap();
+                TimePoint::from_components(h, mm, ss, ms)
+            })
             .parse(s);
         match parse_res {
             Ok(res) => Ok(res.0),
@@ -320,10 +504,114 @@ impl SsaFile {
     }
 }
 
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// output profiles
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Adjustments for the quirks of renderers when writing `.ssa`/`.ass` files (see `SerializeOptions::ass_profile()`).
+///
+/// Renderers guess different values for missing fields of the `[Script Info]` section, so both profiles write
+/// the `ScriptType` and the `PlayResX`/`PlayResY` script resolution if they are missing (with the values which
+/// libass would guess). The dialog lines are not changed.
+pub enum AssProfile {
+    /// For libass (mpv, VLC, ...).
+    Libass,
+
+    /// For XySubFilter, which also uses the `LayoutResX`/`LayoutResY` fields. They are set to the script
+    /// resolution if they are missing.
+    XySubFilter,
+}
+
+/// Add the missing fields of the `[Script Info]` section for the profile.
+fn apply_ass_profile(data: &str, profile: AssProfile) -> String {
+    let newline = if data.contains("\r\n") { "\r\n" } else { "\n" };
+    let lines: Vec<&str> = data.split_inclusive('\n').collect();
+    let is_section_header = |line: &str| line.trim_start().starts_with('[');
+
+    let section_start = lines.iter().position(|line| line.trim().eq_ignore_ascii_case("[Script Info]"));
+    let section = match section_start {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|line| is_section_header(line))
+                .map_or(lines.len(), |i| start + 1 + i);
+            &lines[start + 1..end]
+        }
+        None => &[][..],
+    };
+    let field = |name: &str| {
+        section.iter().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if key.trim().eq_ignore_ascii_case(name) {
+                Some(value.trim().to_string())
+            } else {
+                None
+            }
+        })
+    };
+    // the guessed resolutions are computed in `u64`, so they can't overflow
+    let resolution = |name: &str| field(name).and_then(|value| value.parse::<u32>().ok()).map(u64::from);
+
+    let mut missing = Vec::new();
+    if field("ScriptType").is_none() {
+        let is_ass = lines.iter().any(|line| line.trim().eq_ignore_ascii_case("[V4+ Styles]"));
+        missing.push(format!("ScriptType: {}", if is_ass { "v4.00+" } else { "v4.00" }));
+    }
+    let (play_res_x, play_res_y) = match (resolution("PlayResX"), resolution("PlayResY")) {
+        (Some(x), Some(y)) => (x, y),
+        (None, None) => (384, 288),
+        (Some(x), None) => (x, if x == 1280 { 1024 } else { x * 3 / 4 }),
+        (None, Some(y)) => (if y == 1024 { 1280 } else { y * 4 / 3 }, y),
+    };
+    for &(name, value) in &[("PlayResX", play_res_x), ("PlayResY", play_res_y)] {
+        if field(name).is_none() {
+            missing.push(format!("{}: {}", name, value));
+        }
+    }
+    if profile == AssProfile::XySubFilter {
+        for &(name, value) in &[("LayoutResX", play_res_x), ("LayoutResY", play_res_y)] {
+            if field(name).is_none() {
+                missing.push(format!("{}: {}", name, value));
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        return data.to_string();
+    }
+    let missing: String = missing.iter().map(|line| format!("{}{}", line, newline)).collect();
+
+    let start = match section_start {
+        Some(start) => start,
+        None => return format!("[Script Info]{nl}{}{nl}{}", missing, data, nl = newline),
+    };
+
+    // the fields are inserted after the last field of the section (before the empty lines)
+    let insert_idx = (start..start + 1 + section.len())
+        .rev()
+        .find(|&i| !lines[i].trim().is_empty())
+        .unwrap_or(start)
+        + 1;
+    let mut result = String::with_capacity(data.len() + missing.len());
+    for (i, line) in lines.iter().enumerate() {
+        if i == insert_idx {
+            result.push_str(&missing);
+        }
+        result.push_str(line);
+        if i + 1 == insert_idx && !line.ends_with('\n') {
+            result.push_str(newline);
+        }
+    }
+    if insert_idx == lines.len() {
+        result.push_str(&missing);
+    }
+    result
+}
+
 // ////////////////////////////////////////////////////////////////////////////////////////////////
 // SSA file parts
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum SsaFilePart {
     /// Spaces, field information, comments, unimportant fields, ...
     Filler(String),
@@ -334,13 +622,41 @@ enum SsaFilePart {
     /// Timespan end of a dialogue line
     TimespanEnd(TimePoint),
 
+    /// Name of the speaker of a dialogue line
+    Name(String),
+
+    /// Effect field of a dialogue line (which can hold the tags of the entry)
+    Effect(String),
+
     /// Dialog lines
     Text(String),
+
+    /// The `Format:` line of a styles section (`legacy` for the `[V4 Styles]` section of `.ssa` files)
+    StyleFormat { line: String, legacy: bool },
+
+    /// A `Style:` line
+    Style(String),
+
+    /// Layer, style or margin field of a dialogue line
+    Field(SsaDialogueField, String),
+
+    /// The `fontname:`/`filename:` line and the encoded lines of an attachment (with their line endings)
+    Attachment { kind: SsaAttachmentKind, text: String },
 }
 
 // ////////////////////////////////////////////////////////////////////////////////////////////////
 // SSA file
 
+/// The precision of the timestamps which are written in the dialog lines of a `.ssa`/`.ass` file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SsaTimestampPrecision {
+    /// Timestamps like `0:00:22.21`, as defined by the format (default).
+    Centiseconds,
+
+    /// Timestamps like `0:00:22.213`, which some tools write (and most renderers accept).
+    Milliseconds,
+}
+
 /// Represents a reconstructable `.ssa`/`.ass` file.
 ///
 /// All unimportant information (for this project) are saved into `SsaFilePart::Filler(...)`, so
@@ -348,55 +664,166 @@ enum SsaFilePart {
 #[derive(Debug, Clone)]
 pub struct SsaFile {
     v: Vec<SsaFilePart>,
+
+    /// Where the fields of the dialog lines are in the parsed input (`None` for lines with missing fields).
+    source_ranges: Vec<Option<SubtitleSourceRanges>>,
+
+    /// The user tags of the dialog lines (see `SubtitleEntry::tags`).
+    tags: Vec<Vec<String>>,
+
+    /// How the timestamps are written.
+    timestamp_precision: SsaTimestampPrecision,
 }
 
+// where the dialog lines were in the parsed input is no content of the file
+impl PartialEq for SsaFile {
+    fn eq(&self, other: &SsaFile) -> bool {
+        self.v == other.v && self.tags == other.tags && self.timestamp_precision == other.timestamp_precision
+    }
+}
+
+impl Eq for SsaFile {}
+
+impl Hash for SsaFile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.v.hash(state);
+        self.tags.hash(state);
+        self.timestamp_precision.hash(state);
+    }
+}
+
+/// The start of the `Effect` fields in which `SerializeOptions::write_tags()` stores the tags of an entry.
+const TAGS_EFFECT_PREFIX: &str = "tags:";
+
 impl SsaFile {
-    fn new(v: Vec<SsaFilePart>) -> SsaFile {
+    fn new(v: Vec<SsaFilePart>, source_ranges: Vec<Option<SubtitleSourceRanges>>) -> SsaFile {
         // cleans up multiple fillers after another
         let new_file_parts = dedup_string_parts(v, |part: &mut SsaFilePart| match *part {
             SsaFilePart::Filler(ref mut text) => Some(text),
             _ => None,
         });
 
-        SsaFile { v: new_file_parts }
+        // the tags which were written by `SerializeOptions::write_tags()`
+        let tags = Self::effect_part_indices(&new_file_parts)
+            .into_iter()
+            .map(|effect_idx| match effect_idx.map(|i| &new_file_parts[i]) {
+                Some(SsaFilePart::Effect(effect)) => effect
+                    .strip_prefix(TAGS_EFFECT_PREFIX)
+                    .map(|tags| split_tags(tags, ';'))
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            })
+            .collect();
+
+        SsaFile {
+            v: new_file_parts,
+            source_ranges,
+            tags,
+            timestamp_precision: SsaTimestampPrecision::Centiseconds,
+        }
+    }
+
+    /// The indices of the `Effect` parts of all dialog lines (`None` if the file has no `Effect` field).
+    fn effect_part_indices(parts: &[SsaFilePart]) -> Vec<Option<usize>> {
+        let mut effect_idx = None;
+        parts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, part)| match part {
+                SsaFilePart::Effect(_) => {
+                    effect_idx = Some(i);
+                    None
+                }
+                SsaFilePart::Text(_) => Some(effect_idx.take()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Write the tags into the `Effect` fields which are empty or hold tags (other effects are kept).
+    fn write_tags_to_effects(&mut self) {
+        let parts = &mut self.v;
+        for (effect_idx, tags) in Self::effect_part_indices(parts).into_iter().zip(&self.tags) {
+            if let Some(SsaFilePart::Effect(effect)) = effect_idx.map(|i| &mut parts[i]) {
+                if !effect.trim().is_empty() && !effect.starts_with(TAGS_EFFECT_PREFIX) {
+                    continue;
+                }
+                // commas would start a new field
+                let tags: Vec<String> = tags.iter().map(|tag| tag.replace(',', " ")).collect();
+                let tags = join_tags(&tags, ';');
+                *effect = if tags.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}{}", TAGS_EFFECT_PREFIX, tags)
+                };
+            }
+        }
+    }
+
+    /// How the timestamps are written by `to_data()`.
+    ///
+    /// Parsed files keep the precision of their timestamps: a file with a timestamp like `0:00:22.213` is written
+    /// with milliseconds, all other files with centiseconds.
+    pub fn timestamp_precision(&self) -> SsaTimestampPrecision {
+        self.timestamp_precision
+    }
+
+    /// Set how the timestamps are written by `to_data()`. Times are rounded down to centiseconds when they are
+    /// written with `SsaTimestampPrecision::Centiseconds`.
+    pub fn set_timestamp_precision(&mut self, timestamp_precision: SsaTimestampPrecision) {
+        self.timestamp_precision = timestamp_precision;
+    }
+
+    /// Where the timestamps and the text of an entry are in the parsed input (`None` if there is no such entry or its
+    /// dialog line has missing fields).
+    pub fn source_ranges(&self, entry_idx: usize) -> Option<SubtitleSourceRanges> {
+        self.source_ranges.get(entry_idx).cloned().flatten()
     }
 
     /// This function filters out all start times and end times, and returns them ordered
-    /// (="(start, end, dialog)") so they can be easily read or written to.
+    /// (="(start, end, name, dialog)") so they can be easily read or written to. The name
+    /// is `None` if the file has no `Name` field.
     ///
     /// TODO: implement a single version that takes both `&mut` and `&` (dependent on HKT).
-    fn get_subtitle_entries_mut<'a>(&'a mut self) -> Vec<(&'a mut TimePoint, &'a mut TimePoint, &'a mut String)> {
+    #[allow(clippy::type_complexity)]
+    fn get_subtitle_entries_mut<'a>(&'a mut self) -> Vec<(&'a mut TimePoint, &'a mut TimePoint, Option<&'a mut String>, &'a mut String)> {
         let mut startpoint_buffer: Option<&'a mut TimePoint> = None;
         let mut endpoint_buffer: Option<&'a mut TimePoint> = None;
+        let mut name_buffer: Option<&'a mut String> = None;
 
         // the extra block satisfies the borrow checker
         let timings: Vec<_> = {
-            let filter_map_closure = |part: &'a mut SsaFilePart| -> Option<(&'a mut TimePoint, &'a mut TimePoint, &'a mut String)> {
-                use self::SsaFilePart::*;
-                match *part {
-                    TimespanStart(ref mut start) => {
-                        assert_eq!(startpoint_buffer, None); // parser should have ensured that no two consecutive SSA start times exist
-                        startpoint_buffer = Some(start);
-                        None
+            let filter_map_closure =
+                |part: &'a mut SsaFilePart| -> Option<(&'a mut TimePoint, &'a mut TimePoint, Option<&'a mut String>, &'a mut String)> {
+                    use self::SsaFilePart::*;
+                    match *part {
+                        TimespanStart(ref mut start) => {
+                            assert_eq!(startpoint_buffer, None); // parser should have ensured that no two consecutive SSA start times exist
+                            startpoint_buffer = Some(start);
+                            None
+                        }
+                        TimespanEnd(ref mut end) => {
+                            assert_eq!(endpoint_buffer, None); // parser should have ensured that no two consecutive SSA end times exist
+                            endpoint_buffer = Some(end);
+                            None
+                        }
+                        Name(ref mut name) => {
+                            name_buffer = Some(name);
+                            None
+                        }
+                        Text(ref mut text) => {
+                            // reset the timepoint buffers
+                            let snatched_startpoint_buffer = startpoint_buffer.take();
+                            let snatched_endpoint_buffer = endpoint_buffer.take();
+
+                            let start = snatched_startpoint_buffer.expect("SSA parser should have ensured that every line has a startpoint");
+                            let end = snatched_endpoint_buffer.expect("SSA parser should have ensured that every line has a endpoint");
+
+                            Some((start, end, name_buffer.take(), text))
+                        }
+                        Effect(_) | Filler(_) | StyleFormat { .. } | Style(_) | Field(..) | Attachment { .. } => None,
                     }
-                    TimespanEnd(ref mut end) => {
-                        assert_eq!(endpoint_buffer, None); // parser should have ensured that no two consecutive SSA end times exist
-                        endpoint_buffer = Some(end);
-                        None
-                    }
-                    Text(ref mut text) => {
-                        // reset the timepoint buffers
-                        let snatched_startpoint_buffer = startpoint_buffer.take();
-                        let snatched_endpoint_buffer = endpoint_buffer.take();
-
-                        let start = snatched_startpoint_buffer.expect("SSA parser should have ensured that every line has a startpoint");
-                        let end = snatched_endpoint_buffer.expect("SSA parser should have ensured that every line has a endpoint");
-
-                        Some((start, end, text))
-                    }
-                    Filler(_) => None,
-                }
-            };
+                };
 
             self.v.iter_mut().filter_map(filter_map_closure).collect()
         };
@@ -409,6 +836,63 @@ impl SsaFile {
     }
 }
 
+/// Read the placement from the `\\an`/`\\a` and `\\pos` override tags of a dialog text.
+///
+/// Only the first occurrence of each tag is used (like renderers do). Without an alignment tag the alignment
+/// of the style is used, which isn't known here, so `BottomCenter` (the default style alignment) is assumed.
+fn parse_position(text: &str) -> Option<SubtitlePosition> {
+    let mut alignment = None;
+    let mut coordinates = None;
+
+    let override_blocks = text.split('{').skip(1).filter_map(|block| block.split('}').next());
+    for tag in override_blocks.flat_map(|block| block.split('\\')) {
+        let tag = tag.trim();
+        let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+        if alignment.is_none() && tag.starts_with("an") && is_number(&tag[2..]) {
+            alignment = tag[2..].parse().ok().and_then(SubtitleAlignment::from_numpad);
+        } else if alignment.is_none() && tag.starts_with('a') && is_number(&tag[1..]) {
+            alignment = tag[1..].parse().ok().and_then(alignment_from_legacy);
+        } else if coordinates.is_none() && tag.starts_with("pos(") {
+            let args: Vec<f64> = tag["pos(".len()..]
+                .trim_end_matches(')')
+                .split(',')
+                .filter_map(|arg| arg.trim().parse().ok())
+                .collect();
+            if let [x, y] = args[..] {
+                coordinates = Some(SubtitleCoordinates::Pixels { x, y });
+            }
+        }
+    }
+
+    if alignment.is_none() && coordinates.is_none() {
+        return None;
+    }
+
+    Some(SubtitlePosition {
+        alignment: alignment.unwrap_or(SubtitleAlignment::BottomCenter),
+        coordinates,
+    })
+}
+
+/// Read a legacy SSA alignment (1-3 bottom, 5-7 top, 9-11 middle).
+fn alignment_from_legacy(n: u8) -> Option<SubtitleAlignment> {
+    match n {
+        1..=3 => SubtitleAlignment::from_numpad(n),
+        5..=7 => SubtitleAlignment::from_numpad(n + 2),
+        9..=11 => SubtitleAlignment::from_numpad(n - 5),
+        _ => None,
+    }
+}
+
+/// The legacy SSA number of an alignment (see `alignment_from_legacy()`).
+fn alignment_to_legacy(alignment: SubtitleAlignment) -> u8 {
+    match alignment.to_numpad() {
+        n @ 1..=3 => n,
+        n @ 7..=9 => n - 2,
+        n => n + 5,
+    }
+}
+
 impl SubtitleFileInterface for SsaFile {
     fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
         // it's unfortunate we have to clone the file before using
@@ -421,7 +905,14 @@ impl SubtitleFileInterface for SsaFile {
         let timings = new_file
             .get_subtitle_entries_mut()
             .into_iter()
-            .map(|(&mut start, &mut end, text)| SubtitleEntry::new(TimeSpan::new(start, end), text.clone()))
+            .zip(&self.tags)
+            .map(|((&mut start, &mut end, name, text), tags)| SubtitleEntry {
+                position: parse_position(text),
+                speaker: name.filter(|name| !name.is_empty()).cloned(),
+                index: None,
+                tags: tags.clone(),
+                ..SubtitleEntry::new(TimeSpan::new(start, end), text.clone())
+            })
             .collect();
 
         Ok(timings)
@@ -429,44 +920,1736 @@ impl SubtitleFileInterface for SsaFile {
 
     fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
         let subtitle_entries = self.get_subtitle_entries_mut();
-        assert_eq!(subtitle_entries.len(), new_subtitle_entries.len()); // required by specification of this function
+        check_entry_count(subtitle_entries.len(), new_subtitle_entries.len())?;
 
-        for ((start_ref, end_ref, text_ref), new_entry_ref) in subtitle_entries.into_iter().zip(new_subtitle_entries) {
+        for ((start_ref, end_ref, name_ref, text_ref), new_entry_ref) in subtitle_entries.into_iter().zip(new_subtitle_entries) {
             *start_ref = new_entry_ref.timespan.start;
             *end_ref = new_entry_ref.timespan.end;
+            if let (Some(name_ref), Some(speaker)) = (name_ref, &new_entry_ref.speaker) {
+                // commas would start a new field
+                *name_ref = speaker.replace(',', " ");
+            }
             if let Some(ref text) = new_entry_ref.line {
                 *text_ref = text.clone();
             }
         }
+        self.tags = new_subtitle_entries.iter().map(|entry| entry.tags.clone()).collect();
 
         Ok(())
     }
 
     fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
-        // timing to string like "0:00:22.21"
-        let fn_timing_to_string = |t: TimePoint| {
-            let p = if t.msecs() < 0 { -t } else { t };
-            format!(
-                "{}{}:{:02}:{:02}.{:02}",
-                if t.msecs() < 0 { "-" } else { "" },
-                p.hours(),
-                p.mins_comp(),
-                p.secs_comp(),
-                p.csecs_comp()
-            )
+        Ok(self.to_text().into_bytes())
+    }
+
+    fn to_data_with(&self, options: &SerializeOptions) -> SubtitleParserResult<Vec<u8>> {
+        let text = if options.get_write_tags() {
+            let mut file = self.clone();
+            file.write_tags_to_effects();
+            file.to_text()
+        } else {
+            self.to_text()
+        };
+
+        let text = match options.get_ass_profile() {
+            Some(profile) => apply_ass_profile(&text, profile),
+            None => text,
         };
+        options.apply_to_text(text.into_bytes())
+    }
+}
 
+impl SsaFile {
+    /// The text of the file (see `to_data()`).
+    fn to_text(&self) -> String {
         let fn_file_part_to_string = |part: &SsaFilePart| {
             use self::SsaFilePart::*;
             match *part {
-                Filler(ref t) | Text(ref t) => t.clone(),
-                TimespanStart(start) => fn_timing_to_string(start),
-                TimespanEnd(end) => fn_timing_to_string(end),
+                Filler(ref t)
+                | Name(ref t)
+                | Effect(ref t)
+                | Text(ref t)
+                | Style(ref t)
+                | StyleFormat { line: ref t, .. }
+                | Field(_, ref t)
+                | Attachment { text: ref t, .. } => t.clone(),
+                TimespanStart(start) => timepoint_to_string(start, self.timestamp_precision),
+                TimespanEnd(end) => timepoint_to_string(end, self.timestamp_precision),
+            }
+        };
+
+        self.v.iter().map(fn_file_part_to_string).collect()
+    }
+}
+
+/// Timing to string like "0:00:22.21" (or "0:00:22.213" with milliseconds).
+fn timepoint_to_string(t: TimePoint, precision: SsaTimestampPrecision) -> String {
+    let p = if t.msecs() < 0 { -t } else { t };
+    let fraction = match precision {
+        SsaTimestampPrecision::Centiseconds => format!("{:02}", p.csecs_comp()),
+        SsaTimestampPrecision::Milliseconds => format!("{:03}", p.msecs_comp()),
+    };
+    format!(
+        "{}{}:{:02}:{:02}.{}",
+        if t.msecs() < 0 { "-" } else { "" },
+        p.hours(),
+        p.mins_comp(),
+        p.secs_comp(),
+        fraction
+    )
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA creation
+
+impl SsaFile {
+    /// Creates an `.ass` file with one dialog line (style `Default`) for every entry.
+    ///
+    /// The lines of the texts are joined with `\\N`. The speakers are written into the `Name` field, and the tags are
+    /// kept (see `SubtitleEntry::tags`).
+    pub fn create(entries: &[SubtitleEntry]) -> SubtitleParserResult<SsaFile> {
+        let mut s = Self::created_header(&FileTemplate::new());
+        for entry in entries {
+            s.push_str(&format!(
+                "Dialogue: 0,{},{},Default,{},0,0,0,,{}\n",
+                timepoint_to_string(entry.timespan.start, SsaTimestampPrecision::Centiseconds),
+                timepoint_to_string(entry.timespan.end, SsaTimestampPrecision::Centiseconds),
+                entry.speaker.as_deref().unwrap_or_default().replace(',', " "),
+                entry.line.as_deref().unwrap_or_default().lines().collect::<Vec<_>>().join(SSA_LINE_BREAK)
+            ));
+        }
+
+        let mut file = Self::parse(&s)?;
+        file.tags = entries.iter().map(|entry| entry.tags.clone()).collect();
+        Ok(file)
+    }
+
+    /// Creates an `.ass` file which shows two subtitles at the same time, for example the original
+    /// dialog and a translation.
+    ///
+    /// The `primary` entries are shown at the bottom of the screen (style `Default`), the `secondary`
+    /// entries at the top (style `Top`). Overlapping entries of the same file are split into
+    /// non-overlapping parts, so each part shows all texts at once instead of letting the renderer
+    /// move the lines around.
+    pub fn create_dual(primary: &[SubtitleEntry], secondary: &[SubtitleEntry]) -> SubtitleParserResult<SsaFile> {
+        Self::create_dual_with_template(primary, secondary, &FileTemplate::new())
+    }
+
+    /// Works like `create_dual()`, but the comment of the template is written as `;` comment lines and the title as
+    /// `Title` into the `[Script Info]` section.
+    pub fn create_dual_with_template(
+        primary: &[SubtitleEntry],
+        secondary: &[SubtitleEntry],
+        template: &FileTemplate,
+    ) -> SubtitleParserResult<SsaFile> {
+        let mut s = Self::created_header(template);
+        for &(entries, style) in &[(primary, "Default"), (secondary, "Top")] {
+            for (timespan, text) in split_overlapping_entries(entries) {
+                s.push_str(&format!(
+                    "Dialogue: 0,{},{},{},,0,0,0,,{}\n",
+                    timepoint_to_string(timespan.start, SsaTimestampPrecision::Centiseconds),
+                    timepoint_to_string(timespan.end, SsaTimestampPrecision::Centiseconds),
+                    style,
+                    text
+                ));
+            }
+        }
+
+        Self::parse(&s)
+    }
+
+    /// The sections of a created file up to the format line of the dialog lines.
+    fn created_header(template: &FileTemplate) -> String {
+        let mut s = String::from("[Script Info]\n");
+        for line in template.rendered_comment_lines() {
+            s.push_str(&format!("; {}\n", line));
+        }
+        if let Some(title) = template.rendered_title() {
+            s.push_str(&format!("Title: {}\n", title.replace('\n', " ")));
+        }
+        s.push_str(
+            "ScriptType: v4.00+\n\
+             WrapStyle: 0\n\
+             ScaledBorderAndShadow: yes\n\
+             \n\
+             [V4+ Styles]\n\
+             Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+             Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,10,10,10,1\n\
+             Style: Top,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,8,10,10,10,1\n\
+             \n\
+             [Events]\n\
+             Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        );
+        s
+    }
+}
+
+/// Split the entries into non-overlapping timespans which show the texts of all entries (joined by `\\N`)
+/// that are active during the timespan.
+fn split_overlapping_entries(entries: &[SubtitleEntry]) -> Vec<(TimeSpan, String)> {
+    let entries: Vec<(TimeSpan, String)> = entries
+        .iter()
+        .filter(|entry| entry.timespan.start < entry.timespan.end)
+        .filter_map(|entry| {
+            entry
+                .line
+                .as_ref()
+                .map(|line| (entry.timespan, line.lines().collect::<Vec<_>>().join(SSA_LINE_BREAK)))
+        })
+        .collect();
+
+    let mut boundaries: Vec<TimePoint> = entries.iter().flat_map(|(timespan, _)| vec![timespan.start, timespan.end]).collect();
+    boundaries.sort();
+    boundaries.dedup();
+
+    let mut result: Vec<(TimeSpan, String)> = Vec::new();
+    for window in boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        let texts: Vec<&str> = entries
+            .iter()
+            .filter(|(timespan, _)| timespan.start <= start && end <= timespan.end)
+            .map(|(_, text)| text.as_str())
+            .collect();
+        if texts.is_empty() {
+            continue;
+        }
+        let text = texts.join(SSA_LINE_BREAK);
+
+        // parts with the same text directly after each other are merged again
+        match result.last_mut() {
+            Some((last_timespan, last_text)) if last_timespan.end == start && *last_text == text => last_timespan.end = end,
+            _ => result.push((TimeSpan::new(start, end), text)),
+        }
+    }
+    result
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA builder
+
+/// A `Style:` line of the `[V4+ Styles]` section (see `SsaFileBuilder::style()` and `SsaFile::styles()`).
+///
+/// The colors are given as `0xAABBGGRR` like in the file, where an alpha of `0x00` is opaque.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub struct SsaStyle {
+    pub name: String,
+    pub font_name: String,
+    pub font_size: f64,
+    pub primary_color: u32,
+    pub secondary_color: u32,
+    pub outline_color: u32,
+    pub back_color: u32,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strike_out: bool,
+    /// Horizontal scaling in percent.
+    pub scale_x: f64,
+    /// Vertical scaling in percent.
+    pub scale_y: f64,
+    pub spacing: f64,
+    pub angle: f64,
+    /// `1` for an outline and drop shadow, `3` for an opaque box.
+    pub border_style: u8,
+    pub outline: f64,
+    pub shadow: f64,
+    pub alignment: SubtitleAlignment,
+    pub margin_l: i32,
+    pub margin_r: i32,
+    pub margin_v: i32,
+    pub encoding: i32,
+}
+
+impl SsaStyle {
+    /// A style with the values of the `Default` style of created files (white 20pt Arial with a black outline, at
+    /// the bottom of the screen).
+    pub fn new(name: &str) -> SsaStyle {
+        SsaStyle {
+            name: name.to_string(),
+            font_name: "Arial".to_string(),
+            font_size: 20.0,
+            primary_color: 0x00FF_FFFF,
+            secondary_color: 0x0000_00FF,
+            outline_color: 0,
+            back_color: 0,
+            bold: false,
+            italic: false,
+            underline: false,
+            strike_out: false,
+            scale_x: 100.0,
+            scale_y: 100.0,
+            spacing: 0.0,
+            angle: 0.0,
+            border_style: 1,
+            outline: 2.0,
+            shadow: 0.0,
+            alignment: SubtitleAlignment::BottomCenter,
+            margin_l: 10,
+            margin_r: 10,
+            margin_v: 10,
+            encoding: 1,
+        }
+    }
+
+    fn to_line(&self) -> String {
+        let flag = |b: bool| if b { -1 } else { 0 };
+        format!(
+            "Style: {},{},{},&H{:08X},&H{:08X},&H{:08X},&H{:08X},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            field(&self.name),
+            field(&self.font_name),
+            self.font_size,
+            self.primary_color,
+            self.secondary_color,
+            self.outline_color,
+            self.back_color,
+            flag(self.bold),
+            flag(self.italic),
+            flag(self.underline),
+            flag(self.strike_out),
+            self.scale_x,
+            self.scale_y,
+            self.spacing,
+            self.angle,
+            self.border_style,
+            self.outline,
+            self.shadow,
+            self.alignment.to_numpad(),
+            self.margin_l,
+            self.margin_r,
+            self.margin_v,
+            self.encoding
+        )
+    }
+}
+
+/// A `Dialogue:` (or `Comment:`) line of the `[Events]` section (see `SsaFileBuilder::event()`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsaEvent {
+    /// Write a `Comment:` line, which is not shown (and is no entry of the file).
+    pub comment: bool,
+
+    /// Events of higher layers are drawn over the ones of lower layers.
+    pub layer: i32,
+
+    /// When the text is shown.
+    pub timespan: TimeSpan,
+
+    /// The name of the style (`Default` by default).
+    pub style: String,
+
+    /// The name of the speaker.
+    pub name: String,
+
+    /// The margins which replace the margins of the style (`0` keeps the margin of the style).
+    pub margins: (i32, i32, i32),
+
+    /// The effect of the line.
+    pub effect: Option<SsaEffect>,
+
+    /// The text, which can contain override blocks like `{\\i1}`. Line breaks (`\n`) are written as `\\N`.
+    pub text: String,
+}
+
+impl SsaEvent {
+    /// A dialog line with the style `Default`.
+    pub fn new(timespan: TimeSpan, text: &str) -> SsaEvent {
+        SsaEvent {
+            comment: false,
+            layer: 0,
+            timespan,
+            style: "Default".to_string(),
+            name: String::new(),
+            margins: (0, 0, 0),
+            effect: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}: {},{},{},{},{},{},{},{},{},{}\n",
+            if self.comment { "Comment" } else { "Dialogue" },
+            self.layer,
+            timepoint_to_string(self.timespan.start, SsaTimestampPrecision::Centiseconds),
+            timepoint_to_string(self.timespan.end, SsaTimestampPrecision::Centiseconds),
+            field(&self.style),
+            field(&self.name),
+            self.margins.0,
+            self.margins.1,
+            self.margins.2,
+            self.effect.as_ref().map(SsaEffect::to_field).unwrap_or_default(),
+            self.text.lines().collect::<Vec<_>>().join(SSA_LINE_BREAK)
+        )
+    }
+}
+
+/// A value which can't contain commas or line breaks (they would start a new field or line).
+fn field(s: &str) -> String {
+    s.replace([',', '\n', '\r'], " ")
+}
+
+/// Builds an `.ass` file from script info entries, styles and events, like
+/// `SsaFileBuilder::new().script_info("Title", "Episode 1").event(SsaEvent::new(timespan, "Hello")).build()`.
+///
+/// The values are written so they can't break the structure of the file (commas in names and line breaks in values
+/// are replaced by spaces). If no style is added, the file gets the `Default` style of `SsaStyle::new()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsaFileBuilder {
+    script_info: Vec<(String, String)>,
+    styles: Vec<SsaStyle>,
+    events: Vec<SsaEvent>,
+}
+
+impl Default for SsaFileBuilder {
+    fn default() -> SsaFileBuilder {
+        SsaFileBuilder {
+            script_info: vec![
+                ("ScriptType".to_string(), "v4.00+".to_string()),
+                ("WrapStyle".to_string(), "0".to_string()),
+                ("ScaledBorderAndShadow".to_string(), "yes".to_string()),
+            ],
+            styles: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl SsaFileBuilder {
+    /// Create a builder with the script info of created files (`ScriptType: v4.00+`, `WrapStyle: 0` and
+    /// `ScaledBorderAndShadow: yes`).
+    pub fn new() -> SsaFileBuilder {
+        SsaFileBuilder::default()
+    }
+
+    /// Set an entry of the `[Script Info]` section like `PlayResX` (an existing entry with that key is replaced).
+    pub fn script_info(mut self, key: &str, value: &str) -> SsaFileBuilder {
+        let (key, value) = (key.replace([':', '\n', '\r'], " "), value.replace(['\n', '\r'], " "));
+        match self.script_info.iter_mut().find(|(k, _)| *k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.script_info.push((key, value)),
+        }
+        self
+    }
+
+    /// Add a style (an existing style with that name is replaced).
+    pub fn style(mut self, style: SsaStyle) -> SsaFileBuilder {
+        match self.styles.iter_mut().find(|s| s.name == style.name) {
+            Some(existing) => *existing = style,
+            None => self.styles.push(style),
+        }
+        self
+    }
+
+    /// Add an event. The events are written in the order in which they were added.
+    pub fn event(mut self, event: SsaEvent) -> SsaFileBuilder {
+        self.events.push(event);
+        self
+    }
+
+    /// Create the file.
+    pub fn build(&self) -> SubtitleParserResult<SsaFile> {
+        let mut s = String::from("[Script Info]\n");
+        for (key, value) in &self.script_info {
+            s.push_str(&format!("{}: {}\n", key, value));
+        }
+
+        s.push_str(
+            "\n[V4+ Styles]\n\
+             Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+        );
+        if self.styles.is_empty() {
+            s.push_str(&SsaStyle::new("Default").to_line());
+        }
+        for style in &self.styles {
+            s.push_str(&style.to_line());
+        }
+
+        s.push_str("\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n");
+        for event in &self.events {
+            s.push_str(&event.to_line());
+        }
+
+        SsaFile::parse(&s)
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA styles
+
+/// The fields of `Style:` lines without a `Format:` line (like in `.ass` files).
+const DEFAULT_STYLE_FIELDS: &str = "Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
+
+/// Whether a section with this name holds styles (`Some(true)` for the legacy `[V4 Styles]` of `.ssa` files).
+fn style_section_is_legacy(section: &str) -> Option<bool> {
+    if section.eq_ignore_ascii_case("V4+ Styles") {
+        Some(false)
+    } else if section.eq_ignore_ascii_case("V4 Styles") {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// The field names of a line like `Format: Name, Fontname, ...` (in lowercase).
+fn style_fields(format_line: &str) -> Vec<String> {
+    let fields = format_line.trim().trim_start_matches("Format:");
+    fields.split(',').map(|field| field.trim().to_ascii_lowercase()).collect()
+}
+
+/// A parsed `Style:` line of the file.
+#[derive(Debug, Clone)]
+struct StyleLine {
+    /// The index of the `SsaFilePart::Style` part
+    part_idx: usize,
+
+    /// The field names of the `Format:` line of its section
+    fields: Vec<String>,
+
+    /// Whether the alignment is a legacy SSA alignment
+    legacy: bool,
+
+    style: SsaStyle,
+}
+
+impl SsaStyle {
+    /// Read a `Style:` line with the given fields. Missing or malformed values keep the values of `SsaStyle::new()`.
+    fn from_line(line: &str, fields: &[String], legacy: bool) -> SsaStyle {
+        let values = line.trim_start().trim_start_matches("Style:").splitn(fields.len(), ',');
+        let mut style = SsaStyle::new("");
+        for (field, value) in fields.iter().zip(values) {
+            let value = value.trim();
+            let color = |default: u32| -> u32 {
+                match value.strip_prefix("&H").or_else(|| value.strip_prefix("&h")) {
+                    Some(hex) => u32::from_str_radix(hex.trim_end_matches('&'), 16).unwrap_or(default),
+                    // some `.ssa` files have decimal colors
+                    None => value.parse::<i64>().map(|c| c as u32).unwrap_or(default),
+                }
+            };
+            let flag = |default: bool| value.parse::<i32>().map(|n| n != 0).unwrap_or(default);
+            let number = |default: f64| value.parse().unwrap_or(default);
+            let integer = |default: i32| value.parse().unwrap_or(default);
+
+            match field.as_str() {
+                "name" => style.name = value.to_string(),
+                "fontname" => style.font_name = value.to_string(),
+                "fontsize" => style.font_size = number(style.font_size),
+                "primarycolour" => style.primary_color = color(style.primary_color),
+                "secondarycolour" => style.secondary_color = color(style.secondary_color),
+                "outlinecolour" | "tertiarycolour" => style.outline_color = color(style.outline_color),
+                "backcolour" => style.back_color = color(style.back_color),
+                "bold" => style.bold = flag(style.bold),
+                "italic" => style.italic = flag(style.italic),
+                "underline" => style.underline = flag(style.underline),
+                "strikeout" => style.strike_out = flag(style.strike_out),
+                "scalex" => style.scale_x = number(style.scale_x),
+                "scaley" => style.scale_y = number(style.scale_y),
+                "spacing" => style.spacing = number(style.spacing),
+                "angle" => style.angle = number(style.angle),
+                "borderstyle" => style.border_style = value.parse().unwrap_or(style.border_style),
+                "outline" => style.outline = number(style.outline),
+                "shadow" => style.shadow = number(style.shadow),
+                "alignment" => {
+                    let alignment = value.parse().ok().and_then(|n| {
+                        if legacy {
+                            alignment_from_legacy(n)
+                        } else {
+                            SubtitleAlignment::from_numpad(n)
+                        }
+                    });
+                    style.alignment = alignment.unwrap_or(style.alignment);
+                }
+                "marginl" => style.margin_l = integer(style.margin_l),
+                "marginr" => style.margin_r = integer(style.margin_r),
+                "marginv" => style.margin_v = integer(style.margin_v),
+                "encoding" => style.encoding = integer(style.encoding),
+                _ => {}
+            }
+        }
+        style
+    }
+
+    /// The value of a field like it is written by `to_line()` (`None` for unknown fields like `AlphaLevel`).
+    fn field_value(&self, field: &str, legacy: bool) -> Option<String> {
+        let flag = |b: bool| if b { "-1" } else { "0" }.to_string();
+        let color = |c: u32| format!("&H{:08X}", c);
+        Some(match field {
+            "name" => self::field(&self.name),
+            "fontname" => self::field(&self.font_name),
+            "fontsize" => self.font_size.to_string(),
+            "primarycolour" => color(self.primary_color),
+            "secondarycolour" => color(self.secondary_color),
+            "outlinecolour" | "tertiarycolour" => color(self.outline_color),
+            "backcolour" => color(self.back_color),
+            "bold" => flag(self.bold),
+            "italic" => flag(self.italic),
+            "underline" => flag(self.underline),
+            "strikeout" => flag(self.strike_out),
+            "scalex" => self.scale_x.to_string(),
+            "scaley" => self.scale_y.to_string(),
+            "spacing" => self.spacing.to_string(),
+            "angle" => self.angle.to_string(),
+            "borderstyle" => self.border_style.to_string(),
+            "outline" => self.outline.to_string(),
+            "shadow" => self.shadow.to_string(),
+            "alignment" if legacy => alignment_to_legacy(self.alignment).to_string(),
+            "alignment" => self.alignment.to_numpad().to_string(),
+            "marginl" => self.margin_l.to_string(),
+            "marginr" => self.margin_r.to_string(),
+            "marginv" => self.margin_v.to_string(),
+            "encoding" => self.encoding.to_string(),
+            _ => return None,
+        })
+    }
+}
+
+impl SsaFile {
+    /// The `Style:` lines of the `[V4+ Styles]` (or `[V4 Styles]`) section, in the order of the file.
+    pub fn styles(&self) -> Vec<SsaStyle> {
+        self.style_lines().into_iter().map(|line| line.style).collect()
+    }
+
+    /// Change the styles (see `styles()`). The changes are written into the file when the returned value is dropped;
+    /// only the fields with changed values are rewritten, so unknown fields and the formatting of the others are
+    /// kept.
+    pub fn styles_mut(&mut self) -> SsaStylesMut<'_> {
+        let lines = self.style_lines();
+        SsaStylesMut {
+            styles: lines.iter().map(|line| line.style.clone()).collect(),
+            lines,
+            file: self,
+        }
+    }
+
+    /// Whether this is a `.ssa` (SubStation Alpha v4) file instead of an `.ass` (Advanced SubStation Alpha v4+)
+    /// file: the styles are in a `[V4 Styles]` section or, if there are no styles, the `ScriptType` is `v4.00`.
+    pub fn is_legacy_ssa(&self) -> bool {
+        let style_section_legacy = self.v.iter().find_map(|part| match part {
+            SsaFilePart::StyleFormat { legacy, .. } => Some(*legacy),
+            _ => None,
+        });
+        style_section_legacy.unwrap_or_else(|| {
+            self.v
+                .iter()
+                .filter_map(|part| match part {
+                    SsaFilePart::Filler(text) => Some(text),
+                    _ => None,
+                })
+                .flat_map(|text| text.lines())
+                .filter_map(|line| line.trim().strip_prefix("ScriptType:"))
+                .any(|script_type| script_type.trim().eq_ignore_ascii_case("v4.00"))
+        })
+    }
+
+    fn style_lines(&self) -> Vec<StyleLine> {
+        let mut fields = style_fields(DEFAULT_STYLE_FIELDS);
+        let mut legacy = false;
+        let mut result = Vec::new();
+        for (part_idx, part) in self.v.iter().enumerate() {
+            match part {
+                SsaFilePart::StyleFormat { line, legacy: is_legacy } => {
+                    fields = style_fields(line);
+                    legacy = *is_legacy;
+                }
+                SsaFilePart::Style(line) => result.push(StyleLine {
+                    part_idx,
+                    fields: fields.clone(),
+                    legacy,
+                    style: SsaStyle::from_line(line, &fields, legacy),
+                }),
+                _ => {}
+            }
+        }
+        result
+    }
+}
+
+/// The styles of an `SsaFile` which can be changed (see `SsaFile::styles_mut()`).
+#[derive(Debug)]
+pub struct SsaStylesMut<'a> {
+    file: &'a mut SsaFile,
+    lines: Vec<StyleLine>,
+    styles: Vec<SsaStyle>,
+}
+
+impl Deref for SsaStylesMut<'_> {
+    type Target = [SsaStyle];
+
+    fn deref(&self) -> &[SsaStyle] {
+        &self.styles
+    }
+}
+
+impl DerefMut for SsaStylesMut<'_> {
+    fn deref_mut(&mut self) -> &mut [SsaStyle] {
+        &mut self.styles
+    }
+}
+
+impl Drop for SsaStylesMut<'_> {
+    fn drop(&mut self) {
+        for (line, style) in self.lines.iter().zip(&self.styles) {
+            if line.style == *style {
+                continue;
+            }
+            let text = match self.file.v[line.part_idx] {
+                SsaFilePart::Style(ref mut text) => text,
+                _ => unreachable!("style lines are only created for style parts"),
+            };
+
+            let prefix_len = text.find("Style:").expect("style lines start with `Style:`") + "Style:".len();
+            let mut values: Vec<String> = text[prefix_len..].splitn(line.fields.len(), ',').map(str::to_string).collect();
+            for (field, value) in line.fields.iter().zip(values.iter_mut()) {
+                match style.field_value(field, line.legacy) {
+                    Some(new_value) if Some(&new_value) != line.style.field_value(field, line.legacy).as_ref() => {
+                        let (begin, _, end) = trim_non_destructive(value);
+                        *value = format!("{}{}{}", begin, new_value, end);
+                    }
+                    _ => {}
+                }
+            }
+            *text = format!("{}{}", &text[..prefix_len], values.join(","));
+        }
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA dialogues
+
+/// The fields of a `Dialogue:` line of the `[Events]` section (see `SsaFile::dialogues()`).
+///
+/// Fields which the `Format:` line of the file doesn't have are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsaDialogue {
+    /// When the text is shown.
+    pub timespan: TimeSpan,
+
+    /// Dialogues of higher layers are drawn over the ones of lower layers.
+    pub layer: Option<i32>,
+
+    /// The name of the style.
+    pub style: Option<String>,
+
+    /// The name of the speaker (the `Name` field).
+    pub name: Option<String>,
+
+    /// The left margin which replaces the margin of the style (`0` keeps the margin of the style).
+    pub margin_l: Option<i32>,
+
+    /// The right margin (see `margin_l`).
+    pub margin_r: Option<i32>,
+
+    /// The vertical margin (see `margin_l`).
+    pub margin_v: Option<i32>,
+
+    /// The effect (`None` if the field is empty or holds tags, see `SsaFile::effect()`).
+    pub effect: Option<SsaEffect>,
+
+    /// The text with override blocks like `{\\i1}` and `\\N` line breaks.
+    pub text: String,
+}
+
+/// The indices of the file parts of a dialog line.
+#[derive(Debug, Clone)]
+struct DialogueParts {
+    start: usize,
+    end: usize,
+    name: Option<usize>,
+    effect: Option<usize>,
+    fields: Vec<(SsaDialogueField, usize)>,
+    text: usize,
+}
+
+impl SsaFile {
+    /// The dialog lines (the entries of the file) with all their fields.
+    pub fn dialogues(&self) -> Vec<SsaDialogue> {
+        self.dialogue_parts().iter().map(|parts| self.dialogue(parts)).collect()
+    }
+
+    /// Change the dialog lines (see `dialogues()`). The changes are written into the file when the returned value is
+    /// dropped; only the changed fields are rewritten, and fields which the file doesn't have are ignored.
+    pub fn dialogues_mut(&mut self) -> SsaDialoguesMut<'_> {
+        let parts = self.dialogue_parts();
+        SsaDialoguesMut {
+            dialogues: parts.iter().map(|parts| self.dialogue(parts)).collect(),
+            parts,
+            file: self,
+        }
+    }
+
+    /// Shift only the dialog lines for which `predicate` returns `true` by `delta`, like all lines of a style
+    /// (`|dialogue| dialogue.style.as_deref() == Some("Signs")`). Returns the number of shifted lines.
+    pub fn shift_dialogues<F: FnMut(&SsaDialogue) -> bool>(&mut self, delta: TimeDelta, mut predicate: F) -> usize {
+        let mut dialogues = self.dialogues_mut();
+        let mut shifted = 0;
+        for dialogue in dialogues.iter_mut().filter(|dialogue| predicate(dialogue)) {
+            dialogue.timespan += delta;
+            shifted += 1;
+        }
+        shifted
+    }
+
+    fn dialogue_parts(&self) -> Vec<DialogueParts> {
+        let (mut start, mut end, mut name, mut effect, mut fields) = (None, None, None, None, Vec::new());
+        let mut result = Vec::new();
+        for (i, part) in self.v.iter().enumerate() {
+            match part {
+                SsaFilePart::TimespanStart(_) => start = Some(i),
+                SsaFilePart::TimespanEnd(_) => end = Some(i),
+                SsaFilePart::Name(_) => name = Some(i),
+                SsaFilePart::Effect(_) => effect = Some(i),
+                SsaFilePart::Field(kind, _) => fields.push((*kind, i)),
+                SsaFilePart::Text(_) => result.push(DialogueParts {
+                    start: start.take().expect("SSA parser should have ensured that every line has a startpoint"),
+                    end: end.take().expect("SSA parser should have ensured that every line has a endpoint"),
+                    name: name.take(),
+                    effect: effect.take(),
+                    fields: std::mem::take(&mut fields),
+                    text: i,
+                }),
+                SsaFilePart::Filler(_) | SsaFilePart::StyleFormat { .. } | SsaFilePart::Style(_) | SsaFilePart::Attachment { .. } => {}
             }
+        }
+        result
+    }
+
+    fn dialogue(&self, parts: &DialogueParts) -> SsaDialogue {
+        let text = |idx: usize| match self.v[idx] {
+            SsaFilePart::Name(ref t) | SsaFilePart::Effect(ref t) | SsaFilePart::Field(_, ref t) | SsaFilePart::Text(ref t) => t.as_str(),
+            _ => unreachable!("the parts of dialog lines are text parts"),
+        };
+        let field = |kind: SsaDialogueField| parts.fields.iter().find(|&&(k, _)| k == kind).map(|&(_, idx)| text(idx));
+        let number = |kind: SsaDialogueField| field(kind).and_then(|value| value.parse().ok());
+        let timepoint = |idx: usize| match self.v[idx] {
+            SsaFilePart::TimespanStart(t) | SsaFilePart::TimespanEnd(t) => t,
+            _ => unreachable!("the timespan parts of dialog lines are timepoints"),
         };
 
-        let result: String = self.v.iter().map(fn_file_part_to_string).collect();
+        SsaDialogue {
+            timespan: TimeSpan::new(timepoint(parts.start), timepoint(parts.end)),
+            layer: number(SsaDialogueField::Layer),
+            style: field(SsaDialogueField::Style).map(str::to_string),
+            name: parts.name.map(|idx| text(idx).to_string()),
+            margin_l: number(SsaDialogueField::MarginL),
+            margin_r: number(SsaDialogueField::MarginR),
+            margin_v: number(SsaDialogueField::MarginV),
+            effect: parts.effect.and_then(|idx| SsaEffect::parse(text(idx))),
+            text: text(parts.text).to_string(),
+        }
+    }
+}
+
+/// The dialog lines of an `SsaFile` which can be changed (see `SsaFile::dialogues_mut()`).
+#[derive(Debug)]
+pub struct SsaDialoguesMut<'a> {
+    file: &'a mut SsaFile,
+    parts: Vec<DialogueParts>,
+    dialogues: Vec<SsaDialogue>,
+}
+
+impl Deref for SsaDialoguesMut<'_> {
+    type Target = [SsaDialogue];
 
-        Ok(result.into_bytes())
+    fn deref(&self) -> &[SsaDialogue] {
+        &self.dialogues
+    }
+}
+
+impl DerefMut for SsaDialoguesMut<'_> {
+    fn deref_mut(&mut self) -> &mut [SsaDialogue] {
+        &mut self.dialogues
+    }
+}
+
+impl Drop for SsaDialoguesMut<'_> {
+    fn drop(&mut self) {
+        for (parts, new) in self.parts.iter().zip(&self.dialogues) {
+            let old = self.file.dialogue(parts);
+            if old == *new {
+                continue;
+            }
+
+            let v = &mut self.file.v;
+            let mut set_text = |idx: usize, value: String| match v[idx] {
+                SsaFilePart::Name(ref mut t) | SsaFilePart::Effect(ref mut t) | SsaFilePart::Field(_, ref mut t) | SsaFilePart::Text(ref mut t) => {
+                    *t = value
+                }
+                _ => unreachable!("the parts of dialog lines are text parts"),
+            };
+
+            let field_idx = |kind: SsaDialogueField| parts.fields.iter().find(|&&(k, _)| k == kind).map(|&(_, idx)| idx);
+            let changed_fields = [
+                (SsaDialogueField::Layer, old.layer != new.layer, new.layer.map(|n| n.to_string())),
+                (SsaDialogueField::Style, old.style != new.style, new.style.as_deref().map(field)),
+                (
+                    SsaDialogueField::MarginL,
+                    old.margin_l != new.margin_l,
+                    new.margin_l.map(|n| n.to_string()),
+                ),
+                (
+                    SsaDialogueField::MarginR,
+                    old.margin_r != new.margin_r,
+                    new.margin_r.map(|n| n.to_string()),
+                ),
+                (
+                    SsaDialogueField::MarginV,
+                    old.margin_v != new.margin_v,
+                    new.margin_v.map(|n| n.to_string()),
+                ),
+            ];
+            for (kind, changed, value) in changed_fields {
+                if let (true, Some(idx)) = (changed, field_idx(kind)) {
+                    set_text(idx, value.unwrap_or_default());
+                }
+            }
+            if let (true, Some(idx)) = (old.name != new.name, parts.name) {
+                set_text(idx, new.name.as_deref().map(field).unwrap_or_default());
+            }
+            if let (true, Some(idx)) = (old.effect != new.effect, parts.effect) {
+                set_text(idx, new.effect.as_ref().map(SsaEffect::to_field).unwrap_or_default());
+            }
+            if old.text != new.text {
+                // line breaks would start a new line
+                set_text(parts.text, new.text.lines().collect::<Vec<_>>().join(SSA_LINE_BREAK));
+            }
+            v[parts.start] = SsaFilePart::TimespanStart(new.timespan.start);
+            v[parts.end] = SsaFilePart::TimespanEnd(new.timespan.end);
+        }
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA attachments
+
+/// The section of an embedded file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SsaAttachmentKind {
+    /// A font of the `[Fonts]` section.
+    Font,
+
+    /// An image of the `[Graphics]` section.
+    Graphic,
+}
+
+impl SsaAttachmentKind {
+    fn from_section(section: &str) -> Option<SsaAttachmentKind> {
+        if section.eq_ignore_ascii_case("Fonts") {
+            Some(SsaAttachmentKind::Font)
+        } else if section.eq_ignore_ascii_case("Graphics") {
+            Some(SsaAttachmentKind::Graphic)
+        } else {
+            None
+        }
+    }
+
+    fn section(self) -> &'static str {
+        match self {
+            SsaAttachmentKind::Font => "[Fonts]",
+            SsaAttachmentKind::Graphic => "[Graphics]",
+        }
+    }
+
+    /// The start of the first line of an attachment.
+    fn header(self) -> &'static str {
+        match self {
+            SsaAttachmentKind::Font => "fontname:",
+            SsaAttachmentKind::Graphic => "filename:",
+        }
+    }
+}
+
+/// A file which is embedded into an `.ssa`/`.ass` file (see `SsaFile::attachments()`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsaAttachment {
+    /// The section of the file.
+    pub kind: SsaAttachmentKind,
+
+    /// The file name (like `Arial_0.ttf`).
+    pub name: String,
+
+    /// The decoded content.
+    pub data: Vec<u8>,
+}
+
+/// The number of characters of the encoded lines.
+const ATTACHMENT_LINE_LEN: usize = 80;
+
+/// Decode the lines of an attachment: every character holds six bits (plus 33), four characters hold three bytes.
+fn decode_attachment(lines: &str) -> Vec<u8> {
+    let values: Vec<u8> = lines.bytes().filter(|b| (33..33 + 64).contains(b)).map(|b| b - 33).collect();
+    let mut result = Vec::with_capacity(values.len() * 3 / 4);
+    for chunk in values.chunks(4) {
+        let mut bits = [0u8; 4];
+        bits[..chunk.len()].copy_from_slice(chunk);
+        let bytes = [(bits[0] << 2) | (bits[1] >> 4), (bits[1] << 4) | (bits[2] >> 2), (bits[2] << 6) | bits[3]];
+        // two characters hold one byte and three characters hold two bytes
+        result.extend_from_slice(&bytes[..chunk.len().saturating_sub(1)]);
+    }
+    result
+}
+
+/// Encode the data of an attachment into lines (see `decode_attachment()`).
+fn encode_attachment(data: &[u8], newline: &str) -> String {
+    let mut chars = Vec::with_capacity(data.len() * 4 / 3 + 3);
+    for chunk in data.chunks(3) {
+        let mut bytes = [0u8; 3];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        let values = [
+            bytes[0] >> 2,
+            ((bytes[0] & 0x3) << 4) | (bytes[1] >> 4),
+            ((bytes[1] & 0xF) << 2) | (bytes[2] >> 6),
+            bytes[2] & 0x3F,
+        ];
+        chars.extend(values[..chunk.len() + 1].iter().map(|value| (value + 33) as char));
+    }
+
+    let mut result = String::new();
+    for line in chars.chunks(ATTACHMENT_LINE_LEN) {
+        result.extend(line);
+        result.push_str(newline);
+    }
+    result
+}
+
+impl SsaFile {
+    /// The fonts and images which are embedded into the `[Fonts]` and `[Graphics]` sections.
+    pub fn attachments(&self) -> Vec<SsaAttachment> {
+        self.v
+            .iter()
+            .filter_map(|part| match part {
+                SsaFilePart::Attachment { kind, text } => {
+                    let (header, lines) = text.split_once('\n').unwrap_or((text, ""));
+                    Some(SsaAttachment {
+                        kind: *kind,
+                        name: header.trim()[kind.header().len()..].trim().to_string(),
+                        data: decode_attachment(lines),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Embed a file. It is added after the last attachment of its section, or in a new section at the end of the
+    /// file. Line breaks in the name are replaced by spaces.
+    pub fn add_attachment(&mut self, attachment: &SsaAttachment) {
+        let newline = if self.v.iter().any(|part| matches!(part, SsaFilePart::Filler(t) if t.contains("\r\n"))) {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let part = SsaFilePart::Attachment {
+            kind: attachment.kind,
+            text: format!(
+                "{} {}{}{}",
+                attachment.kind.header(),
+                attachment.name.replace(['\n', '\r'], " "),
+                newline,
+                encode_attachment(&attachment.data, newline)
+            ),
+        };
+
+        let last_idx = self
+            .v
+            .iter()
+            .rposition(|part| matches!(part, SsaFilePart::Attachment { kind, .. } if *kind == attachment.kind));
+        match last_idx {
+            Some(idx) => self.v.insert(idx + 1, part),
+            None => {
+                let data = self.to_data().expect("SSA files can always be written");
+                let mut section = String::new();
+                if !data.is_empty() && !data.ends_with(b"\n") {
+                    section.push_str(newline);
+                }
+                section.push_str(&format!("{}{}{}", newline, attachment.kind.section(), newline));
+                self.v.push(SsaFilePart::Filler(section));
+                self.v.push(part);
+            }
+        }
+    }
+
+    /// Remove the attachments of a section with the given name. Returns whether an attachment was removed.
+    pub fn remove_attachment(&mut self, kind: SsaAttachmentKind, name: &str) -> bool {
+        let attachments = self.attachments();
+        let mut attachment_idx = 0;
+        let old_len = self.v.len();
+        self.v.retain(|part| match part {
+            SsaFilePart::Attachment { .. } => {
+                let attachment = &attachments[attachment_idx];
+                attachment_idx += 1;
+                attachment.kind != kind || attachment.name != name
+            }
+            _ => true,
+        });
+        self.v.len() != old_len
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA karaoke
+
+/// The karaoke effect tag which is used for every syllable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsaKaraokeEffect {
+    /// `\\k`: the syllable switches from the secondary to the primary color when it starts.
+    Highlight,
+
+    /// `\\kf`: the primary color sweeps from left to right over the syllable.
+    Fill,
+
+    /// `\\ko`: like `Highlight`, but the outline is hidden until the syllable starts.
+    Outline,
+}
+
+impl SsaKaraokeEffect {
+    fn tag(self) -> &'static str {
+        match self {
+            SsaKaraokeEffect::Highlight => "k",
+            SsaKaraokeEffect::Fill => "kf",
+            SsaKaraokeEffect::Outline => "ko",
+        }
+    }
+}
+
+/// A syllable of a karaoke line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsaKaraokeSyllable {
+    /// The text of the syllable (including trailing spaces).
+    pub text: String,
+
+    /// How long this syllable is sung.
+    pub duration: TimeDelta,
+
+    /// Additional override tags for this syllable like `\\c&H0000FF&` (without braces).
+    pub style: Option<String>,
+}
+
+/// Create the dialog text of a karaoke line (like `{\\k50}Hel{\\k30}lo`) from the syllables.
+///
+/// The durations are rounded to centiseconds; the rounding errors do not accumulate, so the
+/// karaoke stays in sync with the sum of the durations.
+pub fn create_karaoke_text(syllables: &[SsaKaraokeSyllable], effect: SsaKaraokeEffect) -> String {
+    let mut result = String::new();
+    let mut start = TimeDelta::from_msecs(0);
+    for syllable in syllables {
+        let end = start + syllable.duration;
+        let csecs = ((end.msecs() as f64 / 10.0).round() - (start.msecs() as f64 / 10.0).round()) as i64;
+        start = end;
+
+        result.push_str(&format!("{{\\{}{}", effect.tag(), csecs));
+        if let Some(ref style) = syllable.style {
+            result.push_str(style);
+        }
+        result.push('}');
+        result.push_str(&syllable.text);
+    }
+    result
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// SSA effects
+
+/// The direction of an `SsaEffect::Scroll`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SsaScrollDirection {
+    /// `Scroll up;`: the text moves from the bottom to the top of the region.
+    Up,
+
+    /// `Scroll down;`: the text moves from the top to the bottom of the region.
+    Down,
+}
+
+/// The value of the `Effect` field of a dialog line (see `SsaFile::effect()`).
+///
+/// Missing optional parameters are read as `0`; all parameters are written.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SsaEffect {
+    /// `Banner;delay[;lefttoright[;fadeawaywidth]]`: the text moves horizontally over the screen (ignoring its
+    /// alignment and margins).
+    Banner {
+        /// Milliseconds per pixel of movement (`0` is the fastest speed).
+        delay: u32,
+
+        /// Whether the text moves from left to right instead of right to left.
+        left_to_right: bool,
+
+        /// The width of the fading at the screen edges in pixels.
+        fadeaway_width: u32,
+    },
+
+    /// `Scroll up;y1;y2;delay[;fadeawayheight]`/`Scroll down;...`: the text moves vertically inside the region
+    /// between `y1` and `y2` (like scrolling credits).
+    Scroll {
+        /// Whether the text moves up or down.
+        direction: SsaScrollDirection,
+
+        /// One vertical border of the region (the order of `y1` and `y2` doesn't matter).
+        y1: u32,
+
+        /// The other vertical border of the region.
+        y2: u32,
+
+        /// Milliseconds per pixel of movement (`0` is the fastest speed).
+        delay: u32,
+
+        /// The height of the fading at the region borders in pixels.
+        fadeaway_height: u32,
+    },
+
+    /// Any other effect (like the `Karaoke` effect of old scripts), as it is written in the file.
+    Other(String),
+}
+
+impl SsaEffect {
+    /// Parse the content of an `Effect` field. Returns `None` for an empty field or the tags of
+    /// `SerializeOptions::write_tags()`.
+    pub fn parse(s: &str) -> Option<SsaEffect> {
+        let s = s.trim();
+        if s.is_empty() || s.starts_with(TAGS_EFFECT_PREFIX) {
+            return None;
+        }
+
+        let mut params = s.split(';');
+        let name = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let params: Vec<u32> = params.map(|param| param.trim().parse().unwrap_or(0)).collect();
+        let param = |i: usize| params.get(i).cloned().unwrap_or(0);
+
+        let direction = match name.as_str() {
+            "banner" => {
+                return Some(SsaEffect::Banner {
+                    delay: param(0),
+                    left_to_right: param(1) != 0,
+                    fadeaway_width: param(2),
+                })
+            }
+            "scroll up" => SsaScrollDirection::Up,
+            "scroll down" => SsaScrollDirection::Down,
+            _ => return Some(SsaEffect::Other(s.to_string())),
+        };
+        Some(SsaEffect::Scroll {
+            direction,
+            y1: param(0),
+            y2: param(1),
+            delay: param(2),
+            fadeaway_height: param(3),
+        })
+    }
+
+    /// The content of the `Effect` field (commas of `SsaEffect::Other` are replaced, because they would start a
+    /// new field).
+    pub fn to_field(&self) -> String {
+        match self {
+            SsaEffect::Banner {
+                delay,
+                left_to_right,
+                fadeaway_width,
+            } => format!("Banner;{};{};{}", delay, u32::from(*left_to_right), fadeaway_width),
+            SsaEffect::Scroll {
+                direction,
+                y1,
+                y2,
+                delay,
+                fadeaway_height,
+            } => {
+                let name = match direction {
+                    SsaScrollDirection::Up => "Scroll up",
+                    SsaScrollDirection::Down => "Scroll down",
+                };
+                format!("{};{};{};{};{}", name, y1, y2, delay, fadeaway_height)
+            }
+            SsaEffect::Other(effect) => effect.replace(',', " "),
+        }
+    }
+}
+
+impl SsaFile {
+    /// The effect of the entry with index `entry_idx` (`None` if the field is empty, holds tags, the file has no
+    /// `Effect` field or there is no such entry).
+    pub fn effect(&self, entry_idx: usize) -> Option<SsaEffect> {
+        let effect_idx = Self::effect_part_indices(&self.v).get(entry_idx).cloned().flatten()?;
+        match self.v[effect_idx] {
+            SsaFilePart::Effect(ref effect) => SsaEffect::parse(effect),
+            _ => None,
+        }
+    }
+
+    /// Set or remove (`None`) the effect of the entry with index `entry_idx`. Tags which were written into the
+    /// field are replaced. Returns `false` if the file has no `Effect` field or there is no such entry.
+    pub fn set_effect(&mut self, entry_idx: usize, effect: Option<&SsaEffect>) -> bool {
+        let effect_idx = match Self::effect_part_indices(&self.v).get(entry_idx).cloned().flatten() {
+            Some(effect_idx) => effect_idx,
+            None => return false,
+        };
+        match self.v[effect_idx] {
+            SsaFilePart::Effect(ref mut field) => {
+                *field = effect.map(SsaEffect::to_field).unwrap_or_default();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssa_position_test() {
+        assert_eq!(parse_position("no tags"), None);
+        assert_eq!(parse_position("{\\i1}italic{\\i0}"), None);
+        assert_eq!(
+            parse_position("{\\an8}top"),
+            Some(SubtitlePosition {
+                alignment: SubtitleAlignment::TopCenter,
+                coordinates: None,
+            })
+        );
+        assert_eq!(
+            parse_position("{\\a6}legacy top center"),
+            Some(SubtitlePosition {
+                alignment: SubtitleAlignment::TopCenter,
+                coordinates: None,
+            })
+        );
+        assert_eq!(
+            parse_position("{\\b1\\pos(320.5, 40)\\an7}text{\\an3\\pos(1,2)}"),
+            Some(SubtitlePosition {
+                alignment: SubtitleAlignment::TopLeft,
+                coordinates: Some(SubtitleCoordinates::Pixels { x: 320.5, y: 40.0 }),
+            })
+        );
+        assert_eq!(
+            parse_position("{\\pos(10,20)}"),
+            Some(SubtitlePosition {
+                alignment: SubtitleAlignment::BottomCenter,
+                coordinates: Some(SubtitleCoordinates::Pixels { x: 10.0, y: 20.0 }),
+            })
+        );
+    }
+
+    #[test]
+    fn ssa_karaoke_test() {
+        let syllable = |text: &str, msecs, style: Option<&str>| SsaKaraokeSyllable {
+            text: text.to_string(),
+            duration: TimeDelta::from_msecs(msecs),
+            style: style.map(str::to_string),
+        };
+        let syllables = vec![
+            syllable("Hel", 505, None),
+            syllable("lo ", 305, Some("\\c&H0000FF&")),
+            syllable("world", 1000, None),
+        ];
+
+        assert_eq!(
+            create_karaoke_text(&syllables, SsaKaraokeEffect::Highlight),
+            "{\\k51}Hel{\\k30\\c&H0000FF&}lo {\\k100}world"
+        );
+        assert_eq!(create_karaoke_text(&syllables[..1], SsaKaraokeEffect::Fill), "{\\kf51}Hel");
+    }
+
+    #[test]
+    fn ssa_speaker_test() {
+        use crate::{SpeakerPolicy, SubtitleFile, SubtitleFileInterface};
+
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Name, Text\n\
+                    Dialogue: 0,0:00:01.00,0:00:02.00,Default,Alice,Hello\n\
+                    Dialogue: 0,0:00:03.00,0:00:04.00,Default,,World\n";
+        let file = SsaFile::parse(data).unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].speaker, Some("Alice".to_string()));
+        assert_eq!(entries[1].speaker, None);
+
+        entries[1].speaker = Some("Bob, Jr.".to_string());
+        let mut file = SubtitleFile::SubStationAlpha(file);
+        file.update_subtitle_entries_with_speakers(&entries, SpeakerPolicy::FormatDefault)
+            .unwrap();
+        let data_string = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(data_string.ends_with("Default,Bob  Jr.,World\n"));
+
+        // entries without a speaker get an empty `Name` field
+        entries[0].speaker = None;
+        file.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].speaker.as_deref(), Some("Alice"));
+        file.update_subtitle_entries_with_speakers(&entries, SpeakerPolicy::FormatDefault)
+            .unwrap();
+        assert_eq!(file.get_subtitle_entries().unwrap()[0].speaker, None);
+        assert!(String::from_utf8(file.to_data().unwrap()).unwrap().contains("Default,,Hello\n"));
+    }
+
+    #[test]
+    fn ssa_timestamp_precision_test() {
+        use crate::timetypes::TimePoint;
+        use crate::SubtitleFileInterface;
+
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Text\n\
+                    Dialogue: 0,0:00:01.5,0:00:02.345,Default,Hello\n\
+                    Dialogue: 0,0:00:03.25,0:00:04.1239,Default,World\n";
+        let mut file = SsaFile::parse(data).unwrap();
+        let times: Vec<_> = file
+            .get_subtitle_entries()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.timespan.start, entry.timespan.end))
+            .collect();
+        let ms = TimePoint::from_msecs;
+        assert_eq!(times, vec![(ms(1500), ms(2345)), (ms(3250), ms(4123))]);
+
+        assert_eq!(file.timestamp_precision(), SsaTimestampPrecision::Milliseconds);
+        let written = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(written.contains("0,0:00:01.500,0:00:02.345,Default,Hello\n"));
+        assert!(written.contains("0,0:00:03.250,0:00:04.123,Default,World\n"));
+
+        file.set_timestamp_precision(SsaTimestampPrecision::Centiseconds);
+        let written = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(written.contains("0,0:00:01.50,0:00:02.34,Default,Hello\n"));
+
+        let centiseconds = SsaFile::parse("[Events]\nFormat: Start, End, Text\nDialogue: 0:00:01.50,0:00:02.34,Hello\n").unwrap();
+        assert_eq!(centiseconds.timestamp_precision(), SsaTimestampPrecision::Centiseconds);
+    }
+
+    #[test]
+    fn ssa_tags_test() {
+        use crate::{SerializeOptions, SubtitleFileInterface};
+
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Effect, Text\n\
+                    Dialogue: 0,0:00:01.00,0:00:02.00,Default,,Hello\n\
+                    Dialogue: 0,0:00:03.00,0:00:04.00,Default,Banner;20,World\n";
+        let mut file = SsaFile::parse(data).unwrap();
+        let mut entries = file.get_subtitle_entries().unwrap();
+        assert!(entries[0].tags.is_empty());
+        entries[0].tags = vec!["sign".to_string(), "needs, review".to_string()];
+        entries[1].tags = vec!["song".to_string()];
+        file.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(file.to_data().unwrap(), data.as_bytes());
+
+        // other effects are not replaced by tags
+        let data_string = String::from_utf8(file.to_data_with(&SerializeOptions::new().write_tags(true)).unwrap()).unwrap();
+        assert!(data_string.contains("Default,tags:sign;needs  review,Hello\n"));
+        assert!(data_string.contains("Default,Banner;20,World\n"));
+
+        let parsed = SsaFile::parse(&data_string).unwrap();
+        assert_eq!(parsed.get_subtitle_entries().unwrap()[0].tags, vec!["sign", "needs  review"]);
+        assert!(parsed.get_subtitle_entries().unwrap()[1].tags.is_empty());
+    }
+
+    #[test]
+    fn ssa_effect_test() {
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Effect, Text\n\
+                    Dialogue: 0,0:00:01.00,0:00:02.00,Default,Scroll up;100;400;20,Credits\n\
+                    Dialogue: 0,0:00:03.00,0:00:04.00,Default,Banner;5,News\n\
+                    Dialogue: 0,0:00:05.00,0:00:06.00,Default,,Plain\n";
+        let mut file = SsaFile::parse(data).unwrap();
+        assert_eq!(
+            file.effect(0),
+            Some(SsaEffect::Scroll {
+                direction: SsaScrollDirection::Up,
+                y1: 100,
+                y2: 400,
+                delay: 20,
+                fadeaway_height: 0,
+            })
+        );
+        assert_eq!(
+            file.effect(1),
+            Some(SsaEffect::Banner {
+                delay: 5,
+                left_to_right: false,
+                fadeaway_width: 0,
+            })
+        );
+        assert_eq!(file.effect(2), None);
+        assert_eq!(file.effect(3), None);
+        assert_eq!(SsaEffect::parse("Karaoke"), Some(SsaEffect::Other("Karaoke".to_string())));
+
+        let banner = SsaEffect::Banner {
+            delay: 10,
+            left_to_right: true,
+            fadeaway_width: 30,
+        };
+        assert!(file.set_effect(2, Some(&banner)));
+        assert!(file.set_effect(1, None));
+        assert!(!file.set_effect(3, None));
+        let data_string = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(data_string.contains("Default,,News\n"));
+        assert!(data_string.contains("Default,Banner;10;1;30,Plain\n"));
+        assert_eq!(SsaFile::parse(&data_string).unwrap().effect(2), Some(banner));
+    }
+
+    #[test]
+    fn ssa_create_dual_test() {
+        use crate::timetypes::TimeSpan;
+
+        let entry =
+            |start, end, text: &str| SubtitleEntry::new(TimeSpan::new(TimePoint::from_secs(start), TimePoint::from_secs(end)), text.to_string());
+        let primary = vec![entry(1, 3, "Hello"), entry(2, 4, "World")];
+        let secondary = vec![entry(1, 4, "Hallo\nWelt")];
+
+        let file = SsaFile::create_dual(&primary, &secondary).unwrap();
+        let entries: Vec<_> = file
+            .get_subtitle_entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.timespan.start.secs(), entry.timespan.end.secs(), entry.line.unwrap()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (1, 2, "Hello".to_string()),
+                (2, 3, "Hello\\NWorld".to_string()),
+                (3, 4, "World".to_string()),
+                (1, 4, "Hallo\\NWelt".to_string()),
+            ]
+        );
+
+        let data_string = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(data_string.ends_with("Dialogue: 0,0:00:01.00,0:00:04.00,Top,,0,0,0,,Hallo\\NWelt\n"));
+
+        let template = FileTemplate::new()
+            .title("Episode {n}")
+            .comment("Created by {tool}")
+            .value("n", "1")
+            .value("tool", "subsync");
+        let file = SsaFile::create_dual_with_template(&primary, &[], &template).unwrap();
+        let data_string = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(data_string.starts_with("[Script Info]\n; Created by subsync\nTitle: Episode 1\nScriptType: v4.00+\n"));
+    }
+
+    #[test]
+    fn ass_profile_test() {
+        use super::AssProfile;
+        use crate::{SerializeOptions, SubtitleFileInterface};
+
+        let to_string = |data: &str, profile: AssProfile| {
+            let file = SsaFile::parse(data).unwrap();
+            String::from_utf8(file.to_data_with(&SerializeOptions::new().ass_profile(profile)).unwrap()).unwrap()
+        };
+
+        let data = "[Script Info]\nTitle: A\nPlayResX: 1280\n\n[V4+ Styles]\n\n[Events]\nFormat: Start, End, Text\n";
+        assert_eq!(
+            to_string(data, AssProfile::Libass),
+            "[Script Info]\nTitle: A\nPlayResX: 1280\nScriptType: v4.00+\nPlayResY: 1024\n\n\
+             [V4+ Styles]\n\n[Events]\nFormat: Start, End, Text\n"
+        );
+        assert_eq!(
+            to_string(
+                "[Script Info]\nScriptType: v4.00\nPlayResY: 480\n[Events]\nFormat: Start, End, Text\n",
+                AssProfile::XySubFilter
+            ),
+            "[Script Info]\nScriptType: v4.00\nPlayResY: 480\nPlayResX: 640\nLayoutResX: 640\nLayoutResY: 480\n\
+             [Events]\nFormat: Start, End, Text\n"
+        );
+        assert_eq!(
+            to_string("[Events]\nFormat: Start, End, Text\n", AssProfile::Libass),
+            "[Script Info]\nScriptType: v4.00\nPlayResX: 384\nPlayResY: 288\n\n[Events]\nFormat: Start, End, Text\n"
+        );
+
+        let complete = "[Script Info]\nScriptType: v4.00+\nPlayResX: 640\nPlayResY: 480\n\n[Events]\nFormat: Start, End, Text\n";
+        assert_eq!(to_string(complete, AssProfile::Libass), complete);
+
+        let huge = "[Script Info]\nPlayResX: 4000000000\n\n[Events]\nFormat: Start, End, Text\n";
+        assert!(to_string(huge, AssProfile::Libass).contains("\nPlayResY: 3000000000\n"));
+        let huge = "[Script Info]\nPlayResY: 4000000000\n\n[Events]\nFormat: Start, End, Text\n";
+        assert!(to_string(huge, AssProfile::Libass).contains("\nPlayResX: 5333333333\n"));
+    }
+
+    #[test]
+    fn ssa_file_builder_test() {
+        use super::{SsaEffect, SsaEvent, SsaFileBuilder, SsaStyle};
+        use crate::timetypes::{TimePoint, TimeSpan};
+        use crate::{SubtitleAlignment, SubtitleFileInterface};
+
+        let timespan = TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2500));
+        let top = SsaStyle {
+            italic: true,
+            alignment: SubtitleAlignment::TopCenter,
+            ..SsaStyle::new("Top, italic")
+        };
+        let file = SsaFileBuilder::new()
+            .script_info("Title", "Episode\n1")
+            .script_info("PlayResX", "1280")
+            .style(SsaStyle::new("Default"))
+            .style(top)
+            .event(SsaEvent::new(timespan, "Hello\nworld"))
+            .event(SsaEvent {
+                comment: true,
+                ..SsaEvent::new(timespan, "Note")
+            })
+            .event(SsaEvent {
+                style: "Top, italic".to_string(),
+                name: "Bob".to_string(),
+                effect: Some(SsaEffect::Other("Karaoke".to_string())),
+                ..SsaEvent::new(timespan, "Sign")
+            })
+            .build()
+            .unwrap();
+
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timespan, timespan);
+        assert_eq!(entries[0].line.as_deref(), Some("Hello\\Nworld"));
+        assert_eq!(entries[1].speaker.as_deref(), Some("Bob"));
+        assert_eq!(file.effect(1), Some(SsaEffect::Other("Karaoke".to_string())));
+
+        let data = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(data.starts_with("[Script Info]\nScriptType: v4.00+\nWrapStyle: 0\nScaledBorderAndShadow: yes\nTitle: Episode 1\nPlayResX: 1280\n"));
+        assert!(data.contains("\nStyle: Top  italic,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,-1,0,0,100,100,0,0,1,2,0,8,10,10,10,1\n"));
+        assert!(data.contains("\nComment: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,Note\n"));
+        assert!(data.ends_with("\nDialogue: 0,0:00:01.00,0:00:02.50,Top  italic,Bob,0,0,0,Karaoke,Sign\n"));
+    }
+
+    #[test]
+    fn ssa_styles_test() {
+        use super::{SsaFileBuilder, SsaStyle};
+        use crate::SubtitleAlignment;
+
+        let data = "[Script Info]\nScriptType: v4.00\n\n[V4 Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, TertiaryColour, BackColour, Bold, Italic, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, AlphaLevel, Encoding\nStyle: Default,Tahoma, 24,16777215,65535,65535,-2147483640,-1,0,1,1,2,6,30,30,10,0,0\n\n[Events]\nFormat: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: Marked=0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n";
+        let mut file = SsaFile::parse(data).unwrap();
+        let styles = file.styles();
+        assert_eq!(styles.len(), 1);
+        assert_eq!(styles[0].name, "Default");
+        assert_eq!(styles[0].font_name, "Tahoma");
+        assert_eq!(styles[0].font_size, 24.0);
+        assert_eq!(styles[0].primary_color, 0x00FF_FFFF);
+        assert_eq!(styles[0].back_color, 0x8000_0008);
+        assert!(styles[0].bold);
+        assert_eq!(styles[0].alignment, SubtitleAlignment::TopCenter);
+        assert_eq!(styles[0].margin_l, 30);
+
+        // unchanged styles are not rewritten
+        drop(file.styles_mut());
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), data);
+
+        {
+            let mut styles = file.styles_mut();
+            styles[0].font_size = 30.0;
+            styles[0].alignment = SubtitleAlignment::MiddleLeft;
+        }
+        assert_eq!(file.styles()[0].font_size, 30.0);
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            data.replace("Tahoma, 24,", "Tahoma, 30,").replace(",2,6,30,", ",2,9,30,")
+        );
+
+        let mut file = SsaFileBuilder::new().style(SsaStyle::new("Default")).build().unwrap();
+        file.styles_mut()[0].primary_color = 0x0000_FFFF;
+        assert_eq!(
+            file.styles(),
+            vec![SsaStyle {
+                primary_color: 0x0000_FFFF,
+                ..SsaStyle::new("Default")
+            }]
+        );
+        assert!(String::from_utf8(file.to_data().unwrap())
+            .unwrap()
+            .contains("\nStyle: Default,Arial,20,&H0000FFFF,&H000000FF,"));
+    }
+
+    #[test]
+    fn ssa_dialogues_test() {
+        use super::{SsaDialogue, SsaEffect};
+        use crate::timetypes::{TimePoint, TimeSpan};
+
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 1,0:00:01.00,0:00:02.00,Sign, Anna ,0,0,20,Scroll up;10;100,Hello\nComment: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,Note\nDialogue: 0,0:00:03.00,0:00:04.00,Default,Bob,0010,0,0,,World\n";
+        let mut file = SsaFile::parse(data).unwrap();
+        let dialogues = file.dialogues();
+        assert_eq!(dialogues.len(), 2);
+        assert_eq!(
+            dialogues[0],
+            SsaDialogue {
+                timespan: TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2000)),
+                layer: Some(1),
+                style: Some("Sign".to_string()),
+                name: Some("Anna".to_string()),
+                margin_l: Some(0),
+                margin_r: Some(0),
+                margin_v: Some(20),
+                effect: SsaEffect::parse("Scroll up;10;100"),
+                text: "Hello".to_string(),
+            }
+        );
+        assert_eq!(dialogues[1].margin_l, Some(10));
+
+        // unchanged fields keep their formatting
+        {
+            let mut dialogues = file.dialogues_mut();
+            dialogues[1].style = Some("Top, italic".to_string());
+            dialogues[1].layer = Some(2);
+            dialogues[1].text = "Line\nbreak".to_string();
+        }
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            data.replace("0,0:00:03.00,0:00:04.00,Default,", "2,0:00:03.00,0:00:04.00,Top  italic,")
+                .replace("World", "Line\\Nbreak")
+        );
+
+        let v4 = "[Events]\nFormat: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: Marked=0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n";
+        let mut file = SsaFile::parse(v4).unwrap();
+        assert_eq!(file.dialogues()[0].layer, None);
+        file.dialogues_mut()[0].layer = Some(3);
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), v4);
+    }
+
+    #[test]
+    fn ssa_shift_dialogues_test() {
+        use crate::timetypes::TimeDelta;
+
+        let data = "[Events]\nFormat: Layer, Start, End, Style, Name, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Signs,,EXIT\nDialogue: 0,0:00:01.00,0:00:02.00,Default,Anna,Hello\n";
+        let mut file = SsaFile::parse(data).unwrap();
+        let shifted = file.shift_dialogues(TimeDelta::from_msecs(500), |dialogue| dialogue.style.as_deref() == Some("Signs"));
+        assert_eq!(shifted, 1);
+        assert_eq!(
+            String::from_utf8(file.to_data().unwrap()).unwrap(),
+            data.replacen("0:00:01.00,0:00:02.00", "0:00:01.50,0:00:02.50", 1)
+        );
+
+        let mut file = crate::SubtitleFile::from(file);
+        let shifted = file
+            .shift_matching(TimeDelta::from_msecs(-1000), |entry| entry.speaker.as_deref() == Some("Anna"))
+            .unwrap();
+        assert_eq!(shifted, 1);
+        let starts: Vec<i64> = file.get_subtitle_entries().unwrap().iter().map(|e| e.timespan.start.msecs()).collect();
+        assert_eq!(starts, vec![1500, 0]);
+    }
+
+    #[test]
+    fn ssa_attachments_test() {
+        use super::{SsaAttachment, SsaAttachmentKind};
+
+        let data = "[Script Info]\r\nTitle: Test\r\n\r\n[Fonts]\r\nfontname: a_0.ttf\r\n15*$\r\n\r\n[Events]\r\nFormat: Start, End, Text\r\nDialogue: 0:00:01.00,0:00:02.00,Hello";
+        let mut file = SsaFile::parse(data).unwrap();
+        assert_eq!(file.to_data().unwrap(), data.as_bytes());
+        assert_eq!(
+            file.attachments(),
+            vec![SsaAttachment {
+                kind: SsaAttachmentKind::Font,
+                name: "a_0.ttf".to_string(),
+                data: b"ABC".to_vec(),
+            }]
+        );
+
+        let font = SsaAttachment {
+            kind: SsaAttachmentKind::Font,
+            name: "b_0.ttf".to_string(),
+            data: (0..=255).collect(),
+        };
+        let image = SsaAttachment {
+            kind: SsaAttachmentKind::Graphic,
+            name: "logo.png".to_string(),
+            data: b"PNG!!".to_vec(),
+        };
+        file.add_attachment(&font);
+        file.add_attachment(&image);
+        let written = String::from_utf8(file.to_data().unwrap()).unwrap();
+        assert!(written.contains("15*$\r\nfontname: b_0.ttf\r\n"));
+        assert!(written.contains("Hello\r\n\r\n[Graphics]\r\nfilename: logo.png\r\n"));
+        let mut file = SsaFile::parse(&written).unwrap();
+        assert_eq!(file.attachments()[1..], [font, image.clone()]);
+        assert_eq!(file.get_subtitle_entries().unwrap().len(), 1);
+
+        assert!(file.remove_attachment(SsaAttachmentKind::Font, "a_0.ttf"));
+        assert!(!file.remove_attachment(SsaAttachmentKind::Graphic, "a_0.ttf"));
+        assert_eq!(file.attachments().len(), 2);
+        assert_eq!(file.attachments()[1], image);
     }
 }
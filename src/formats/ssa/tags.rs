@@ -0,0 +1,356 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The override blocks of `.ssa`/`.ass` dialog texts, like `{\fad(150,150)\blur0.5}`.
+//!
+//! `parse_text()` splits a text into plain text and override blocks with typed tags, and `write_text()` creates the
+//! text again. Tags which are not typed (like `\blur0.5` or `\t(...)`) are kept as `SsaTag::Other`, so
+//! `write_text(&parse_text(text))` gives the same text, except that typed tags are written in their canonical form
+//! (`\pos(100.0,200)` becomes `\pos(100,200)` and `\1c` becomes `\c`).
+
+use super::SsaKaraokeEffect;
+use crate::SubtitleAlignment;
+use std::fmt;
+
+/// A part of a dialog text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsaTextPart {
+    /// Text outside of override blocks (or drawing commands after `\p1`), with escapes like `\N`.
+    Text(String),
+
+    /// The tags of an override block `{...}`.
+    Override(Vec<SsaTag>),
+}
+
+/// A tag of an override block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SsaTag {
+    /// `\pos(x,y)`: the position of the aligned point.
+    Position {
+        /// horizontal position in pixels of the script resolution
+        x: f64,
+        /// vertical position in pixels of the script resolution
+        y: f64,
+    },
+
+    /// `\move(x1,y1,x2,y2)` or `\move(x1,y1,x2,y2,t1,t2)`: a movement of the aligned point.
+    Move {
+        /// the start position
+        from: (f64, f64),
+        /// the end position
+        to: (f64, f64),
+        /// the start and end of the movement in milliseconds after the start of the line (the whole line if `None`)
+        times: Option<(i64, i64)>,
+    },
+
+    /// `\fad(fade_in,fade_out)`: fading in and out (in milliseconds).
+    Fade {
+        /// the duration of the fade in
+        fade_in: i64,
+        /// the duration of the fade out
+        fade_out: i64,
+    },
+
+    /// `\fade(a1,a2,a3,t1,t2,t3,t4)`: the alpha changes from `a1` to `a2` between `t1` and `t2` and to `a3` between `t3`
+    /// and `t4` (in milliseconds after the start of the line).
+    ComplexFade {
+        /// the alpha values (`0` is opaque)
+        alphas: (u8, u8, u8),
+        /// the times of the changes
+        times: (i64, i64, i64, i64),
+    },
+
+    /// `\an1` to `\an9`: the alignment of the line.
+    Alignment(SubtitleAlignment),
+
+    /// `\c&HBBGGRR&` or `\1c` to `\4c`: a color (`1` primary, `2` secondary, `3` outline, `4` shadow).
+    Color {
+        /// `1` to `4`
+        index: u8,
+        /// the color as `0xBBGGRR`
+        color: u32,
+    },
+
+    /// `\alpha&HAA&` (all colors, `index` is `None`) or `\1a` to `\4a`: the alpha of colors (`0` is opaque).
+    Alpha {
+        /// `1` to `4` (see `Color`)
+        index: Option<u8>,
+        /// the alpha value
+        alpha: u8,
+    },
+
+    /// `\k`, `\kf` or `\ko`: a karaoke syllable (see `create_karaoke_text()`).
+    Karaoke {
+        /// the tag
+        effect: SsaKaraokeEffect,
+        /// the duration of the syllable in centiseconds
+        duration: i64,
+    },
+
+    /// `\p1` (and higher scales) starts drawing mode, in which the text is made of drawing commands; `\p0` ends it.
+    Drawing(u32),
+
+    /// Text in an override block which is not a tag (like `{comment}`).
+    Comment(String),
+
+    /// Any other tag like `\i1` or `\blur0.5` (without the backslash).
+    Other(String),
+}
+
+/// Split a dialog text into plain text and override blocks. An opening `{` without a closing `}` is plain text.
+pub fn parse_text(text: &str) -> Vec<SsaTextPart> {
+    let mut result = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let block = rest.find('{').and_then(|start| Some((start, start + rest[start..].find('}')?)));
+        match block {
+            Some((start, end)) => {
+                if start > 0 {
+                    result.push(SsaTextPart::Text(rest[..start].to_string()));
+                }
+                result.push(SsaTextPart::Override(parse_block(&rest[start + 1..end])));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push(SsaTextPart::Text(rest.to_string()));
+                break;
+            }
+        }
+    }
+    result
+}
+
+/// Create a dialog text from its parts.
+pub fn write_text(parts: &[SsaTextPart]) -> String {
+    let mut result = String::new();
+    for part in parts {
+        match part {
+            SsaTextPart::Text(text) => result.push_str(text),
+            SsaTextPart::Override(tags) => {
+                result.push('{');
+                for tag in tags {
+                    result.push_str(&tag.to_string());
+                }
+                result.push('}');
+            }
+        }
+    }
+    result
+}
+
+/// Parse the content of an override block (without the braces).
+fn parse_block(block: &str) -> Vec<SsaTag> {
+    // split at the backslashes outside of parentheses (`\t(...)` contains other tags)
+    let mut result = Vec::new();
+    let mut depth = 0;
+    let mut tag_start = 0;
+    for (i, c) in block.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = (depth - 1).max(0),
+            '\\' if depth == 0 => {
+                if i > tag_start || tag_start > 0 {
+                    result.push(parse_tag(&block[tag_start..i], tag_start > 0));
+                }
+                tag_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if tag_start < block.len() || tag_start > 0 {
+        result.push(parse_tag(&block[tag_start..], tag_start > 0));
+    }
+    result
+}
+
+/// Parse a tag without its backslash (`is_tag` is `false` for the text before the first backslash).
+fn parse_tag(s: &str, is_tag: bool) -> SsaTag {
+    if !is_tag {
+        return SsaTag::Comment(s.to_string());
+    }
+    parse_typed_tag(s.trim_end()).unwrap_or_else(|| SsaTag::Other(s.to_string()))
+}
+
+fn parse_typed_tag(s: &str) -> Option<SsaTag> {
+    let args = |name: &str| -> Option<Vec<&str>> {
+        let args = s.strip_prefix(name)?.trim_start().strip_prefix('(')?.strip_suffix(')')?;
+        Some(args.split(',').map(str::trim).collect())
+    };
+    let numbers = |name: &str| -> Option<Vec<f64>> { args(name)?.into_iter().map(|arg| arg.parse().ok()).collect() };
+    let hex = |s: &str| -> Option<u32> {
+        let s = s.trim().strip_prefix("&H").or_else(|| s.trim().strip_prefix("&h"))?;
+        u32::from_str_radix(s.trim_end_matches('&'), 16).ok()
+    };
+
+    if let Some(v) = numbers("pos") {
+        if let [x, y] = v[..] {
+            return Some(SsaTag::Position { x, y });
+        }
+    }
+    if let Some(v) = numbers("move") {
+        return match v[..] {
+            [x1, y1, x2, y2] => Some(SsaTag::Move {
+                from: (x1, y1),
+                to: (x2, y2),
+                times: None,
+            }),
+            [x1, y1, x2, y2, t1, t2] => Some(SsaTag::Move {
+                from: (x1, y1),
+                to: (x2, y2),
+                times: Some((t1 as i64, t2 as i64)),
+            }),
+            _ => None,
+        };
+    }
+    if let Some(v) = numbers("fade") {
+        return match v[..] {
+            [a1, a2, a3, t1, t2, t3, t4] => Some(SsaTag::ComplexFade {
+                alphas: (a1 as u8, a2 as u8, a3 as u8),
+                times: (t1 as i64, t2 as i64, t3 as i64, t4 as i64),
+            }),
+            _ => None,
+        };
+    }
+    if let Some(v) = numbers("fad") {
+        return match v[..] {
+            [fade_in, fade_out] => Some(SsaTag::Fade {
+                fade_in: fade_in as i64,
+                fade_out: fade_out as i64,
+            }),
+            _ => None,
+        };
+    }
+    if let Some(alpha) = s.strip_prefix("alpha") {
+        return Some(SsaTag::Alpha {
+            index: None,
+            alpha: hex(alpha)? as u8,
+        });
+    }
+    if let Some(n) = s.strip_prefix("an") {
+        return Some(SsaTag::Alignment(SubtitleAlignment::from_numpad(n.parse().ok()?)?));
+    }
+
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some('c'), _) => Some(SsaTag::Color {
+            index: 1,
+            color: hex(&s[1..])?,
+        }),
+        (Some(index @ '1'..='4'), Some(kind @ ('c' | 'a'))) => {
+            let index = index as u8 - b'0';
+            let value = hex(&s[2..])?;
+            Some(match kind {
+                'c' => SsaTag::Color { index, color: value },
+                _ => SsaTag::Alpha {
+                    index: Some(index),
+                    alpha: value as u8,
+                },
+            })
+        }
+        (Some('k'), _) => {
+            let (effect, duration) = match s[1..].chars().next() {
+                Some('f') => (SsaKaraokeEffect::Fill, &s[2..]),
+                Some('o') => (SsaKaraokeEffect::Outline, &s[2..]),
+                _ => (SsaKaraokeEffect::Highlight, &s[1..]),
+            };
+            Some(SsaTag::Karaoke {
+                effect,
+                duration: duration.parse().ok()?,
+            })
+        }
+        (Some('p'), _) => Some(SsaTag::Drawing(s[1..].parse().ok()?)),
+        _ => None,
+    }
+}
+
+impl fmt::Display for SsaTag {
+    /// Write the tag with its backslash (comments are written as they are).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SsaTag::Position { x, y } => write!(f, "\\pos({},{})", x, y),
+            SsaTag::Move { from, to, times: None } => write!(f, "\\move({},{},{},{})", from.0, from.1, to.0, to.1),
+            SsaTag::Move {
+                from,
+                to,
+                times: Some((t1, t2)),
+            } => write!(f, "\\move({},{},{},{},{},{})", from.0, from.1, to.0, to.1, t1, t2),
+            SsaTag::Fade { fade_in, fade_out } => write!(f, "\\fad({},{})", fade_in, fade_out),
+            SsaTag::ComplexFade { alphas, times } => write!(
+                f,
+                "\\fade({},{},{},{},{},{},{})",
+                alphas.0, alphas.1, alphas.2, times.0, times.1, times.2, times.3
+            ),
+            SsaTag::Alignment(alignment) => write!(f, "\\an{}", alignment.to_numpad()),
+            SsaTag::Color { index: 1, color } => write!(f, "\\c&H{:06X}&", color),
+            SsaTag::Color { index, color } => write!(f, "\\{}c&H{:06X}&", index, color),
+            SsaTag::Alpha { index: None, alpha } => write!(f, "\\alpha&H{:02X}&", alpha),
+            SsaTag::Alpha { index: Some(index), alpha } => write!(f, "\\{}a&H{:02X}&", index, alpha),
+            SsaTag::Karaoke { effect, duration } => write!(f, "\\{}{}", effect.tag(), duration),
+            SsaTag::Drawing(scale) => write!(f, "\\p{}", scale),
+            SsaTag::Comment(text) => write!(f, "{}", text),
+            SsaTag::Other(tag) => write!(f, "\\{}", tag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssa_tags_test() {
+        let text = "{\\fad(150,150)\\blur0.5\\pos(320, 50)}Hello{\\1c&H00FFFF&\\3a&H80&\\kf25}world{\\p1}m 0 0 l 10 0{\\p0}{not a tag";
+        let parts = parse_text(text);
+        assert_eq!(
+            parts,
+            vec![
+                SsaTextPart::Override(vec![
+                    SsaTag::Fade { fade_in: 150, fade_out: 150 },
+                    SsaTag::Other("blur0.5".to_string()),
+                    SsaTag::Position { x: 320.0, y: 50.0 },
+                ]),
+                SsaTextPart::Text("Hello".to_string()),
+                SsaTextPart::Override(vec![
+                    SsaTag::Color { index: 1, color: 0x00FFFF },
+                    SsaTag::Alpha { index: Some(3), alpha: 0x80 },
+                    SsaTag::Karaoke {
+                        effect: SsaKaraokeEffect::Fill,
+                        duration: 25
+                    },
+                ]),
+                SsaTextPart::Text("world".to_string()),
+                SsaTextPart::Override(vec![SsaTag::Drawing(1)]),
+                SsaTextPart::Text("m 0 0 l 10 0".to_string()),
+                SsaTextPart::Override(vec![SsaTag::Drawing(0)]),
+                SsaTextPart::Text("{not a tag".to_string()),
+            ]
+        );
+        assert_eq!(
+            write_text(&parts),
+            "{\\fad(150,150)\\blur0.5\\pos(320,50)}Hello{\\c&H00FFFF&\\3a&H80&\\kf25}world{\\p1}m 0 0 l 10 0{\\p0}{not a tag"
+        );
+
+        let text = "{comment\\an8\\t(0,500,\\fs20\\c&HFF&)\\move(0,0,10,10,0,500)\\fade(255,0,255,0,100,900,1000)}{}Hi";
+        let parts = parse_text(text);
+        assert_eq!(
+            parts[0],
+            SsaTextPart::Override(vec![
+                SsaTag::Comment("comment".to_string()),
+                SsaTag::Alignment(SubtitleAlignment::TopCenter),
+                SsaTag::Other("t(0,500,\\fs20\\c&HFF&)".to_string()),
+                SsaTag::Move {
+                    from: (0.0, 0.0),
+                    to: (10.0, 10.0),
+                    times: Some((0, 500))
+                },
+                SsaTag::ComplexFade {
+                    alphas: (255, 0, 255),
+                    times: (0, 100, 900, 1000)
+                },
+            ])
+        );
+        assert_eq!(parts[1], SsaTextPart::Override(Vec::new()));
+        assert_eq!(write_text(&parts), text);
+    }
+}
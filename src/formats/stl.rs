@@ -0,0 +1,627 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use self::errors::ErrorKind::*;
+use self::errors::*;
+use crate::errors::Result as SubtitleParserResult;
+use crate::formats::common::check_entry_count;
+use crate::formats::MemoryFootprint;
+use crate::timetypes::{TimePoint, TimeSpan};
+use crate::{SubtitleEntry, SubtitleFileInterface};
+use failure::ResultExt;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+/// `.stl` (EBU-STL)-parser-specific errors
+#[allow(missing_docs, non_local_definitions)]
+pub mod errors {
+    pub type Result<T> = std::result::Result<T, Error>;
+
+    define_error!(Error, ErrorKind);
+
+    #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        #[fail(display = "the file is too short for the GSI block ({} bytes)", len)]
+        MissingGsiBlock { len: usize },
+
+        #[fail(display = "the size of the TTI blocks ({} bytes) is not a multiple of 128 bytes", len)]
+        IncompleteTtiBlock { len: usize },
+
+        #[fail(display = "unknown disk format code `{}`", code)]
+        UnknownDiskFormatCode { code: String },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::MissingGsiBlock { .. } => "stl-missing-gsi-block",
+                ErrorKind::IncompleteTtiBlock { .. } => "stl-incomplete-tti-block",
+                ErrorKind::UnknownDiskFormatCode { .. } => "stl-unknown-disk-format-code",
+            }
+        }
+    }
+}
+
+/// Size of the GSI (General Subtitle Information) block at the start of the file.
+const GSI_BLOCK_SIZE: usize = 1024;
+
+/// Size of a TTI (Text and Timing Information) block.
+const TTI_BLOCK_SIZE: usize = 128;
+
+/// Size of the text field of a TTI block.
+const TEXT_FIELD_SIZE: usize = 112;
+
+/// The extension block number of the last (or only) TTI block of a subtitle.
+const LAST_EXTENSION_BLOCK: u8 = 0xff;
+
+/// The extension block number of TTI blocks with user data instead of a subtitle.
+const USER_DATA_BLOCK: u8 = 0xfe;
+
+/// Text field codes: new line, unused space and the italics/underline switches.
+const NEWLINE: u8 = 0x8a;
+const UNUSED_SPACE: u8 = 0x8f;
+const STYLE_CODES: [(u8, &str); 4] = [(0x80, "<i>"), (0x81, "</i>"), (0x82, "<u>"), (0x83, "</u>")];
+
+/// The fields of the GSI block of a `.stl` file (see `StlFile::gsi_field()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum StlGsiField {
+    CodePageNumber,
+    DiskFormatCode,
+    DisplayStandardCode,
+    CharacterCodeTable,
+    LanguageCode,
+    OriginalProgrammeTitle,
+    OriginalEpisodeTitle,
+    TranslatedProgrammeTitle,
+    TranslatedEpisodeTitle,
+    TranslatorName,
+    TranslatorContactDetails,
+    SubtitleListReferenceCode,
+    CreationDate,
+    RevisionDate,
+    RevisionNumber,
+    TotalTtiBlocks,
+    TotalSubtitles,
+    TotalSubtitleGroups,
+    MaxCharactersPerRow,
+    MaxRows,
+    TimeCodeStatus,
+    TimeCodeStartOfProgramme,
+    TimeCodeFirstInCue,
+    TotalDisks,
+    DiskSequenceNumber,
+    CountryOfOrigin,
+    Publisher,
+    EditorName,
+    EditorContactDetails,
+    UserDefinedArea,
+}
+
+impl StlGsiField {
+    /// The bytes of the field in the GSI block.
+    fn range(self) -> Range<usize> {
+        use self::StlGsiField::*;
+        let (start, len) = match self {
+            CodePageNumber => (0, 3),
+            DiskFormatCode => (3, 8),
+            DisplayStandardCode => (11, 1),
+            CharacterCodeTable => (12, 2),
+            LanguageCode => (14, 2),
+            OriginalProgrammeTitle => (16, 32),
+            OriginalEpisodeTitle => (48, 32),
+            TranslatedProgrammeTitle => (80, 32),
+            TranslatedEpisodeTitle => (112, 32),
+            TranslatorName => (144, 32),
+            TranslatorContactDetails => (176, 32),
+            SubtitleListReferenceCode => (208, 16),
+            CreationDate => (224, 6),
+            RevisionDate => (230, 6),
+            RevisionNumber => (236, 2),
+            TotalTtiBlocks => (238, 5),
+            TotalSubtitles => (243, 5),
+            TotalSubtitleGroups => (248, 3),
+            MaxCharactersPerRow => (251, 2),
+            MaxRows => (253, 2),
+            TimeCodeStatus => (255, 1),
+            TimeCodeStartOfProgramme => (256, 8),
+            TimeCodeFirstInCue => (264, 8),
+            TotalDisks => (272, 1),
+            DiskSequenceNumber => (273, 1),
+            CountryOfOrigin => (274, 3),
+            Publisher => (277, 32),
+            EditorName => (309, 32),
+            EditorContactDetails => (341, 32),
+            UserDefinedArea => (448, 576),
+        };
+        start..start + len
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.stl` (EBU-STL) file, the binary format for broadcast subtitles.
+///
+/// The file consists of the GSI block with metadata (see `gsi_field()`) and the TTI blocks with the subtitles. A
+/// subtitle can span multiple TTI blocks (extension blocks). Comment and user data blocks are kept, but are no
+/// entries.
+///
+/// The texts are read with the Latin character code table (ISO 6937). Line breaks are returned as `\n`,
+/// italics and underline as `<i>` and `<u>` tags; other control codes (like teletext colors) are removed from the
+/// entries, but unchanged texts are written as they were.
+pub struct StlFile {
+    gsi: Vec<u8>,
+    blocks: Vec<[u8; TTI_BLOCK_SIZE]>,
+
+    /// The frames per second of the time codes (from the disk format code like `STL25.01`).
+    fps: u32,
+}
+
+impl StlFile {
+    /// Parse the content of a `.stl` file.
+    pub fn parse(b: &[u8]) -> SubtitleParserResult<StlFile> {
+        Ok(Self::parse_inner(b).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    fn parse_inner(b: &[u8]) -> Result<StlFile> {
+        if b.len() < GSI_BLOCK_SIZE {
+            return Err(MissingGsiBlock { len: b.len() }.into());
+        }
+        let (gsi, tti) = b.split_at(GSI_BLOCK_SIZE);
+        if tti.len() % TTI_BLOCK_SIZE != 0 {
+            return Err(IncompleteTtiBlock { len: tti.len() }.into());
+        }
+
+        let disk_format_code = latin1_to_string(&gsi[StlGsiField::DiskFormatCode.range()]);
+        let fps = disk_format_code
+            .strip_prefix("STL")
+            .and_then(|s| s.strip_suffix(".01"))
+            .and_then(|s| s.parse::<u32>().ok())
+            .filter(|&fps| fps > 0)
+            .ok_or(UnknownDiskFormatCode {
+                code: disk_format_code.clone(),
+            })?;
+
+        Ok(StlFile {
+            gsi: gsi.to_vec(),
+            blocks: tti
+                .chunks(TTI_BLOCK_SIZE)
+                .map(|chunk| {
+                    let mut block = [0; TTI_BLOCK_SIZE];
+                    block.copy_from_slice(chunk);
+                    block
+                })
+                .collect(),
+            fps,
+        })
+    }
+
+    /// Create a `.stl` file with 25 frames per second and Latin texts. Characters which can't be written are
+    /// replaced by `?`.
+    pub fn create(v: Vec<(TimeSpan, String)>) -> SubtitleParserResult<StlFile> {
+        let mut gsi = vec![b' '; GSI_BLOCK_SIZE];
+        let defaults = [
+            (StlGsiField::CodePageNumber, "850"),
+            (StlGsiField::DiskFormatCode, "STL25.01"),
+            (StlGsiField::DisplayStandardCode, "1"),
+            (StlGsiField::CharacterCodeTable, "00"),
+            (StlGsiField::LanguageCode, "00"),
+            (StlGsiField::RevisionNumber, "00"),
+            (StlGsiField::TotalSubtitleGroups, "001"),
+            (StlGsiField::MaxCharactersPerRow, "40"),
+            (StlGsiField::MaxRows, "23"),
+            (StlGsiField::TimeCodeStatus, "1"),
+            (StlGsiField::TimeCodeStartOfProgramme, "00000000"),
+            (StlGsiField::TimeCodeFirstInCue, "00000000"),
+            (StlGsiField::TotalDisks, "1"),
+            (StlGsiField::DiskSequenceNumber, "1"),
+        ];
+        for &(field, value) in &defaults {
+            gsi[field.range()].copy_from_slice(value.as_bytes());
+        }
+
+        let mut file = StlFile {
+            gsi,
+            blocks: Vec::new(),
+            fps: 25,
+        };
+        for (i, (timespan, text)) in v.into_iter().enumerate() {
+            let mut block = [0; TTI_BLOCK_SIZE];
+            block[1..3].copy_from_slice(&(i as u16).to_le_bytes());
+            block[3] = LAST_EXTENSION_BLOCK;
+            // vertical position (teletext row) and centered justification
+            block[13] = 20;
+            block[14] = 2;
+            file.blocks.push(block);
+            let subtitle = file.blocks.len() - 1..file.blocks.len();
+            file.set_subtitle(subtitle, timespan, Some(&text));
+        }
+        file.update_counts();
+        Ok(file)
+    }
+
+    /// The frames per second of the time codes.
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// The value of a field of the GSI block (read as ISO 8859-1 without the padding spaces).
+    pub fn gsi_field(&self, field: StlGsiField) -> String {
+        latin1_to_string(&self.gsi[field.range()]).trim_end().to_string()
+    }
+
+    /// Set the value of a field of the GSI block (padded with spaces). Returns `false` if the value is longer than
+    /// the field or can't be written as ISO 8859-1.
+    ///
+    /// Changing the `DiskFormatCode` does not convert the time codes.
+    pub fn set_gsi_field(&mut self, field: StlGsiField, value: &str) -> bool {
+        let range = field.range();
+        let bytes: Option<Vec<u8>> = value.chars().map(|c| u8::try_from(u32::from(c)).ok()).collect();
+        match bytes {
+            Some(bytes) if bytes.len() <= range.len() => {
+                let padding = range.len() - bytes.len();
+                self.gsi[range].copy_from_slice(&[bytes, vec![b' '; padding]].concat());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The TTI blocks of every subtitle (comment and user data blocks are skipped).
+    fn subtitles(&self) -> Vec<Range<usize>> {
+        let mut result: Vec<Range<usize>> = Vec::new();
+        let mut continued = false;
+        for (i, block) in self.blocks.iter().enumerate() {
+            let (subtitle_number, extension_block, comment) = (&block[1..3], block[3], block[15] != 0);
+            if extension_block == USER_DATA_BLOCK || comment {
+                continued = false;
+                continue;
+            }
+
+            match result.last_mut() {
+                Some(last) if continued && self.blocks[last.start][1..3] == *subtitle_number => last.end = i + 1,
+                _ => result.push(i..i + 1),
+            }
+            continued = extension_block != LAST_EXTENSION_BLOCK;
+        }
+        result
+    }
+
+    fn timecode_to_timepoint(&self, timecode: &[u8]) -> TimePoint {
+        let (hours, mins, secs, frames) = (timecode[0] as i64, timecode[1] as i64, timecode[2] as i64, timecode[3] as i64);
+        let frame_msecs = (frames * 1000 + self.fps as i64 / 2) / self.fps as i64;
+        TimePoint::from_components(hours, mins, secs, frame_msecs)
+    }
+
+    fn timepoint_to_timecode(&self, t: TimePoint) -> [u8; 4] {
+        let fps = self.fps as i64;
+        let frames = (t.msecs().max(0) * fps + 500) / 1000;
+        let secs = frames / fps;
+        [
+            (secs / 3600).min(255) as u8,
+            (secs / 60 % 60) as u8,
+            (secs % 60) as u8,
+            (frames % fps) as u8,
+        ]
+    }
+
+    /// Set the time codes (and text) of the subtitle in `blocks`. The subtitle gets as many extension blocks as
+    /// the text needs.
+    fn set_subtitle(&mut self, blocks: Range<usize>, timespan: TimeSpan, text: Option<&str>) {
+        let (time_code_in, time_code_out) = (self.timepoint_to_timecode(timespan.start), self.timepoint_to_timecode(timespan.end));
+
+        let subtitle_blocks = match text {
+            Some(text) => {
+                let text_fields = split_text_fields(&encode_text(text));
+                let template = self.blocks[blocks.start];
+                let new_blocks: Vec<[u8; TTI_BLOCK_SIZE]> = text_fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, text_field)| {
+                        let mut block = template;
+                        block[3] = if i + 1 == text_fields.len() { LAST_EXTENSION_BLOCK } else { i as u8 };
+                        block[16..].copy_from_slice(text_field);
+                        block
+                    })
+                    .collect();
+                self.blocks.splice(blocks.clone(), new_blocks);
+                blocks.start..blocks.start + text_fields.len()
+            }
+            None => blocks,
+        };
+        for block in &mut self.blocks[subtitle_blocks] {
+            block[5..9].copy_from_slice(&time_code_in);
+            block[9..13].copy_from_slice(&time_code_out);
+        }
+    }
+
+    /// Write the number of TTI blocks and subtitles into the GSI block.
+    fn update_counts(&mut self) {
+        let block_count = format!("{:05}", self.blocks.len().min(99999));
+        let subtitle_count = format!("{:05}", self.subtitles().len().min(99999));
+        self.set_gsi_field(StlGsiField::TotalTtiBlocks, &block_count);
+        self.set_gsi_field(StlGsiField::TotalSubtitles, &subtitle_count);
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.subtitles().len();
+        footprint.add_vec(&self.gsi);
+        footprint.add_vec(&self.blocks);
+        // the first 16 bytes of a TTI block are its number, timecodes and positions
+        footprint.text_bytes = self.blocks.len() * (TTI_BLOCK_SIZE - 16);
+        footprint.filler_bytes = self.gsi.len() + self.blocks.len() * 16;
+        footprint
+    }
+}
+
+impl SubtitleFileInterface for StlFile {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        Ok(self
+            .subtitles()
+            .into_iter()
+            .map(|blocks| {
+                let first = &self.blocks[blocks.start];
+                let timespan = TimeSpan::new(self.timecode_to_timepoint(&first[5..9]), self.timecode_to_timepoint(&first[9..13]));
+                let text_field: Vec<u8> = self.blocks[blocks].iter().flat_map(|block| block[16..].iter().cloned()).collect();
+                SubtitleEntry::new(timespan, decode_text(&text_field))
+            })
+            .collect())
+    }
+
+    fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        let subtitles = self.subtitles();
+        check_entry_count(subtitles.len(), new_subtitle_entries.len())?;
+
+        let old_entries = self.get_subtitle_entries()?;
+        // from the end, so the block indices of the previous subtitles don't change
+        for ((blocks, new_entry), old_entry) in subtitles.into_iter().zip(new_subtitle_entries).zip(old_entries).rev() {
+            // unchanged texts are not written again, so the control codes are kept
+            let text = new_entry.line.as_deref().filter(|&line| Some(line) != old_entry.line.as_deref());
+            self.set_subtitle(blocks, new_entry.timespan, text);
+        }
+        self.update_counts();
+        Ok(())
+    }
+
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        let mut result = self.gsi.clone();
+        for block in &self.blocks {
+            result.extend_from_slice(block);
+        }
+        Ok(result)
+    }
+}
+
+fn latin1_to_string(b: &[u8]) -> String {
+    b.iter().map(|&b| char::from(b)).collect()
+}
+
+/// The ISO 6937 diacritic bytes (which are written before the letter) and the combining characters.
+const DIACRITICS: [(u8, char); 13] = [
+    (0xc1, '\u{300}'),
+    (0xc2, '\u{301}'),
+    (0xc3, '\u{302}'),
+    (0xc4, '\u{303}'),
+    (0xc5, '\u{304}'),
+    (0xc6, '\u{306}'),
+    (0xc7, '\u{307}'),
+    (0xc8, '\u{308}'),
+    (0xca, '\u{30a}'),
+    (0xcb, '\u{327}'),
+    (0xcd, '\u{30b}'),
+    (0xce, '\u{328}'),
+    (0xcf, '\u{30c}'),
+];
+
+/// Letters with diacritics: the diacritic byte, the letters and the same letters with the diacritic.
+const COMPOSED_LETTERS: [(u8, &str, &str); 12] = [
+    (0xc1, "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    (0xc2, "ACEINOSUYZaceinosuyz", "ÁĆÉÍŃÓŚÚÝŹáćéíńóśúýź"),
+    (0xc3, "AEIOUaeiou", "ÂÊÎÔÛâêîôû"),
+    (0xc4, "ANOano", "ÃÑÕãñõ"),
+    (0xc6, "AGag", "ĂĞăğ"),
+    (0xc7, "Zz", "Żż"),
+    (0xc8, "AEIOUaeiouy", "ÄËÏÖÜäëïöüÿ"),
+    (0xca, "AUau", "ÅŮåů"),
+    (0xcb, "CSTcst", "ÇŞŢçşţ"),
+    (0xcd, "OUou", "ŐŰőű"),
+    (0xce, "AEae", "ĄĘąę"),
+    (0xcf, "CDENRSTZcdenrstz", "ČĎĚŇŘŠŤŽčďěňřšťž"),
+];
+
+/// Other characters of the ISO 6937 table (above `0x7f`).
+const OTHER_CHARACTERS: [(u8, char); 23] = [
+    (0xa1, '¡'),
+    (0xa2, '¢'),
+    (0xa3, '£'),
+    (0xa5, '¥'),
+    (0xa7, '§'),
+    (0xab, '«'),
+    (0xb0, '°'),
+    (0xb1, '±'),
+    (0xb2, '²'),
+    (0xb3, '³'),
+    (0xb5, 'µ'),
+    (0xb6, '¶'),
+    (0xb7, '·'),
+    (0xbb, '»'),
+    (0xbc, '¼'),
+    (0xbd, '½'),
+    (0xbe, '¾'),
+    (0xbf, '¿'),
+    (0xe1, 'Æ'),
+    (0xe9, 'Ø'),
+    (0xf1, 'æ'),
+    (0xf9, 'ø'),
+    (0xfb, 'ß'),
+];
+
+/// Decode a text field (of all blocks of a subtitle) to the text of an entry.
+fn decode_text(b: &[u8]) -> String {
+    let mut lines: Vec<String> = vec![String::new()];
+    let mut i = 0;
+    while i < b.len() {
+        let byte = b[i];
+        i += 1;
+
+        let line = lines.last_mut().expect("there is always a line");
+        if byte == NEWLINE {
+            // double height texts have two newlines between the lines
+            if !line.trim().is_empty() {
+                lines.push(String::new());
+            }
+        } else if let Some(&(_, tag)) = STYLE_CODES.iter().find(|&&(code, _)| code == byte) {
+            line.push_str(tag);
+        } else if (0x20..0x7f).contains(&byte) {
+            line.push(char::from(byte));
+        } else if let Some(&(_, combining)) = DIACRITICS.iter().find(|&&(code, _)| code == byte) {
+            let letter = b.get(i).filter(|b| (0x20..0x7f).contains(*b)).map(|&b| char::from(b));
+            if let Some(letter) = letter {
+                i += 1;
+                let composed = COMPOSED_LETTERS
+                    .iter()
+                    .filter(|&&(code, _, _)| code == byte)
+                    .find_map(|&(_, letters, composed)| letters.chars().position(|c| c == letter).and_then(|pos| composed.chars().nth(pos)));
+                match composed {
+                    Some(c) => line.push(c),
+                    None => {
+                        line.push(letter);
+                        line.push(combining);
+                    }
+                }
+            }
+        } else if let Some(&(_, c)) = OTHER_CHARACTERS.iter().find(|&&(code, _)| code == byte) {
+            line.push(c);
+        }
+        // other bytes are control codes (teletext colors, boxing, unused space, ...)
+    }
+
+    let lines: Vec<&str> = lines.iter().map(|line| line.trim()).collect();
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Encode the text of an entry for the text field (without the padding).
+fn encode_text(s: &str) -> Vec<u8> {
+    let mut result = Vec::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+
+        if c == '<' {
+            if let Some(&(code, tag)) = STYLE_CODES.iter().find(|&&(_, tag)| ("<".to_string() + rest).starts_with(tag)) {
+                result.push(code);
+                rest = &rest[tag.len() - 1..];
+                continue;
+            }
+        }
+
+        let combining = rest
+            .chars()
+            .next()
+            .and_then(|next| DIACRITICS.iter().find(|&&(_, combining)| combining == next));
+        if let (true, Some(&(code, combining))) = (c.is_ascii_alphabetic(), combining) {
+            result.extend_from_slice(&[code, c as u8]);
+            rest = &rest[combining.len_utf8()..];
+        } else if c == '\n' {
+            result.push(NEWLINE);
+        } else if c == '\r' {
+        } else if (' '..'\u{7f}').contains(&c) {
+            result.push(c as u8);
+        } else if let Some(&(code, _)) = OTHER_CHARACTERS.iter().find(|&&(_, other)| other == c) {
+            result.push(code);
+        } else if let Some((code, letter)) = COMPOSED_LETTERS.iter().find_map(|&(code, letters, composed)| {
+            composed
+                .chars()
+                .position(|composed| composed == c)
+                .and_then(|pos| letters.chars().nth(pos))
+                .map(|letter| (code, letter))
+        }) {
+            result.extend_from_slice(&[code, letter as u8]);
+        } else {
+            result.push(b'?');
+        }
+    }
+    result
+}
+
+/// Split an encoded text into padded text fields (at least one). A diacritic is kept in the same field as its letter.
+fn split_text_fields(b: &[u8]) -> Vec<[u8; TEXT_FIELD_SIZE]> {
+    let mut result = Vec::new();
+    let mut rest = b;
+    loop {
+        let mut len = rest.len().min(TEXT_FIELD_SIZE);
+        if len < rest.len() && DIACRITICS.iter().any(|&(code, _)| code == rest[len - 1]) {
+            len -= 1;
+        }
+
+        let mut text_field = [UNUSED_SPACE; TEXT_FIELD_SIZE];
+        text_field[..len].copy_from_slice(&rest[..len]);
+        result.push(text_field);
+
+        rest = &rest[len..];
+        if rest.is_empty() {
+            return result;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stl_test() {
+        let long_text = "A".repeat(150);
+        let entries = vec![
+            (
+                TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2500)),
+                "<i>Hallo</i>\nWelt für €".to_string(),
+            ),
+            (TimeSpan::new(TimePoint::from_msecs(3000), TimePoint::from_msecs(4000)), long_text.clone()),
+        ];
+        let file = StlFile::create(entries).unwrap();
+        assert_eq!(file.gsi_field(StlGsiField::TotalTtiBlocks), "00003");
+        assert_eq!(file.gsi_field(StlGsiField::TotalSubtitles), "00002");
+
+        let data = file.to_data().unwrap();
+        assert_eq!(data.len(), GSI_BLOCK_SIZE + 3 * TTI_BLOCK_SIZE);
+        assert_eq!(&data[GSI_BLOCK_SIZE + 16..GSI_BLOCK_SIZE + 32], b"\x80Hallo\x81\x8aWelt f\xc8u");
+
+        let mut parsed = StlFile::parse(&data).unwrap();
+        assert_eq!(parsed, file);
+        let mut entries = parsed.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].timespan.end, TimePoint::from_msecs(2520));
+        assert_eq!(entries[0].line.as_deref(), Some("<i>Hallo</i>\nWelt für ?"));
+        assert_eq!(entries[1].line.as_deref(), Some(long_text.as_str()));
+
+        entries[1].line = Some("Kurz".to_string());
+        entries[1].timespan.start = TimePoint::from_msecs(2800);
+        parsed.update_subtitle_entries(&entries).unwrap();
+        assert_eq!(
+            parsed.get_subtitle_entries().unwrap()[1],
+            SubtitleEntry::new(entries[1].timespan, "Kurz".to_string())
+        );
+        assert_eq!(parsed.gsi_field(StlGsiField::TotalTtiBlocks), "00002");
+
+        assert!(parsed.set_gsi_field(StlGsiField::OriginalProgrammeTitle, "Nachrichten"));
+        assert!(!parsed.set_gsi_field(StlGsiField::LanguageCode, "German"));
+        assert_eq!(parsed.gsi_field(StlGsiField::OriginalProgrammeTitle), "Nachrichten");
+        assert_eq!(parsed.fps(), 25);
+
+        assert!(StlFile::parse(&data[..GSI_BLOCK_SIZE + 100]).is_err());
+    }
+
+    #[test]
+    fn stl_decode_text_test() {
+        // teletext color and double height codes, combining diacritic without composed letter
+        assert_eq!(
+            decode_text(b"\x0d\x07 Line 1 \x8a\x8a\x0d\x07 Line 2 \xc1y\x8f\x8f"),
+            "Line 1\nLine 2 y\u{300}"
+        );
+        assert_eq!(encode_text("Line 2 y\u{300}"), b"Line 2 \xc1y".to_vec());
+    }
+}
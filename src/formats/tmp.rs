@@ -0,0 +1,266 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use self::errors::ErrorKind::*;
+use self::errors::*;
+use crate::{SubtitleEntry, SubtitleFileInterface};
+
+use crate::errors::Result as SubtitleParserResult;
+use crate::escaping::{mdvd_escape, MDVD_LINE_BREAK};
+use crate::formats::common::*;
+use crate::formats::MemoryFootprint;
+use combine::char::char;
+use combine::combinator::{many, optional, parser as p, satisfy};
+use combine::primitives::Parser;
+
+use failure::ResultExt;
+
+use crate::timetypes::TimePoint;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Errors specific to `.txt` (TMPlayer) parsing.
+#[allow(missing_docs, non_local_definitions)]
+pub mod errors {
+
+    define_error!(Error, ErrorKind);
+
+    #[derive(PartialEq, Debug, Fail)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        #[fail(display = "expected TMPlayer line like `00:01:23:Text`, found '{}'", line)]
+        ExpectedSubtitleLine { line: String },
+
+        #[fail(display = "parse error at line `{}`", line_num)]
+        ErrorAtLine { line_num: usize },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::ExpectedSubtitleLine { .. } => "tmp-expected-subtitle-line",
+                ErrorKind::ErrorAtLine { .. } => "tmp-error-at-line",
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// Represents a `.txt` file in the TMPlayer format.
+///
+/// Every line is an entry like `00:01:23:Line|Line2` (the separator after the seconds can also be `=`). Like in
+/// `.idx` files only the starts are stored (in seconds), so every subtitle is shown until the next one starts.
+pub struct TmpFile {
+    v: Vec<TmpLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// One entry of a TMPlayer file.
+struct TmpLine {
+    /// start time in seconds
+    start: i64,
+
+    /// the dialog/text lines of the entry
+    texts: Vec<String>,
+}
+
+fn to_secs(t: TimePoint) -> i64 {
+    (t.msecs() as f64 / 1000.0).round() as i64
+}
+
+impl TmpFile {
+    /// Parse a TMPlayer subtitle string to `TmpFile`.
+    pub fn parse(s: &str) -> SubtitleParserResult<TmpFile> {
+        Ok(Self::parse_file(s).with_context(|_| crate::ErrorKind::ParsingError)?)
+    }
+
+    /// Creates a TMPlayer file from scratch (the starts are rounded to seconds).
+    pub fn create(v: Vec<(TimePoint, String)>) -> SubtitleParserResult<TmpFile> {
+        Ok(TmpFile {
+            v: v.into_iter()
+                .map(|(start, text)| TmpLine {
+                    start: to_secs(start),
+                    texts: text.lines().map(mdvd_escape).collect(),
+                })
+                .collect(),
+        })
+    }
+
+    fn parse_file(i: &str) -> Result<TmpFile> {
+        let (_, s) = split_bom(i);
+
+        let v = s
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line_num, line)| Self::parse_line(line_num, line))
+            .collect::<Result<Vec<TmpLine>>>()?;
+
+        Ok(TmpFile { v })
+    }
+
+    /// Matches a line like "00:01:23:Line|Line2".
+    fn parse_line(line_num: usize, line: &str) -> Result<TmpLine> {
+        let (negative, hours, _, mins, _, secs, _, text) = (
+            optional(char('-')),
+            p(number_i64),
+            char(':'),
+            p(number_i64),
+            char(':'),
+            p(number_i64),
+            char(':').or(char('=')),
+            many(satisfy(|_| true)),
+        )
+            .map(|t: (_, i64, _, i64, _, i64, _, String)| t)
+            .parse(line.trim())
+            .map(|x| x.0)
+            .map_err(|_| Error::from(ExpectedSubtitleLine { line: line.to_string() }))
+            .with_context(|_| ErrorAtLine { line_num })?;
+
+        let start = hours * 3600 + mins * 60 + secs;
+        Ok(TmpLine {
+            start: if negative.is_some() { -start } else { start },
+            texts: text.split(MDVD_LINE_BREAK).map(|s| s.trim().to_string()).collect(),
+        })
+    }
+
+    /// Whether the line starts like a TMPlayer entry (for detecting the format of `.txt` files).
+    pub(crate) fn is_tmp_line(line: &str) -> bool {
+        Self::parse_line(0, line).is_ok()
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.v.len();
+        footprint.add_vec(&self.v);
+        for line in &self.v {
+            footprint.add_vec(&line.texts);
+            line.texts.iter().for_each(|text| footprint.add_text(text));
+        }
+        footprint
+    }
+}
+
+impl SubtitleFileInterface for TmpFile {
+    fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        let starts: Vec<TimePoint> = self.v.iter().map(|line| TimePoint::from_secs(line.start)).collect();
+        Ok(timespans_until_next(&starts)
+            .into_iter()
+            .zip(&self.v)
+            .map(|(timespan, line)| SubtitleEntry::new(timespan, line.texts.join("\n")))
+            .collect())
+    }
+
+    /// Only the starts of the timespans are stored, the ends are discarded.
+    fn update_subtitle_entries(&mut self, new_subtitle_entries: &[SubtitleEntry]) -> SubtitleParserResult<()> {
+        check_entry_count(self.v.len(), new_subtitle_entries.len())?;
+
+        for (line_ref, new_entry_ref) in self.v.iter_mut().zip(new_subtitle_entries) {
+            line_ref.start = to_secs(new_entry_ref.timespan.start);
+            if let Some(ref text) = new_entry_ref.line {
+                line_ref.texts = text.lines().map(mdvd_escape).collect();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
+        let line_to_str = |line: &TmpLine| -> String {
+            let secs = line.start.abs();
+            format!(
+                "{}{:02}:{:02}:{:02}:{}\n",
+                if line.start < 0 { "-" } else { "" },
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60,
+                line.texts.join(MDVD_LINE_BREAK)
+            )
+        };
+
+        Ok(self.v.iter().map(line_to_str).collect::<String>().into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimeSpan;
+
+    #[test]
+    fn tmp_parse_test() {
+        let data = "\u{feff}00:01:23:Hello|World\r\n\r\n0:01:30: Bye \n-00:00:05:Negative\n";
+        let entries = TmpFile::parse(data).unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].timespan.start, TimePoint::from_secs(83));
+        assert_eq!(entries[0].line.as_deref(), Some("Hello\nWorld"));
+        assert_eq!(entries[1].timespan.start, TimePoint::from_secs(90));
+        assert_eq!(entries[1].line.as_deref(), Some("Bye"));
+        assert_eq!(entries[2].timespan.start, TimePoint::from_secs(-5));
+    }
+
+    #[test]
+    fn tmp_equals_separator_test() {
+        let file = TmpFile::parse("00:01:23=Hello\n00:01:30:Bye=Bye\n").unwrap();
+        let entries = file.get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].timespan.start, TimePoint::from_secs(83));
+        assert_eq!(entries[0].line.as_deref(), Some("Hello"));
+        // only the separator after the seconds can be `=`, later ones are part of the text
+        assert_eq!(entries[1].line.as_deref(), Some("Bye=Bye"));
+
+        // the file is written with the `:` separator
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "00:01:23:Hello\n00:01:30:Bye=Bye\n");
+
+        assert!(TmpFile::parse("00=01=23=Hello\n").is_err());
+    }
+
+    #[test]
+    fn tmp_until_next_test() {
+        let entries = TmpFile::parse("00:01:23:A\n00:01:30:B\n").unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries[0].timespan, TimeSpan::new(TimePoint::from_secs(83), TimePoint::from_secs(90)));
+        // there is no next entry, so the last one is shown for one minute
+        assert_eq!(entries[1].timespan, TimeSpan::new(TimePoint::from_secs(90), TimePoint::from_secs(150)));
+
+        let entries = TmpFile::parse("00:00:10:Only\n").unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timespan, TimeSpan::new(TimePoint::from_secs(10), TimePoint::from_secs(70)));
+    }
+
+    #[test]
+    fn tmp_serialize_test() {
+        let created = TmpFile::create(vec![
+            (TimePoint::from_msecs(3_600_499), "A\nB".to_string()),
+            (TimePoint::from_msecs(-1500), "C|D".to_string()),
+        ])
+        .unwrap();
+        let created_data = String::from_utf8(created.to_data().unwrap()).unwrap();
+        assert_eq!(created_data, "01:00:00:A|B\n-00:00:02:C¦D\n");
+        assert_eq!(TmpFile::parse(&created_data).unwrap(), created);
+
+        // the ends are discarded when the entries are changed
+        let mut file = TmpFile::parse("00:00:01:Hello\n").unwrap();
+        file.update_subtitle_entries(&[SubtitleEntry::new(
+            TimeSpan::new(TimePoint::from_msecs(2500), TimePoint::from_secs(100)),
+            "A|1\nB".to_string(),
+        )])
+        .unwrap();
+        assert_eq!(String::from_utf8(file.to_data().unwrap()).unwrap(), "00:00:03:A¦1|B\n");
+    }
+
+    #[test]
+    fn tmp_invalid_line_test() {
+        for line in &["Hello", "00:01:Hello", "00:01:23 Hello", "[1][2]Hello"] {
+            let err = TmpFile::parse(&format!("00:00:01:A\n\n{}\n", line)).unwrap_err();
+            assert_eq!(
+                err.message_ids(),
+                ["error-parsing", "tmp-error-at-line", "tmp-expected-subtitle-line"],
+                "{}",
+                line
+            );
+            assert_eq!(err.line_num(), Some(2), "{}", line);
+        }
+    }
+}
@@ -4,46 +4,143 @@
 
 use self::errors::*;
 use crate::errors::Result as SubtitleParserResult;
-use crate::timetypes::{TimePoint, TimeSpan};
-use crate::{SubtitleEntry, SubtitleFileInterface, SubtitleFormat};
+use crate::formats::idx::{IdxFile, IdxTrackInfo};
+use crate::formats::images;
+use crate::formats::MemoryFootprint;
+use crate::timetypes::{TimeConversionError, TimeDelta, TimePoint, TimeSpan};
+use crate::warnings::{ParseWarning, ParseWarningKind};
+use crate::{
+    SerializeOptions, SubtitleAlignment, SubtitleCoordinates, SubtitleEntry, SubtitleFileInterface, SubtitleFormat, SubtitleImage, SubtitlePosition,
+};
 use failure::ResultExt;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
 use vobsub;
 
 /// `.sub` `VobSub`-parser-specific errors
-#[allow(missing_docs)]
+#[allow(missing_docs, non_local_definitions)]
 pub mod errors {
+    use crate::timetypes::TimeConversionError;
     use vobsub;
 
     define_error!(Error, ErrorKind);
 
     #[derive(Debug, Fail)]
+    #[non_exhaustive]
     pub enum ErrorKind {
         // TODO: Vobsub-ErrorKind display
         /// Since `vobsub::Error` does not implement Sync. We cannot use #[cause] for it.
         VobSubError { cause: vobsub::ErrorKind },
+
+        /// The palette could not be read from a DVD `.ifo` file.
+        IfoPaletteError { msg: &'static str },
+
+        /// A subtitle image can not be stored in a `VobSub` file.
+        InvalidImage { msg: &'static str },
+
+        /// The start of a subtitle is not a valid time.
+        InvalidTime { cause: TimeConversionError },
+
+        /// The subtitles can not be selected in the given order.
+        InvalidSubtitleOrder { msg: &'static str },
+    }
+
+    impl ErrorKind {
+        /// A stable identifier of the error (see `crate::ErrorKind::message_id()`).
+        pub fn message_id(&self) -> &'static str {
+            match self {
+                ErrorKind::VobSubError { .. } => "vobsub-error",
+                ErrorKind::IfoPaletteError { .. } => "vobsub-ifo-palette-error",
+                ErrorKind::InvalidImage { .. } => "vobsub-invalid-image",
+                ErrorKind::InvalidTime { .. } => "vobsub-invalid-time",
+                ErrorKind::InvalidSubtitleOrder { .. } => "vobsub-invalid-subtitle-order",
+            }
+        }
     }
 
     impl fmt::Display for ErrorKind {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             match self {
                 ErrorKind::VobSubError { cause } => writeln!(f, "VobSub error: {}", cause),
+                ErrorKind::IfoPaletteError { msg } => writeln!(f, "reading palette from .ifo file failed: {}", msg),
+                ErrorKind::InvalidImage { msg } => writeln!(f, "invalid VobSub image: {}", msg),
+                ErrorKind::InvalidTime { cause } => writeln!(f, "invalid VobSub time: {}", cause),
+                ErrorKind::InvalidSubtitleOrder { msg } => writeln!(f, "invalid order of VobSub subtitles: {}", msg),
             }
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// The 16 RGB colors which the subtitle images of a `VobSub` file index into.
+///
+/// This palette is usually stored in the `.idx` file or in the `.ifo` file of the DVD.
+pub type VobPalette = [[u8; 3]; 16];
+
+/// The palette which is used if no other palette was set (the default of most `VobSub` tools).
+pub const DEFAULT_VOB_PALETTE: VobPalette = [
+    [0x00, 0x00, 0x00],
+    [0xf0, 0xf0, 0xf0],
+    [0xcc, 0xcc, 0xcc],
+    [0x99, 0x99, 0x99],
+    [0x33, 0x33, 0xfa],
+    [0x11, 0x11, 0xbb],
+    [0xfa, 0x33, 0x33],
+    [0xbb, 0x11, 0x11],
+    [0x33, 0xfa, 0x33],
+    [0x11, 0xbb, 0x11],
+    [0xfa, 0xfa, 0x33],
+    [0xbb, 0xbb, 0x11],
+    [0xfa, 0x33, 0xfa],
+    [0xbb, 0x11, 0xbb],
+    [0x33, 0xfa, 0xfa],
+    [0x11, 0xbb, 0xbb],
+];
+
+/// Size of a sector in `.ifo` files and `VobSub` `.sub` files (every packet is padded to this size).
+const SECTOR_SIZE: usize = 2048;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Represents a `.sub` (`VobSub`) file.
+///
+/// The (big) file data is shared between clones, so cloning is cheap. Like all file types, `VobFile` is
+/// `Send + Sync` and can be shared between threads (for example in an `Arc`).
 pub struct VobFile {
-    /// Saves the file data.
-    data: Vec<u8>,
+    /// Saves the file data (shared between clones, because it is only replaced as a whole).
+    data: Arc<[u8]>,
 
     /// The (with vobsub) extracted subtitle lines.
     lines: Vec<VobSubSubtitle>,
+
+    /// The palette used for decoding the subtitle images.
+    palette: VobPalette,
 }
 
-#[derive(Debug, Clone)]
+/// A packet of a `.sub` (`VobSub`) file (see `VobFile::packets()`).
+///
+/// Every packet fills one sector of 2048 bytes. The image data of a subtitle (its subpicture unit) is split
+/// into the payloads of one or more packets.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VobPacket {
+    /// The offset of the packet in the `.sub` data.
+    pub offset: u64,
+
+    /// The presentation timestamp in units of 1/90000 seconds (only the first packet of a subtitle has one).
+    pub pts: Option<u64>,
+
+    /// The substream id of the subtitle stream (`0x20` for the first stream), `None` for packets which do not
+    /// contain subtitle data.
+    pub stream_id: Option<u8>,
+
+    /// The index of the subtitle (like in `get_subtitle_entries()`) whose data is in this packet.
+    pub subtitle_idx: Option<usize>,
+
+    /// The part of the subpicture unit which is stored in this packet.
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Represents a line in a `VobSub` `.sub` file.
 struct VobSubSubtitle {
     timespan: TimeSpan,
@@ -52,39 +149,449 @@ struct VobSubSubtitle {
 impl VobFile {
     /// Parse contents of a `VobSub` `.sub` file to `VobFile`.
     pub fn parse(b: &[u8]) -> SubtitleParserResult<Self> {
-        let lines = vobsub::subtitles(b)
-            .map(|sub_res| -> vobsub::Result<VobSubSubtitle> {
-                let sub = sub_res?;
+        // only extract the timestamps, discard the big image data
+        let times = vobsub::subtitles(b)
+            .map(|sub_res| sub_res.map(|sub| (sub.start_time(), sub.end_time())))
+            .collect::<vobsub::Result<Vec<(f64, f64)>>>()
+            .map_err(|e| ErrorKind::VobSubError {
+                cause: vobsub::ErrorKind::from(e),
+            })
+            .with_context(|_| crate::errors::ErrorKind::ParsingError)?;
 
-                // only extract the timestamps, discard the big image data
+        let lines = times
+            .into_iter()
+            .map(|(start_time, end_time)| -> Result<VobSubSubtitle, ErrorKind> {
+                let start = TimePoint::from_secs_f64(start_time).map_err(|cause| ErrorKind::InvalidTime { cause })?;
+
+                // `vobsub` reads the stop date as 1/100 seconds, but it is given in units of 1024/90000 seconds
+                let stop_date = secs_to_stop_date(end_time - start_time).map_err(|cause| ErrorKind::InvalidTime { cause })?;
                 Ok(VobSubSubtitle {
-                    timespan: TimeSpan {
-                        start: TimePoint::from_msecs((sub.start_time() * 1000.0) as i64),
-                        end: TimePoint::from_msecs((sub.end_time() * 1000.0) as i64),
-                    },
+                    timespan: TimeSpan::new(start, start + TimeDelta::from_msecs(stop_date_to_msecs(stop_date))),
                 })
             })
-            .collect::<vobsub::Result<Vec<VobSubSubtitle>>>()
+            .collect::<Result<Vec<VobSubSubtitle>, ErrorKind>>()
+            .with_context(|_| crate::errors::ErrorKind::ParsingError)?;
+
+        Ok(VobFile {
+            data: b.into(),
+            lines,
+            palette: DEFAULT_VOB_PALETTE,
+        })
+    }
+
+    /// Set the palette which is used to decode the subtitle images.
+    ///
+    /// Use this if there is no paired `.idx` file, for example with the palette from `read_ifo_palette()`.
+    pub fn set_palette(&mut self, palette: VobPalette) {
+        self.palette = palette;
+    }
+
+    /// The palette which is used to decode the subtitle images (`DEFAULT_VOB_PALETTE` if none was set).
+    pub fn palette(&self) -> &VobPalette {
+        &self.palette
+    }
+
+    /// Decode the subtitle images with the current palette.
+    ///
+    /// The images are in the same order as the entries from `get_subtitle_entries()`.
+    pub fn images(&self) -> SubtitleParserResult<Vec<SubtitleImage>> {
+        Ok(vobsub::subtitles(&self.data)
+            .map(|sub_res| -> vobsub::Result<SubtitleImage> { Ok(decode_image(&sub_res?, &self.palette)) })
+            .collect::<vobsub::Result<Vec<SubtitleImage>>>()
             .map_err(|e| ErrorKind::VobSubError {
                 cause: vobsub::ErrorKind::from(e),
             })
-            .with_context(|_| crate::errors::ErrorKind::ParsingError)?;
+            .with_context(|_| crate::errors::ErrorKind::ParsingError)?)
+    }
 
-        Ok(VobFile {
-            data: b.to_vec(),
-            lines: lines,
+    /// Decode the subtitle images like `images()`, but with `None` for every image which can not be decoded (and
+    /// for missing images), so one broken subtitle does not hide the others. There is one image for every entry.
+    fn decoded_images(&self) -> Vec<Option<SubtitleImage>> {
+        let mut images: Vec<Option<SubtitleImage>> = vobsub::subtitles(&self.data)
+            .take(self.lines.len())
+            .map(|sub_res| sub_res.ok().map(|sub| decode_image(&sub, &self.palette)))
+            .collect();
+        images.resize(self.lines.len(), None);
+        images
+    }
+
+    /// A `ParseWarningKind::ImageNotDecoded` warning for every entry whose image can not be decoded (these entries
+    /// have no image in `get_subtitle_entries()`). This decodes all images.
+    pub fn image_warnings(&self) -> Vec<ParseWarning> {
+        self.decoded_images()
+            .iter()
+            .enumerate()
+            .filter(|(_, image)| image.is_none())
+            .map(|(entry_idx, _)| ParseWarning {
+                line_num: 0,
+                entry_idx: Some(entry_idx),
+                kind: ParseWarningKind::ImageNotDecoded,
+            })
+            .collect()
+    }
+
+    /// The offsets of the first packet of every subtitle in the `.sub` data (these are the `filepos:` values
+    /// of the `.idx` file).
+    pub fn packet_offsets(&self) -> Vec<u64> {
+        self.data
+            .chunks(SECTOR_SIZE)
+            .enumerate()
+            .filter(|(_, sector)| is_subtitle_start_sector(sector))
+            .map(|(i, _)| (i * SECTOR_SIZE) as u64)
+            .collect()
+    }
+
+    /// The packets of the `.sub` data in the order of the file.
+    pub fn packets(&self) -> Vec<VobPacket> {
+        let subtitle_indices = self.sector_subtitle_indices();
+        self.data
+            .chunks(SECTOR_SIZE)
+            .zip(subtitle_indices)
+            .enumerate()
+            .map(|(i, (sector, subtitle_idx))| {
+                let packet = parse_packet(sector);
+                VobPacket {
+                    offset: (i * SECTOR_SIZE) as u64,
+                    pts: packet.as_ref().and_then(|packet| packet.pts),
+                    stream_id: packet.as_ref().map(|packet| packet.stream_id),
+                    subtitle_idx,
+                    payload: packet.map(|packet| packet.payload.to_vec()).unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Keep only the subtitles with the given indices (like in `get_subtitle_entries()`) in the given order, so
+    /// subtitles can be dropped or reordered. The `.sub` data is regenerated from the complete packets of the
+    /// subtitles; packets which do not belong to a subtitle are kept at the start of the data.
+    ///
+    /// An index must not appear twice. The `filepos:` values of a paired `.idx` file change, the new ones are
+    /// returned by `packet_offsets()`.
+    pub fn select_subtitles(&mut self, order: &[usize]) -> SubtitleParserResult<()> {
+        Ok(self
+            .select_subtitles_inner(order)
+            .with_context(|_| crate::errors::ErrorKind::SerializingError)?)
+    }
+
+    fn select_subtitles_inner(&mut self, order: &[usize]) -> Result<(), Error> {
+        let mut other_sectors = Vec::new();
+        let mut subtitle_sectors: Vec<Vec<&[u8]>> = Vec::new();
+        for (sector, subtitle_idx) in self.data.chunks(SECTOR_SIZE).zip(self.sector_subtitle_indices()) {
+            match subtitle_idx {
+                Some(idx) if idx == subtitle_sectors.len() => subtitle_sectors.push(vec![sector]),
+                Some(idx) => subtitle_sectors[idx].push(sector),
+                None => other_sectors.push(sector),
+            }
+        }
+
+        if subtitle_sectors.len() != self.lines.len() {
+            return Err(ErrorKind::InvalidSubtitleOrder {
+                msg: "the packets of the file do not match its subtitles",
+            }
+            .into());
+        }
+        let mut selected = vec![false; self.lines.len()];
+        for &idx in order {
+            match selected.get_mut(idx) {
+                Some(true) => {
+                    return Err(ErrorKind::InvalidSubtitleOrder {
+                        msg: "a subtitle was selected twice",
+                    }
+                    .into())
+                }
+                Some(selected) => *selected = true,
+                None => {
+                    return Err(ErrorKind::InvalidSubtitleOrder {
+                        msg: "subtitle index out of bounds",
+                    }
+                    .into())
+                }
+            }
+        }
+
+        let sectors = other_sectors
+            .into_iter()
+            .chain(order.iter().flat_map(|&idx| subtitle_sectors[idx].iter().cloned()));
+        let data: Vec<u8> = sectors.flatten().cloned().collect();
+        self.lines = order.iter().map(|&idx| self.lines[idx].clone()).collect();
+        self.data = data.into();
+        Ok(())
+    }
+
+    /// The index of the subtitle which every sector belongs to. The data of a subtitle starts with a sector with
+    /// a presentation timestamp and continues in the following sectors of the same subtitle stream.
+    fn sector_subtitle_indices(&self) -> Vec<Option<usize>> {
+        let mut num_subtitles = 0;
+        let mut current_subtitles: HashMap<u8, usize> = HashMap::new();
+        self.data
+            .chunks(SECTOR_SIZE)
+            .map(|sector| {
+                let packet = parse_packet(sector).filter(|packet| (0x20..0x40).contains(&packet.stream_id))?;
+                if packet.pts.is_some() {
+                    current_subtitles.insert(packet.stream_id, num_subtitles);
+                    num_subtitles += 1;
+                }
+                current_subtitles.get(&packet.stream_id).cloned()
+            })
+            .collect()
+    }
+
+    /// Write every subtitle image as numbered `.png` file into the (existing) directory `dir`, together
+    /// with a `manifest.csv` that lists the timespan and screen position of every image.
+    ///
+    /// This is the usual input layout for external OCR tools.
+    pub fn export_images(&self, dir: &Path) -> SubtitleParserResult<()> {
+        let images = self.images()?;
+        images::export_images(dir, self.lines.iter().map(|line| line.timespan).zip(&images))
+    }
+
+    /// An estimate of the memory which this file uses (see `SubtitleFile::memory_footprint()`).
+    pub fn memory_footprint(&self) -> MemoryFootprint {
+        let mut footprint = MemoryFootprint::of(self);
+        footprint.entry_count = self.lines.len();
+        footprint.add_vec(&self.lines);
+        footprint.add_image(&self.data);
+        footprint
+    }
+}
+
+/// The timespans of the subtitles in `.sub` data like in `VobFile::parse()`, but only the packet headers and the
+/// control sequences of the subpicture units are read, the images are not decoded (see `scan_metadata()`).
+///
+/// Subtitles without start date are skipped. Subtitles without stop date end 5 seconds after their start or 1ms
+/// before the next subtitle, like in `vobsub`.
+pub(crate) fn scan_timespans(data: &[u8]) -> Vec<TimeSpan> {
+    // the presentation timestamp and the subpicture unit of every subtitle
+    let mut subtitles: Vec<(u64, Vec<u8>)> = Vec::new();
+    let mut current_subtitles: HashMap<u8, usize> = HashMap::new();
+    let packets = data
+        .chunks(SECTOR_SIZE)
+        .filter_map(parse_packet)
+        .filter(|packet| (0x20..0x40).contains(&packet.stream_id));
+    for packet in packets {
+        match packet.pts {
+            Some(pts) => {
+                current_subtitles.insert(packet.stream_id, subtitles.len());
+                subtitles.push((pts, packet.payload.to_vec()));
+            }
+            None => {
+                if let Some(&subtitle_idx) = current_subtitles.get(&packet.stream_id) {
+                    subtitles[subtitle_idx].1.extend_from_slice(packet.payload);
+                }
+            }
+        }
+    }
+
+    // the start (in seconds) and the duration (in 1/100 seconds, as `vobsub` reads the stop date) of every subtitle
+    let starts: Vec<(f64, Option<u16>)> = subtitles
+        .iter()
+        .filter_map(|(pts, spu)| {
+            let (start_date, stop_date) = spu_dates(spu);
+            let start_date = start_date?;
+            Some((
+                *pts as f64 / 90000.0 + f64::from(start_date) / 100.0,
+                stop_date.map(|stop_date| stop_date.saturating_sub(start_date)),
+            ))
         })
+        .collect();
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &(start_time, duration))| {
+            let duration = match (duration, starts.get(i + 1)) {
+                (Some(duration), _) => i64::from(duration),
+                (None, Some(&(next_start, _))) => secs_to_stop_date((next_start - 0.001 - start_time).min(5.0)).ok()?,
+                (None, None) => 500,
+            };
+            let start = TimePoint::from_secs_f64(start_time).ok()?;
+            Some(TimeSpan::new(start, start + TimeDelta::from_msecs(stop_date_to_msecs(duration))))
+        })
+        .collect()
+}
+
+/// The dates (in 1/100 seconds as `vobsub` reads them, see `VobFile::parse()`) of the first start and stop
+/// commands in the control sequences of a subpicture unit.
+fn spu_dates(spu: &[u8]) -> (Option<u16>, Option<u16>) {
+    let read_u16 = |offset: usize| spu.get(offset..offset + 2).map(|b| u16::from(b[0]) << 8 | u16::from(b[1]));
+    let (mut start_date, mut stop_date) = (None, None);
+    let mut control_offset = read_u16(2).map(usize::from);
+    while let Some(offset) = control_offset {
+        let (date, next) = match (read_u16(offset), read_u16(offset + 2)) {
+            (Some(date), Some(next)) => (date, usize::from(next)),
+            _ => break,
+        };
+        let mut pos = offset + 4;
+        while let Some(&command) = spu.get(pos) {
+            pos += match command {
+                0x01 => {
+                    start_date = start_date.or(Some(date));
+                    1
+                }
+                0x02 => {
+                    stop_date = stop_date.or(Some(date));
+                    1
+                }
+                0x00 => 1,
+                0x03 | 0x04 => 3,
+                0x05 => 7,
+                0x06 => 5,
+                0xff => break,
+                // unknown commands are skipped until the end of the control sequence
+                _ => spu[pos..].iter().position(|&b| b == 0xff).unwrap_or(spu.len() - pos),
+            };
+        }
+        // the last control sequence points to itself
+        control_offset = Some(next).filter(|&next| next > offset);
     }
+    (start_date, stop_date)
+}
+
+/// Does this sector start with an MPEG-2 pack header followed by the first PES
+/// packet (the one with a presentation timestamp) of a subpicture stream?
+fn is_subtitle_start_sector(sector: &[u8]) -> bool {
+    parse_packet(sector).map_or(false, |packet| packet.pts.is_some() && (0x20..0x40).contains(&packet.stream_id))
+}
+
+/// The contents of the private stream PES packet in a sector.
+struct RawPacket<'a> {
+    pts: Option<u64>,
+    stream_id: u8,
+    payload: &'a [u8],
+}
+
+/// Read the PES packet (behind the MPEG-2 pack header) of a sector, `None` if the sector does not start with
+/// a private stream packet.
+fn parse_packet(sector: &[u8]) -> Option<RawPacket<'_>> {
+    if !sector.starts_with(&[0x00, 0x00, 0x01, 0xBA]) || sector.len() < 14 {
+        return None;
+    }
+    let pes = sector.get(14 + (sector[13] & 0x07) as usize..)?;
+    if !pes.starts_with(&[0x00, 0x00, 0x01, 0xBD]) || pes.len() < 9 {
+        return None;
+    }
+    let pes_end = (6 + ((pes[4] as usize) << 8 | pes[5] as usize)).min(pes.len());
+    let stream_id_pos = 9 + pes[8] as usize;
+    let stream_id = *pes.get(stream_id_pos)?;
+    let pts = match pes.get(9..14) {
+        Some(b) if pes[7] & 0x80 != 0 => {
+            Some(u64::from(b[0] >> 1 & 0x07) << 30 | u64::from(b[1]) << 22 | u64::from(b[2] >> 1) << 15 | u64::from(b[3]) << 7 | u64::from(b[4] >> 1))
+        }
+        _ => None,
+    };
+    Some(RawPacket {
+        pts,
+        stream_id,
+        payload: pes.get(stream_id_pos + 1..pes_end).unwrap_or(&[]),
+    })
+}
+
+/// Convert the 2-bit color indices of a subtitle to RGBA pixels.
+fn decode_image(sub: &vobsub::Subtitle, palette: &VobPalette) -> SubtitleImage {
+    let coordinates = sub.coordinates();
+    let data = sub
+        .raw_image()
+        .iter()
+        .flat_map(|&raw| {
+            // the raw index has to be subtracted from 3 (same as `vobsub::Subtitle::to_image()`)
+            let px = 3 - raw as usize;
+            let rgb = palette[sub.palette()[px] as usize];
+            let a = sub.alpha()[px];
+            vec![rgb[0], rgb[1], rgb[2], a << 4 | a]
+        })
+        .collect();
+
+    SubtitleImage {
+        x: u32::from(coordinates.left()),
+        y: u32::from(coordinates.top()),
+        width: u32::from(coordinates.width()),
+        height: u32::from(coordinates.height()),
+        data,
+    }
+}
+
+/// Read the palette from the program chain of a DVD video title set `.ifo` file (like `VTS_01_0.IFO`).
+///
+/// The palette of the first program chain is returned. DVDs store the colors as YCrCb, they are converted to RGB.
+pub fn read_ifo_palette(ifo: &[u8]) -> SubtitleParserResult<VobPalette> {
+    Ok(read_ifo_palette_inner(ifo).with_context(|_| crate::errors::ErrorKind::ParsingError)?)
+}
+
+fn read_ifo_palette_inner(ifo: &[u8]) -> Result<VobPalette, Error> {
+    // the offsets are read from the file, so they are checked for overflows
+    let read_u32 = |offset: usize| -> Option<usize> {
+        ifo.get(offset..)
+            .and_then(|b| b.get(..4))
+            .map(|b| (b[0] as usize) << 24 | (b[1] as usize) << 16 | (b[2] as usize) << 8 | b[3] as usize)
+    };
+
+    if !ifo.starts_with(b"DVDVIDEO-VTS") {
+        return Err(ErrorKind::IfoPaletteError {
+            msg: "not a video title set (no `DVDVIDEO-VTS` identifier)",
+        }
+        .into());
+    }
+
+    // the sector of the program chain information table is stored at 0xCC; the
+    // first search pointer of the table points to the first program chain
+    let pgci_offset = read_u32(0xCC)
+        .ok_or(ErrorKind::IfoPaletteError { msg: "file too short" })?
+        .checked_mul(SECTOR_SIZE)
+        .ok_or(ErrorKind::IfoPaletteError {
+            msg: "program chain table out of bounds",
+        })?;
+    let pgc_offset = pgci_offset
+        .checked_add(8 + 4)
+        .and_then(read_u32)
+        .and_then(|offset| pgci_offset.checked_add(offset))
+        .ok_or(ErrorKind::IfoPaletteError {
+            msg: "program chain table out of bounds",
+        })?;
+
+    // the palette of a program chain consists of 16 entries (0, Y, Cr, Cb) at offset 0xA4
+    let palette_data = pgc_offset
+        .checked_add(0xA4)
+        .and_then(|palette_offset| ifo.get(palette_offset..))
+        .and_then(|b| b.get(..16 * 4))
+        .ok_or(ErrorKind::IfoPaletteError {
+            msg: "program chain out of bounds",
+        })?;
+
+    let mut palette = [[0u8; 3]; 16];
+    for (color, ycrcb) in palette.iter_mut().zip(palette_data.chunks(4)) {
+        *color = ycrcb_to_rgb(ycrcb[1], ycrcb[2], ycrcb[3]);
+    }
+
+    Ok(palette)
+}
+
+/// Convert a (ITU-R BT.601, studio range) YCrCb color to RGB.
+fn ycrcb_to_rgb(y: u8, cr: u8, cb: u8) -> [u8; 3] {
+    let (y, cr, cb) = (1.164 * (f64::from(y) - 16.0), f64::from(cr) - 128.0, f64::from(cb) - 128.0);
+    let clamp = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+    [clamp(y + 1.596 * cr), clamp(y - 0.813 * cr - 0.391 * cb), clamp(y + 2.018 * cb)]
 }
 
 impl SubtitleFileInterface for VobFile {
     fn get_subtitle_entries(&self) -> SubtitleParserResult<Vec<SubtitleEntry>> {
+        // entries whose image can not be decoded are kept without image (see `image_warnings()`)
         Ok(self
             .lines
             .iter()
-            .map(|vsub| SubtitleEntry {
+            .zip(self.decoded_images())
+            .map(|(vsub, image)| SubtitleEntry {
                 timespan: vsub.timespan,
                 line: None,
+                position: image.as_ref().map(|image| SubtitlePosition {
+                    alignment: SubtitleAlignment::TopLeft,
+                    coordinates: Some(SubtitleCoordinates::Pixels {
+                        x: f64::from(image.x),
+                        y: f64::from(image.y),
+                    }),
+                }),
+                image,
+                speaker: None,
+                index: None,
+                tags: Vec::new(),
             })
             .collect())
     }
@@ -97,6 +604,605 @@ impl SubtitleFileInterface for VobFile {
     }
 
     fn to_data(&self) -> SubtitleParserResult<Vec<u8>> {
-        Ok(self.data.clone())
+        Ok(self.data.to_vec())
+    }
+
+    fn to_data_with(&self, options: &SerializeOptions) -> SubtitleParserResult<Vec<u8>> {
+        if options.changes_text() {
+            return Err(crate::errors::ErrorKind::TextFormatOnly.into());
+        }
+        self.to_data()
+    }
+}
+
+// ////////////////////////////////////////////////////////////////////////////////////////////////
+// VobSub writer
+
+impl VobFile {
+    /// Creates a `.sub` (`VobSub`) file and the matching `.idx` file from scratch.
+    ///
+    /// Every image is shown during its timespan at its position on a screen with the given width and
+    /// height (which is stored in the `.idx` file). The entries should be sorted by their start times.
+    ///
+    /// `VobSub` images only have four colors (one of them is used as transparent background) which are
+    /// taken from a 16-color palette. The palette is created from the most common colors of all images
+    /// and each image is reduced to its three most common palette colors; pixels with an alpha value below
+    /// 128 become the transparent background.
+    pub fn create(v: Vec<(TimeSpan, SubtitleImage)>, width: u32, height: u32) -> SubtitleParserResult<(IdxFile, VobFile)> {
+        let (timestamps, vob_file) = Self::create_inner(v).with_context(|_| crate::errors::ErrorKind::SerializingError)?;
+        let info = IdxTrackInfo {
+            palette: vob_file.palette,
+            ..IdxTrackInfo::new(width, height)
+        };
+        Ok((IdxFile::create(timestamps, &info)?, vob_file))
+    }
+
+    /// Create the `.sub` data and return the timestamps and file positions for the `.idx` file.
+    fn create_inner(v: Vec<(TimeSpan, SubtitleImage)>) -> Result<(Vec<(TimePoint, u64)>, VobFile), Error> {
+        let palette = create_palette(v.iter().map(|(_, image)| image));
+
+        let mut data = Vec::new();
+        let mut lines = Vec::new();
+        let mut timestamps = Vec::new();
+        for (timespan, image) in &v {
+            if image.data.len() != image.width as usize * image.height as usize * 4 {
+                return Err(ErrorKind::InvalidImage {
+                    msg: "the length of the RGBA data does not match the image dimensions",
+                }
+                .into());
+            }
+
+            let start = timespan.start.abs();
+            timestamps.push((start, data.len() as u64));
+
+            // the stop date is less precise than milliseconds, so the file stores the end time that is
+            // actually written
+            let stop_date = msecs_to_stop_date(timespan.len().msecs());
+            let spu = encode_spu(image, &palette, stop_date)?;
+            write_pes_packets(&mut data, &spu, start.msecs() as u64 * 90);
+            lines.push(VobSubSubtitle {
+                timespan: TimeSpan::new(
+                    timespan.start,
+                    timespan.start + TimeDelta::from_msecs(stop_date_to_msecs(stop_date as i64)),
+                ),
+            });
+        }
+
+        Ok((
+            timestamps,
+            VobFile {
+                data: data.into(),
+                lines,
+                palette,
+            },
+        ))
+    }
+}
+
+/// The stop date of a subtitle (after its start) with the given duration. The stop date is given in units of
+/// 1024/90000 seconds.
+fn msecs_to_stop_date(duration_ms: i64) -> usize {
+    (duration_ms.max(0) * 90 / 1024).min(i64::from(u16::MAX)) as usize
+}
+
+/// The duration in milliseconds of a subtitle with the given stop date.
+fn stop_date_to_msecs(stop_date: i64) -> i64 {
+    stop_date * 1024 / 90
+}
+
+/// Convert a number of seconds to a stop date (in 1/100 seconds as `vobsub` reads them, see `VobFile::parse()`),
+/// which is small enough for `stop_date_to_msecs()`.
+fn secs_to_stop_date(secs: f64) -> Result<i64, TimeConversionError> {
+    let stop_date = (secs * 100.0).round();
+    if stop_date.is_nan() {
+        Err(TimeConversionError::NotANumber)
+    } else if stop_date.abs() >= (i64::MAX / 1024) as f64 {
+        Err(TimeConversionError::OutOfRange)
+    } else {
+        Ok(stop_date as i64)
+    }
+}
+
+/// Squared distance between two RGB colors.
+fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b.iter()).map(|(&x, &y)| (i32::from(x) - i32::from(y)).pow(2) as u32).sum()
+}
+
+/// Index of the palette color (from the colors with indices in `candidates`) which is nearest to `color`.
+fn nearest_color(palette: &VobPalette, candidates: &[usize], color: [u8; 3]) -> usize {
+    *candidates
+        .iter()
+        .min_by_key(|&&i| color_distance(palette[i], color))
+        .expect("candidate list should not be empty")
+}
+
+/// Iterate over the non-transparent pixels (RGB and alpha) of an image.
+fn opaque_pixels(image: &SubtitleImage) -> impl Iterator<Item = ([u8; 3], u8)> + '_ {
+    image.data.chunks(4).filter(|px| px[3] >= 128).map(|px| ([px[0], px[1], px[2]], px[3]))
+}
+
+/// The first palette entry is used for the transparent background, the other 15 entries are
+/// the most common colors in all images.
+fn create_palette<'a>(images: impl Iterator<Item = &'a SubtitleImage>) -> VobPalette {
+    let mut color_counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for image in images {
+        for (rgb, _) in opaque_pixels(image) {
+            *color_counts.entry(rgb).or_insert(0) += 1;
+        }
+    }
+
+    let mut colors: Vec<([u8; 3], usize)> = color_counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut palette = DEFAULT_VOB_PALETTE;
+    palette[0] = [0, 0, 0];
+    for (entry, (rgb, _)) in palette.iter_mut().skip(1).zip(colors) {
+        *entry = rgb;
+    }
+    palette
+}
+
+/// Collects nibbles of the run-length encoded image data.
+struct NibbleWriter {
+    data: Vec<u8>,
+    half: bool,
+}
+
+impl NibbleWriter {
+    fn push(&mut self, nibble: u8) {
+        if self.half {
+            *self.data.last_mut().expect("half byte was written before") |= nibble & 0xF;
+        } else {
+            self.data.push(nibble << 4);
+        }
+        self.half = !self.half;
+    }
+
+    /// Scan lines always start at a byte boundary.
+    fn align(&mut self) {
+        self.half = false;
+    }
+}
+
+/// Run-length encode a scan line of 2-bit values.
+fn encode_scan_line(line: &[u8], writer: &mut NibbleWriter) {
+    let mut x = 0;
+    while x < line.len() {
+        let value = line[x];
+        let run = line[x..].iter().take_while(|&&v| v == value).count();
+
+        if x + run == line.len() && run > 3 {
+            // fill the rest of the line
+            for &nibble in &[0, 0, 0, value] {
+                writer.push(nibble);
+            }
+            x += run;
+        } else {
+            let run = run.min(255);
+            let code = (run as u16) << 2 | u16::from(value);
+            let num_nibbles = match run {
+                1..=3 => 1,
+                4..=15 => 2,
+                16..=63 => 3,
+                _ => 4,
+            };
+            for i in (0..num_nibbles).rev() {
+                writer.push((code >> (4 * i)) as u8 & 0xF);
+            }
+            x += run;
+        }
+    }
+    writer.align();
+}
+
+/// Create the subpicture unit (image data and control sequences) for one subtitle.
+fn encode_spu(image: &SubtitleImage, palette: &VobPalette, stop_date: usize) -> Result<Vec<u8>, Error> {
+    let fits = |position: u32, size: u32| position.checked_add(size).map_or(false, |end| end <= 4096);
+    if image.width < 2 || image.height < 2 || !fits(image.x, image.width) || !fits(image.y, image.height) {
+        return Err(ErrorKind::InvalidImage {
+            msg: "images have to be at least 2x2 pixels and have to fit on a 4096x4096 screen",
+        }
+        .into());
+    }
+
+    // choose the three most common palette colors of this image
+    let all_colors: Vec<usize> = (1..16).collect();
+    let mut color_counts = [0usize; 16];
+    for (rgb, _) in opaque_pixels(image) {
+        color_counts[nearest_color(palette, &all_colors, rgb)] += 1;
+    }
+    let mut used_colors: Vec<usize> = all_colors.iter().cloned().filter(|&i| color_counts[i] > 0).collect();
+    used_colors.sort_by(|&a, &b| color_counts[b].cmp(&color_counts[a]).then(a.cmp(&b)));
+    used_colors.truncate(3);
+    if used_colors.is_empty() {
+        used_colors.push(1);
+    }
+
+    // map the pixels to 2-bit values (0 is the transparent background) and average the alpha per value
+    let mut alpha_sums = [0u32; 4];
+    let mut value_counts = [0u32; 4];
+    let values: Vec<u8> = image
+        .data
+        .chunks(4)
+        .map(|px| {
+            if px[3] < 128 {
+                return 0;
+            }
+            let color = nearest_color(palette, &used_colors, [px[0], px[1], px[2]]);
+            let value = used_colors.iter().position(|&c| c == color).expect("color is in the list") as u8 + 1;
+            alpha_sums[value as usize] += u32::from(px[3]);
+            value_counts[value as usize] += 1;
+            value
+        })
+        .collect();
+
+    let mut value_palette = [0u8; 4];
+    let mut value_alpha = [0u8; 4];
+    for value in 1..4 {
+        value_palette[value] = used_colors.get(value - 1).cloned().unwrap_or(0) as u8;
+        value_alpha[value] = match alpha_sums[value].checked_div(value_counts[value]) {
+            Some(alpha) => ((alpha + 8) / 17) as u8,
+            None => 0xF,
+        };
+    }
+
+    // the image is interlaced: even lines are stored in the first field, odd lines in the second field
+    let width = image.width as usize;
+    let mut fields = [
+        NibbleWriter {
+            data: Vec::new(),
+            half: false,
+        },
+        NibbleWriter {
+            data: Vec::new(),
+            half: false,
+        },
+    ];
+    for (y, line) in values.chunks(width).enumerate() {
+        encode_scan_line(line, &mut fields[y % 2]);
+    }
+
+    let field0_offset = 4;
+    let field1_offset = field0_offset + fields[0].data.len();
+    let control_offset = field1_offset + fields[1].data.len();
+    // the first control sequence has 2 + 2 + 3 + 3 + 7 + 5 + 1 + 1 bytes
+    let stop_control_offset = control_offset + 24;
+    let spu_size = stop_control_offset + 6;
+    if spu_size > usize::from(u16::MAX) {
+        return Err(ErrorKind::InvalidImage {
+            msg: "the compressed image is larger than 64 KiB",
+        }
+        .into());
+    }
+
+    let u16_bytes = |v: usize| [(v >> 8) as u8, v as u8];
+    let (x1, x2, y1, y2) = (image.x, image.x + image.width - 1, image.y, image.y + image.height - 1);
+
+    // the colors/alpha values of the control sequence are ordered from the 2-bit value 3 down to 0
+    let mut spu = Vec::with_capacity(spu_size);
+    spu.extend_from_slice(&u16_bytes(spu_size));
+    spu.extend_from_slice(&u16_bytes(control_offset));
+    spu.append(&mut fields[0].data);
+    spu.append(&mut fields[1].data);
+
+    spu.extend_from_slice(&[0, 0]);
+    spu.extend_from_slice(&u16_bytes(stop_control_offset));
+    spu.extend_from_slice(&[0x03, value_palette[3] << 4 | value_palette[2], value_palette[1] << 4 | value_palette[0]]);
+    spu.extend_from_slice(&[0x04, value_alpha[3] << 4 | value_alpha[2], value_alpha[1] << 4 | value_alpha[0]]);
+    spu.extend_from_slice(&[
+        0x05,
+        (x1 >> 4) as u8,
+        ((x1 & 0xF) << 4 | x2 >> 8) as u8,
+        x2 as u8,
+        (y1 >> 4) as u8,
+        ((y1 & 0xF) << 4 | y2 >> 8) as u8,
+        y2 as u8,
+    ]);
+    spu.push(0x06);
+    spu.extend_from_slice(&u16_bytes(field0_offset));
+    spu.extend_from_slice(&u16_bytes(field1_offset));
+    spu.extend_from_slice(&[0x01, 0xFF]);
+
+    spu.extend_from_slice(&u16_bytes(stop_date));
+    spu.extend_from_slice(&u16_bytes(stop_control_offset));
+    spu.extend_from_slice(&[0x02, 0xFF]);
+
+    Ok(spu)
+}
+
+/// Split the subpicture unit into MPEG-2 program stream packets which are padded to full sectors.
+fn write_pes_packets(data: &mut Vec<u8>, spu: &[u8], pts: u64) {
+    // program stream pack header (14 bytes) + PES header (9 bytes) + substream id
+    const FIXED_HEADERS_SIZE: usize = 14 + 9 + 1;
+    const PTS_SIZE: usize = 5;
+
+    let mut rest = spu;
+    let mut first = true;
+    while !rest.is_empty() {
+        let pts_size = if first { PTS_SIZE } else { 0 };
+        let payload_size = rest.len().min(SECTOR_SIZE - FIXED_HEADERS_SIZE - pts_size);
+        let (payload, new_rest) = rest.split_at(payload_size);
+        rest = new_rest;
+
+        // a padding packet needs at least 6 bytes, fewer remaining bytes are used as stuffing in the PES header
+        let remaining = SECTOR_SIZE - FIXED_HEADERS_SIZE - pts_size - payload_size;
+        let stuffing = if remaining < 6 { remaining } else { 0 };
+
+        write_pack_header(data, pts);
+
+        let pes_length = 3 + pts_size + stuffing + 1 + payload_size;
+        data.extend_from_slice(&[0x00, 0x00, 0x01, 0xBD, (pes_length >> 8) as u8, pes_length as u8, 0x81]);
+        data.push(if first { 0x80 } else { 0x00 });
+        data.push((pts_size + stuffing) as u8);
+        if first {
+            data.extend_from_slice(&[
+                0x21 | ((pts >> 29) & 0x0E) as u8,
+                (pts >> 22) as u8,
+                0x01 | ((pts >> 14) & 0xFE) as u8,
+                (pts >> 7) as u8,
+                0x01 | ((pts << 1) & 0xFE) as u8,
+            ]);
+        }
+        data.extend(std::iter::repeat(0xFF).take(stuffing));
+        data.push(0x20);
+        data.extend_from_slice(payload);
+
+        if remaining >= 6 {
+            let padding_length = remaining - 6;
+            data.extend_from_slice(&[0x00, 0x00, 0x01, 0xBE, (padding_length >> 8) as u8, padding_length as u8]);
+            data.extend(std::iter::repeat(0xFF).take(padding_length));
+        }
+
+        first = false;
+    }
+}
+
+/// Write an MPEG-2 program stream pack header with the given system clock reference.
+fn write_pack_header(data: &mut Vec<u8>, scr: u64) {
+    // 10.08 MBit/s in units of 50 bytes per second
+    const MUX_RATE: u32 = 25200;
+
+    data.extend_from_slice(&[
+        0x00,
+        0x00,
+        0x01,
+        0xBA,
+        0x44 | ((scr >> 27) & 0x38) as u8 | ((scr >> 28) & 0x03) as u8,
+        (scr >> 20) as u8,
+        0x04 | ((scr >> 12) & 0xF8) as u8 | ((scr >> 13) & 0x03) as u8,
+        (scr >> 5) as u8,
+        0x04 | ((scr << 3) & 0xF8) as u8,
+        0x01,
+        (MUX_RATE >> 14) as u8,
+        (MUX_RATE >> 6) as u8,
+        ((MUX_RATE << 2) & 0xFC) as u8 | 0x03,
+        0xF8,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn secs_to_stop_date_test() {
+        use super::secs_to_stop_date;
+        use crate::timetypes::TimeConversionError;
+
+        assert_eq!(secs_to_stop_date(1.234), Ok(123));
+        assert_eq!(secs_to_stop_date(-0.006), Ok(-1));
+        assert_eq!(secs_to_stop_date(f64::NAN), Err(TimeConversionError::NotANumber));
+        assert_eq!(secs_to_stop_date(f64::INFINITY), Err(TimeConversionError::OutOfRange));
+        assert_eq!(secs_to_stop_date(1e17), Err(TimeConversionError::OutOfRange));
+    }
+
+    #[test]
+    fn read_ifo_palette_test() {
+        // program chain table in sector 1, first program chain directly behind the 16 byte table header
+        let mut ifo = vec![0u8; 2 * 2048 + 16 + 0xA4 + 64];
+        ifo[..12].copy_from_slice(b"DVDVIDEO-VTS");
+        ifo[0xCF] = 1;
+        ifo[2048 + 15] = 16;
+
+        let palette_offset = 2048 + 16 + 0xA4;
+        ifo[palette_offset..palette_offset + 4].copy_from_slice(&[0, 235, 128, 128]);
+        ifo[palette_offset + 4..palette_offset + 8].copy_from_slice(&[0, 16, 128, 128]);
+        ifo[palette_offset + 8..palette_offset + 12].copy_from_slice(&[0, 82, 240, 90]);
+
+        let palette = super::read_ifo_palette(&ifo).unwrap();
+        assert_eq!(palette[0], [255, 255, 255]);
+        assert_eq!(palette[1], [0, 0, 0]);
+        assert_eq!(palette[2], [255, 1, 0]);
+
+        assert!(super::read_ifo_palette(b"DVDVIDEO-VMG").is_err());
+
+        // offsets at the end of the address space
+        ifo[0xCC..0xD0].copy_from_slice(&[0xFF; 4]);
+        assert!(super::read_ifo_palette(&ifo).is_err());
+        ifo[0xCC..0xD0].copy_from_slice(&[0, 0, 0, 1]);
+        ifo[2048 + 12..2048 + 16].copy_from_slice(&[0xFF; 4]);
+        assert!(super::read_ifo_palette(&ifo).is_err());
+    }
+
+    #[test]
+    fn parse_vobsub_stop_date_test() {
+        use super::VobFile;
+        use crate::timetypes::{TimePoint, TimeSpan};
+        use crate::SubtitleFileInterface;
+
+        // a sector with one subtitle at 1s (pts 90000) whose stop date is 90 units of 1024/90000 seconds
+        let spu = [
+            0x00, 0x24, 0x00, 0x06, // size and offset of the first control sequence
+            0x80, 0x80, // both lines of the 2x2 image: two pixels of color 0
+            0x00, 0x00, 0x00, 0x1E, 0x01, 0x03, 0x10, 0x00, 0x04, 0xF0, 0x00, // start date, palette and alpha
+            0x05, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x06, 0x00, 0x04, 0x00, 0x05, 0xFF, // coordinates and lines
+            0x00, 0x5A, 0x00, 0x1E, 0x02, 0xFF, // stop date
+        ];
+        let mut sector = vec![0x00, 0x00, 0x01, 0xBA, 0x44, 0x00, 0x04, 0x00, 0x04, 0x01, 0x01, 0x89, 0xC3, 0xF8];
+        sector.extend_from_slice(&[0x00, 0x00, 0x01, 0xBD, 0x00, 0x2D, 0x81, 0x80, 0x05, 0x21, 0x00, 0x05, 0xBF, 0x21, 0x20]);
+        sector.extend_from_slice(&spu);
+        let padding_length = 2048 - sector.len() - 6;
+        sector.extend_from_slice(&[0x00, 0x00, 0x01, 0xBE, (padding_length >> 8) as u8, padding_length as u8]);
+        sector.resize(2048, 0xFF);
+
+        let entries = VobFile::parse(&sector).unwrap().get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].timespan,
+            TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2024))
+        );
+        assert_eq!(entries[0].image.as_ref().map(|image| (image.width, image.height)), Some((2, 2)));
+    }
+
+    #[test]
+    fn create_vobsub_test() {
+        use super::VobFile;
+        use crate::timetypes::{TimePoint, TimeSpan};
+        use crate::{SubtitleFileInterface, SubtitleImage};
+
+        // a 300x4 image with a white text area and a red border (wide enough for runs longer than 255 pixels)
+        let (width, height) = (300, 4);
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let px: [u8; 4] = match (x, y) {
+                    (0..=9, _) => [0, 0, 0, 0],
+                    (10..=19, _) | (_, 0) => [255, 0, 0, 255],
+                    _ => [255, 255, 255, 255],
+                };
+                data.extend_from_slice(&px);
+            }
+        }
+        let image1 = SubtitleImage {
+            x: 100,
+            y: 400,
+            width,
+            height,
+            data,
+        };
+        let image2 = SubtitleImage {
+            x: 5,
+            y: 6,
+            width: 2,
+            height: 2,
+            data: vec![0, 0, 0, 0, 0, 0, 255, 255, 0, 0, 255, 255, 0, 0, 0, 0],
+        };
+
+        let ts1 = TimeSpan::new(TimePoint::from_msecs(1500), TimePoint::from_msecs(3500));
+        let ts2 = TimeSpan::new(TimePoint::from_msecs(600_000), TimePoint::from_msecs(601_000));
+        let (idx_file, vob_file) = VobFile::create(vec![(ts1, image1.clone()), (ts2, image2.clone())], 720, 576).unwrap();
+
+        // an image which doesn't fit on the screen (even with an overflowing position) can't be written
+        let outside = SubtitleImage {
+            x: u32::MAX,
+            ..image2.clone()
+        };
+        let err = VobFile::create(vec![(ts1, outside)], 720, 576).unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::SerializingError);
+
+        let idx_string = String::from_utf8(idx_file.to_data().unwrap()).unwrap();
+        assert!(idx_string.contains("size: 720x576\n"));
+        assert!(idx_string.contains("palette: 000000, ffffff, ff0000, 0000ff, "));
+        assert!(idx_string.contains("timestamp: 00:00:01:500, filepos: 000000000\n"));
+        assert!(idx_string.contains("timestamp: 00:10:00:000, filepos: 000000800\n"));
+
+        // read back the generated `.sub` data (the palette is stored in the `.idx` file)
+        let mut parsed_vob_file = VobFile::parse(&vob_file.to_data().unwrap()).unwrap();
+        parsed_vob_file.set_palette(*vob_file.palette());
+        let entries = parsed_vob_file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timespan.start, ts1.start);
+        assert_eq!(entries[1].timespan.start, ts2.start);
+
+        // the end times are stored in units of 1024/90000 seconds
+        assert_eq!(entries[0].timespan.end, TimePoint::from_msecs(3491));
+        assert_eq!(entries[1].timespan.end, TimePoint::from_msecs(600_989));
+        assert_eq!(entries[0].timespan, vob_file.get_subtitle_entries().unwrap()[0].timespan);
+
+        let images = parsed_vob_file.images().unwrap();
+        assert_eq!(images, vec![image1.clone(), image2.clone()]);
+        assert_eq!(entries[0].image, Some(image1.clone()));
+        assert_eq!(entries[1].image, Some(image2));
+        assert!(parsed_vob_file.image_warnings().is_empty());
+
+        // the second subtitle is cut off, so only its image is missing
+        let cut_vob_file = VobFile {
+            data: parsed_vob_file.data[..2048].into(),
+            ..parsed_vob_file.clone()
+        };
+        let entries = cut_vob_file.get_subtitle_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].image, Some(image1));
+        assert_eq!(entries[1].image, None);
+        assert_eq!(entries[1].position, None);
+        assert_eq!(entries[1].timespan.start, ts2.start);
+        let warnings = cut_vob_file.image_warnings();
+        assert_eq!(
+            super::scan_timespans(&parsed_vob_file.data),
+            parsed_vob_file.lines.iter().map(|line| line.timespan).collect::<Vec<_>>()
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].entry_idx, Some(1));
+        assert_eq!(warnings[0].kind, crate::warnings::ParseWarningKind::ImageNotDecoded);
+    }
+
+    #[test]
+    fn select_subtitles_test() {
+        use super::VobFile;
+        use crate::timetypes::{TimePoint, TimeSpan};
+        use crate::{SubtitleFileInterface, SubtitleImage};
+
+        // the first image needs two packets
+        let image = |width: u32, height: u32, color: u8| SubtitleImage {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            data: (0..width * height)
+                .flat_map(|i| vec![color, (i % 7 * 40) as u8, (i % 3 * 100) as u8, 255])
+                .collect(),
+        };
+        let images = [image(200, 40, 255), image(2, 2, 0), image(4, 4, 100)];
+        let spans = [(1000, 2000), (3000, 4000), (5000, 6000)];
+        let (_, mut vob_file) = VobFile::create(
+            images
+                .iter()
+                .zip(&spans)
+                .map(|(image, &(start, end))| (TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end)), image.clone()))
+                .collect(),
+            720,
+            576,
+        )
+        .unwrap();
+
+        let packets = vob_file.packets();
+        assert_eq!(packets.len(), 4);
+        assert_eq!(
+            packets.iter().map(|p| p.subtitle_idx).collect::<Vec<_>>(),
+            vec![Some(0), Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            packets.iter().map(|p| p.pts).collect::<Vec<_>>(),
+            vec![Some(90_000), None, Some(270_000), Some(450_000)]
+        );
+        assert_eq!(packets[2].offset, 2 * 2048);
+        assert_eq!(packets[1].stream_id, Some(0x20));
+
+        assert!(vob_file.select_subtitles(&[0, 0]).is_err());
+        assert!(vob_file.select_subtitles(&[3]).is_err());
+
+        let old_images = vob_file.images().unwrap();
+        vob_file.select_subtitles(&[2, 0]).unwrap();
+        assert_eq!(vob_file.packet_offsets(), vec![0, 2048]);
+
+        let mut parsed_vob_file = VobFile::parse(&vob_file.to_data().unwrap()).unwrap();
+        parsed_vob_file.set_palette(*vob_file.palette());
+        let starts: Vec<i64> = parsed_vob_file
+            .get_subtitle_entries()
+            .unwrap()
+            .iter()
+            .map(|e| e.timespan.start.msecs())
+            .collect();
+        assert_eq!(starts, vec![5000, 1000]);
+        assert_eq!(parsed_vob_file.images().unwrap(), vec![old_images[2].clone(), old_images[0].clone()]);
+        assert_eq!(vob_file.get_subtitle_entries().unwrap().len(), 2);
     }
 }
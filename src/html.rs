@@ -0,0 +1,403 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::escaping::{line_break, ssa_unescape};
+use crate::ssa_tags::{parse_text, SsaTag, SsaTextPart};
+use crate::timetypes::{TimePoint, TimeStyle};
+use crate::{SubtitleEntry, SubtitleFormat};
+
+/// What `to_html()` creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtmlDocument {
+    /// A standalone page with a `<style>` element.
+    Page,
+
+    /// Only the `<div class="subtitles">` element, for embedding the entries into another page (for example next to a
+    /// `<video>` element: the `data-start` and `data-end` attributes are the times in seconds, like the times of the
+    /// cues of a `<track>`).
+    Fragment,
+}
+
+/// Options for `SubtitleFile::to_html()`, which are created like
+/// `HtmlOptions::new().title("Episode 1").document(HtmlDocument::Fragment)`.
+///
+/// By default a standalone page with timestamps is created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlOptions {
+    document: HtmlDocument,
+    title: String,
+    timestamps: bool,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> HtmlOptions {
+        HtmlOptions {
+            document: HtmlDocument::Page,
+            title: "Subtitles".to_string(),
+            timestamps: true,
+        }
+    }
+}
+
+impl HtmlOptions {
+    /// Create the default options.
+    pub fn new() -> HtmlOptions {
+        HtmlOptions::default()
+    }
+
+    /// Create a page or a fragment.
+    pub fn document(mut self, document: HtmlDocument) -> HtmlOptions {
+        self.document = document;
+        self
+    }
+
+    /// Set the title of the page (`Subtitles` by default).
+    pub fn title(mut self, title: &str) -> HtmlOptions {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Show the start and end of every entry.
+    pub fn timestamps(mut self, timestamps: bool) -> HtmlOptions {
+        self.timestamps = timestamps;
+        self
+    }
+}
+
+const PAGE_STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: 2em auto; }
+.cue { margin: 0.5em 0; }
+.time { color: #777; font-family: monospace; margin-right: 1em; }
+.speaker { font-weight: bold; margin-right: 0.5em; }
+";
+
+/// Create HTML of the entries of a file of the format `format`.
+///
+/// Every entry is a `<p class="cue">` element. Italic, bold, underlined and struck out text and text colors are
+/// kept (from tags like `<i>`, `<font color="red">`, `{\\i1}`, `{\\c&H0000FF&}` and `{y:i}`), other formatting is
+/// removed. Entries without text (like images) are skipped.
+pub fn to_html(entries: &[SubtitleEntry], format: SubtitleFormat, options: &HtmlOptions) -> String {
+    let mut fragment = String::from("<div class=\"subtitles\">\n");
+    for entry in entries {
+        let line = match entry.line {
+            Some(ref line) => line,
+            None => continue,
+        };
+
+        fragment.push_str(&format!(
+            "<p class=\"cue\" data-start=\"{}\" data-end=\"{}\">",
+            seconds(entry.timespan.start),
+            seconds(entry.timespan.end)
+        ));
+        if options.timestamps {
+            fragment.push_str(&format!(
+                "<span class=\"time\">{} &ndash; {}</span>",
+                entry.timespan.start.format(TimeStyle::Default),
+                entry.timespan.end.format(TimeStyle::Default)
+            ));
+        }
+        if let Some(speaker) = entry.speaker.as_deref().filter(|speaker| !speaker.trim().is_empty()) {
+            fragment.push_str(&format!("<span class=\"speaker\">{}:</span>", escape_html(speaker.trim())));
+        }
+        fragment.push_str(&text_to_html(line, format));
+        fragment.push_str("</p>\n");
+    }
+    fragment.push_str("</div>\n");
+
+    match options.document {
+        HtmlDocument::Fragment => fragment,
+        HtmlDocument::Page => format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+            escape_html(&options.title),
+            PAGE_STYLE,
+            escape_html(&options.title),
+            fragment
+        ),
+    }
+}
+
+/// A time in seconds like `62.500`.
+fn seconds(t: TimePoint) -> String {
+    format!("{:.3}", t.msecs() as f64 / 1000.0)
+}
+
+fn escape_html(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// The styling of a piece of text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct TextStyle {
+    italic: bool,
+    bold: bool,
+    underline: bool,
+    strike_out: bool,
+
+    /// A CSS color like `#FF0000` or `red`
+    color: Option<String>,
+}
+
+/// Writes styled text as HTML with properly nested elements.
+#[derive(Debug, Default)]
+struct HtmlWriter {
+    result: String,
+
+    /// The style of the open elements
+    written: TextStyle,
+}
+
+impl HtmlWriter {
+    fn text(&mut self, style: &TextStyle, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if *style != self.written {
+            self.close();
+            if let Some(ref color) = style.color {
+                self.result.push_str(&format!("<span style=\"color: {}\">", color));
+            }
+            for &(enabled, tag) in &[(style.bold, "b"), (style.italic, "i"), (style.underline, "u"), (style.strike_out, "s")] {
+                if enabled {
+                    self.result.push_str(&format!("<{}>", tag));
+                }
+            }
+            self.written = style.clone();
+        }
+        self.result.push_str(&escape_html(text));
+    }
+
+    fn line_break(&mut self) {
+        self.close();
+        self.result.push_str("<br>");
+    }
+
+    fn close(&mut self) {
+        let style = &self.written;
+        for &(enabled, tag) in &[(style.strike_out, "s"), (style.underline, "u"), (style.italic, "i"), (style.bold, "b")] {
+            if enabled {
+                self.result.push_str(&format!("</{}>", tag));
+            }
+        }
+        if style.color.is_some() {
+            self.result.push_str("</span>");
+        }
+        self.written = TextStyle::default();
+    }
+
+    fn finish(mut self) -> String {
+        self.close();
+        self.result
+    }
+}
+
+/// A color of a file without characters which could break out of the `style` attribute.
+fn css_color(color: &str) -> Option<String> {
+    let color = color.trim().trim_matches(|c| c == '"' || c == '\'');
+    if color.is_empty() || !color.chars().all(|c| c.is_ascii_alphanumeric() || c == '#') {
+        return None;
+    }
+    Some(color.to_string())
+}
+
+/// A color like `0xBBGGRR` (like in `.ssa` and `MicroDVD` files) as `#RRGGBB`.
+fn bgr_color(bgr: u32) -> String {
+    format!("#{:02X}{:02X}{:02X}", bgr & 0xFF, (bgr >> 8) & 0xFF, (bgr >> 16) & 0xFF)
+}
+
+fn text_to_html(line: &str, format: SubtitleFormat) -> String {
+    let mut writer = HtmlWriter::default();
+    let mut style = TextStyle::default();
+    match format {
+        SubtitleFormat::SubStationAlpha => ssa_to_html(line, &mut writer, &mut style),
+        _ => {
+            let lines: Vec<&str> = match line_break(format) {
+                Some(separator) => line.split(separator).collect(),
+                None => vec![line],
+            };
+            for (i, line) in lines.into_iter().enumerate() {
+                if i > 0 {
+                    writer.line_break();
+                }
+                match format {
+                    SubtitleFormat::MicroDVD => mdvd_to_html(line, &mut writer, &mut style),
+                    SubtitleFormat::Mpl2 => match line.strip_prefix('/') {
+                        Some(line) => writer.text(
+                            &TextStyle {
+                                italic: true,
+                                ..style.clone()
+                            },
+                            line,
+                        ),
+                        None => writer.text(&style, line),
+                    },
+                    _ => tags_to_html(line, &mut writer, &mut style),
+                }
+            }
+        }
+    }
+    writer.finish()
+}
+
+/// Text with HTML-like tags like `<i>` and `<font color="red">` (like in `.srt` files). Override blocks like `{\an8}`
+/// are removed.
+fn tags_to_html(line: &str, writer: &mut HtmlWriter, style: &mut TextStyle) {
+    let mut rest = line;
+    while let Some(start) = rest.find(['<', '{']) {
+        writer.text(style, &rest[..start]);
+        let is_tag = rest[start..].starts_with('<');
+        let after = &rest[start + 1..];
+        let end = if is_tag {
+            // not a tag, like in `x < y` or `<3`
+            let is_tag_start = after.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/');
+            after.find(['<', '>']).filter(|&end| is_tag_start && after[end..].starts_with('>'))
+        } else {
+            after.find('}')
+        };
+        let end = match end {
+            Some(end) => end,
+            None => {
+                writer.text(style, &rest[start..=start]);
+                rest = after;
+                continue;
+            }
+        };
+        rest = &after[end + 1..];
+        if !is_tag {
+            continue;
+        }
+
+        let tag = after[..end].trim().to_ascii_lowercase();
+        match tag.as_str() {
+            "i" | "/i" => style.italic = !tag.starts_with('/'),
+            "b" | "/b" => style.bold = !tag.starts_with('/'),
+            "u" | "/u" => style.underline = !tag.starts_with('/'),
+            "s" | "/s" => style.strike_out = !tag.starts_with('/'),
+            "/font" => style.color = None,
+            _ if tag.starts_with("font") => {
+                if let Some(color) = tag.split("color=").nth(1) {
+                    style.color = color.split_whitespace().next().and_then(css_color);
+                }
+            }
+            _ => {}
+        }
+    }
+    writer.text(style, rest);
+}
+
+/// A line of a `MicroDVD` file with control codes like `{y:i}` (styles) and `{c:$0000FF}` (colors).
+fn mdvd_to_html(line: &str, writer: &mut HtmlWriter, style: &mut TextStyle) {
+    let mut rest = line;
+    while let Some(block) = rest.strip_prefix('{').and_then(|s| Some(s.split_at(s.find('}')?))) {
+        let (code, value) = block.0.split_once(':').unwrap_or((block.0, ""));
+        match code.trim() {
+            "y" | "Y" => {
+                for c in value.chars() {
+                    match c {
+                        'i' | 'I' => style.italic = true,
+                        'b' | 'B' => style.bold = true,
+                        'u' | 'U' => style.underline = true,
+                        's' | 'S' => style.strike_out = true,
+                        _ => {}
+                    }
+                }
+            }
+            "c" | "C" => {
+                if let Ok(bgr) = u32::from_str_radix(value.trim().trim_start_matches('$'), 16) {
+                    style.color = Some(bgr_color(bgr));
+                }
+            }
+            _ => {}
+        }
+        rest = &block.1[1..];
+    }
+    writer.text(style, rest);
+}
+
+/// A dialog text of an `.ssa`/`.ass` file.
+fn ssa_to_html(line: &str, writer: &mut HtmlWriter, style: &mut TextStyle) {
+    let mut drawing = false;
+    for part in parse_text(line) {
+        match part {
+            SsaTextPart::Text(_) if drawing => {}
+            SsaTextPart::Text(text) => {
+                for (i, line) in ssa_unescape(&text).split('\n').enumerate() {
+                    if i > 0 {
+                        writer.line_break();
+                    }
+                    writer.text(style, line);
+                }
+            }
+            SsaTextPart::Override(tags) => {
+                for tag in tags {
+                    match tag {
+                        SsaTag::Color { index: 1, color } => style.color = Some(bgr_color(color)),
+                        SsaTag::Drawing(scale) => drawing = scale > 0,
+                        SsaTag::Other(ref tag) if tag == "r" => *style = TextStyle::default(),
+                        SsaTag::Other(ref tag) => {
+                            let (name, value) = tag.split_at(tag.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(tag.len()));
+                            let enabled = value.trim().parse::<u32>().ok().map(|n| n != 0);
+                            match (name, enabled) {
+                                ("i", Some(enabled)) => style.italic = enabled,
+                                ("b", Some(enabled)) => style.bold = enabled,
+                                ("u", Some(enabled)) => style.underline = enabled,
+                                ("s", Some(enabled)) => style.strike_out = enabled,
+                                _ => {}
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimeSpan;
+
+    #[test]
+    fn html_test() {
+        assert_eq!(
+            text_to_html(
+                "<i>Hello</i> <font color=\"red\">x < y & <b>z</b></font>\n{\\an8}<3",
+                SubtitleFormat::SubRip
+            ),
+            "<i>Hello</i> <span style=\"color: red\">x &lt; y &amp; </span><span style=\"color: red\"><b>z</b></span><br>&lt;3"
+        );
+        assert_eq!(
+            text_to_html("{\\i1}A{\\b1\\c&H0000FF&}B{\\r}C\\ND{\\p1}m 0 0{\\p0}", SubtitleFormat::SubStationAlpha),
+            "<i>A</i><span style=\"color: #FF0000\"><b><i>B</i></b></span>C<br>D"
+        );
+        assert_eq!(
+            text_to_html("{y:i}{c:$00FF00}A|B", SubtitleFormat::MicroDVD),
+            "<span style=\"color: #00FF00\"><i>A</i></span><br><span style=\"color: #00FF00\"><i>B</i></span>"
+        );
+        assert_eq!(text_to_html("/A|B", SubtitleFormat::Mpl2), "<i>A</i><br>B");
+        assert_eq!(text_to_html("<font color=\"red\\\" onclick=\">A</font>", SubtitleFormat::SubRip), "A");
+
+        let entries = vec![SubtitleEntry {
+            speaker: Some("Anna".to_string()),
+            ..SubtitleEntry::new(TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2500)), "Hi".to_string())
+        }];
+        assert_eq!(
+            to_html(&entries, SubtitleFormat::SubRip, &HtmlOptions::new().document(HtmlDocument::Fragment).timestamps(false)),
+            "<div class=\"subtitles\">\n<p class=\"cue\" data-start=\"1.000\" data-end=\"2.500\"><span class=\"speaker\">Anna:</span>Hi</p>\n</div>\n"
+        );
+        let page = to_html(&entries, SubtitleFormat::SubRip, &HtmlOptions::new().title("<Episode 1>"));
+        assert!(page.starts_with("<!DOCTYPE html>\n"));
+        assert!(page.contains("<title>&lt;Episode 1&gt;</title>"));
+        assert!(page.contains("<span class=\"time\">0:00:01.000 &ndash; 0:00:02.500</span><span class=\"speaker\">Anna:</span>Hi</p>"));
+    }
+}
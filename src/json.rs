@@ -0,0 +1,241 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The schema of `SubtitleFile::to_json()` and `SubtitleFile::from_json()`, so tools in other languages can read
+//! and create the entries of all formats:
+//!
+//! ```json
+//! {
+//!   "format": "SubRip",
+//!   "entries": [
+//!     {
+//!       "start_ms": 1000,
+//!       "end_ms": 2500,
+//!       "text": "<i>Hello</i>\nWorld",
+//!       "speaker": "Anna",
+//!       "index": 1,
+//!       "tags": ["needs-review"],
+//!       "position": { "alignment": 8, "coordinates": { "unit": "pixels", "x": 320.0, "y": 50.0 } }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! * `format` is the name of the `SubtitleFormat` variant.
+//! * The texts are formatted like in the file (with `<i>` or `{\i1}`), but the line breaks are always `\n`. The
+//!   text of image based formats is `null` (the images are not exported).
+//! * `speaker`, `index` and `position` can be `null` and `tags` can be empty; all of them can be left out when
+//!   reading. The `alignment` is a numpad number (`1` is bottom left, `9` is top right) and the `unit` of the
+//!   coordinates is `"pixels"` or `"percent"`. The created files only keep what their format can store (no format
+//!   writes positions yet).
+
+use crate::errors::*;
+use crate::escaping::line_break;
+use crate::timetypes::{TimePoint, TimeSpan};
+use crate::{convert_entries, SubtitleAlignment, SubtitleCoordinates, SubtitleEntry, SubtitleFile, SubtitleFormat, SubtitlePosition};
+use failure::ResultExt;
+use serde::{Deserialize, Serialize};
+
+/// All entries of a subtitle file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSubtitles {
+    /// The name of the format (like `"SubRip"` or `"SubStationAlpha"`).
+    pub format: String,
+
+    /// The entries in the order of the file.
+    pub entries: Vec<JsonEntry>,
+}
+
+/// One subtitle entry (see `SubtitleEntry`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonEntry {
+    /// The start in milliseconds.
+    pub start_ms: i64,
+
+    /// The end in milliseconds.
+    pub end_ms: i64,
+
+    /// The text with `\n` line breaks.
+    pub text: Option<String>,
+
+    /// The name of the person who speaks.
+    #[serde(default)]
+    pub speaker: Option<String>,
+
+    /// The number of the entry as it is written in the file.
+    #[serde(default)]
+    pub index: Option<i64>,
+
+    /// User tags of the entry.
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    /// The placement on the screen.
+    #[serde(default)]
+    pub position: Option<JsonPosition>,
+}
+
+/// The placement of an entry (see `SubtitlePosition`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct JsonPosition {
+    /// The numpad number of the alignment (`1` to `9`).
+    pub alignment: u8,
+
+    /// Where the aligned point is placed.
+    #[serde(default)]
+    pub coordinates: Option<JsonCoordinates>,
+}
+
+/// The coordinates of the aligned point (see `SubtitleCoordinates`).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "unit", rename_all = "lowercase")]
+pub enum JsonCoordinates {
+    /// Distance from the left and top edge of the screen in pixels.
+    Pixels {
+        /// horizontal distance
+        x: f64,
+        /// vertical distance
+        y: f64,
+    },
+
+    /// Distance from the left and top edge of the screen in percent.
+    Percent {
+        /// horizontal distance
+        x: f64,
+        /// vertical distance
+        y: f64,
+    },
+}
+
+impl From<SubtitlePosition> for JsonPosition {
+    fn from(position: SubtitlePosition) -> JsonPosition {
+        JsonPosition {
+            alignment: position.alignment.to_numpad(),
+            coordinates: position.coordinates.map(|coordinates| match coordinates {
+                SubtitleCoordinates::Pixels { x, y } => JsonCoordinates::Pixels { x, y },
+                SubtitleCoordinates::Percent { x, y } => JsonCoordinates::Percent { x, y },
+            }),
+        }
+    }
+}
+
+impl JsonPosition {
+    /// The position, or `None` if the alignment is not a numpad number.
+    fn to_position(self) -> Option<SubtitlePosition> {
+        Some(SubtitlePosition {
+            alignment: SubtitleAlignment::from_numpad(self.alignment)?,
+            coordinates: self.coordinates.map(|coordinates| match coordinates {
+                JsonCoordinates::Pixels { x, y } => SubtitleCoordinates::Pixels { x, y },
+                JsonCoordinates::Percent { x, y } => SubtitleCoordinates::Percent { x, y },
+            }),
+        })
+    }
+}
+
+impl JsonSubtitles {
+    /// The entries of a subtitle file.
+    pub fn from_file(file: &SubtitleFile) -> Result<JsonSubtitles> {
+        let separator = line_break(file.format());
+        let entries = file
+            .get_subtitle_entries()?
+            .into_iter()
+            .map(|entry| JsonEntry {
+                start_ms: entry.timespan.start.msecs(),
+                end_ms: entry.timespan.end.msecs(),
+                text: match (entry.line, separator) {
+                    (Some(line), Some(separator)) => Some(line.split(separator).collect::<Vec<_>>().join("\n")),
+                    (line, _) => line,
+                },
+                speaker: entry.speaker,
+                index: entry.index,
+                tags: entry.tags,
+                position: entry.position.map(JsonPosition::from),
+            })
+            .collect();
+
+        Ok(JsonSubtitles {
+            format: format!("{:?}", file.format()),
+            entries,
+        })
+    }
+
+    /// Create a file of the format with the entries (see `convert_entries()` for the formats which can be created).
+    /// Unknown format names are an `ErrorKind::ParsingError`.
+    pub fn to_file(&self) -> Result<SubtitleFile> {
+        let format = SubtitleFormat::ALL
+            .iter()
+            .copied()
+            .find(|format| format!("{:?}", format) == self.format)
+            .ok_or(ErrorKind::ParsingError)?;
+
+        let entries: Vec<SubtitleEntry> = self
+            .entries
+            .iter()
+            .map(|entry| SubtitleEntry {
+                position: entry.position.and_then(JsonPosition::to_position),
+                speaker: entry.speaker.clone(),
+                index: entry.index,
+                tags: entry.tags.clone(),
+                ..SubtitleEntry::new(
+                    TimeSpan::new(TimePoint::from_msecs(entry.start_ms), TimePoint::from_msecs(entry.end_ms)),
+                    entry.text.clone().unwrap_or_default(),
+                )
+            })
+            .collect();
+
+        // the texts already have `\n` line breaks like `.srt` files
+        convert_entries(&entries, SubtitleFormat::SubRip, format)
+    }
+
+    /// The JSON document.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self).context(ErrorKind::SerializingError)?)
+    }
+
+    /// Read a JSON document.
+    pub fn from_json(s: &str) -> Result<JsonSubtitles> {
+        Ok(serde_json::from_str(s).context(ErrorKind::ParsingError)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str, SrtFile};
+
+    #[test]
+    fn json_test() {
+        let ssa = "[Script Info]\nScriptType: v4.00+\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.50,Default,Anna,0,0,0,,{\\i1}Hello{\\i0}\\NWorld\n";
+        let file = parse_str(SubtitleFormat::SubStationAlpha, ssa, 25.0).unwrap();
+        let json = file.to_json().unwrap();
+        let subtitles = JsonSubtitles::from_json(&json).unwrap();
+        assert_eq!(subtitles.format, "SubStationAlpha");
+        assert_eq!(subtitles.entries[0].start_ms, 1000);
+        assert_eq!(subtitles.entries[0].end_ms, 2500);
+        assert_eq!(subtitles.entries[0].text.as_deref(), Some("{\\i1}Hello{\\i0}\nWorld"));
+        assert_eq!(subtitles.entries[0].speaker.as_deref(), Some("Anna"));
+
+        let created = SubtitleFile::from_json(&json).unwrap();
+        assert_eq!(created.get_subtitle_entries().unwrap(), file.get_subtitle_entries().unwrap());
+
+        let minimal = r#"{"format": "SubRip", "entries": [{"start_ms": 0, "end_ms": 1000, "text": "A\nB",
+            "position": {"alignment": 8, "coordinates": {"unit": "percent", "x": 50.0, "y": 10.0}}}]}"#;
+        let position = JsonSubtitles::from_json(minimal).unwrap().entries[0].position.unwrap().to_position();
+        assert_eq!(position.map(|position| position.alignment), Some(SubtitleAlignment::TopCenter));
+        let created = SubtitleFile::from_json(minimal).unwrap();
+        assert_eq!(created.format(), SubtitleFormat::SubRip);
+        assert_eq!(created.get_subtitle_entries().unwrap()[0].line.as_deref(), Some("A\nB"));
+        assert_eq!(
+            JsonSubtitles::from_file(&SubtitleFile::from(SrtFile::parse("1\n00:00:00,000 --> 00:00:01,000\nA\n").unwrap()))
+                .unwrap()
+                .entries[0]
+                .position,
+            None
+        );
+
+        let wrong_format = SubtitleFile::from_json(r#"{"format": "Text", "entries": []}"#);
+        assert_eq!(wrong_format.unwrap_err().kind(), ErrorKind::ParsingError);
+        assert!(SubtitleFile::from_json("{").is_err());
+    }
+}
@@ -13,41 +13,182 @@
     unused_import_braces,
     unused_qualifications
 )]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-//! This crate provides a common interface for popular subtitle formats (`.srt`, `.ssa`, `.ass`, `.idx`, `.sub`).
+//! This crate provides a common interface for popular subtitle formats (`.srt`, `.ssa`, `.ass`, `.idx`, `.sub`, `.sbv`, `.stl`, `.sup`, `.lrc`, `.scc`, MPL2 and TMPlayer `.txt`).
 //!
 //! Files can be parsed, modified and saved again - some formats can be created from scratch.
 //! The focus is on non-destructive parsing, meaning that formatting and other information are preserved
 //! if not explicitely changed.
+//!
+//! Without the default `std` feature, only the time types, the entry types and the `lite` parsers for `.srt` and
+//! `MicroDVD` files are available (they only need `alloc`).
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
 extern crate combine;
+#[cfg(feature = "std")]
 extern crate encoding_rs;
+#[cfg(feature = "std")]
 extern crate failure;
+#[cfg(feature = "std")]
 extern crate itertools;
+#[cfg(feature = "std")]
+extern crate memchr;
+#[cfg(feature = "std")]
 extern crate vobsub;
 
 /// Error-chain generated error types.
+#[cfg(feature = "std")]
 #[macro_use]
+// the `failure` derive macro generates its impls inside of anonymous constants, so the `errors` modules of the
+// formats allow this lint too
+#[allow(non_local_definitions)]
 pub mod errors;
 
+#[cfg(feature = "std")]
 mod formats;
 
 /// Types that represent a time point, duration and time span.
 pub mod timetypes;
 
+/// Simple `.srt` and `MicroDVD` parsers, which only need `alloc` (for embedded media boxes or sandboxed plugins). They
+/// only read the entries; use `SrtFile` and `MdvdFile` to change and write files without losing information.
+pub mod lite;
+
+/// Parsers for single timestamps, which work like the ones of the subtitle parsers.
+#[cfg(feature = "std")]
+pub mod timeparse;
+
+/// Problems in subtitle files which do not prevent parsing them.
+#[cfg(feature = "std")]
+pub mod warnings;
+
+/// Options for creating the data of subtitle files.
+#[cfg(feature = "std")]
+pub mod serialize;
+
+/// Transformations of the text and the timing of subtitle entries.
+#[cfg(feature = "std")]
+pub mod transform;
+
+/// Plain text and Markdown transcripts of subtitle files.
+#[cfg(feature = "std")]
+pub mod transcript;
+
+/// HTML pages and fragments of subtitle files.
+#[cfg(feature = "std")]
+pub mod html;
+
+/// Text lengths in graphemes and display columns.
+#[cfg(feature = "std")]
+pub mod measure;
+
+/// Escaping rules of the text formats.
+#[cfg(feature = "std")]
+pub mod escaping;
+
+/// Helpers for burning subtitles into videos with ffmpeg.
+#[cfg(feature = "std")]
+pub mod ffmpeg;
+
+/// Recording changes of subtitle files for undo and redo.
+#[cfg(feature = "std")]
+pub mod edit;
+
+/// Checks for entries which are hard to read.
+#[cfg(feature = "std")]
+pub mod lint;
+
+/// Groups of subtitle files which are edited together.
+#[cfg(feature = "std")]
+pub mod project;
+
+/// Headers with provenance information for created files.
+#[cfg(feature = "std")]
+pub mod template;
+
+/// Converting many files at once.
+#[cfg(feature = "std")]
+pub mod pipeline;
+
+/// Reading the metadata of subtitle files without parsing them completely.
+#[cfg(feature = "std")]
+pub mod scan;
+
+/// Running all parsers on a corpus of problematic files (for regression tests).
+#[cfg(feature = "std")]
+pub mod replay;
+
+/// A JSON schema for the entries of all formats (requires the `serde` feature).
+#[cfg(feature = "serde")]
+pub mod json;
+
+/// Corpora for benchmarks (requires the `bench` feature).
+#[cfg(feature = "bench")]
+pub mod bench;
+/// The OpenSubtitles moviehash and upload metadata (requires the `opensubtitles` feature).
+#[cfg(feature = "opensubtitles")]
+pub mod opensubtitles;
+/// Random subtitle files and round-trip checks for tests (requires the `testutil` feature).
+#[cfg(all(feature = "std", any(test, feature = "testutil")))]
+pub mod testutil;
+
+use core::ops::Range;
+#[cfg(feature = "std")]
 use errors::*;
-pub use formats::idx::IdxFile;
-pub use formats::microdvd::MdvdFile;
-pub use formats::srt::SrtFile;
-pub use formats::ssa::SsaFile;
-pub use formats::vobsub::VobFile;
+#[cfg(feature = "std")]
+pub use formats::idx::{IdxDuplicatePolicy, IdxFile, IdxTrackInfo};
+#[cfg(feature = "std")]
+pub use formats::lrc::LrcFile;
+#[cfg(feature = "std")]
+pub use formats::markup;
+#[cfg(feature = "std")]
+pub use formats::microdvd::{MdvdDuplicatePolicy, MdvdFile, MdvdParseOptions};
+#[cfg(feature = "std")]
+pub use formats::mpl2::Mpl2File;
+#[cfg(feature = "std")]
+pub use formats::pgs::PgsFile;
+#[cfg(feature = "std")]
+pub use formats::sbv::SbvFile;
+#[cfg(feature = "std")]
+pub use formats::scc::SccFile;
+#[cfg(feature = "std")]
+pub use formats::srt::{SrtFile, SrtItalicNormalization, SrtMillisecondSeparator, SrtParseOptions, SrtProfile, SrtTimespanFix};
+#[cfg(feature = "std")]
+pub use formats::ssa::tags as ssa_tags;
+#[cfg(feature = "std")]
+pub use formats::ssa::{
+    create_karaoke_text, AssProfile, SsaAttachment, SsaAttachmentKind, SsaEffect, SsaEvent, SsaFile, SsaFileBuilder, SsaKaraokeEffect,
+    SsaKaraokeSyllable, SsaScrollDirection, SsaStyle, SsaStylesMut, SsaTimestampPrecision,
+};
+#[cfg(feature = "std")]
+pub use formats::stl::{StlFile, StlGsiField};
+#[cfg(feature = "std")]
+pub use formats::tmp::TmpFile;
+#[cfg(feature = "std")]
+pub use formats::vobsub::{read_ifo_palette, VobFile, VobPacket, VobPalette, DEFAULT_VOB_PALETTE};
+#[cfg(feature = "std")]
+pub use formats::{
+    convert, convert_entries, convert_entries_with_fps, get_subtitle_format, get_subtitle_format_by_extension, get_subtitle_format_by_extension_err,
+    get_subtitle_format_err, is_valid_extension_for_subtitle_format, parse_bytes, parse_bytes_auto, parse_bytes_lossy, parse_bytes_with_fallbacks,
+    parse_bytes_with_warnings, parse_str, parse_str_with_warnings, sniff_encoding,
+};
+#[cfg(feature = "std")]
 pub use formats::{
-    get_subtitle_format, get_subtitle_format_by_extension, get_subtitle_format_by_extension_err, get_subtitle_format_err,
-    is_valid_extension_for_subtitle_format, parse_bytes, parse_str,
+    ConvertOptions, DecodingReport, MemoryFootprint, ShiftedView, SubtitleDialect, SubtitleFile, SubtitleFormat, SubtitleFormatCapabilities,
 };
-pub use formats::{SubtitleFile, SubtitleFormat};
-use timetypes::TimeSpan;
+#[cfg(feature = "std")]
+pub use serialize::SerializeOptions;
+#[cfg(feature = "std")]
+pub use template::FileTemplate;
+use timetypes::{TimePoint, TimeSpan};
 
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "std")]
 /// This trait represents the generic interface for reading and writing subtitle information across all subtitle formats.
 ///
 /// This trait allows you to read, change and rewrite the subtitle file.
@@ -57,24 +198,53 @@ pub trait SubtitleFileInterface {
 
     /// Set the entries from the subtitle entries from the `get_subtitle_entries()`.
     ///
-    /// The length of the given input slice should always match the length of the vector length from
-    /// `get_subtitle_entries()`. This function can not delete/create new entries, but preserves
-    /// everything else in the file (formatting, authors, ...).
+    /// The length of the given input slice has to match the length of the vector length from
+    /// `get_subtitle_entries()` (otherwise `ErrorKind::EntryCountMismatch` is returned and the file is not
+    /// changed). This function can not delete/create new entries, but preserves everything else in the file
+    /// (formatting, authors, ...).
     ///
     /// If the input entry has `entry.line == None`, the line will not be overwritten.
     ///
     /// Be aware that .idx files cannot save time_spans_ (a subtitle will be shown between two
     /// consecutive timepoints/there are no separate starts and ends) - so the timepoint will be set
-    /// to the start of the corresponding input-timespan.
+    /// to the start of the corresponding input-timespan. Use `IdxFile::update_start_times()` to only
+    /// pass the timepoints.
     fn update_subtitle_entries(&mut self, i: &[SubtitleEntry]) -> Result<()>;
 
+    /// Works like `get_subtitle_entries()`, but the timespans are given as frame numbers for a video
+    /// with `fps` frames per second.
+    fn get_subtitle_entries_frames(&self, fps: f64) -> Result<Vec<SubtitleEntryFrames>> {
+        Ok(self
+            .get_subtitle_entries()?
+            .into_iter()
+            .map(|entry| SubtitleEntryFrames::from_entry(entry, fps))
+            .collect())
+    }
+
+    /// Works like `update_subtitle_entries()`, but the timespans are given as frame numbers for a
+    /// video with `fps` frames per second.
+    fn update_subtitle_entries_frames(&mut self, i: &[SubtitleEntryFrames], fps: f64) -> Result<()> {
+        let entries: Vec<SubtitleEntry> = i.iter().map(|entry| entry.to_entry(fps)).collect();
+        self.update_subtitle_entries(&entries)
+    }
+
     /// Returns a byte-stream in the respective format (.ssa, .srt, etc.) with the
     /// (probably) altered information.
     fn to_data(&self) -> Result<Vec<u8>>;
+
+    /// Works like `to_data()`, but the line endings, byte order mark, character encoding etc.
+    /// can be chosen with the `options`.
+    fn to_data_with(&self, options: &SerializeOptions) -> Result<Vec<u8>> {
+        options.apply_to_text(self.to_data()?)
+    }
 }
 
 /// The data which can be read from/written to a subtitle file.
-#[derive(Debug)]
+///
+/// Fields can be added in new versions, so entries are created with `SubtitleEntry::new()` or
+/// `SubtitleEntry::from(timespan)` and the other fields are set afterwards.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub struct SubtitleEntry {
     /// The duration for which the current subtitle will be shown.
     pub timespan: TimeSpan,
@@ -82,22 +252,264 @@ pub struct SubtitleEntry {
     // TODO: to Vec<String>
     /// The text which will be shown in this subtitle. Be aware that
     /// for example VobSub files (and any other image based format)
-    /// will have `None` as value.
+    /// will have `None` as value (and an `image` instead).
     pub line: Option<String>,
+
+    /// The image which will be shown in this subtitle. This is only
+    /// set for image based formats like VobSub.
+    pub image: Option<SubtitleImage>,
+
+    /// Where this subtitle should be placed on the screen, if the
+    /// subtitle file specifies it.
+    pub position: Option<SubtitlePosition>,
+
+    /// The name of the person who speaks this subtitle (for example the `Name` field
+    /// of `.ssa`/`.ass` files, several speakers of one cue are separated by `/`). Use
+    /// `SubtitleFile::update_subtitle_entries_with_speakers()` to write speakers to formats without such a field.
+    pub speaker: Option<String>,
+
+    /// The number of the entry as it is written in the file (the index line of `.srt` files). It
+    /// is only written back by `update_subtitle_entries()` if it is not `None`.
+    pub index: Option<i64>,
+
+    /// User tags of the entry (like `"needs-review"`, `"sign"` or `"song"`). They are kept in memory by `.srt`
+    /// and `.ssa`/`.ass` files (see `SubtitleFormatCapabilities::entry_tags`) and only written into the file
+    /// with `SerializeOptions::write_tags()`.
+    pub tags: Vec<String>,
 }
 
 impl SubtitleEntry {
-    /// Create subtitle entry with text.
-    fn new(timespan: TimeSpan, line: String) -> SubtitleEntry {
+    /// Create subtitle entry with text (the other fields are empty).
+    pub fn new(timespan: TimeSpan, line: String) -> SubtitleEntry {
         SubtitleEntry {
-            timespan: timespan,
+            timespan,
             line: Some(line),
+            image: None,
+            position: None,
+            speaker: None,
+            index: None,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// How the speakers of subtitle entries are written by `SubtitleFile::update_subtitle_entries_with_speakers()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpeakerPolicy {
+    /// The speakers are not written.
+    Omit,
+
+    /// Use the convention of the format: the `Name` field for `.ssa`/`.ass` files and `Dash` for all other text formats.
+    FormatDefault,
+
+    /// Start every line of a cue with several speakers with `- ` (dialogue convention of `.srt` files). The speakers of
+    /// such a cue are separated by `/`, like `Alice/Bob`; cues with one speaker are not changed.
+    Dash,
+
+    /// Start the text with the name of the speaker, like `Alice: text`.
+    NamePrefix,
+}
+
+/// The point of a subtitle that is placed at its position (in the layout of a numpad, like ASS's `\an`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum SubtitleAlignment {
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    MiddleLeft,
+    MiddleCenter,
+    MiddleRight,
+    TopLeft,
+    TopCenter,
+    TopRight,
+}
+
+impl SubtitleAlignment {
+    /// Get the alignment from a numpad number (`1` is `BottomLeft`, `9` is `TopRight`).
+    pub fn from_numpad(n: u8) -> Option<SubtitleAlignment> {
+        use self::SubtitleAlignment::*;
+        Some(match n {
+            1 => BottomLeft,
+            2 => BottomCenter,
+            3 => BottomRight,
+            4 => MiddleLeft,
+            5 => MiddleCenter,
+            6 => MiddleRight,
+            7 => TopLeft,
+            8 => TopCenter,
+            9 => TopRight,
+            _ => return None,
+        })
+    }
+
+    /// The numpad number of the alignment (`1` for `BottomLeft` to `9` for `TopRight`).
+    pub fn to_numpad(self) -> u8 {
+        self as u8 + 1
+    }
+}
+
+/// Coordinates of the aligned point of a subtitle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleCoordinates {
+    /// Distance from the left and top edge of the screen in pixels (of the resolution the subtitle file was made for).
+    Pixels {
+        /// horizontal distance
+        x: f64,
+        /// vertical distance
+        y: f64,
+    },
+
+    /// Distance from the left and top edge of the screen in percent of the screen width and height.
+    Percent {
+        /// horizontal distance
+        x: f64,
+        /// vertical distance
+        y: f64,
+    },
+}
+
+/// Placement of a subtitle on the screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubtitlePosition {
+    /// Which point of the subtitle is placed at the coordinates (or at the edge of the screen, if there are no coordinates).
+    pub alignment: SubtitleAlignment,
+
+    /// Where the aligned point is placed. With `None` the default position for the alignment is used.
+    pub coordinates: Option<SubtitleCoordinates>,
+}
+
+/// A decoded subtitle bitmap of an image based format like VobSub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleImage {
+    /// Distance of the left edge of the image to the left edge of the screen (in pixels).
+    pub x: u32,
+
+    /// Distance of the top edge of the image to the top edge of the screen (in pixels).
+    pub y: u32,
+
+    /// Width of the image in pixels.
+    pub width: u32,
+
+    /// Height of the image in pixels.
+    pub height: u32,
+
+    /// The RGBA pixel data in row-major order (four bytes per pixel).
+    pub data: Vec<u8>,
+}
+
+/// Where the fields of a subtitle entry are in the parsed input, as byte ranges (see `SubtitleFile::source_ranges()`).
+///
+/// The ranges always refer to the original input, even after the entries were changed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubtitleSourceRanges {
+    /// The start of the timespan.
+    pub start: Range<usize>,
+
+    /// The end of the timespan.
+    pub end: Range<usize>,
+
+    /// The text (one range per line if the lines are stored separately, like in `.srt` files).
+    pub text: Vec<Range<usize>>,
+}
+
+#[cfg(feature = "std")]
+/// Where an entry of a converted file comes from (see `SubtitleFile::convert_to_with_provenance()`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryProvenance {
+    /// The format of the source file.
+    pub format: SubtitleFormat,
+
+    /// The position of the entry in the entries of the source file.
+    pub entry_idx: usize,
+
+    /// The number of the entry as it is written in the source file (the index line of `.srt` files).
+    pub index: Option<i64>,
+
+    /// Where the entry is in the parsed input (only for parsed `.srt` and `.ssa`/`.ass` files, see
+    /// `SubtitleFile::source_ranges()`).
+    pub source: Option<SubtitleSourceRanges>,
+}
+
+#[cfg(feature = "std")]
+impl EntryProvenance {
+    /// The (zero-based) line with the start of the timespan in the parsed input `input`.
+    pub fn line_num(&self, input: &str) -> Option<usize> {
+        let source = self.source.as_ref()?;
+        Some(input.as_bytes().get(..source.start.start)?.iter().filter(|&&b| b == b'\n').count())
+    }
+}
+
+/// A `SubtitleEntry` where the start and end of the timespan are given as frame numbers.
+///
+/// This is useful for frame-centric tools, which would otherwise have to convert every
+/// timestamp from milliseconds to frames and back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleEntryFrames {
+    /// The frame at which this subtitle will be shown.
+    pub start_frame: i64,
+
+    /// The frame at which this subtitle will be hidden.
+    pub end_frame: i64,
+
+    /// The text which will be shown in this subtitle (see `SubtitleEntry::line`).
+    pub line: Option<String>,
+
+    /// The image which will be shown in this subtitle (see `SubtitleEntry::image`).
+    pub image: Option<SubtitleImage>,
+
+    /// The placement of this subtitle (see `SubtitleEntry::position`).
+    pub position: Option<SubtitlePosition>,
+
+    /// The speaker of this subtitle (see `SubtitleEntry::speaker`).
+    pub speaker: Option<String>,
+
+    /// The number of this subtitle in the file (see `SubtitleEntry::index`).
+    pub index: Option<i64>,
+
+    /// The user tags of this subtitle (see `SubtitleEntry::tags`).
+    pub tags: Vec<String>,
+}
+
+impl SubtitleEntryFrames {
+    /// Convert a subtitle entry into frame numbers with the given number of frames per second.
+    pub fn from_entry(entry: SubtitleEntry, fps: f64) -> SubtitleEntryFrames {
+        SubtitleEntryFrames {
+            start_frame: entry.timespan.start.to_frame(fps),
+            end_frame: entry.timespan.end.to_frame(fps),
+            line: entry.line,
+            image: entry.image,
+            position: entry.position,
+            speaker: entry.speaker,
+            index: entry.index,
+            tags: entry.tags,
+        }
+    }
+
+    /// Convert frame numbers back into a subtitle entry with the given number of frames per second.
+    pub fn to_entry(&self, fps: f64) -> SubtitleEntry {
+        SubtitleEntry {
+            timespan: TimeSpan::new(TimePoint::from_frame(self.start_frame, fps), TimePoint::from_frame(self.end_frame, fps)),
+            line: self.line.clone(),
+            image: self.image.clone(),
+            position: self.position,
+            speaker: self.speaker.clone(),
+            index: self.index,
+            tags: self.tags.clone(),
         }
     }
 }
 
 impl From<TimeSpan> for SubtitleEntry {
     fn from(f: TimeSpan) -> SubtitleEntry {
-        SubtitleEntry { timespan: f, line: None }
+        SubtitleEntry {
+            timespan: f,
+            line: None,
+            image: None,
+            position: None,
+            speaker: None,
+            index: None,
+            tags: Vec::new(),
+        }
     }
 }
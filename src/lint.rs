@@ -0,0 +1,529 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::timetypes::{TimeDelta, TimeStyle};
+use crate::transform::{mojibake_count, visible_len, visible_width};
+use crate::SubtitleEntry;
+use std::fmt;
+
+/// A problem of a subtitle entry which makes it hard to read (found by `lint()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LintWarning {
+    /// The (zero-based) index of the entry with the problem.
+    pub entry_idx: usize,
+
+    /// What kind of problem was found.
+    pub kind: LintKind,
+}
+
+/// The different problems which are reported by `lint()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// The entry is still shown when the entry with index `other_entry_idx` starts.
+    Overlap {
+        /// The (zero-based) index of the entry which starts while this entry is shown.
+        other_entry_idx: usize,
+    },
+
+    /// The end of the timespan lies before its start.
+    NegativeDuration,
+
+    /// The entry is shown for less than the minimum duration.
+    DurationTooShort {
+        /// How long the entry is shown.
+        duration: TimeDelta,
+    },
+
+    /// The entry is shown for more than the maximum duration.
+    DurationTooLong {
+        /// How long the entry is shown.
+        duration: TimeDelta,
+    },
+
+    /// The text has to be read faster than the maximum characters per second.
+    TooManyCharactersPerSecond {
+        /// The number of characters (graphemes, without formatting and line breaks, see `transform::visible_len()`).
+        characters: usize,
+
+        /// How long the entry is shown.
+        duration: TimeDelta,
+    },
+
+    /// A line has more than the maximum number of characters.
+    LineTooLong {
+        /// The (zero-based) index of the line in the text.
+        line_idx: usize,
+
+        /// The display width of the line (without formatting, see `transform::visible_width()`), so CJK characters
+        /// count twice.
+        length: usize,
+    },
+
+    /// The text has more than the maximum number of lines.
+    TooManyLines {
+        /// The number of lines.
+        lines: usize,
+    },
+
+    /// The entry has no visible text.
+    EmptyText,
+
+    /// The text contains characters which are probably the result of a wrong encoding (like `�` or `Ã¤`) or
+    /// control characters.
+    SuspiciousCharacters {
+        /// The number of suspicious characters.
+        count: usize,
+    },
+}
+
+impl LintKind {
+    /// A stable identifier of the kind of problem (like `"lint-overlap"`), so frontends can show their own
+    /// (translated) messages with the values of the variant.
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            LintKind::Overlap { .. } => "lint-overlap",
+            LintKind::NegativeDuration => "lint-negative-duration",
+            LintKind::DurationTooShort { .. } => "lint-duration-too-short",
+            LintKind::DurationTooLong { .. } => "lint-duration-too-long",
+            LintKind::TooManyCharactersPerSecond { .. } => "lint-too-many-characters-per-second",
+            LintKind::LineTooLong { .. } => "lint-line-too-long",
+            LintKind::TooManyLines { .. } => "lint-too-many-lines",
+            LintKind::EmptyText => "lint-empty-text",
+            LintKind::SuspiciousCharacters { .. } => "lint-suspicious-characters",
+        }
+    }
+
+    /// The (English) message with times in the given style. `Display` uses `TimeStyle::Default`.
+    pub fn message(&self, style: TimeStyle) -> String {
+        match self {
+            LintKind::Overlap { other_entry_idx } => format!("the entry overlaps with entry {}", other_entry_idx),
+            LintKind::NegativeDuration => "the timespan ends before it starts".to_string(),
+            LintKind::DurationTooShort { duration } => format!("the entry is only shown for {}", duration.format(style)),
+            LintKind::DurationTooLong { duration } => format!("the entry is shown for {}", duration.format(style)),
+            LintKind::TooManyCharactersPerSecond { characters, duration } => format!(
+                "the text has to be read with {:.1} characters per second",
+                characters_per_second(*characters, *duration)
+            ),
+            LintKind::LineTooLong { line_idx, length } => format!("line {} has {} characters", line_idx, length),
+            LintKind::TooManyLines { lines } => format!("the text has {} lines", lines),
+            LintKind::EmptyText => "the entry has no text".to_string(),
+            LintKind::SuspiciousCharacters { count } => format!("the text has {} suspicious characters", count),
+        }
+    }
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(TimeStyle::Default))
+    }
+}
+
+impl LintWarning {
+    /// The (English) message with times in the given style. `Display` uses `TimeStyle::Default`.
+    pub fn message(&self, style: TimeStyle) -> String {
+        format!("{} (entry {})", self.kind.message(style), self.entry_idx)
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(TimeStyle::Default))
+    }
+}
+
+/// The limits which are checked by `lint()`, created like `LintOptions::new().max_line_len(Some(37))`.
+///
+/// Checks with a limit can be disabled by setting the limit to `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LintOptions {
+    overlaps: bool,
+    min_duration: Option<TimeDelta>,
+    max_duration: Option<TimeDelta>,
+    max_characters_per_second: Option<f64>,
+    max_line_len: Option<usize>,
+    max_lines: Option<usize>,
+    suspicious_characters: bool,
+}
+
+impl Default for LintOptions {
+    fn default() -> LintOptions {
+        LintOptions {
+            overlaps: true,
+            min_duration: Some(TimeDelta::from_msecs(700)),
+            max_duration: Some(TimeDelta::from_secs(7)),
+            max_characters_per_second: Some(25.0),
+            max_line_len: Some(42),
+            max_lines: Some(2),
+            suspicious_characters: true,
+        }
+    }
+}
+
+impl LintOptions {
+    /// Create the default options (all checks enabled with common limits).
+    pub fn new() -> LintOptions {
+        LintOptions::default()
+    }
+
+    /// Whether overlapping entries are reported (`true` by default).
+    pub fn overlaps(mut self, overlaps: bool) -> LintOptions {
+        self.overlaps = overlaps;
+        self
+    }
+
+    /// Set the minimum duration of entries (700 milliseconds by default).
+    pub fn min_duration(mut self, min_duration: Option<TimeDelta>) -> LintOptions {
+        self.min_duration = min_duration;
+        self
+    }
+
+    /// Set the maximum duration of entries (7 seconds by default).
+    pub fn max_duration(mut self, max_duration: Option<TimeDelta>) -> LintOptions {
+        self.max_duration = max_duration;
+        self
+    }
+
+    /// Set the maximum number of characters per second (25 by default).
+    pub fn max_characters_per_second(mut self, max_characters_per_second: Option<f64>) -> LintOptions {
+        self.max_characters_per_second = max_characters_per_second;
+        self
+    }
+
+    /// Set the maximum display width of a line (42 by default), in which CJK characters count twice.
+    pub fn max_line_len(mut self, max_line_len: Option<usize>) -> LintOptions {
+        self.max_line_len = max_line_len;
+        self
+    }
+
+    /// Set the maximum number of lines per entry (2 by default).
+    pub fn max_lines(mut self, max_lines: Option<usize>) -> LintOptions {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Whether suspicious characters are reported (`true` by default).
+    pub fn suspicious_characters(mut self, suspicious_characters: bool) -> LintOptions {
+        self.suspicious_characters = suspicious_characters;
+        self
+    }
+}
+
+/// Find problems which make the entries hard to read, sorted by the entry index.
+///
+/// The text checks are skipped for entries without text (like the images of VobSub files). Line breaks may be
+/// given as `\n` or as `\N` (like in `.ssa`/`.ass` files), formatting is not counted.
+pub fn lint(entries: &[SubtitleEntry], options: &LintOptions) -> Vec<LintWarning> {
+    let mut result = Vec::new();
+    let mut warn = |entry_idx: usize, kind: LintKind| result.push(LintWarning { entry_idx, kind });
+
+    let mut by_start: Vec<usize> = (0..entries.len()).collect();
+    by_start.sort_by_key(|&i| entries[i].timespan.start);
+    let mut sorted_pos = vec![0; entries.len()];
+    for (pos, &i) in by_start.iter().enumerate() {
+        sorted_pos[i] = pos;
+    }
+
+    for (entry_idx, entry) in entries.iter().enumerate() {
+        let duration = entry.timespan.len();
+        if duration < TimeDelta::from_msecs(0) {
+            warn(entry_idx, LintKind::NegativeDuration);
+        } else if options.min_duration.map_or(false, |min| duration < min) {
+            warn(entry_idx, LintKind::DurationTooShort { duration });
+        } else if options.max_duration.map_or(false, |max| duration > max) {
+            warn(entry_idx, LintKind::DurationTooLong { duration });
+        }
+
+        if options.overlaps {
+            for &other_entry_idx in &by_start[sorted_pos[entry_idx] + 1..] {
+                if entries[other_entry_idx].timespan.start >= entry.timespan.end {
+                    break;
+                }
+                warn(entry_idx, LintKind::Overlap { other_entry_idx });
+            }
+        }
+
+        let text = match entry.line {
+            Some(ref text) => text,
+            None => continue,
+        };
+        let lines: Vec<&str> = text.split('\n').flat_map(|line| line.split("\\N")).collect();
+        let characters: usize = lines.iter().map(|line| visible_len(line.trim())).sum();
+
+        if characters == 0 {
+            warn(entry_idx, LintKind::EmptyText);
+            continue;
+        }
+        if let Some(max_cps) = options.max_characters_per_second {
+            if duration > TimeDelta::from_msecs(0) && characters_per_second(characters, duration) > max_cps {
+                warn(entry_idx, LintKind::TooManyCharactersPerSecond { characters, duration });
+            }
+        }
+        if let Some(max_line_len) = options.max_line_len {
+            for (line_idx, line) in lines.iter().enumerate() {
+                let length = visible_width(line.trim());
+                if length > max_line_len {
+                    warn(entry_idx, LintKind::LineTooLong { line_idx, length });
+                }
+            }
+        }
+        if options.max_lines.map_or(false, |max_lines| lines.len() > max_lines) {
+            warn(entry_idx, LintKind::TooManyLines { lines: lines.len() });
+        }
+        if options.suspicious_characters {
+            let count = suspicious_character_count(text);
+            if count > 0 {
+                warn(entry_idx, LintKind::SuspiciousCharacters { count });
+            }
+        }
+    }
+
+    result
+}
+
+fn characters_per_second(characters: usize, duration: TimeDelta) -> f64 {
+    characters as f64 / duration.secs_f64()
+}
+
+/// The number of replacement characters, control characters (except line breaks and tabs) and UTF-8 sequences
+/// which were decoded with a wrong encoding (like `Ã¤` instead of `ä`, see `transform::repair_mojibake()`).
+fn suspicious_character_count(text: &str) -> usize {
+    let replaced = text
+        .chars()
+        .filter(|&c| c == '\u{FFFD}' || (c.is_control() && !['\n', '\r', '\t'].contains(&c)))
+        .count();
+    replaced + mojibake_count(text)
+}
+
+/// The groups of problems which are weighted by `quality_score()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QualityCategory {
+    /// `LintKind::Overlap`
+    Overlaps,
+
+    /// `LintKind::TooManyCharactersPerSecond`
+    ReadingSpeed,
+
+    /// `LintKind::NegativeDuration`, `LintKind::DurationTooShort` and `LintKind::DurationTooLong`
+    Durations,
+
+    /// `LintKind::LineTooLong` and `LintKind::TooManyLines`
+    Layout,
+
+    /// `LintKind::SuspiciousCharacters`
+    SuspiciousCharacters,
+
+    /// `LintKind::EmptyText`
+    EmptyTexts,
+}
+
+impl QualityCategory {
+    /// All categories, in the order of the breakdown of `QualityScore`.
+    pub const ALL: [QualityCategory; 6] = [
+        QualityCategory::Overlaps,
+        QualityCategory::ReadingSpeed,
+        QualityCategory::Durations,
+        QualityCategory::Layout,
+        QualityCategory::SuspiciousCharacters,
+        QualityCategory::EmptyTexts,
+    ];
+
+    /// The points which are lost if every entry has a problem of this category (the weights add up to 100).
+    pub fn weight(self) -> f64 {
+        match self {
+            QualityCategory::Overlaps => 25.0,
+            QualityCategory::ReadingSpeed => 25.0,
+            QualityCategory::Durations => 20.0,
+            QualityCategory::Layout => 10.0,
+            QualityCategory::SuspiciousCharacters => 15.0,
+            QualityCategory::EmptyTexts => 5.0,
+        }
+    }
+
+    /// The category of a problem.
+    pub fn of(kind: &LintKind) -> QualityCategory {
+        match kind {
+            LintKind::Overlap { .. } => QualityCategory::Overlaps,
+            LintKind::TooManyCharactersPerSecond { .. } => QualityCategory::ReadingSpeed,
+            LintKind::NegativeDuration | LintKind::DurationTooShort { .. } | LintKind::DurationTooLong { .. } => QualityCategory::Durations,
+            LintKind::LineTooLong { .. } | LintKind::TooManyLines { .. } => QualityCategory::Layout,
+            LintKind::SuspiciousCharacters { .. } => QualityCategory::SuspiciousCharacters,
+            LintKind::EmptyText => QualityCategory::EmptyTexts,
+        }
+    }
+}
+
+/// The points a file loses for the problems of one category (see `QualityScore::breakdown`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityPenalty {
+    /// The category of the problems.
+    pub category: QualityCategory,
+
+    /// The number of entries with at least one problem of this category.
+    pub affected_entries: usize,
+
+    /// The lost points: the weight of the category times the share of affected entries.
+    pub penalty: f64,
+}
+
+/// The result of `quality_score()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityScore {
+    /// The score from `0` (every entry has every kind of problem) to `100` (no problems).
+    pub score: f64,
+
+    /// The penalties of all categories (in the order of `QualityCategory::ALL`), which add up to `100 - score`.
+    pub breakdown: Vec<QualityPenalty>,
+}
+
+/// Rate the entries with a score from 0 to 100 which is based on the warnings of `lint()`, for example for
+/// ranking uploads of the same movie.
+///
+/// Every category of problems (see `QualityCategory::weight()`) costs points in proportion to the share of entries
+/// which have such a problem, so the score doesn't depend on the length of the file. Files without entries get
+/// the full score.
+pub fn quality_score(entries: &[SubtitleEntry], options: &LintOptions) -> QualityScore {
+    let warnings = lint(entries, options);
+    let breakdown: Vec<QualityPenalty> = QualityCategory::ALL
+        .iter()
+        .map(|&category| {
+            let mut affected: Vec<usize> = warnings
+                .iter()
+                .filter(|warning| QualityCategory::of(&warning.kind) == category)
+                .map(|warning| warning.entry_idx)
+                .collect();
+            affected.dedup(); // the warnings are sorted by the entry index
+
+            let penalty = if entries.is_empty() {
+                0.0
+            } else {
+                category.weight() * affected.len() as f64 / entries.len() as f64
+            };
+            QualityPenalty {
+                category,
+                affected_entries: affected.len(),
+                penalty,
+            }
+        })
+        .collect();
+
+    let score = (100.0 - breakdown.iter().map(|penalty| penalty.penalty).sum::<f64>()).max(0.0);
+    QualityScore { score, breakdown }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::{TimePoint, TimeSpan};
+
+    #[test]
+    fn lint_test() {
+        let entry = |start: i64, end: i64, text: &str| {
+            SubtitleEntry::new(TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end)), text.to_string())
+        };
+        let entries = vec![
+            entry(0, 2000, "<i>Hello</i>\nthere"),
+            entry(1500, 1800, "Overlapping and too fast"),
+            entry(5000, 4000, "Negative"),
+            entry(6000, 20000, "{\\i1}a\\Nb\\Nc{\\i0}"),
+            entry(30000, 31000, " "),
+        ];
+
+        let kinds = |options: &LintOptions| lint(&entries, options).into_iter().map(|w| (w.entry_idx, w.kind)).collect::<Vec<_>>();
+        assert_eq!(
+            kinds(&LintOptions::new().max_line_len(Some(20))),
+            vec![
+                (0, LintKind::Overlap { other_entry_idx: 1 }),
+                (
+                    1,
+                    LintKind::DurationTooShort {
+                        duration: TimeDelta::from_msecs(300)
+                    }
+                ),
+                (
+                    1,
+                    LintKind::TooManyCharactersPerSecond {
+                        characters: 24,
+                        duration: TimeDelta::from_msecs(300)
+                    }
+                ),
+                (1, LintKind::LineTooLong { line_idx: 0, length: 24 }),
+                (2, LintKind::NegativeDuration),
+                (
+                    3,
+                    LintKind::DurationTooLong {
+                        duration: TimeDelta::from_secs(14)
+                    }
+                ),
+                (3, LintKind::TooManyLines { lines: 3 }),
+                (4, LintKind::EmptyText),
+            ]
+        );
+
+        let options = LintOptions::new()
+            .overlaps(false)
+            .min_duration(None)
+            .max_duration(None)
+            .max_characters_per_second(None)
+            .max_lines(None);
+        assert_eq!(kinds(&options), vec![(2, LintKind::NegativeDuration), (4, LintKind::EmptyText)]);
+        assert_eq!(lint(&entries, &options)[0].to_string(), "the timespan ends before it starts (entry 2)");
+
+        let warning = lint(&entries, &LintOptions::new())[1];
+        assert_eq!(warning.kind.message_id(), "lint-duration-too-short");
+        assert_eq!(warning.message(TimeStyle::Seconds), "the entry is only shown for 0.300s (entry 1)");
+        assert_eq!(warning.message(TimeStyle::Srt), "the entry is only shown for 00:00:00,300 (entry 1)");
+    }
+
+    #[test]
+    fn lint_grapheme_test() {
+        let entry = |text: &str| SubtitleEntry::new(TimeSpan::new(TimePoint::from_msecs(0), TimePoint::from_msecs(1000)), text.to_string());
+        let options = LintOptions::new().max_characters_per_second(Some(4.0)).max_line_len(Some(15));
+        let kinds = |text: &str| lint(&[entry(text)], &options).into_iter().map(|w| w.kind).collect::<Vec<_>>();
+
+        // the combining accent is part of the `e`
+        assert_eq!(kinds("Cafe\u{301}"), vec![]);
+        assert_eq!(
+            kinds("日本語のテキスト"),
+            vec![
+                LintKind::TooManyCharactersPerSecond {
+                    characters: 8,
+                    duration: TimeDelta::from_secs(1)
+                },
+                LintKind::LineTooLong { line_idx: 0, length: 16 },
+            ]
+        );
+    }
+
+    #[test]
+    fn quality_score_test() {
+        let entry = |start: i64, end: i64, text: &str| {
+            SubtitleEntry::new(TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end)), text.to_string())
+        };
+        let entries = vec![
+            entry(0, 2000, "Hello"),
+            entry(1500, 3000, "Gr\u{FFFD}\u{FFFD}e"),
+            entry(4000, 6000, "Ãœber"),
+            entry(7000, 9000, "Fine"),
+        ];
+        assert_eq!(
+            lint(&entries, &LintOptions::new())[1..],
+            [
+                LintWarning {
+                    entry_idx: 1,
+                    kind: LintKind::SuspiciousCharacters { count: 2 }
+                },
+                LintWarning {
+                    entry_idx: 2,
+                    kind: LintKind::SuspiciousCharacters { count: 1 }
+                },
+            ]
+        );
+
+        let score = quality_score(&entries, &LintOptions::new());
+        assert_eq!(score.breakdown[0].affected_entries, 1);
+        assert_eq!(score.breakdown[4].affected_entries, 2);
+        assert!((score.score - (100.0 - 25.0 / 4.0 - 15.0 / 2.0)).abs() < 1e-9);
+        assert_eq!(quality_score(&[], &LintOptions::new()).score, 100.0);
+    }
+}
@@ -0,0 +1,143 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::timetypes::{TimePoint, TimeSpan};
+use crate::SubtitleEntry;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Errors of the `lite` parsers (with the zero-based line number).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteError {
+    /// An `.srt` entry without a timespan line like `00:00:01,000 --> 00:00:02,000`.
+    ExpectedTimespanLine {
+        /// The first line of the entry.
+        line_num: usize,
+    },
+
+    /// A `MicroDVD` line which doesn't start like `{100}{200}`.
+    ExpectedMdvdLine {
+        /// The line.
+        line_num: usize,
+    },
+}
+
+impl fmt::Display for LiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LiteError::ExpectedTimespanLine { line_num } => {
+                write!(
+                    f,
+                    "expected SubRip timespan line like `00:00:01,000 --> 00:00:02,000` in entry at line `{}`",
+                    line_num
+                )
+            }
+            LiteError::ExpectedMdvdLine { line_num } => write!(f, "expected MicroDVD line like `{{100}}{{200}}Text` at line `{}`", line_num),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LiteError {}
+
+/// Parse a `.srt` timestamp like `00:24:45,670`.
+pub fn parse_srt_timestamp(s: &str) -> Option<TimePoint> {
+    let number = |s: &str| -> Option<i64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    };
+    let (hms, ms) = s.trim().split_once(',')?;
+    let mut parts = hms.splitn(3, ':');
+    let (hours, mins, secs) = (number(parts.next()?)?, number(parts.next()?)?, number(parts.next()?)?);
+    Some(TimePoint::from_components(hours, mins, secs, number(ms)?))
+}
+
+/// Parse the entries of a `.srt` file.
+///
+/// The entries have to be separated by empty lines; the text of an entry is the lines after its timespan line
+/// (joined with `\n`) and the number before it is the `SubtitleEntry::index`.
+pub fn parse_srt(s: &str) -> Result<Vec<SubtitleEntry>, LiteError> {
+    let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+    let mut entries = Vec::new();
+    let mut lines = s.lines().enumerate().peekable();
+
+    while let Some((line_num, _)) = lines.peek().copied() {
+        let block: Vec<&str> = core::iter::from_fn(|| lines.next_if(|(_, line)| !line.trim().is_empty()).map(|(_, line)| line)).collect();
+        if block.is_empty() {
+            lines.next();
+            continue;
+        }
+
+        let index = block[0].trim().parse::<i64>().ok();
+        let mut block = block.into_iter().skip(index.is_some() as usize);
+        let timespan = block
+            .next()
+            .and_then(parse_srt_timespan_line)
+            .ok_or(LiteError::ExpectedTimespanLine { line_num })?;
+
+        let mut entry = SubtitleEntry::new(timespan, block.collect::<Vec<_>>().join("\n"));
+        entry.index = index;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Parse a line like `00:24:45,670 --> 00:24:48,000` (coordinates after the end are ignored).
+fn parse_srt_timespan_line(line: &str) -> Option<TimeSpan> {
+    let (start, end) = line.split_once("-->")?;
+    let end = end.split_whitespace().next()?;
+    Some(TimeSpan::new(parse_srt_timestamp(start)?, parse_srt_timestamp(end)?))
+}
+
+/// Parse the entries of a `MicroDVD` `.sub` file (lines like `{100}{200}Line|Line2`) with `fps` frames per second.
+///
+/// The texts are returned as they are (with the `|` line breaks and formatting like `{y:i}`).
+pub fn parse_mdvd(s: &str, fps: f64) -> Result<Vec<SubtitleEntry>, LiteError> {
+    let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+    let frame = |frame: &str| -> Option<TimePoint> {
+        let frame = frame.strip_prefix('{')?.trim().parse::<i64>().ok()?;
+        TimePoint::from_secs_f64(frame as f64 / fps).ok()
+    };
+
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_num, line)| {
+            let mut parts = line.trim_start().splitn(3, '}');
+            let start = parts.next().and_then(frame);
+            let end = parts.next().and_then(frame);
+            match (start, end, parts.next()) {
+                (Some(start), Some(end), Some(text)) => Ok(SubtitleEntry::new(TimeSpan::new(start, end), text.to_string())),
+                _ => Err(LiteError::ExpectedMdvdLine { line_num }),
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{MdvdFile, SrtFile, SubtitleFileInterface};
+
+    #[test]
+    fn lite_test() {
+        let srt = "\u{feff}1\n00:00:01,000 --> 00:00:02,500\nHello\nWorld\n\n\n2\n00:01:00,000 --> 00:01:01,000\n<i>Bye</i>\n";
+        assert_eq!(parse_srt(srt).unwrap(), SrtFile::parse(srt).unwrap().get_subtitle_entries().unwrap());
+        assert_eq!(parse_srt("1\nHello\n"), Err(LiteError::ExpectedTimespanLine { line_num: 0 }));
+        assert_eq!(parse_srt_timestamp("01:02:03,004"), Some(TimePoint::from_components(1, 2, 3, 4)));
+        assert_eq!(parse_srt_timestamp("01:02:03.004"), None);
+
+        let mdvd = "{0}{25}Hello\n{50}{100}Bye\n";
+        assert_eq!(
+            parse_mdvd(mdvd, 25.0).unwrap(),
+            MdvdFile::parse(mdvd, 25.0).unwrap().get_subtitle_entries().unwrap()
+        );
+        assert_eq!(parse_mdvd("{0}{25}{y:i}A|B", 25.0).unwrap()[0].line.as_deref(), Some("{y:i}A|B"));
+        assert_eq!(parse_mdvd("{0}{25}A\nB\n", 25.0), Err(LiteError::ExpectedMdvdLine { line_num: 1 }));
+    }
+}
@@ -0,0 +1,165 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The length of a text as a viewer sees it: `grapheme_count()` counts `e` with a combining accent, a flag or an
+//! emoji with skin tone as one character, and `display_width()` counts the columns of a monospaced terminal, where
+//! CJK characters take two columns.
+//!
+//! The rules are a simplified version of Unicode's grapheme clusters (UAX #29) and East Asian Width (UAX #11) with
+//! the ranges which appear in subtitles; there are no Unicode tables in this library.
+
+/// The number of graphemes (user-perceived characters) in the text.
+pub fn grapheme_count(s: &str) -> usize {
+    graphemes(s).count()
+}
+
+/// The number of columns which the text takes in a monospaced font. Full-width characters (like CJK ideographs,
+/// kana, Hangul and most emoji) take two columns, combining marks and control characters none.
+pub fn display_width(s: &str) -> usize {
+    graphemes(s).map(grapheme_width).sum()
+}
+
+/// The graphemes of the text.
+pub fn graphemes(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        let (_, first) = chars.next()?;
+        let mut prev = first;
+        let mut end = rest.len();
+        for (i, c) in chars {
+            let joined = (prev == '\r' && c == '\n')
+                || is_extend(c)
+                || prev == '\u{200D}'
+                || (is_virama(prev) && is_indic_consonant(c))
+                || (is_regional_indicator(prev)
+                    && is_regional_indicator(c)
+                    && rest[..i].chars().filter(|&c| is_regional_indicator(c)).count() % 2 == 1);
+            if !joined {
+                end = i;
+                break;
+            }
+            prev = c;
+        }
+        let (grapheme, after) = rest.split_at(end);
+        rest = after;
+        Some(grapheme)
+    })
+}
+
+/// The width of a grapheme (see `display_width()`), which is the width of its first character.
+fn grapheme_width(grapheme: &str) -> usize {
+    match grapheme.chars().next() {
+        Some(c) if c.is_control() || is_extend(c) => 0,
+        Some(c) if is_wide(c) || is_regional_indicator(c) => 2,
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+/// Characters which belong to the previous grapheme: combining marks, joiners, variation selectors and emoji
+/// modifiers.
+fn is_extend(c: char) -> bool {
+    let c = c as u32;
+    match c {
+        0x0300..=0x036F | 0x0483..=0x0489 | 0x0591..=0x05BD | 0x05BF | 0x05C1..=0x05C2 | 0x05C4..=0x05C5 | 0x05C7 => true,
+        0x0610..=0x061A | 0x064B..=0x065F | 0x0670 | 0x06D6..=0x06DC | 0x06DF..=0x06E4 | 0x06E7..=0x06E8 | 0x06EA..=0x06ED => true,
+        // the Indic scripts (Devanagari to Sinhala) have their vowel signs and other marks at the same offsets
+        0x0900..=0x0DFF => matches!(c % 0x80, 0x00..=0x03 | 0x3A..=0x3C | 0x3E..=0x4F | 0x51..=0x57 | 0x62..=0x63),
+        0x0E31 | 0x0E34..=0x0E3A | 0x0E47..=0x0E4E | 0x0EB1 | 0x0EB4..=0x0EBC | 0x0EC8..=0x0ECD => true,
+        // Hangul vowels and final consonants after a leading consonant
+        0x1160..=0x11FF => true,
+        0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200C..=0x200D | 0x20D0..=0x20FF | 0x302A..=0x302F | 0x3099..=0x309A => true,
+        0xFE00..=0xFE0F | 0xFE20..=0xFE2F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F | 0xE0100..=0xE01EF => true,
+        _ => false,
+    }
+}
+
+/// The virama of an Indic script, which joins the consonants around it into a conjunct (like `स्त`).
+fn is_virama(c: char) -> bool {
+    matches!(c as u32, 0x0900..=0x0DFF) && c as u32 % 0x80 == 0x4D
+}
+
+fn is_indic_consonant(c: char) -> bool {
+    matches!(c as u32, 0x0900..=0x0DFF) && matches!(c as u32 % 0x80, 0x15..=0x39)
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Full-width characters (East Asian Width `W` and `F`).
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F
+            | 0x231A..=0x231B
+            | 0x2329..=0x232A
+            | 0x23E9..=0x23EC
+            | 0x25FD..=0x25FE
+            | 0x2614..=0x2615
+            | 0x26AA..=0x26AB
+            | 0x26BD..=0x26BE
+            | 0x26F5
+            | 0x26FA
+            | 0x26FD
+            | 0x2705
+            | 0x270A..=0x270B
+            | 0x2728
+            | 0x274C
+            | 0x2753..=0x2755
+            | 0x2757
+            | 0x2B1B..=0x2B1C
+            | 0x2B50
+            | 0x2E80..=0x303E
+            | 0x3041..=0x33FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF
+            | 0xA960..=0xA97F
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE10..=0xFE19
+            | 0xFE30..=0xFE6F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1B000..=0x1B2FF
+            | 0x1F004
+            | 0x1F0CF
+            | 0x1F18E
+            | 0x1F191..=0x1F19A
+            | 0x1F200..=0x1F251
+            | 0x1F300..=0x1F64F
+            | 0x1F680..=0x1F6FF
+            | 0x1F7E0..=0x1F7EB
+            | 0x1F90C..=0x1F9FF
+            | 0x1FA70..=0x1FAFF
+            | 0x20000..=0x2FFFD
+            | 0x30000..=0x3FFFD
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_test() {
+        assert_eq!(grapheme_count("Hello"), 5);
+        assert_eq!(grapheme_count("Cafe\u{301}"), 4);
+        assert_eq!(graphemes("a\r\nb").collect::<Vec<_>>(), vec!["a", "\r\n", "b"]);
+        // a family (joined with ZWJ), a space and a thumb with skin tone
+        assert_eq!(grapheme_count("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} \u{1F44D}\u{1F3FD}"), 3);
+        assert_eq!(graphemes("\u{1F1E9}\u{1F1EA}\u{1F1EB}\u{1F1F7}").count(), 2);
+        assert_eq!(grapheme_count("नमस्ते"), 3);
+
+        assert_eq!(display_width("Hello"), 5);
+        assert_eq!(display_width("こんにちは"), 10);
+        assert_eq!(display_width("你好, world"), 11);
+        assert_eq!(display_width("안녕"), 4);
+        assert_eq!(display_width("Ｈｉ"), 4);
+        assert_eq!(display_width("Cafe\u{301}"), 4);
+        assert_eq!(display_width("\u{1F44D}\u{1F3FD}!"), 3);
+    }
+}
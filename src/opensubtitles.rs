@@ -0,0 +1,135 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::{SubtitleFile, SubtitleFormat};
+use failure::ResultExt;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The size of the chunks at the start and end of the video which are used for the hash.
+const HASH_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Compute the OpenSubtitles "moviehash" of a video: the file size plus the sums of the first and last 64 KiB
+/// (read as little endian 64 bit numbers, overflowing). Use `format_moviehash()` to get the hexadecimal form.
+///
+/// The video has to be at least 64 KiB large, otherwise `ErrorKind::IoError` is returned.
+pub fn moviehash<R: Read + Seek>(video: &mut R) -> Result<u64> {
+    let size = video.seek(SeekFrom::End(0)).context(ErrorKind::IoError)?;
+    if size < HASH_CHUNK_SIZE {
+        return Err(ErrorKind::IoError.into());
+    }
+
+    let mut hash = size;
+    for &chunk_start in &[0, size - HASH_CHUNK_SIZE] {
+        video.seek(SeekFrom::Start(chunk_start)).context(ErrorKind::IoError)?;
+        let mut chunk = vec![0; HASH_CHUNK_SIZE as usize];
+        video.read_exact(&mut chunk).context(ErrorKind::IoError)?;
+
+        for word in chunk.chunks(8) {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(word);
+            hash = hash.wrapping_add(u64::from_le_bytes(bytes));
+        }
+    }
+    Ok(hash)
+}
+
+/// The moviehash as 16 hexadecimal digits, like OpenSubtitles expects it.
+pub fn format_moviehash(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+/// The metadata of a subtitle file for an upload to OpenSubtitles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadMetadata {
+    /// The format of the subtitle file.
+    pub format: SubtitleFormat,
+
+    /// The language of the subtitles (like `"eng"`).
+    pub language: String,
+
+    /// The frames per second of the video.
+    pub fps: f64,
+
+    /// The frame at which the last subtitle ends (the video has at least this many frames).
+    pub frame_count: i64,
+
+    /// The number of subtitle entries.
+    pub entry_count: usize,
+
+    /// The moviehash (see `moviehash()`) of the video, if it was given with `with_video()`.
+    pub moviehash: Option<String>,
+
+    /// The size of the video in bytes, if it was given with `with_video()`.
+    pub movie_byte_size: Option<u64>,
+}
+
+impl UploadMetadata {
+    /// Collect the metadata of a parsed subtitle file for a video with `fps` frames per second.
+    pub fn new(file: &SubtitleFile, language: &str, fps: f64) -> Result<UploadMetadata> {
+        let entries = file.get_subtitle_entries()?;
+        let format = match file {
+            SubtitleFile::SubRipFile(_) => SubtitleFormat::SubRip,
+            SubtitleFile::SubStationAlpha(_) => SubtitleFormat::SubStationAlpha,
+            SubtitleFile::VobSubIdxFile(_) => SubtitleFormat::VobSubIdx,
+            SubtitleFile::VobSubSubFile(_) => SubtitleFormat::VobSubSub,
+            SubtitleFile::MicroDVDFile(_) => SubtitleFormat::MicroDVD,
+            SubtitleFile::YouTubeSbvFile(_) => SubtitleFormat::YouTubeSbv,
+            SubtitleFile::EbuStlFile(_) => SubtitleFormat::EbuStl,
+            SubtitleFile::BluRayPgsFile(_) => SubtitleFormat::BluRayPgs,
+            SubtitleFile::Mpl2File(_) => SubtitleFormat::Mpl2,
+            SubtitleFile::TmPlayerFile(_) => SubtitleFormat::TmPlayer,
+            SubtitleFile::LrcFile(_) => SubtitleFormat::Lrc,
+            SubtitleFile::SccFile(_) => SubtitleFormat::Scc,
+        };
+
+        Ok(UploadMetadata {
+            format,
+            language: language.to_string(),
+            fps,
+            frame_count: entries.iter().map(|entry| entry.timespan.end.to_frame(fps)).max().unwrap_or(0),
+            entry_count: entries.len(),
+            moviehash: None,
+            movie_byte_size: None,
+        })
+    }
+
+    /// Add the moviehash and the size of the video.
+    pub fn with_video<R: Read + Seek>(mut self, video: &mut R) -> Result<UploadMetadata> {
+        self.moviehash = Some(format_moviehash(moviehash(video)?));
+        self.movie_byte_size = Some(video.seek(SeekFrom::End(0)).context(ErrorKind::IoError)?);
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use std::io::Cursor;
+
+    #[test]
+    fn moviehash_test() {
+        // all words are zero, except the first one of the first chunk and the last one of the last chunk
+        let mut video = vec![0u8; 3 * HASH_CHUNK_SIZE as usize];
+        video[0] = 1;
+        let len = video.len();
+        video[len - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+        let hash = moviehash(&mut Cursor::new(&video)).unwrap();
+        assert_eq!(hash, (len as u64).wrapping_add(1).wrapping_add(u64::MAX));
+        assert_eq!(format_moviehash(0xab), "00000000000000ab");
+
+        assert!(moviehash(&mut Cursor::new(vec![0u8; 100])).is_err());
+
+        let file = parse_str(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000\nHello\n", 25.0).unwrap();
+        let metadata = UploadMetadata::new(&file, "eng", 25.0)
+            .unwrap()
+            .with_video(&mut Cursor::new(&video))
+            .unwrap();
+        assert_eq!(metadata.frame_count, 50);
+        assert_eq!(metadata.entry_count, 1);
+        assert_eq!(metadata.moviehash, Some(format_moviehash(hash)));
+        assert_eq!(metadata.movie_byte_size, Some(len as u64));
+    }
+}
@@ -0,0 +1,252 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A pipeline for converting many files at once:
+//!
+//! ```ignore
+//! use subparse::pipeline::Pipeline;
+//! use subparse::transform::{convert_case, TextCase};
+//! use subparse::SubtitleFormat;
+//!
+//! let pipeline = Pipeline::new()
+//!     .transform(|entries| {
+//!         convert_case(entries, TextCase::Sentence, &[]);
+//!         Ok(())
+//!     })
+//!     .convert_to(SubtitleFormat::SubRip);
+//!
+//! let report = pipeline.run(std::env::args().skip(1));
+//! for output in &report.outputs {
+//!     std::fs::write(output.path.with_extension("srt"), &output.data).unwrap();
+//! }
+//! for (path, error) in &report.failures {
+//!     eprintln!("{}: {}", path.display(), error);
+//! }
+//! ```
+
+use crate::errors::*;
+use crate::{convert_entries_with_fps, get_subtitle_format_err, parse_bytes, SerializeOptions, SubtitleEntry, SubtitleFile, SubtitleFormat};
+use encoding_rs::Encoding;
+use failure::ResultExt;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A step of a `Pipeline` which changes the entries of a file.
+type Transform = Box<dyn Fn(&mut Vec<SubtitleEntry>) -> Result<()> + Send + Sync>;
+
+/// The steps for processing a file: detect the format, decode and parse the file, change the entries, convert the
+/// file into another format and serialize it.
+///
+/// The steps are declared like `Pipeline::new().fps(23.976).convert_to(SubtitleFormat::SubRip)`. Without any
+/// declared step, the files are only parsed and written again.
+pub struct Pipeline {
+    format: Option<SubtitleFormat>,
+    encoding: Option<&'static Encoding>,
+    fps: f64,
+    transforms: Vec<Transform>,
+    target_format: Option<SubtitleFormat>,
+    serialize_options: SerializeOptions,
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field("format", &self.format)
+            .field("encoding", &self.encoding)
+            .field("fps", &self.fps)
+            .field("transforms", &self.transforms.len())
+            .field("target_format", &self.target_format)
+            .field("serialize_options", &self.serialize_options)
+            .finish()
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Pipeline {
+        Pipeline {
+            format: None,
+            encoding: None,
+            fps: 25.0,
+            transforms: Vec::new(),
+            target_format: None,
+            serialize_options: SerializeOptions::default(),
+        }
+    }
+}
+
+/// A file which was processed by a `Pipeline`.
+#[derive(Debug, Clone)]
+pub struct PipelineOutput {
+    /// The path of the input file.
+    pub path: PathBuf,
+
+    /// The format of the input file.
+    pub input_format: SubtitleFormat,
+
+    /// The processed file (in the format of `Pipeline::convert_to()`).
+    pub file: SubtitleFile,
+
+    /// The serialized file.
+    pub data: Vec<u8>,
+}
+
+/// The results of `Pipeline::run()`.
+#[derive(Debug, Default)]
+pub struct PipelineReport {
+    /// The files which were processed successfully, in the order of the inputs.
+    pub outputs: Vec<PipelineOutput>,
+
+    /// The files which could not be processed with the reason, in the order of the inputs.
+    pub failures: Vec<(PathBuf, Error)>,
+}
+
+impl PipelineReport {
+    /// Whether all files were processed successfully.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Pipeline {
+    /// Create a pipeline which detects the format and encoding of every file.
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Parse all files as `format` instead of detecting the format from the extension and content.
+    pub fn format(mut self, format: SubtitleFormat) -> Pipeline {
+        self.format = Some(format);
+        self
+    }
+
+    /// Decode all text files with `encoding` instead of detecting the encoding.
+    pub fn encoding(mut self, encoding: &'static Encoding) -> Pipeline {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Set the frames per second of `MicroDVD` files (25 by default), for parsing them and for converting into them.
+    pub fn fps(mut self, fps: f64) -> Pipeline {
+        self.fps = fps;
+        self
+    }
+
+    /// Add a step which changes the entries. The steps run in the order in which they were added.
+    ///
+    /// Entries can only be added or removed if the files are converted (see `convert_to()`).
+    pub fn transform<F>(mut self, transform: F) -> Pipeline
+    where
+        F: Fn(&mut Vec<SubtitleEntry>) -> Result<()> + Send + Sync + 'static,
+    {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Convert all files into `format` (see `convert_entries()`; `MicroDVD` files are created with the frame rate of `fps()`).
+    pub fn convert_to(mut self, format: SubtitleFormat) -> Pipeline {
+        self.target_format = Some(format);
+        self
+    }
+
+    /// Set the options for serializing the files (see `SubtitleFile::to_data_with()`).
+    pub fn serialize_options(mut self, options: SerializeOptions) -> Pipeline {
+        self.serialize_options = options;
+        self
+    }
+
+    /// Run all steps on the content of a file. The `path` is only used for detecting the format.
+    pub fn process(&self, path: &Path, data: &[u8]) -> Result<PipelineOutput> {
+        let input_format = match self.format {
+            Some(format) => format,
+            None => get_subtitle_format_err(path.extension(), data)?,
+        };
+        let mut file = parse_bytes(input_format, data, self.encoding, self.fps)?;
+
+        if !self.transforms.is_empty() || self.target_format.is_some() {
+            let mut entries = file.get_subtitle_entries()?;
+            for transform in &self.transforms {
+                transform(&mut entries)?;
+            }
+
+            file = match self.target_format {
+                Some(target_format) => convert_entries_with_fps(&entries, input_format, target_format, self.fps)?,
+                None => {
+                    file.try_update_subtitle_entries(&entries)?;
+                    file
+                }
+            };
+        }
+
+        let data = file.to_data_with(&self.serialize_options)?;
+        Ok(PipelineOutput {
+            path: path.to_path_buf(),
+            input_format,
+            file,
+            data,
+        })
+    }
+
+    /// Read and process all files. Files which can't be read or processed are collected in
+    /// `PipelineReport::failures`, so one broken file doesn't stop the others.
+    pub fn run<I, P>(&self, paths: I) -> PipelineReport
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut report = PipelineReport::default();
+        for path in paths {
+            let path = path.as_ref();
+            let result = std::fs::read(path)
+                .context(ErrorKind::IoError)
+                .map_err(Error::from)
+                .and_then(|data| self.process(path, &data));
+            match result {
+                Ok(output) => report.outputs.push(output),
+                Err(e) => report.failures.push((path.to_path_buf(), e)),
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimeDelta;
+
+    #[test]
+    fn pipeline_test() {
+        let dir = std::env::temp_dir().join(format!("subparse-pipeline-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.srt"), "1\n00:00:01,000 --> 00:00:02,000\nHello\n").unwrap();
+        std::fs::write(dir.join("b.sub"), "{25}{50}World").unwrap();
+        std::fs::write(dir.join("c.srt"), "no subtitles").unwrap();
+
+        let pipeline = Pipeline::new()
+            .transform(|entries| {
+                for entry in entries.iter_mut() {
+                    entry.timespan += TimeDelta::from_secs(1);
+                }
+                Ok(())
+            })
+            .convert_to(SubtitleFormat::SubRip);
+        let paths = ["a.srt", "b.sub", "c.srt", "missing.srt"].iter().map(|name| dir.join(name));
+        let report = pipeline.run(paths);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!report.is_success());
+        assert_eq!(report.outputs.len(), 2);
+        assert_eq!(report.outputs[1].input_format, SubtitleFormat::MicroDVD);
+        assert_eq!(
+            String::from_utf8(report.outputs[1].data.clone()).unwrap(),
+            "1\n00:00:02,000 --> 00:00:03,000\nWorld\n\n"
+        );
+        let failures: Vec<_> = report
+            .failures
+            .iter()
+            .map(|(path, e)| (path.file_name().unwrap().to_str().unwrap(), e.kind()))
+            .collect();
+        assert_eq!(failures, vec![("c.srt", ErrorKind::ParsingError), ("missing.srt", ErrorKind::IoError)]);
+    }
+}
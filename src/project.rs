@@ -0,0 +1,129 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::lint::{lint, LintOptions, LintWarning};
+use crate::timetypes::TimeDelta;
+use crate::SubtitleFile;
+use failure::ResultExt;
+use std::path::{Path, PathBuf};
+
+/// A subtitle file in a `SubtitleProject`.
+#[derive(Debug, Clone)]
+pub struct SubtitleTrack {
+    /// The name of the track (like `"original"`, `"translation"` or `"signs"`).
+    pub name: String,
+
+    /// Where the file is written by `SubtitleProject::export_all()`.
+    pub path: PathBuf,
+
+    /// The subtitles of the track.
+    pub file: SubtitleFile,
+}
+
+/// Multiple subtitle files which belong together, like the original, a translation and signs of a video.
+#[derive(Debug, Clone, Default)]
+pub struct SubtitleProject {
+    /// The tracks of the project, in the order in which they were added.
+    pub tracks: Vec<SubtitleTrack>,
+}
+
+impl SubtitleProject {
+    /// Create a project without tracks.
+    pub fn new() -> SubtitleProject {
+        SubtitleProject::default()
+    }
+
+    /// Add a track with the name `name`, which is written to `path`.
+    pub fn add_track<P: Into<PathBuf>>(&mut self, name: &str, path: P, file: SubtitleFile) {
+        self.tracks.push(SubtitleTrack {
+            name: name.to_string(),
+            path: path.into(),
+            file,
+        });
+    }
+
+    /// The first track with the name `name`.
+    pub fn track(&self, name: &str) -> Option<&SubtitleTrack> {
+        self.tracks.iter().find(|track| track.name == name)
+    }
+
+    /// The first track with the name `name`.
+    pub fn track_mut(&mut self, name: &str) -> Option<&mut SubtitleTrack> {
+        self.tracks.iter_mut().find(|track| track.name == name)
+    }
+
+    /// Shift the entries of all tracks by `delta`.
+    ///
+    /// If a track can't be shifted (like VobSub `.sub` files, whose entries can't be updated), an error is
+    /// returned and no track is changed.
+    pub fn shift_all(&mut self, delta: TimeDelta) -> Result<()> {
+        let shifted = self
+            .tracks
+            .iter()
+            .map(|track| track.file.shifted(delta).to_file())
+            .collect::<Result<Vec<SubtitleFile>>>()?;
+
+        for (track, file) in self.tracks.iter_mut().zip(shifted) {
+            track.file = file;
+        }
+        Ok(())
+    }
+
+    /// Lint the entries of all tracks (see `lint()`) and return the warnings of every track (in the order of the
+    /// tracks).
+    pub fn lint_all(&self, options: &LintOptions) -> Result<Vec<(&SubtitleTrack, Vec<LintWarning>)>> {
+        self.tracks
+            .iter()
+            .map(|track| Ok((track, lint(&track.file.get_subtitle_entries()?, options))))
+            .collect()
+    }
+
+    /// Write all tracks to their paths. The paths are relative to `dir` unless they are absolute.
+    pub fn export_all(&self, dir: &Path) -> Result<()> {
+        for track in &self.tracks {
+            let data = track.file.to_data()?;
+            std::fs::write(dir.join(&track.path), data).context(ErrorKind::IoError)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimePoint;
+    use crate::{parse_str, SubtitleFormat, VobFile};
+
+    #[test]
+    fn project_test() {
+        let original = parse_str(SubtitleFormat::SubRip, "1\n00:00:01,000 --> 00:00:02,000\nHello\n", 25.0).unwrap();
+        let signs = parse_str(SubtitleFormat::MicroDVD, "{0}{50}Station", 25.0).unwrap();
+
+        let mut project = SubtitleProject::new();
+        project.add_track("original", "original.srt", original);
+        project.add_track("signs", "signs.sub", signs);
+
+        project.shift_all(TimeDelta::from_msecs(1000)).unwrap();
+        let start = |project: &SubtitleProject, name: &str| project.track(name).unwrap().file.get_subtitle_entries().unwrap()[0].timespan.start;
+        assert_eq!(start(&project, "original"), TimePoint::from_msecs(2000));
+        assert_eq!(start(&project, "signs"), TimePoint::from_msecs(1000));
+
+        let warnings = project.lint_all(&LintOptions::new().min_duration(Some(TimeDelta::from_secs(2)))).unwrap();
+        assert_eq!(warnings[0].0.name, "original");
+        assert_eq!(warnings[0].1.len(), 1);
+        assert!(warnings[1].1.is_empty());
+
+        let dir = std::env::temp_dir().join(format!("subparse-project-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        project.export_all(&dir).unwrap();
+        assert_eq!(std::fs::read(dir.join("signs.sub")).unwrap(), b"{25}{75}Station".to_vec());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // no track is shifted if one of them can't be shifted
+        project.add_track("images", "images.sub", SubtitleFile::VobSubSubFile(VobFile::parse(&[]).unwrap()));
+        assert!(project.shift_all(TimeDelta::from_msecs(1000)).is_err());
+        assert_eq!(start(&project, "original"), TimePoint::from_msecs(2000));
+    }
+}
@@ -0,0 +1,318 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Every file of a corpus (like the sample files from bug reports) is parsed by every parser, strictly and
+//! leniently, so a regression suite can compare the outcomes with a stored report:
+//!
+//! ```ignore
+//! use subparse::replay::CorpusReplay;
+//!
+//! let report = CorpusReplay::new().run("tests/corpus".as_ref()).unwrap();
+//! assert!(report.panics().next().is_none());
+//! assert_eq!(report.to_tsv(), std::fs::read_to_string("tests/corpus.tsv").unwrap());
+//! ```
+
+use crate::errors::*;
+use crate::formats::{decode_bytes_to_string_lossy, detect_encoding};
+use crate::{
+    get_subtitle_format, parse_bytes, parse_bytes_lossy, MdvdFile, MdvdParseOptions, SrtFile, SrtParseOptions, SubtitleFile, SubtitleFormat,
+};
+use encoding_rs::Encoding;
+use failure::ResultExt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+/// How the files are parsed by a `CorpusReplay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplayMode {
+    /// With `parse_bytes()`, so malformed characters and all parse errors fail.
+    Strict,
+
+    /// With `parse_bytes_lossy()`; `.srt` and `.sub` files are also parsed with the repairs of `SrtParseOptions` and
+    /// `MdvdParseOptions`.
+    Lenient,
+}
+
+impl ReplayMode {
+    /// All modes, in the order of the results of a file.
+    pub const ALL: [ReplayMode; 2] = [ReplayMode::Strict, ReplayMode::Lenient];
+
+    /// The name of the mode in the report (`"strict"` or `"lenient"`).
+    pub fn name(self) -> &'static str {
+        match self {
+            ReplayMode::Strict => "strict",
+            ReplayMode::Lenient => "lenient",
+        }
+    }
+}
+
+/// What happened when a parser read a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    /// The file was parsed.
+    Parsed {
+        /// The number of entries.
+        entry_count: usize,
+
+        /// The number of parse warnings (only `.srt` and `MicroDVD` files report warnings) and replaced malformed
+        /// characters.
+        warning_count: usize,
+    },
+
+    /// The parser returned an error.
+    Failed {
+        /// The identifiers of the error and its causes (see `Error::message_ids()`).
+        message_ids: Vec<&'static str>,
+
+        /// The message of the error.
+        message: String,
+    },
+
+    /// The parser panicked, which is always a bug in this crate.
+    Panicked {
+        /// The message of the panic.
+        message: String,
+    },
+}
+
+/// The outcome of parsing a file with one parser in one mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayResult {
+    /// The path of the file.
+    pub path: PathBuf,
+
+    /// The format of the parser.
+    pub format: SubtitleFormat,
+
+    /// Whether `format` is the format which `get_subtitle_format()` detects for the file.
+    pub detected: bool,
+
+    /// How the file was parsed.
+    pub mode: ReplayMode,
+
+    /// What happened.
+    pub outcome: ReplayOutcome,
+}
+
+/// The results of `CorpusReplay::run()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// The results ordered by path, then by format (in the order of `SubtitleFormat::ALL`) and mode.
+    pub results: Vec<ReplayResult>,
+}
+
+impl ReplayReport {
+    /// The results in which a parser panicked.
+    pub fn panics(&self) -> impl Iterator<Item = &ReplayResult> {
+        self.results
+            .iter()
+            .filter(|result| matches!(result.outcome, ReplayOutcome::Panicked { .. }))
+    }
+
+    /// The report as tab-separated values with a header line: path, format, detected, mode, outcome (`parsed`,
+    /// `failed` or `panicked`), entry count, warning count, message ids (separated by commas) and message. Tabs and
+    /// line breaks in paths and messages are replaced by spaces.
+    pub fn to_tsv(&self) -> String {
+        let clean = |s: &str| s.replace(['\t', '\r', '\n'], " ");
+        let mut result = String::from("path\tformat\tdetected\tmode\toutcome\tentries\twarnings\tmessage_ids\tmessage\n");
+        for r in &self.results {
+            let (outcome, entry_count, warning_count, message_ids, message) = match &r.outcome {
+                ReplayOutcome::Parsed { entry_count, warning_count } => {
+                    ("parsed", entry_count.to_string(), warning_count.to_string(), String::new(), String::new())
+                }
+                ReplayOutcome::Failed { message_ids, message } => ("failed", String::new(), String::new(), message_ids.join(","), clean(message)),
+                ReplayOutcome::Panicked { message } => ("panicked", String::new(), String::new(), String::new(), clean(message)),
+            };
+            result.push_str(&format!(
+                "{}\t{:?}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                clean(&r.path.to_string_lossy()),
+                r.format,
+                r.detected,
+                r.mode.name(),
+                outcome,
+                entry_count,
+                warning_count,
+                message_ids,
+                message
+            ));
+        }
+        result
+    }
+}
+
+/// Runs all parsers in all modes on the files of a directory.
+///
+/// The results only depend on the files, so they can be compared with a stored report. Panics of the parsers are
+/// caught (the panic hook still prints them).
+#[derive(Debug, Clone, Copy)]
+pub struct CorpusReplay {
+    encoding: Option<&'static Encoding>,
+    fps: f64,
+}
+
+impl Default for CorpusReplay {
+    fn default() -> CorpusReplay {
+        CorpusReplay { encoding: None, fps: 25.0 }
+    }
+}
+
+impl CorpusReplay {
+    /// Create a replay which detects the encoding of every file.
+    pub fn new() -> CorpusReplay {
+        CorpusReplay::default()
+    }
+
+    /// Decode all text files with `encoding` instead of detecting the encoding.
+    pub fn encoding(mut self, encoding: &'static Encoding) -> CorpusReplay {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Set the frames per second of `MicroDVD` files (25 by default).
+    pub fn fps(mut self, fps: f64) -> CorpusReplay {
+        self.fps = fps;
+        self
+    }
+
+    /// Parse the content of a file with all parsers in all modes. The `path` is only used for detecting the format.
+    pub fn replay_file(&self, path: &Path, data: &[u8]) -> Vec<ReplayResult> {
+        let detected_format = get_subtitle_format(path.extension(), data);
+        let mut results = Vec::new();
+        for &format in &SubtitleFormat::ALL {
+            for &mode in &ReplayMode::ALL {
+                let outcome = match catch_unwind(AssertUnwindSafe(|| self.parse(format, mode, data))) {
+                    Ok(Ok(outcome)) => outcome,
+                    Ok(Err(e)) => ReplayOutcome::Failed {
+                        message_ids: e.message_ids(),
+                        message: e.to_string(),
+                    },
+                    Err(payload) => ReplayOutcome::Panicked {
+                        message: payload
+                            .downcast_ref::<&str>()
+                            .map(|s| s.to_string())
+                            .or_else(|| payload.downcast_ref::<String>().cloned())
+                            .unwrap_or_default(),
+                    },
+                };
+                results.push(ReplayResult {
+                    path: path.to_path_buf(),
+                    format,
+                    detected: detected_format == Some(format),
+                    mode,
+                    outcome,
+                });
+            }
+        }
+        results
+    }
+
+    fn parse(&self, format: SubtitleFormat, mode: ReplayMode, data: &[u8]) -> Result<ReplayOutcome> {
+        let (file, replaced_count) = match mode {
+            ReplayMode::Strict => (parse_bytes(format, data, self.encoding, self.fps)?, 0),
+            ReplayMode::Lenient if format == SubtitleFormat::SubRip => {
+                let options = SrtParseOptions {
+                    preserve_non_entry_blocks: true,
+                    fold_stray_lines: true,
+                    missing_index_lines: true,
+                    ..SrtParseOptions::default()
+                };
+                let (decoded, replaced) = decode_bytes_to_string_lossy(data, detect_encoding(data, self.encoding)?);
+                (SrtFile::parse_with_options(&decoded, options)?.into(), replaced.len())
+            }
+            ReplayMode::Lenient if format == SubtitleFormat::MicroDVD => {
+                let (decoded, replaced) = decode_bytes_to_string_lossy(data, detect_encoding(data, self.encoding)?);
+                (
+                    MdvdFile::parse_with_options(&decoded, self.fps, MdvdParseOptions::lenient())?.into(),
+                    replaced.len(),
+                )
+            }
+            ReplayMode::Lenient => {
+                let (file, report) = parse_bytes_lossy(format, data, self.encoding, self.fps)?;
+                (file, report.replaced_count())
+            }
+        };
+
+        let warning_count = match &file {
+            SubtitleFile::SubRipFile(f) => f.warnings().len(),
+            SubtitleFile::MicroDVDFile(f) => f.warnings().len(),
+            _ => 0,
+        };
+        Ok(ReplayOutcome::Parsed {
+            entry_count: file.get_subtitle_entries()?.len(),
+            warning_count: warning_count + replaced_count,
+        })
+    }
+
+    /// Replay all files in the directory `dir` and its subdirectories (ordered by their paths).
+    pub fn run(&self, dir: &Path) -> Result<ReplayReport> {
+        let mut paths = Vec::new();
+        collect_files(dir, &mut paths)?;
+        paths.sort();
+
+        let mut report = ReplayReport::default();
+        for path in paths {
+            let data = std::fs::read(&path).context(ErrorKind::IoError)?;
+            report.results.extend(self.replay_file(&path, &data));
+        }
+        Ok(report)
+    }
+}
+
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir).context(ErrorKind::IoError)? {
+        let path = entry.context(ErrorKind::IoError)?.path();
+        if path.is_dir() {
+            collect_files(&path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_test() {
+        let dir = std::env::temp_dir().join(format!("subparse-replay-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("issues")).unwrap();
+        std::fs::write(dir.join("a.srt"), "1\n00:00:01,000 --> 00:00:02,000\nHello\n\nstray\n").unwrap();
+        std::fs::write(dir.join("issues").join("b.sub"), "{25}{50}World").unwrap();
+
+        let replay = CorpusReplay::new().encoding(encoding_rs::UTF_8);
+        let report = replay.run(&dir).unwrap();
+        let again = replay.run(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report, again);
+        assert_eq!(report.results.len(), 2 * SubtitleFormat::ALL.len() * ReplayMode::ALL.len());
+        assert_eq!(report.panics().count(), 0);
+
+        let srt_results: Vec<_> = report
+            .results
+            .iter()
+            .filter(|r| r.detected && r.path.ends_with("a.srt"))
+            .map(|r| (r.mode, &r.outcome))
+            .collect();
+        assert_eq!(srt_results.len(), 2);
+        assert_eq!(srt_results[0].0, ReplayMode::Strict);
+        assert!(matches!(srt_results[0].1, ReplayOutcome::Failed { message_ids, .. } if message_ids[0] == "error-parsing"));
+        assert_eq!(
+            srt_results[1],
+            (
+                ReplayMode::Lenient,
+                &ReplayOutcome::Parsed {
+                    entry_count: 1,
+                    warning_count: 1
+                }
+            )
+        );
+
+        let tsv = report.to_tsv();
+        assert_eq!(tsv.lines().count(), report.results.len() + 1);
+        assert!(tsv.contains("b.sub\tMicroDVD\ttrue\tstrict\tparsed\t1\t0\t\t\n"));
+    }
+}
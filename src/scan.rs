@@ -0,0 +1,300 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::formats::detect_encoding;
+use crate::formats::lrc::LrcFile;
+use crate::formats::mpl2::Mpl2File;
+use crate::formats::pgs::PgsFile;
+use crate::formats::sbv::SbvFile;
+use crate::formats::scc::SccFile;
+use crate::formats::stl::StlFile;
+use crate::formats::tmp::TmpFile;
+use crate::formats::vobsub;
+use crate::timeparse::{parse_ass_timestamp, parse_idx_timestamp, parse_srt_timestamp};
+use crate::timetypes::{TimePoint, TimeSpan};
+use crate::{SubtitleFileInterface, SubtitleFormat};
+use encoding_rs::Encoding;
+
+/// The information of `scan_metadata()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleMetadata {
+    /// The format which was detected from the content.
+    pub format: SubtitleFormat,
+
+    /// The encoding of the text (`None` for binary formats).
+    pub encoding: Option<&'static Encoding>,
+
+    /// The number of entries.
+    pub entry_count: usize,
+
+    /// The earliest start of an entry (always `None` for `MicroDVD` files, which have no timestamps but frame
+    /// numbers).
+    pub first_timestamp: Option<TimePoint>,
+
+    /// The latest end of an entry (the latest start for `.idx` files, which have no end times).
+    pub last_timestamp: Option<TimePoint>,
+
+    /// The languages which are declared in the file (the `id:` lines of `.idx` files and the `Language` of
+    /// `.ssa`/`.ass` files), in the order of the file.
+    pub languages: Vec<String>,
+}
+
+/// Read the metadata of a subtitle file without creating the file (for indexing many files).
+///
+/// The format is detected from the content only, so this also works for files with wrong extensions. The text is
+/// decoded with the detected encoding; malformed characters and lines which can't be read are skipped. Returns
+/// `ErrorKind::UnknownFileFormat` if the content has no known format.
+pub fn scan_metadata(content: &[u8]) -> Result<SubtitleMetadata> {
+    // test for VobSub .sub magic number
+    if content.starts_with(&[0x00, 0x00, 0x01, 0xba]) {
+        return Ok(Scan::default()
+            .with_timespans(vobsub::scan_timespans(content))
+            .finish(SubtitleFormat::VobSubSub, None));
+    }
+
+    // every segment of a Blu-ray PGS stream starts with `PG`
+    if content.starts_with(b"PG") {
+        return Ok(Scan::default()
+            .with_timespans(PgsFile::scan_timespans(content)?)
+            .finish(SubtitleFormat::BluRayPgs, None));
+    }
+
+    // test for the disk format code "STLxx.01" of EBU-STL files
+    if content.len() >= 1024 && &content[3..6] == b"STL" {
+        let timespans = StlFile::parse(content)?.get_subtitle_entries()?.into_iter().map(|entry| entry.timespan);
+        return Ok(Scan::default().with_timespans(timespans).finish(SubtitleFormat::EbuStl, None));
+    }
+
+    let encoding = detect_encoding(content, None)?;
+    let (text, _, _) = encoding.decode(content);
+    let format = detect_text_format(&text).ok_or(ErrorKind::UnknownFileFormat)?;
+
+    let scan = match format {
+        SubtitleFormat::SubRip => scan_srt(&text),
+        SubtitleFormat::SubStationAlpha => scan_ssa(&text),
+        SubtitleFormat::VobSubIdx => scan_idx(&text),
+        SubtitleFormat::YouTubeSbv => Scan::default().with_timespans(text.lines().filter_map(|line| SbvFile::parse_timespan_line(0, line).ok())),
+        SubtitleFormat::Mpl2 => Scan::default().with_timespans(
+            text.lines()
+                .filter_map(|line| Mpl2File::parse(line).ok()?.get_subtitle_entries().ok()?.pop())
+                .map(|entry| entry.timespan),
+        ),
+        SubtitleFormat::TmPlayer => Scan::default().with_timespans(
+            TmpFile::parse(&text.lines().filter(|line| TmpFile::is_tmp_line(line)).collect::<Vec<_>>().join("\n"))?
+                .get_subtitle_entries()?
+                .into_iter()
+                .map(|entry| entry.timespan),
+        ),
+        SubtitleFormat::Lrc => Scan::default().with_timespans(LrcFile::parse(&text)?.get_subtitle_entries()?.into_iter().map(|entry| entry.timespan)),
+        SubtitleFormat::Scc => Scan::default().with_timespans(SccFile::parse(&text)?.get_subtitle_entries()?.into_iter().map(|entry| entry.timespan)),
+        SubtitleFormat::MicroDVD => Scan {
+            entry_count: text.lines().filter(|line| is_mdvd_line(line)).count(),
+            ..Scan::default()
+        },
+        SubtitleFormat::VobSubSub | SubtitleFormat::EbuStl | SubtitleFormat::BluRayPgs => unreachable!(),
+    };
+    Ok(scan.finish(format, Some(encoding)))
+}
+
+/// The format of the first line which only appears in one format.
+fn detect_text_format(text: &str) -> Option<SubtitleFormat> {
+    text.lines().map(|line| line.trim_start_matches('\u{feff}').trim()).find_map(|line| {
+        if line.starts_with("Scenarist_SCC") {
+            Some(SubtitleFormat::Scc)
+        } else if line.contains("VobSub index file") || line.starts_with("timestamp:") {
+            Some(SubtitleFormat::VobSubIdx)
+        } else if line.starts_with('[') && ["[script info]", "[v4 styles]", "[v4+ styles]", "[events]"].contains(&line.to_ascii_lowercase().as_str())
+        {
+            Some(SubtitleFormat::SubStationAlpha)
+        } else if is_mdvd_line(line) {
+            Some(SubtitleFormat::MicroDVD)
+        } else if Mpl2File::is_mpl2_line(line) {
+            Some(SubtitleFormat::Mpl2)
+        } else if TmpFile::is_tmp_line(line) {
+            Some(SubtitleFormat::TmPlayer)
+        } else if LrcFile::is_lrc_line(line) {
+            Some(SubtitleFormat::Lrc)
+        } else if SbvFile::parse_timespan_line(0, line).is_ok() {
+            Some(SubtitleFormat::YouTubeSbv)
+        } else if line.contains("-->") {
+            Some(SubtitleFormat::SubRip)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether the line starts like `{100}{200}` (the end frame can be empty).
+fn is_mdvd_line(line: &str) -> bool {
+    let mut parts = line.trim_start().splitn(3, '}');
+    let start = parts.next().and_then(|s| s.strip_prefix('{'));
+    let end = parts.next().and_then(|s| s.strip_prefix('{'));
+    match (start, end, parts.next()) {
+        (Some(start), Some(end), Some(_)) => {
+            !start.is_empty() && start.bytes().all(|b| b.is_ascii_digit()) && end.bytes().all(|b| b.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// The metadata which is collected while reading a file.
+#[derive(Debug, Default)]
+struct Scan {
+    entry_count: usize,
+    first_timestamp: Option<TimePoint>,
+    last_timestamp: Option<TimePoint>,
+    languages: Vec<String>,
+}
+
+impl Scan {
+    fn add_entry(&mut self, start: TimePoint, end: TimePoint) {
+        self.entry_count += 1;
+        self.first_timestamp = Some(self.first_timestamp.map_or(start, |t| t.min(start)));
+        self.last_timestamp = Some(self.last_timestamp.map_or(end, |t| t.max(end)));
+    }
+
+    fn with_timespans<I: IntoIterator<Item = TimeSpan>>(mut self, timespans: I) -> Scan {
+        for timespan in timespans {
+            self.add_entry(timespan.start, timespan.end);
+        }
+        self
+    }
+
+    fn finish(self, format: SubtitleFormat, encoding: Option<&'static Encoding>) -> SubtitleMetadata {
+        SubtitleMetadata {
+            format,
+            encoding,
+            entry_count: self.entry_count,
+            first_timestamp: self.first_timestamp,
+            last_timestamp: self.last_timestamp,
+            languages: self.languages,
+        }
+    }
+}
+
+fn scan_srt(text: &str) -> Scan {
+    let mut scan = Scan::default();
+    for line in text.lines().filter(|line| line.contains("-->")) {
+        let (start, end) = line.split_at(line.find("-->").unwrap());
+        // the end can be followed by coordinates like `X1:100 X2:200 Y1:100 Y2:200`
+        let end = end[3..].split_whitespace().next().unwrap_or_default();
+        if let (Ok(start), Ok(end)) = (parse_srt_timestamp(start), parse_srt_timestamp(end)) {
+            scan.add_entry(start, end);
+        }
+    }
+    scan
+}
+
+fn scan_ssa(text: &str) -> Scan {
+    let mut scan = Scan::default();
+    let mut section = String::new();
+    let mut fields: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            section = line.to_ascii_lowercase();
+            continue;
+        }
+
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        if section == "[script info]" && key.eq_ignore_ascii_case("Language") && !value.is_empty() {
+            scan.languages.push(value.to_string());
+        } else if section == "[events]" && key == "Format" {
+            fields = value.split(',').map(|field| field.trim().to_ascii_lowercase()).collect();
+        } else if section == "[events]" && key == "Dialogue" {
+            let values: Vec<&str> = value.splitn(fields.len().max(1), ',').collect();
+            let timestamp = |name: &str| {
+                let idx = fields.iter().position(|field| field == name)?;
+                parse_ass_timestamp(values.get(idx)?).ok()
+            };
+            if let (Some(start), Some(end)) = (timestamp("start"), timestamp("end")) {
+                scan.add_entry(start, end);
+            }
+        }
+    }
+    scan
+}
+
+fn scan_idx(text: &str) -> Scan {
+    let mut scan = Scan::default();
+    for line in text.lines().map(str::trim) {
+        if let Some(id) = line.strip_prefix("id:") {
+            // like `id: en, index: 0`
+            let language = id.split(',').next().unwrap_or_default().trim();
+            if !language.is_empty() {
+                scan.languages.push(language.to_string());
+            }
+        } else if let Some(timestamp) = line.strip_prefix("timestamp:") {
+            // like `timestamp: 00:00:10:000, filepos: 000000000`
+            if let Ok(t) = parse_idx_timestamp(timestamp.split(',').next().unwrap_or_default()) {
+                scan.add_entry(t, t);
+            }
+        }
+    }
+    scan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_metadata_test() {
+        let srt = "\u{feff}1\n00:00:01,000 --> 00:00:02,000\nA --> B\n\n2\n00:00:00,500 --> 00:00:03,000 X1:1 X2:2 Y1:1 Y2:2\nC\n";
+        let metadata = scan_metadata(srt.as_bytes()).unwrap();
+        assert_eq!(metadata.format, SubtitleFormat::SubRip);
+        assert_eq!(metadata.entry_count, 2);
+        assert_eq!(metadata.first_timestamp, Some(TimePoint::from_msecs(500)));
+        assert_eq!(metadata.last_timestamp, Some(TimePoint::from_msecs(3000)));
+        assert!(metadata.encoding.is_some());
+
+        let ssa = "[Script Info]\nLanguage: de\n\n[Events]\nFormat: Layer, Start, End, Text\n\
+                   Dialogue: 0,0:00:01.00,0:00:02.50,A, b\nComment: 0,0:00:09.00,0:00:10.00,C\n";
+        let metadata = scan_metadata(ssa.as_bytes()).unwrap();
+        assert_eq!(metadata.format, SubtitleFormat::SubStationAlpha);
+        assert_eq!(metadata.entry_count, 1);
+        assert_eq!(metadata.last_timestamp, Some(TimePoint::from_msecs(2500)));
+        assert_eq!(metadata.languages, vec!["de"]);
+
+        let idx = "# VobSub index file, v7 (do not modify this line!)\nid: en, index: 0\n\
+                   timestamp: 00:00:10:000, filepos: 000000000\ntimestamp: 00:00:12:000, filepos: 000000800\n";
+        let metadata = scan_metadata(idx.as_bytes()).unwrap();
+        assert_eq!((metadata.format, metadata.entry_count), (SubtitleFormat::VobSubIdx, 2));
+        assert_eq!(metadata.last_timestamp, Some(TimePoint::from_msecs(12000)));
+        assert_eq!(metadata.languages, vec!["en"]);
+
+        let metadata = scan_metadata(b"{0}{25}Hello\n{30}{}World\n").unwrap();
+        assert_eq!(
+            (metadata.format, metadata.entry_count, metadata.first_timestamp),
+            (SubtitleFormat::MicroDVD, 2, None)
+        );
+
+        let metadata = scan_metadata(b"0:00:01.500,0:00:04.000\nHello\n\n0:00:05.000,0:00:06.000\nWorld\n").unwrap();
+        assert_eq!((metadata.format, metadata.entry_count), (SubtitleFormat::YouTubeSbv, 2));
+        assert_eq!(metadata.last_timestamp, Some(TimePoint::from_msecs(6000)));
+
+        let image = crate::SubtitleImage {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        };
+        let timespans = [
+            TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(2000)),
+            TimeSpan::new(TimePoint::from_msecs(3000), TimePoint::from_msecs(4000)),
+        ];
+        let (_, vob_file) = crate::VobFile::create(timespans.iter().map(|&timespan| (timespan, image.clone())).collect(), 720, 576).unwrap();
+        let metadata = scan_metadata(&vob_file.to_data().unwrap()).unwrap();
+        let entries = vob_file.get_subtitle_entries().unwrap();
+        assert_eq!((metadata.format, metadata.entry_count), (SubtitleFormat::VobSubSub, 2));
+        assert_eq!(metadata.first_timestamp, Some(entries[0].timespan.start));
+        assert_eq!(metadata.last_timestamp, Some(entries[1].timespan.end));
+
+        assert_eq!(scan_metadata(b"no subtitles").unwrap_err().kind(), ErrorKind::UnknownFileFormat);
+    }
+}
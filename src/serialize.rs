@@ -0,0 +1,290 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::{AssProfile, IdxDuplicatePolicy, MdvdDuplicatePolicy, SrtItalicNormalization, SrtProfile};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// The line endings of the created subtitle data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewlineStyle {
+    /// Keep the line endings which `to_data()` creates (the ones of the parsed file if they are preserved).
+    Keep,
+
+    /// Unix line endings (`\n`).
+    Lf,
+
+    /// Windows line endings (`\r\n`).
+    CrLf,
+}
+
+/// What happens with the byte order mark at the start of the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrderMark {
+    /// Keep the byte order mark if `to_data()` creates one (because the parsed file had one).
+    Keep,
+
+    /// Always start with a byte order mark (only for UTF-8 and UTF-16).
+    Add,
+
+    /// Never start with a byte order mark.
+    Remove,
+}
+
+/// How much the text of the created subtitle data is cleaned up. Each level includes the previous ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TextNormalization {
+    /// The text is not changed.
+    None,
+
+    /// Remove spaces and tabs at the end of every line.
+    TrailingWhitespace,
+}
+
+/// Options for `to_data_with()`, which are created like
+/// `SerializeOptions::new().newline(NewlineStyle::CrLf).encoding(encoding_rs::WINDOWS_1252)`.
+///
+/// The default options create the same data as `to_data()`. All options except the per-format
+/// options only work on text formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializeOptions {
+    newline: NewlineStyle,
+    byte_order_mark: ByteOrderMark,
+    encoding: &'static Encoding,
+    normalization: TextNormalization,
+    srt_italic_normalization: Option<SrtItalicNormalization>,
+    mdvd_duplicate_policy: Option<MdvdDuplicatePolicy>,
+    idx_duplicate_policy: Option<IdxDuplicatePolicy>,
+    write_tags: bool,
+    srt_profile: Option<SrtProfile>,
+    ass_profile: Option<AssProfile>,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> SerializeOptions {
+        SerializeOptions {
+            newline: NewlineStyle::Keep,
+            byte_order_mark: ByteOrderMark::Keep,
+            encoding: UTF_8,
+            normalization: TextNormalization::None,
+            srt_italic_normalization: None,
+            mdvd_duplicate_policy: None,
+            idx_duplicate_policy: None,
+            write_tags: false,
+            srt_profile: None,
+            ass_profile: None,
+        }
+    }
+}
+
+impl SerializeOptions {
+    /// Create the default options (the data is the same as from `to_data()`).
+    pub fn new() -> SerializeOptions {
+        SerializeOptions::default()
+    }
+
+    /// Set the line endings (`NewlineStyle::Keep` by default).
+    pub fn newline(mut self, newline: NewlineStyle) -> SerializeOptions {
+        self.newline = newline;
+        self
+    }
+
+    /// Set whether the data starts with a byte order mark (`ByteOrderMark::Keep` by default).
+    pub fn byte_order_mark(mut self, byte_order_mark: ByteOrderMark) -> SerializeOptions {
+        self.byte_order_mark = byte_order_mark;
+        self
+    }
+
+    /// Set the character encoding of the data (UTF-8 by default).
+    pub fn encoding(mut self, encoding: &'static Encoding) -> SerializeOptions {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Set how much the text is cleaned up (`TextNormalization::None` by default).
+    pub fn normalization(mut self, normalization: TextNormalization) -> SerializeOptions {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Balance the `<i>`-tags of `.srt` files before writing them (see `SrtFile::normalize_italics()`).
+    pub fn srt_italic_normalization(mut self, italic_normalization: SrtItalicNormalization) -> SerializeOptions {
+        self.srt_italic_normalization = Some(italic_normalization);
+        self
+    }
+
+    /// Set how `.sub` (`MicroDVD`) files write lines with identical frame ranges (see `MdvdFile::set_duplicate_policy()`).
+    pub fn mdvd_duplicate_policy(mut self, duplicate_policy: MdvdDuplicatePolicy) -> SerializeOptions {
+        self.mdvd_duplicate_policy = Some(duplicate_policy);
+        self
+    }
+
+    /// Set how `.idx` files write timestamp lines with the same timestamp (see `IdxFile::set_duplicate_policy()`).
+    pub fn idx_duplicate_policy(mut self, duplicate_policy: IdxDuplicatePolicy) -> SerializeOptions {
+        self.idx_duplicate_policy = Some(duplicate_policy);
+        self
+    }
+
+    /// Write the tags of the entries (`SubtitleEntry::tags`) into the file (`false` by default).
+    ///
+    /// `.srt` files get a `NOTE tags: needs-review, sign` block after every tagged entry and `.ssa`/`.ass` files
+    /// get an `Effect` field like `tags:needs-review;sign` (only if the field holds no other effect). Both are
+    /// read back into the tags when the file is parsed (`.srt` files with `SrtParseOptions::read_tags_comments`).
+    /// Other formats can't store tags.
+    pub fn write_tags(mut self, write_tags: bool) -> SerializeOptions {
+        self.write_tags = write_tags;
+        self
+    }
+
+    /// Adjust `.srt` files for the quirks of players (see `SrtProfile`).
+    pub fn srt_profile(mut self, profile: SrtProfile) -> SerializeOptions {
+        self.srt_profile = Some(profile);
+        self
+    }
+
+    /// Adjust `.ssa`/`.ass` files for the quirks of renderers (see `AssProfile`).
+    pub fn ass_profile(mut self, profile: AssProfile) -> SerializeOptions {
+        self.ass_profile = Some(profile);
+        self
+    }
+
+    /// The `SrtItalicNormalization` which is applied before writing a `.srt` file.
+    pub fn get_srt_italic_normalization(&self) -> Option<SrtItalicNormalization> {
+        self.srt_italic_normalization
+    }
+
+    /// The `MdvdDuplicatePolicy` which is used for writing a `.sub` (`MicroDVD`) file.
+    pub fn get_mdvd_duplicate_policy(&self) -> Option<MdvdDuplicatePolicy> {
+        self.mdvd_duplicate_policy
+    }
+
+    /// The `IdxDuplicatePolicy` which is used for writing a `.idx` file.
+    pub fn get_idx_duplicate_policy(&self) -> Option<IdxDuplicatePolicy> {
+        self.idx_duplicate_policy
+    }
+
+    /// The `SrtProfile` which is used for writing a `.srt` file.
+    pub fn get_srt_profile(&self) -> Option<SrtProfile> {
+        self.srt_profile
+    }
+
+    /// The `AssProfile` which is used for writing a `.ssa`/`.ass` file.
+    pub fn get_ass_profile(&self) -> Option<AssProfile> {
+        self.ass_profile
+    }
+
+    /// Whether the tags of the entries are written into the file.
+    pub fn get_write_tags(&self) -> bool {
+        self.write_tags
+    }
+
+    /// Use `newline` if the line endings are not set (`NewlineStyle::Keep`).
+    pub(crate) fn or_newline(mut self, newline: NewlineStyle) -> SerializeOptions {
+        if self.newline == NewlineStyle::Keep {
+            self.newline = newline;
+        }
+        self
+    }
+
+    /// Do these options change anything about text data (besides the per-format options)?
+    pub(crate) fn changes_text(&self) -> bool {
+        let defaults = SerializeOptions::default();
+        self.newline != defaults.newline
+            || self.byte_order_mark != defaults.byte_order_mark
+            || self.encoding != defaults.encoding
+            || self.normalization != defaults.normalization
+    }
+
+    /// Apply the text options to the UTF-8 data from `to_data()`.
+    pub(crate) fn apply_to_text(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.changes_text() {
+            return Ok(data);
+        }
+
+        let text = String::from_utf8(data).map_err(|_| Error::from(ErrorKind::TextFormatOnly))?;
+        let (had_bom, text) = match text.strip_prefix('\u{FEFF}') {
+            Some(rest) => (true, rest),
+            None => (false, text.as_str()),
+        };
+
+        let mut text = match self.normalization {
+            TextNormalization::None => text.to_string(),
+            TextNormalization::TrailingWhitespace => {
+                // split at `\n` so `\r` stays at the end of the line
+                text.split('\n')
+                    .map(|line| match line.strip_suffix('\r') {
+                        Some(line) => line.trim_end_matches([' ', '\t']).to_string() + "\r",
+                        None => line.trim_end_matches([' ', '\t']).to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+
+        text = match self.newline {
+            NewlineStyle::Keep => text,
+            NewlineStyle::Lf => text.replace("\r\n", "\n"),
+            NewlineStyle::CrLf => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+        };
+
+        let write_bom = match self.byte_order_mark {
+            ByteOrderMark::Keep => had_bom,
+            ByteOrderMark::Add => true,
+            ByteOrderMark::Remove => false,
+        };
+
+        // `encoding_rs` can't encode to UTF-16 (it falls back to UTF-8)
+        let (bom, data): (&[u8], Vec<u8>) = if self.encoding == UTF_16LE {
+            (&[0xFF, 0xFE], text.encode_utf16().flat_map(|c| c.to_le_bytes().to_vec()).collect())
+        } else if self.encoding == UTF_16BE {
+            (&[0xFE, 0xFF], text.encode_utf16().flat_map(|c| c.to_be_bytes().to_vec()).collect())
+        } else {
+            let (data, _, had_errors) = self.encoding.encode(&text);
+            if had_errors {
+                return Err(ErrorKind::SerializingError.into());
+            }
+            (if self.encoding == UTF_8 { &[0xEF, 0xBB, 0xBF] } else { &[] }, data.into_owned())
+        };
+
+        let mut result = Vec::with_capacity(data.len() + bom.len());
+        if write_bom {
+            result.extend_from_slice(bom);
+        }
+        result.extend(data);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::{TimePoint, TimeSpan};
+    use crate::{SrtFile, SubtitleFileInterface};
+
+    #[test]
+    fn serialize_options_test() {
+        let timespan = TimeSpan::new(TimePoint::from_msecs(0), TimePoint::from_msecs(1000));
+        let file = SrtFile::create(vec![(timespan, "<i>Grüße\nline</i> \t".to_string())]).unwrap();
+        assert_eq!(file.to_data_with(&SerializeOptions::new()).unwrap(), file.to_data().unwrap());
+
+        let options = SerializeOptions::new()
+            .newline(NewlineStyle::CrLf)
+            .byte_order_mark(ByteOrderMark::Add)
+            .normalization(TextNormalization::TrailingWhitespace)
+            .srt_italic_normalization(SrtItalicNormalization::BalancePerLine);
+        let data = file.to_data_with(&options).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "\u{FEFF}1\r\n00:00:00,000 --> 00:00:01,000\r\n<i>Grüße</i>\r\n<i>line</i>\r\n\r\n"
+        );
+
+        let options = SerializeOptions::new().encoding(encoding_rs::WINDOWS_1252);
+        let data = file.to_data_with(&options).unwrap();
+        assert!(data.windows(4).any(|w| w == b"Gr\xFC\xDF"));
+
+        let options = SerializeOptions::new().encoding(UTF_16LE).byte_order_mark(ByteOrderMark::Add);
+        let data = file.to_data_with(&options).unwrap();
+        assert_eq!(&data[..4], &[0xFF, 0xFE, b'1', 0]);
+    }
+}
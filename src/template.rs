@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+/// The header of a created file (like `SrtFile::create_with_template()`), created like
+/// `FileTemplate::new().comment("Created by {tool} on {date}").value("tool", "subsync 1.0").value("date", "2026-10-14")`.
+///
+/// The title and comment are templates in which `{name}` is replaced by the value with that name. Placeholders
+/// without a value are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileTemplate {
+    title: Option<String>,
+    comment: Option<String>,
+    values: Vec<(String, String)>,
+    srt_note_block: bool,
+}
+
+impl FileTemplate {
+    /// Create a template without title and comment (the created files are the same as without a template).
+    pub fn new() -> FileTemplate {
+        FileTemplate::default()
+    }
+
+    /// Set the title of the file (the `Title` of `.ssa`/`.ass` files, the first line of the `NOTE` block in `.srt`
+    /// files, see `srt_note_block()`).
+    pub fn title(mut self, title: &str) -> FileTemplate {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// Set the comment at the start of the file, which may have multiple lines.
+    pub fn comment(mut self, comment: &str) -> FileTemplate {
+        self.comment = Some(comment.to_string());
+        self
+    }
+
+    /// Write the title and comment of `.srt` files into a `NOTE` block at the start of the file. `.srt` files have no
+    /// header, so this is off by default: `SrtFile::parse()` fails on such a block and only
+    /// `SrtParseOptions::preserve_non_entry_blocks` reads it.
+    pub fn srt_note_block(mut self, enabled: bool) -> FileTemplate {
+        self.srt_note_block = enabled;
+        self
+    }
+
+    /// Whether `.srt` files get a `NOTE` block (see `srt_note_block()`).
+    pub fn has_srt_note_block(&self) -> bool {
+        self.srt_note_block
+    }
+
+    /// Set the value which replaces the placeholder `{name}`.
+    pub fn value(mut self, name: &str, value: &str) -> FileTemplate {
+        self.values.retain(|(n, _)| n != name);
+        self.values.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Replace the placeholders in `template` with the values.
+    pub fn render(&self, template: &str) -> String {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let value = rest.find('}').and_then(|end| {
+                let name = &rest[1..end];
+                self.values.iter().find(|(n, _)| n == name).map(|(_, value)| (value, end))
+            });
+            match value {
+                Some((value, end)) => {
+                    result.push_str(value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    result.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// The title with the placeholders replaced.
+    pub fn rendered_title(&self) -> Option<String> {
+        self.title.as_ref().map(|title| self.render(title))
+    }
+
+    /// The lines of the comment with the placeholders replaced.
+    pub fn rendered_comment_lines(&self) -> Vec<String> {
+        match self.comment {
+            Some(ref comment) => self.render(comment).lines().map(str::to_string).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_test() {
+        let template = FileTemplate::new()
+            .title("{title} ({lang})")
+            .comment("Created by {tool}\non {date} {unknown} {")
+            .value("tool", "subsync")
+            .value("date", "2026-10-14")
+            .value("title", "Episode 1")
+            .value("tool", "subsync 1.0");
+        assert_eq!(template.rendered_title(), Some("Episode 1 ({lang})".to_string()));
+        assert_eq!(
+            template.rendered_comment_lines(),
+            vec!["Created by subsync 1.0", "on 2026-10-14 {unknown} {"]
+        );
+        assert!(FileTemplate::new().rendered_comment_lines().is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Generators for random subtitle files and the round-trip check which the tests of this crate use
+//! (requires the `testutil` feature outside of this crate).
+//!
+//! The generators are deterministic for a seed, so a failing seed can be reproduced:
+//!
+//! ```ignore
+//! use subparse::testutil::{assert_roundtrip, random_file, TestRng};
+//! use subparse::SubtitleFormat;
+//!
+//! for seed in 0..100 {
+//!     let file = random_file(&mut TestRng::new(seed), SubtitleFormat::SubRip, 20).unwrap();
+//!     assert_roundtrip(SubtitleFormat::SubRip, &file);
+//! }
+//! ```
+
+use crate::errors::*;
+use crate::timetypes::{TimeDelta, TimePoint, TimeSpan};
+use crate::{
+    parse_bytes, LrcFile, MdvdFile, Mpl2File, SbvFile, SccFile, SrtFile, SsaFile, StlFile, SubtitleEntry, SubtitleFile, SubtitleFormat,
+    SubtitleImage, TmpFile, VobFile,
+};
+use itertools::Itertools;
+use std::ops::Range;
+
+/// The frames per second of the MicroDVD files from `random_file()`, which `assert_roundtrip()` parses them with.
+pub const TEST_FPS: f64 = 25.0;
+
+/// A small pseudo random number generator (xorshift64*), so the tests don't depend on a random number crate.
+#[derive(Debug, Clone, Copy)]
+pub struct TestRng {
+    state: u64,
+}
+
+impl TestRng {
+    /// Create a generator; generators with the same seed return the same numbers.
+    pub fn new(seed: u64) -> TestRng {
+        // the state must never be zero
+        TestRng {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    /// The next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A random number in `range`, which must not be empty.
+    pub fn gen_range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "empty range");
+        range.start + self.next_u64() % (range.end - range.start)
+    }
+}
+
+const WORDS: [&str; 12] = [
+    "where", "are", "you", "going", "home", "I", "guess", "Ärger", "über", "the", "station", "42",
+];
+
+/// A random text of one or two lines (separated by `\n`) which is valid in every text format.
+pub fn random_text(rng: &mut TestRng) -> String {
+    let line_count = rng.gen_range(1..3);
+    (0..line_count)
+        .map(|_| {
+            let word_count = rng.gen_range(1..6);
+            (0..word_count).map(|_| WORDS[rng.gen_range(0..WORDS.len() as u64) as usize]).join(" ")
+        })
+        .join("\n")
+}
+
+/// `count` sorted entries with random text and timespans which don't overlap.
+///
+/// All times are multiples of 40 milliseconds, so they can be stored exactly in every format (also as frames with
+/// `TEST_FPS`).
+pub fn random_entries(rng: &mut TestRng, count: usize) -> Vec<SubtitleEntry> {
+    let mut start = TimePoint::from_msecs(40 * rng.gen_range(0..50) as i64);
+    (0..count)
+        .map(|_| {
+            let end = start + TimeDelta::from_msecs(40 * rng.gen_range(1..100) as i64);
+            let entry = SubtitleEntry::new(TimeSpan::new(start, end), random_text(rng));
+            start = end + TimeDelta::from_msecs(40 * rng.gen_range(0..50) as i64);
+            entry
+        })
+        .collect()
+}
+
+/// A random image with two colors for VobSub files.
+fn random_image(rng: &mut TestRng) -> SubtitleImage {
+    let (width, height) = (rng.gen_range(4..64) as u32, rng.gen_range(2..16) as u32);
+    let color = [rng.gen_range(0..256) as u8, rng.gen_range(0..256) as u8, rng.gen_range(0..256) as u8, 255];
+    let data = (0..width * height)
+        .flat_map(|i| if i % 3 == 0 { [255, 255, 255, 255] } else { color })
+        .collect();
+    SubtitleImage {
+        x: rng.gen_range(0..100) as u32,
+        y: rng.gen_range(0..100) as u32,
+        width,
+        height,
+        data,
+    }
+}
+
+/// A random file of the given format with `count` entries from `random_entries()`.
+///
+/// MicroDVD files are created with `TEST_FPS`. VobSub `.idx` and `.sub` files are created with random images by
+/// `VobFile::create()`. Blu-ray `.sup` files can't be created and return `ErrorKind::ConversionNotSupported`.
+pub fn random_file(rng: &mut TestRng, format: SubtitleFormat, count: usize) -> Result<SubtitleFile> {
+    let entries = random_entries(rng, count);
+    let file = match format {
+        SubtitleFormat::SubRip => SubtitleFile::SubRipFile(SrtFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::SubStationAlpha => SubtitleFile::SubStationAlpha(SsaFile::create_dual(&entries, &[])?),
+        SubtitleFormat::YouTubeSbv => SubtitleFile::YouTubeSbvFile(SbvFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::EbuStl => SubtitleFile::EbuStlFile(StlFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::Mpl2 => SubtitleFile::Mpl2File(Mpl2File::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::TmPlayer => SubtitleFile::TmPlayerFile(TmpFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan.start, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::Lrc => SubtitleFile::LrcFile(LrcFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan.start, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::Scc => SubtitleFile::SccFile(SccFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+        )?),
+        SubtitleFormat::MicroDVD => SubtitleFile::MicroDVDFile(MdvdFile::create(
+            entries
+                .into_iter()
+                .map(|entry| (entry.timespan, entry.line.unwrap_or_default()))
+                .collect(),
+            TEST_FPS,
+        )?),
+        SubtitleFormat::VobSubIdx | SubtitleFormat::VobSubSub => {
+            let images = entries.iter().map(|entry| (entry.timespan, random_image(rng))).collect();
+            let (idx_file, vob_file) = VobFile::create(images, 720, 480)?;
+            if format == SubtitleFormat::VobSubIdx {
+                SubtitleFile::VobSubIdxFile(idx_file)
+            } else {
+                SubtitleFile::VobSubSubFile(vob_file)
+            }
+        }
+        SubtitleFormat::BluRayPgs => return Err(ErrorKind::ConversionNotSupported { format }.into()),
+    };
+    Ok(file)
+}
+
+/// Check that `file` survives writing and parsing it again: the parsed file has the same entries and is written
+/// to the same data. MicroDVD files are parsed with `TEST_FPS`.
+///
+/// Panics with a description of the difference if the check fails.
+pub fn assert_roundtrip(format: SubtitleFormat, file: &SubtitleFile) {
+    let data = file.to_data().expect("writing the file failed");
+    // the text formats are written as UTF-8, which can't be detected reliably in small files
+    let mut parsed = match parse_bytes(format, &data, Some(encoding_rs::UTF_8), TEST_FPS) {
+        Ok(parsed) => parsed,
+        Err(e) => panic!(
+            "parsing the written {} data failed: {}\n{}",
+            format.get_name(),
+            e,
+            String::from_utf8_lossy(&data)
+        ),
+    };
+
+    // the palette of `.sub` files is stored in the `.idx` file
+    if let (SubtitleFile::VobSubSubFile(vob_file), SubtitleFile::VobSubSubFile(parsed_vob_file)) = (file, &mut parsed) {
+        parsed_vob_file.set_palette(*vob_file.palette());
+    }
+
+    let entries = file.get_subtitle_entries().expect("reading the entries failed");
+    let parsed_entries = parsed.get_subtitle_entries().expect("reading the parsed entries failed");
+    assert_eq!(entries.len(), parsed_entries.len(), "the number of entries changed");
+    for (i, (entry, parsed_entry)) in entries.iter().zip(parsed_entries.iter()).enumerate() {
+        assert_eq!(entry, parsed_entry, "entry {} changed", i);
+    }
+
+    let parsed_data = parsed.to_data().expect("writing the parsed file failed");
+    assert!(data == parsed_data, "writing the parsed {} file gives different data", format.get_name());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_test() {
+        let formats = [
+            SubtitleFormat::SubRip,
+            SubtitleFormat::SubStationAlpha,
+            SubtitleFormat::MicroDVD,
+            SubtitleFormat::VobSubIdx,
+            SubtitleFormat::VobSubSub,
+            SubtitleFormat::YouTubeSbv,
+            SubtitleFormat::EbuStl,
+            SubtitleFormat::Mpl2,
+            SubtitleFormat::TmPlayer,
+            SubtitleFormat::Lrc,
+            SubtitleFormat::Scc,
+        ];
+        for &format in &formats {
+            for seed in 0..20 {
+                let file = random_file(&mut TestRng::new(seed), format, seed as usize % 7).unwrap();
+                assert_roundtrip(format, &file);
+            }
+        }
+    }
+}
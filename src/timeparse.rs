@@ -0,0 +1,43 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::errors::*;
+use crate::timetypes::TimePoint;
+use crate::{IdxFile, SrtFile, SsaFile};
+use failure::ResultExt;
+
+/// Parse a `.srt` timestamp like `00:24:45,670` (or `00:24:45.670`).
+pub fn parse_srt_timestamp(s: &str) -> Result<TimePoint> {
+    Ok(SrtFile::parse_timestamp(s).with_context(|_| ErrorKind::ParsingError)?)
+}
+
+/// Parse a `.ssa`/`.ass` timestamp like `0:19:41.99` (the last number is in centiseconds, or in milliseconds if it has
+/// three digits).
+pub fn parse_ass_timestamp(s: &str) -> Result<TimePoint> {
+    Ok(SsaFile::parse_timepoint(0, s.trim()).with_context(|_| ErrorKind::ParsingError)?)
+}
+
+/// Parse a `.idx` timestamp like `00:41:36:961`.
+pub fn parse_idx_timestamp(s: &str) -> Result<TimePoint> {
+    Ok(IdxFile::parse_timestamp(0, s.trim()).with_context(|_| ErrorKind::ParsingError)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeparse_test() {
+        let t = TimePoint::from_components(0, 24, 45, 670);
+        assert_eq!(parse_srt_timestamp("00:24:45,670").unwrap(), t);
+        assert_eq!(parse_srt_timestamp(" 00:24:45,670\n").unwrap(), t);
+        assert_eq!(parse_ass_timestamp("0:24:45.67").unwrap(), t);
+        assert_eq!(parse_idx_timestamp("00:24:45:670").unwrap(), t);
+
+        assert_eq!(parse_srt_timestamp("00:24:45.670").unwrap(), t);
+        assert_eq!(parse_srt_timestamp("00:24:45;670").unwrap_err().kind(), ErrorKind::ParsingError);
+        assert!(parse_ass_timestamp("0:24:45,67 x").is_err());
+        assert!(parse_idx_timestamp("").is_err());
+    }
+}
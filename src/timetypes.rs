@@ -2,13 +2,68 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
-use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use core::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// Round to the nearest integer (`f64::round()` needs `std`).
+#[cfg(feature = "std")]
+fn round(x: f64) -> i64 {
+    x.round() as i64
+}
+
+/// Round to the nearest integer (`f64::round()` needs `std`).
+#[cfg(not(feature = "std"))]
+fn round(x: f64) -> i64 {
+    if x < 0.0 {
+        -((0.5 - x) as i64)
+    } else {
+        (x + 0.5) as i64
+    }
+}
+
+/// Why a number can't be converted into a `TimePoint` or `TimeDelta` (see `TimePoint::from_secs_f64()`), or why
+/// times can't be scaled (see `transform::scale_times()`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeConversionError {
+    /// The number is NaN (or a division by zero).
+    NotANumber,
+
+    /// The number is infinite or does not fit into the milliseconds of the time type.
+    OutOfRange,
+}
+
+impl Display for TimeConversionError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            TimeConversionError::NotANumber => write!(f, "the number of seconds is NaN"),
+            TimeConversionError::OutOfRange => write!(f, "the number of seconds is infinite or too large"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TimeConversionError {}
 
 /// Represents a timepoint (e.g. start timepoint of a subtitle line).
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Timing(i64 /* number of milliseconds */);
 
+/// How times are written by `TimePoint::format()` and `TimeDelta::format()` (for example in the messages of lint
+/// warnings).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeStyle {
+    /// Like `0:01:02.500`, which is also used by `Display`.
+    Default,
+
+    /// Like the timestamps of `.srt` files: `00:01:02,500`.
+    Srt,
+
+    /// The number of seconds: `62.500s`.
+    Seconds,
+}
+
 /// The internal timing in `TimePoint` and `TimeDelta` (with all necessary functions and nice Debug information, etc.).
 impl Timing {
     fn from_components(hours: i64, mins: i64, secs: i64, ms: i64) -> Timing {
@@ -35,6 +90,21 @@ impl Timing {
         Timing(h * 1000 * 60 * 60)
     }
 
+    fn from_secs_f64(s: f64) -> Result<Timing, TimeConversionError> {
+        Self::from_msecs_f64(s * 1000.0)
+    }
+
+    fn from_msecs_f64(ms: f64) -> Result<Timing, TimeConversionError> {
+        if ms.is_nan() {
+            Err(TimeConversionError::NotANumber)
+        } else if ms.is_infinite() || ms < i64::MIN as f64 || ms >= i64::MAX as f64 {
+            // `i64::MAX as f64` is 2^63, which does not fit
+            Err(TimeConversionError::OutOfRange)
+        } else {
+            Ok(Timing(round(ms)))
+        }
+    }
+
     fn msecs(&self) -> i64 {
         self.0
     }
@@ -82,7 +152,19 @@ impl Timing {
 
 impl Debug for Timing {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "Timing({})", self.to_string())
+        write!(f, "Timing({})", self)
+    }
+}
+
+impl Timing {
+    fn format(&self, style: TimeStyle) -> String {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let t = if self.0 < 0 { -*self } else { *self };
+        match style {
+            TimeStyle::Default => self.to_string(),
+            TimeStyle::Srt => format!("{}{:02}:{:02}:{:02},{:03}", sign, t.hours(), t.mins_comp(), t.secs_comp(), t.msecs_comp()),
+            TimeStyle::Seconds => format!("{}{}.{:03}s", sign, t.0 / 1000, t.msecs_comp()),
+        }
     }
 }
 
@@ -123,7 +205,7 @@ impl AddAssign for Timing {
 
 impl SubAssign for Timing {
     fn sub_assign(&mut self, r: Timing) {
-        self.0 += r.0;
+        self.0 -= r.0;
     }
 }
 
@@ -178,6 +260,18 @@ macro_rules! create_time_type {
                 Self::new(Timing::from_secs(ms))
             }
 
+            /// Create the time type from a number of seconds, which is rounded to the nearest millisecond (halves are
+            /// rounded away from zero). NaN, infinite numbers and numbers beyond the range of the milliseconds are
+            /// errors instead of being clamped.
+            pub fn from_secs_f64(secs: f64) -> Result<$i, TimeConversionError> {
+                Timing::from_secs_f64(secs).map(Self::new)
+            }
+
+            /// Works like `from_secs_f64()` with a number of milliseconds.
+            pub fn from_msecs_f64(msecs: f64) -> Result<$i, TimeConversionError> {
+                Timing::from_msecs_f64(msecs).map(Self::new)
+            }
+
             /// Create the time type with a given number of minutes.
             pub fn from_mins(mins: i64) -> $i {
                 Self::new(Timing::from_mins(mins))
@@ -251,6 +345,11 @@ macro_rules! create_time_type {
                     *self
                 }
             }
+
+            /// Write the time in the given style.
+            pub fn format(&self, style: TimeStyle) -> String {
+                self.intern.format(style)
+            }
         }
 
         impl Neg for $i {
@@ -271,6 +370,23 @@ macro_rules! create_time_type {
 create_time_type! {TimePoint}
 create_time_type! {TimeDelta}
 
+impl TimePoint {
+    /// Create the time point at which the frame `frame` of a video with `fps` frames per second starts.
+    ///
+    /// The result is rounded to the nearest millisecond.
+    pub fn from_frame(frame: i64, fps: f64) -> TimePoint {
+        TimePoint::from_msecs(round(frame as f64 * 1000.0 / fps))
+    }
+
+    /// Get the number of the frame of a video with `fps` frames per second which starts closest to this time point.
+    ///
+    /// This is not always the frame which is shown at this time point (that would be rounded down), but it reverses
+    /// `from_frame()`: `TimePoint::from_frame(f, fps).to_frame(fps) == f`.
+    pub fn to_frame(&self, fps: f64) -> i64 {
+        round(self.msecs() as f64 * fps / 1000.0)
+    }
+}
+
 macro_rules! impl_add {
     ($a:ty, $b:ty, $output:ident) => {
         impl Add<$b> for $a {
@@ -341,7 +457,7 @@ pub struct TimeSpan {
 impl TimeSpan {
     /// Constructor of `TimeSpan`s.
     pub fn new(start: TimePoint, end: TimePoint) -> TimeSpan {
-        TimeSpan { start: start, end: end }
+        TimeSpan { start, end }
     }
 
     /// Get the length of the `TimeSpan` (can be negative).
@@ -388,4 +504,35 @@ mod tests {
         let t = super::Timing::from_msecs(0);
         assert_eq!(t.to_string(), "0:00:00.000".to_string());
     }
+
+    #[test]
+    fn test_sub_assign() {
+        let mut t = super::TimePoint::from_msecs(1500);
+        t -= super::TimeDelta::from_msecs(500);
+        assert_eq!(t, super::TimePoint::from_msecs(1000));
+    }
+
+    #[test]
+    fn test_time_style() {
+        use super::{TimeDelta, TimeStyle};
+
+        let t = TimeDelta::from_msecs(62_500);
+        assert_eq!(t.format(TimeStyle::Default), "0:01:02.500");
+        assert_eq!(t.format(TimeStyle::Srt), "00:01:02,500");
+        assert_eq!(t.format(TimeStyle::Seconds), "62.500s");
+        assert_eq!((-t).format(TimeStyle::Seconds), "-62.500s");
+        assert_eq!(TimeDelta::from_msecs(-40).format(TimeStyle::Srt), "-00:00:00,040");
+    }
+
+    #[test]
+    fn test_from_secs_f64() {
+        use super::{TimeConversionError, TimeDelta, TimePoint};
+
+        assert_eq!(TimePoint::from_secs_f64(1.2345), Ok(TimePoint::from_msecs(1235)));
+        assert_eq!(TimePoint::from_secs_f64(0.0004), Ok(TimePoint::from_msecs(0)));
+        assert_eq!(TimeDelta::from_secs_f64(-0.0026), Ok(TimeDelta::from_msecs(-3)));
+        assert_eq!(TimePoint::from_secs_f64(f64::NAN), Err(TimeConversionError::NotANumber));
+        assert_eq!(TimeDelta::from_secs_f64(f64::NEG_INFINITY), Err(TimeConversionError::OutOfRange));
+        assert_eq!(TimePoint::from_secs_f64(1e17), Err(TimeConversionError::OutOfRange));
+    }
 }
@@ -0,0 +1,191 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::escaping::line_break;
+use crate::timetypes::TimePoint;
+use crate::transform::strip_markup;
+use crate::{SubtitleEntry, SubtitleFormat};
+
+/// The markup of a transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptStyle {
+    /// One line per paragraph like `[00:01:02] Anna: Hello there.`.
+    PlainText,
+
+    /// Paragraphs like `**[00:01:02] Anna:** Hello there.` which are separated by empty lines. Markdown characters
+    /// in the texts are escaped.
+    Markdown,
+}
+
+/// Options for `SubtitleFile::to_transcript()`, which are created like
+/// `TranscriptOptions::new().style(TranscriptStyle::Markdown).timestamps(false)`.
+///
+/// By default a plain text transcript with timestamps is created, in which consecutive entries of the same speaker
+/// are merged into one paragraph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranscriptOptions {
+    style: TranscriptStyle,
+    timestamps: bool,
+    merge_speakers: bool,
+}
+
+impl Default for TranscriptOptions {
+    fn default() -> TranscriptOptions {
+        TranscriptOptions {
+            style: TranscriptStyle::PlainText,
+            timestamps: true,
+            merge_speakers: true,
+        }
+    }
+}
+
+impl TranscriptOptions {
+    /// Create the default options.
+    pub fn new() -> TranscriptOptions {
+        TranscriptOptions::default()
+    }
+
+    /// Set the markup of the transcript.
+    pub fn style(mut self, style: TranscriptStyle) -> TranscriptOptions {
+        self.style = style;
+        self
+    }
+
+    /// Start every paragraph with the start of its first entry (like `[01:02:03]`).
+    pub fn timestamps(mut self, timestamps: bool) -> TranscriptOptions {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Merge consecutive entries of the same speaker into one paragraph. Entries without a speaker are never merged.
+    pub fn merge_speakers(mut self, merge_speakers: bool) -> TranscriptOptions {
+        self.merge_speakers = merge_speakers;
+        self
+    }
+}
+
+/// Create a transcript of the entries of a file of the format `format`.
+///
+/// Formatting (like `<i>` or `{\\i1}`) is removed and the lines of an entry are joined with spaces. The speakers
+/// are the ones of the entries (see `SubtitleEntry::speaker`); entries without text (like images) are skipped.
+pub fn transcript(entries: &[SubtitleEntry], format: SubtitleFormat, options: &TranscriptOptions) -> String {
+    let separator = line_break(format);
+    let mut paragraphs: Vec<(TimePoint, Option<&str>, String)> = Vec::new();
+    for entry in entries {
+        let line = match (&entry.line, separator) {
+            (Some(line), Some(separator)) => line.replace(separator, " "),
+            (Some(line), None) => line.clone(),
+            (None, _) => continue,
+        };
+        let text = strip_markup(&line).split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            continue;
+        }
+
+        let speaker = entry.speaker.as_deref().filter(|speaker| !speaker.trim().is_empty());
+        match paragraphs.last_mut() {
+            Some((_, last_speaker, last_text)) if options.merge_speakers && speaker.is_some() && *last_speaker == speaker => {
+                last_text.push(' ');
+                last_text.push_str(&text);
+            }
+            _ => paragraphs.push((entry.timespan.start, speaker, text)),
+        }
+    }
+
+    let mut result = String::new();
+    for (start, speaker, text) in paragraphs {
+        let escape = |s: &str| match options.style {
+            TranscriptStyle::PlainText => s.to_string(),
+            TranscriptStyle::Markdown => escape_markdown(s),
+        };
+        let mut prefix = Vec::new();
+        if options.timestamps {
+            prefix.push(format!("[{}]", timestamp(start)));
+        }
+        if let Some(speaker) = speaker {
+            prefix.push(format!("{}:", escape(speaker.trim())));
+        }
+        let prefix = prefix.join(" ");
+
+        match options.style {
+            TranscriptStyle::PlainText if prefix.is_empty() => result.push_str(&text),
+            TranscriptStyle::PlainText => result.push_str(&format!("{} {}", prefix, text)),
+            TranscriptStyle::Markdown => {
+                if !result.is_empty() {
+                    result.push('\n');
+                }
+                if !prefix.is_empty() {
+                    result.push_str(&format!("**{}** ", prefix));
+                }
+                result.push_str(&escape(&text));
+            }
+        }
+        result.push('\n');
+    }
+    result
+}
+
+/// A time like `01:02:03` (negative times are written as `00:00:00`).
+fn timestamp(t: TimePoint) -> String {
+    let t = if t.is_negative() { TimePoint::from_msecs(0) } else { t };
+    format!("{:02}:{:02}:{:02}", t.hours(), t.mins_comp(), t.secs_comp())
+}
+
+/// Escape the characters which Markdown uses for formatting.
+fn escape_markdown(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '#' | '<' | '>' | '~' | '|') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimeSpan;
+
+    #[test]
+    fn transcript_test() {
+        let entry = |secs: i64, speaker: Option<&str>, text: &str| SubtitleEntry {
+            speaker: speaker.map(str::to_string),
+            ..SubtitleEntry::new(
+                TimeSpan::new(TimePoint::from_secs(secs), TimePoint::from_secs(secs + 1)),
+                text.to_string(),
+            )
+        };
+        let entries = vec![
+            entry(1, Some("Anna"), "{\\i1}Hello{\\i0}\\Nthere."),
+            entry(3, Some("Anna"), "How are you?"),
+            entry(3725, Some("Bob"), "Fine_ *thanks*"),
+            entry(3727, None, "{\\an8}[music]"),
+            entry(3728, None, "{\\p1}"),
+            entry(3729, None, "- Yes"),
+        ];
+
+        assert_eq!(
+            transcript(&entries, SubtitleFormat::SubStationAlpha, &TranscriptOptions::new()),
+            "[00:00:01] Anna: Hello there. How are you?\n[01:02:05] Bob: Fine_ *thanks*\n[01:02:07] [music]\n[01:02:09] - Yes\n"
+        );
+        assert_eq!(
+            transcript(
+                &entries[..3],
+                SubtitleFormat::SubStationAlpha,
+                &TranscriptOptions::new().timestamps(false).merge_speakers(false)
+            ),
+            "Anna: Hello there.\nAnna: How are you?\nBob: Fine_ *thanks*\n"
+        );
+        assert_eq!(
+            transcript(
+                &entries[1..4],
+                SubtitleFormat::SubStationAlpha,
+                &TranscriptOptions::new().style(TranscriptStyle::Markdown)
+            ),
+            "**[00:00:03] Anna:** How are you?\n\n**[01:02:05] Bob:** Fine\\_ \\*thanks\\*\n\n**[01:02:07]** \\[music\\]\n"
+        );
+    }
+}
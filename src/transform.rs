@@ -0,0 +1,1103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::measure::{display_width, grapheme_count};
+use crate::timetypes::{TimeConversionError, TimeDelta, TimePoint, TimeSpan};
+use crate::SubtitleEntry;
+use encoding_rs::WINDOWS_1252;
+use itertools::Itertools;
+use std::convert::TryFrom;
+
+/// The casing which `convert_case()` converts the text to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextCase {
+    /// Only the first word of a sentence, the pronoun "I" and acronyms are capitalized (`THE FBI IS HERE.` becomes
+    /// `The FBI is here.`). This is meant for sources which are written in all-caps, like broadcast captions.
+    Sentence,
+
+    /// Every letter is capitalized.
+    Upper,
+}
+
+/// Convert the text of all entries to the given casing.
+///
+/// Words in `acronyms` (like `"FBI"`) and words with inner dots (like `U.S.`) stay capitalized when converting
+/// to `TextCase::Sentence`. A sentence can continue in the next entry, so the entries should be in order.
+///
+/// Formatting like `<i>`, `{\i1}` and escapes like `\N` are not changed.
+pub fn convert_case(entries: &mut [SubtitleEntry], case: TextCase, acronyms: &[&str]) {
+    let mut sentence_start = true;
+    for line in entries.iter_mut().filter_map(|entry| entry.line.as_mut()) {
+        *line = match case {
+            TextCase::Sentence => to_sentence_case(line, acronyms, &mut sentence_start),
+            TextCase::Upper => map_text(line, |word| word.to_uppercase()),
+        };
+    }
+}
+
+/// Repair the text of all entries which was decoded with a wrong encoding: UTF-8 which was read as Latin-1 or
+/// Windows-1252 and saved as UTF-8 again (like `CafÃ©` instead of `Café` or `donâ€™t` instead of `don’t`), also
+/// if this happened more than once. Returns the number of changed entries.
+///
+/// Only character sequences which form valid UTF-8 are replaced, so correct non-ASCII characters in the same
+/// text are kept.
+pub fn repair_mojibake(entries: &mut [SubtitleEntry]) -> usize {
+    let mut changed = 0;
+    for line in entries.iter_mut().filter_map(|entry| entry.line.as_mut()) {
+        let repaired = repair_mojibake_text(line);
+        if repaired != *line {
+            *line = repaired;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Repair a single text (see `repair_mojibake()`).
+pub fn repair_mojibake_text(s: &str) -> String {
+    let mut result = s.to_string();
+    // every round repairs one level of wrong decoding
+    for _ in 0..3 {
+        let (repaired, count) = decode_mojibake(&result);
+        if count == 0 {
+            break;
+        }
+        result = repaired;
+    }
+    result
+}
+
+/// The number of wrongly decoded UTF-8 sequences in the text (see `repair_mojibake()`).
+pub(crate) fn mojibake_count(s: &str) -> usize {
+    decode_mojibake(s).1
+}
+
+/// Decode every character sequence which is valid UTF-8 when it is encoded as Windows-1252 (or Latin-1) and return
+/// the number of decoded sequences.
+fn decode_mojibake(s: &str) -> (String, usize) {
+    let chars: Vec<char> = s.chars().collect();
+    let mut result = String::with_capacity(s.len());
+    let mut count = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let sequence_len = match single_byte(chars[i]) {
+            Some(0xC2..=0xDF) => 2,
+            Some(0xE0..=0xEF) => 3,
+            Some(0xF0..=0xF4) => 4,
+            _ => 1,
+        };
+        let bytes: Option<Vec<u8>> = chars
+            .get(i..i + sequence_len)
+            .and_then(|sequence| sequence.iter().map(|&c| single_byte(c)).collect());
+        let decoded = match bytes {
+            Some(ref bytes) if sequence_len > 1 => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                result.push_str(decoded);
+                count += 1;
+                i += sequence_len;
+            }
+            None => {
+                result.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    (result, count)
+}
+
+/// The byte of a non-ASCII character in Windows-1252 (the control characters `U+0080..U+009F` are read as Latin-1).
+fn single_byte(c: char) -> Option<u8> {
+    if ('\u{80}'..='\u{9F}').contains(&c) {
+        return Some(c as u8);
+    }
+    let mut buffer = [0; 4];
+    let (bytes, _, had_errors) = WINDOWS_1252.encode(c.encode_utf8(&mut buffer));
+    match *bytes {
+        [byte] if !had_errors && byte >= 0x80 => Some(byte),
+        _ => None,
+    }
+}
+
+/// Apply the typography of the language `language` (an ISO 639-1 code like `"fr"` or a tag like `"fr-CA"`) to the
+/// text of all entries. Returns the number of changed entries.
+///
+/// - Straight double quotes (`"`) become the quotation marks of the language (`“…”` for English, `„…“` for German,
+///   `«…»` for French, Spanish, Italian and Russian, ...).
+/// - French (`fr`): a narrow no-break space is put before `?`, `!`, `:` and `;` and inside of `«…»` (normal spaces
+///   are replaced). A colon between digits or letters (like `12:30`) is not changed.
+/// - Spanish (`es`): questions and exclamations without an opening `¿` or `¡` get one at the start of the sentence.
+///
+/// Formatting like `<i>` and `{\i1}` is not changed. Text of other languages is only changed if the quotation
+/// marks of the language are known.
+pub fn fix_punctuation(entries: &mut [SubtitleEntry], language: &str) -> usize {
+    let mut changed = 0;
+    for line in entries.iter_mut().filter_map(|entry| entry.line.as_mut()) {
+        let fixed = fix_punctuation_text(line, language);
+        if fixed != *line {
+            *line = fixed;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Fix the punctuation of a single text (see `fix_punctuation()`).
+pub fn fix_punctuation_text(s: &str, language: &str) -> String {
+    let language = language.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+    let mut parts: Vec<(String, bool)> = Vec::new();
+    map_text_parts(s, |part, is_word| {
+        parts.push((part.to_string(), is_word));
+        String::new()
+    });
+
+    if let Some(quotes) = quotation_marks(&language) {
+        replace_quotes(&mut parts, quotes);
+    }
+    match language.as_str() {
+        "fr" => add_french_spaces(&mut parts),
+        "es" => add_inverted_marks(&mut parts),
+        _ => {}
+    }
+    parts.into_iter().map(|(part, _)| part).collect()
+}
+
+/// The opening and closing quotation marks of a language.
+fn quotation_marks(language: &str) -> Option<(&'static str, &'static str)> {
+    match language {
+        "en" | "nl" | "pt" => Some(("“", "”")),
+        "de" | "cs" => Some(("„", "“")),
+        "pl" | "ro" | "hu" => Some(("„", "”")),
+        "fr" | "es" | "it" | "ru" | "uk" | "el" => Some(("«", "»")),
+        "sv" | "fi" => Some(("”", "”")),
+        _ => None,
+    }
+}
+
+/// Whether a non-word part is a space (escapes like `\N` separate words like spaces).
+fn is_space_part(part: &str) -> bool {
+    part.starts_with('\\') || part.chars().all(char::is_whitespace)
+}
+
+/// The index of the first part before/after `i` which is not formatting (escapes count as spaces).
+fn visible_neighbour(parts: &[(String, bool)], i: usize, forward: bool) -> Option<usize> {
+    let mut indices: Box<dyn Iterator<Item = usize>> = if forward { Box::new(i + 1..parts.len()) } else { Box::new((0..i).rev()) };
+    indices.find(|&j| parts[j].1 || !is_markup(&parts[j].0) || parts[j].0.starts_with('\\'))
+}
+
+/// Replace straight double quotes: a quote at the start of the text, after a space or after an opening bracket opens
+/// a quotation, all others close it.
+fn replace_quotes(parts: &mut [(String, bool)], (open, close): (&str, &str)) {
+    for i in 0..parts.len() {
+        if parts[i].0 != "\"" {
+            continue;
+        }
+        let opening = match visible_neighbour(parts, i, false) {
+            Some(prev) => is_space_part(&parts[prev].0) || matches!(parts[prev].0.as_str(), "(" | "[" | "-" | "–" | "—"),
+            None => true,
+        };
+        parts[i].0 = if opening { open } else { close }.to_string();
+    }
+}
+
+/// Put narrow no-break spaces before `?!:;` and `»` and after `«`.
+fn add_french_spaces(parts: &mut Vec<(String, bool)>) {
+    const NARROW_NBSP: &str = "\u{202F}";
+    let mut i = 0;
+    while i < parts.len() {
+        let part = parts[i].0.as_str();
+        let forward = part == "«";
+        if parts[i].1 || !matches!(part, "?" | "!" | ":" | ";" | "»" | "«") {
+            i += 1;
+            continue;
+        }
+
+        let neighbour = visible_neighbour(parts, i, forward);
+        let after = visible_neighbour(parts, i, true);
+        let is_space = |j: usize| is_space_part(&parts[j].0);
+        match neighbour {
+            // a space at the start or end of the text is kept
+            Some(j) if !parts[j].0.starts_with('\\') && is_space(j) && visible_neighbour(parts, j, forward).is_some() => {
+                parts[j].0 = NARROW_NBSP.to_string();
+            }
+            Some(j) if is_space(j) => {}
+            // `12:30` or `http://`
+            Some(_) if part == ":" && !after.map_or(true, is_space) => {}
+            Some(j) if parts[j].1 || part == "»" || matches!(parts[j].0.as_str(), "»" | "«" | ")" | "…") => {
+                parts.insert(if forward { i + 1 } else { i }, (NARROW_NBSP.to_string(), false));
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// Insert `¿` and `¡` at the start of sentences which end with `?` or `!` but don't contain the opening mark.
+fn add_inverted_marks(parts: &mut Vec<(String, bool)>) {
+    let mut insertions: Vec<(usize, &str)> = Vec::new();
+    let mut sentence_start: Option<usize> = None;
+    let (mut has_question, mut has_exclamation) = (false, false);
+    let mut sentence_end = false;
+    for (i, (part, is_word)) in parts.iter().enumerate() {
+        if !is_word && is_markup(part) && !part.starts_with('\\') {
+            continue;
+        }
+        if !is_word && is_space_part(part) {
+            if sentence_end {
+                sentence_start = None;
+                has_question = false;
+                has_exclamation = false;
+                sentence_end = false;
+            }
+            continue;
+        }
+        match part.as_str() {
+            "?" | "!" => {
+                let (opening, has_opening) = if part == "?" {
+                    ("¿", &mut has_question)
+                } else {
+                    ("¡", &mut has_exclamation)
+                };
+                if let (Some(start), false) = (sentence_start, *has_opening) {
+                    insertions.push((start, opening));
+                }
+                *has_opening = true;
+                sentence_end = true;
+            }
+            "." | "…" => sentence_end = true,
+            // dialogue dashes and quotes are not part of the sentence
+            "-" | "–" | "—" | "«" | "\"" | "“" | "„" if sentence_start.is_none() => {}
+            _ => {
+                if sentence_start.is_none() {
+                    sentence_start = Some(i);
+                }
+                match part.as_str() {
+                    "¿" => has_question = true,
+                    "¡" => has_exclamation = true,
+                    _ => {}
+                }
+                sentence_end = false;
+            }
+        }
+    }
+    for (i, mark) in insertions.into_iter().rev() {
+        parts.insert(i, (mark.to_string(), false));
+    }
+}
+
+/// An entry with the entries before and after it (see `entry_windows()`).
+pub type EntryWindow<'a> = (Option<&'a SubtitleEntry>, &'a SubtitleEntry, Option<&'a SubtitleEntry>);
+
+/// The iterator returned by `entry_windows()`.
+#[derive(Debug, Clone)]
+pub struct EntryWindows<'a> {
+    entries: &'a [SubtitleEntry],
+    idx: usize,
+}
+
+impl<'a> Iterator for EntryWindows<'a> {
+    type Item = EntryWindow<'a>;
+
+    fn next(&mut self) -> Option<EntryWindow<'a>> {
+        let current = self.entries.get(self.idx)?;
+        let prev = self.idx.checked_sub(1).map(|i| &self.entries[i]);
+        let next = self.entries.get(self.idx + 1);
+        self.idx += 1;
+        Some((prev, current, next))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.entries.len() - self.idx;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for EntryWindows<'_> {}
+
+/// Iterate over all entries as `(previous, current, next)`, so transformations which depend on the gaps or overlaps
+/// with the neighbours (like extending an entry to the start of the next one) don't need indices. The first entry
+/// has no previous and the last entry has no next entry.
+pub fn entry_windows(entries: &[SubtitleEntry]) -> EntryWindows<'_> {
+    EntryWindows { entries, idx: 0 }
+}
+
+/// Call `f` with `(previous, current, next)` for all entries (like `entry_windows()`), where the current entry can be
+/// changed. The entries are visited in order, so the previous entry already contains the changes of `f`.
+pub fn for_each_entry_window_mut<F>(entries: &mut [SubtitleEntry], mut f: F)
+where
+    F: FnMut(Option<&SubtitleEntry>, &mut SubtitleEntry, Option<&SubtitleEntry>),
+{
+    for idx in 0..entries.len() {
+        let (before, rest) = entries.split_at_mut(idx);
+        let (current, after) = rest.split_first_mut().expect("index is in bounds");
+        f(before.last(), current, after.first());
+    }
+}
+
+/// Lengthen every entry by up to `max_extension`, but only until `min_gap` before the start of the next entry (a
+/// readability fix for machine-generated subtitles which are shown too briefly). Entries are never shortened, so
+/// the entries should be sorted by their start. Returns the number of changed entries.
+pub fn extend_durations(entries: &mut [SubtitleEntry], max_extension: TimeDelta, min_gap: TimeDelta) -> usize {
+    let mut changed = 0;
+    for_each_entry_window_mut(entries, |_, current, next| {
+        let mut end = current.timespan.end + max_extension;
+        if let Some(next) = next {
+            end = end.min(next.timespan.start - min_gap);
+        }
+        if end > current.timespan.end {
+            current.timespan.end = end;
+            changed += 1;
+        }
+    });
+    changed
+}
+
+/// Shift only the entries for which `predicate` returns `true` by `delta` (like the signs of a file, whose timing
+/// differs from the timing of the dialogue). Returns the number of shifted entries.
+///
+/// The entries can be selected by their speaker, their text (also with a regular expression) or any other field;
+/// use `SsaFile::shift_dialogues()` to select the lines of `.ssa`/`.ass` files by their style.
+pub fn shift_matching<F: FnMut(&SubtitleEntry) -> bool>(entries: &mut [SubtitleEntry], delta: TimeDelta, mut predicate: F) -> usize {
+    let mut shifted = 0;
+    for entry in entries.iter_mut().filter(|entry| predicate(entry)) {
+        entry.timespan += delta;
+        shifted += 1;
+    }
+    shifted
+}
+
+/// Keep only the entries which overlap `range` (like for an excerpt or a preview of the video); entries which start
+/// before or end after the range are trimmed to fit. The times are not shifted, so subtract `range.start` for an
+/// excerpt which starts at zero.
+pub fn clip_to(entries: &[SubtitleEntry], range: TimeSpan) -> Vec<SubtitleEntry> {
+    entries
+        .iter()
+        .filter(|entry| entry.timespan.start < range.end && entry.timespan.end > range.start)
+        .map(|entry| SubtitleEntry {
+            timespan: TimeSpan::new(entry.timespan.start.max(range.start), entry.timespan.end.min(range.end)),
+            ..entry.clone()
+        })
+        .collect()
+}
+
+/// Change the speed of all entries by `factor` around `anchor` (which keeps its time): a time `t` becomes
+/// `anchor + (t - anchor) * factor`, so durations are scaled as well. This is meant for audio which was sped up or
+/// slowed down as a whole, like the 4% PAL speedup (`factor = 25.0 / 23.976`); unlike shifting, the offset grows
+/// with the distance to the anchor.
+///
+/// Every time is computed from its original value and rounded to the nearest millisecond, so the rounding does not
+/// add up over the file. Use `scale_times_exact()` for factors like `25025 / 24000` which are not exact as `f64`.
+///
+/// A NaN or infinite `factor` and times which don't fit into the milliseconds are errors; the entries are unchanged
+/// then.
+pub fn scale_times(entries: &mut [SubtitleEntry], factor: f64, anchor: TimePoint) -> Result<(), TimeConversionError> {
+    scale_entries(entries, anchor, |distance| TimeDelta::from_msecs_f64(distance as f64 * factor))
+}
+
+/// Works like `scale_times()` with the factor `numerator / denominator`, but the times are computed with integers
+/// (halves are rounded away from zero), so the result is exact for ratios of frame rates like `25025 / 24000`
+/// (25 fps to 24000/1001 fps).
+///
+/// A zero `denominator` is a `TimeConversionError::NotANumber`.
+pub fn scale_times_exact(entries: &mut [SubtitleEntry], numerator: i64, denominator: i64, anchor: TimePoint) -> Result<(), TimeConversionError> {
+    if denominator == 0 {
+        return Err(TimeConversionError::NotANumber);
+    }
+    scale_entries(entries, anchor, |distance| {
+        let scaled = i128::from(distance) * i128::from(numerator);
+        let denominator = i128::from(denominator);
+        let half = if (scaled < 0) == (denominator < 0) {
+            denominator.abs()
+        } else {
+            -denominator.abs()
+        };
+        i64::try_from((2 * scaled + half) / (2 * denominator))
+            .map(TimeDelta::from_msecs)
+            .map_err(|_| TimeConversionError::OutOfRange)
+    })
+}
+
+/// Set every time `t` of the entries to `anchor + scale(t - anchor)` (with the distance in milliseconds). The entries
+/// are only changed if all times can be scaled.
+fn scale_entries<F>(entries: &mut [SubtitleEntry], anchor: TimePoint, scale: F) -> Result<(), TimeConversionError>
+where
+    F: Fn(i64) -> Result<TimeDelta, TimeConversionError>,
+{
+    let scale_time = |t: TimePoint| -> Result<TimePoint, TimeConversionError> {
+        let distance = t.msecs().checked_sub(anchor.msecs()).ok_or(TimeConversionError::OutOfRange)?;
+        let msecs = anchor
+            .msecs()
+            .checked_add(scale(distance)?.msecs())
+            .ok_or(TimeConversionError::OutOfRange)?;
+        Ok(TimePoint::from_msecs(msecs))
+    };
+    let timespans = entries
+        .iter()
+        .map(|entry| Ok(TimeSpan::new(scale_time(entry.timespan.start)?, scale_time(entry.timespan.end)?)))
+        .collect::<Result<Vec<TimeSpan>, TimeConversionError>>()?;
+    for (entry, timespan) in entries.iter_mut().zip(timespans) {
+        entry.timespan = timespan;
+    }
+    Ok(())
+}
+
+/// What follows the dash of a speaker in `normalize_dialogue()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DashSpacing {
+    /// No space (`-Hi`, like in many English style guides).
+    None,
+
+    /// A normal space (`- Hi`).
+    Space,
+
+    /// A no-break space, so the dash is never separated from the text (common in French).
+    NoBreakSpace,
+}
+
+/// The options of `normalize_dialogue()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialogueOptions {
+    /// The dash which starts the line of a speaker (`'-'` by default, some style guides use `'–'`).
+    pub dash: char,
+
+    /// What follows the dash (`DashSpacing::Space` by default).
+    pub spacing: DashSpacing,
+
+    /// Two consecutive entries with one dashed line each are only merged if the gap between them is at most this
+    /// long (500 ms by default).
+    pub max_merge_gap: TimeDelta,
+}
+
+impl Default for DialogueOptions {
+    fn default() -> DialogueOptions {
+        DialogueOptions {
+            dash: '-',
+            spacing: DashSpacing::Space,
+            max_merge_gap: TimeDelta::from_msecs(500),
+        }
+    }
+}
+
+/// Normalize the lines of speakers to the dash convention: an entry has at most two speakers, each speaker has one
+/// line, which starts with a dash (`- Hello` / `- Hi`), and entries with a single speaker have no dash.
+///
+/// A line which starts with a dash (`-`, `–` or `—`, also behind formatting like `<i>`) starts the text of a speaker,
+/// the other lines continue the text of the previous speaker. Entries with more than two speakers are split (the time
+/// is divided by the length of the texts), consecutive entries with one dashed speaker each are merged. The lines
+/// are separated by `line_break` (see `escaping::line_break()`). Entries without text are not changed.
+pub fn normalize_dialogue(entries: &[SubtitleEntry], line_break: &str, options: &DialogueOptions) -> Vec<SubtitleEntry> {
+    let mut result = Vec::new();
+    // an entry with a single dashed speaker which can be merged with the next entry
+    let mut pending: Option<(&SubtitleEntry, DialogueTurn)> = None;
+    for entry in entries {
+        let turns = match &entry.line {
+            Some(line) => dialogue_turns(line, line_break),
+            None => Vec::new(),
+        };
+
+        if let [turn] = &turns[..] {
+            if turn.dashed {
+                match pending.take() {
+                    Some((first, first_turn)) if entry.timespan.start - first.timespan.end <= options.max_merge_gap => {
+                        result.push(SubtitleEntry {
+                            timespan: TimeSpan::new(first.timespan.start, entry.timespan.end),
+                            line: Some(format_turns(&[first_turn, turn.clone()], line_break, options)),
+                            speaker: if first.speaker == entry.speaker { first.speaker.clone() } else { None },
+                            ..first.clone()
+                        });
+                    }
+                    Some((first, first_turn)) => {
+                        result.push(with_turns(first, &[first_turn], line_break, options));
+                        pending = Some((entry, turn.clone()));
+                    }
+                    None => pending = Some((entry, turn.clone())),
+                }
+                continue;
+            }
+        }
+
+        if let Some((first, first_turn)) = pending.take() {
+            result.push(with_turns(first, &[first_turn], line_break, options));
+        }
+        match turns.len() {
+            0 | 1 => result.push(entry.clone()),
+            2 => result.push(with_turns(entry, &turns, line_break, options)),
+            _ => {
+                let total_len: usize = turns.iter().map(|turn| visible_len(&turn.text)).sum();
+                let duration = entry.timespan.len().msecs();
+                let time_at = |len: usize| entry.timespan.start + TimeDelta::from_msecs(duration * len as i64 / total_len.max(1) as i64);
+                let mut len_before = 0;
+                for (i, chunk) in turns.chunks(2).enumerate() {
+                    let chunk_len: usize = chunk.iter().map(|turn| visible_len(&turn.text)).sum();
+                    let start = time_at(len_before);
+                    len_before += chunk_len;
+                    let mut chunk_entry = with_turns(entry, chunk, line_break, options);
+                    chunk_entry.timespan = TimeSpan::new(
+                        start,
+                        if len_before == total_len {
+                            entry.timespan.end
+                        } else {
+                            time_at(len_before)
+                        },
+                    );
+                    if i > 0 {
+                        chunk_entry.index = None;
+                    }
+                    result.push(chunk_entry);
+                }
+            }
+        }
+    }
+    if let Some((first, first_turn)) = pending {
+        result.push(with_turns(first, &[first_turn], line_break, options));
+    }
+    result
+}
+
+/// The text of a speaker in an entry (see `normalize_dialogue()`).
+#[derive(Debug, Clone)]
+struct DialogueTurn {
+    /// The formatting before the dash (like `<i>`).
+    prefix: String,
+
+    /// Whether the text started with a dash.
+    dashed: bool,
+
+    /// The text without the dash.
+    text: String,
+}
+
+/// Split the text of an entry into the texts of the speakers.
+fn dialogue_turns(s: &str, line_break: &str) -> Vec<DialogueTurn> {
+    let mut turns: Vec<DialogueTurn> = Vec::new();
+    for line in s.split(line_break).map(str::trim).filter(|line| !line.is_empty()) {
+        let prefix_len = markup_prefix_len(line);
+        let (prefix, rest) = line.split_at(prefix_len);
+        let mut chars = rest.chars();
+        let dashed = matches!(chars.next(), Some('-') | Some('–') | Some('—')) && !matches!(chars.next(), Some('-') | None);
+
+        match turns.last_mut() {
+            Some(turn) if !dashed => {
+                turn.text.push(' ');
+                turn.text.push_str(line);
+            }
+            _ => turns.push(DialogueTurn {
+                prefix: prefix.to_string(),
+                dashed,
+                text: if dashed {
+                    rest[rest.chars().next().map_or(0, char::len_utf8)..].trim_start().to_string()
+                } else {
+                    rest.to_string()
+                },
+            }),
+        }
+    }
+    turns
+}
+
+/// The length of the formatting (`<...>` and `{...}`) and spaces at the start of the line.
+fn markup_prefix_len(line: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &line[len..];
+        let closing = match rest.chars().next() {
+            Some('<') => '>',
+            Some('{') => '}',
+            Some(c) if c.is_whitespace() => {
+                len += c.len_utf8();
+                continue;
+            }
+            _ => return len,
+        };
+        match rest.find(closing) {
+            Some(pos) => len += pos + 1,
+            None => return len,
+        }
+    }
+}
+
+/// Write the texts of the speakers: with dashes if there are two speakers.
+fn format_turns(turns: &[DialogueTurn], line_break: &str, options: &DialogueOptions) -> String {
+    if let [turn] = turns {
+        return format!("{}{}", turn.prefix, turn.text);
+    }
+    let spacing = match options.spacing {
+        DashSpacing::None => "",
+        DashSpacing::Space => " ",
+        DashSpacing::NoBreakSpace => "\u{A0}",
+    };
+    turns
+        .iter()
+        .map(|turn| format!("{}{}{}{}", turn.prefix, options.dash, spacing, turn.text))
+        .join(line_break)
+}
+
+/// The entry with the texts of the given speakers.
+fn with_turns(entry: &SubtitleEntry, turns: &[DialogueTurn], line_break: &str, options: &DialogueOptions) -> SubtitleEntry {
+    SubtitleEntry {
+        line: Some(format_turns(turns, line_break, options)),
+        ..entry.clone()
+    }
+}
+
+/// How the lines of the entries are broken by `SubtitleFile::convert_line_breaks()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineBreakStyle {
+    /// All lines of an entry are merged into one line (for example for mobile presentations).
+    SingleLine,
+
+    /// Entries with a single line that is longer than `max_line_len` characters (without formatting) are split
+    /// into two lines of similar length. Entries which already have multiple lines are not changed.
+    TwoLines {
+        /// The maximum number of characters in a single line.
+        max_line_len: usize,
+    },
+}
+
+impl LineBreakStyle {
+    /// Apply the style to the lines of an entry.
+    pub(crate) fn apply(self, lines: Vec<String>) -> Vec<String> {
+        match self {
+            LineBreakStyle::SingleLine if lines.len() > 1 => {
+                vec![lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()).join(" ")]
+            }
+            LineBreakStyle::TwoLines { max_line_len } if lines.len() == 1 && visible_len(&lines[0]) > max_line_len => match split_in_two(&lines[0]) {
+                Some((first, second)) => vec![first, second],
+                None => lines,
+            },
+            _ => lines,
+        }
+    }
+}
+
+/// The text without formatting and escapes.
+fn visible_text(s: &str) -> String {
+    map_text_parts(
+        s,
+        |part, is_word| if is_word || !is_markup(part) { part.to_string() } else { String::new() },
+    )
+}
+
+/// The number of graphemes without formatting and escapes (see `measure::grapheme_count()`).
+pub fn visible_len(s: &str) -> usize {
+    grapheme_count(&visible_text(s))
+}
+
+/// The display width without formatting and escapes (see `measure::display_width()`).
+pub fn visible_width(s: &str) -> usize {
+    display_width(&visible_text(s))
+}
+
+/// The text without formatting; escapes like `\N` become spaces.
+pub(crate) fn strip_markup(s: &str) -> String {
+    map_text_parts(s, |part, is_word| {
+        if is_word || !is_markup(part) {
+            part.to_string()
+        } else if part.starts_with('\\') {
+            " ".to_string()
+        } else {
+            String::new()
+        }
+    })
+}
+
+/// Split the line at the space (outside of formatting) which gives two lines of the most similar length.
+fn split_in_two(s: &str) -> Option<(String, String)> {
+    let mut parts: Vec<(String, bool)> = Vec::new();
+    map_text_parts(s, |part, is_word| {
+        parts.push((part.to_string(), is_word));
+        String::new()
+    });
+
+    let total_len = visible_len(s);
+    let mut len_before = 0;
+    let mut best: Option<(usize, usize)> = None; // (part index, difference of the line lengths)
+    for (i, (part, is_word)) in parts.iter().enumerate() {
+        if !is_word && part == " " {
+            let diff = (total_len - len_before - 1).abs_diff(len_before);
+            if best.map_or(true, |(_, best_diff)| diff < best_diff) {
+                best = Some((i, diff));
+            }
+        }
+        if *is_word || !is_markup(part) {
+            len_before += part.chars().count();
+        }
+    }
+
+    let (split_idx, _) = best?;
+    let first: String = parts[..split_idx].iter().map(|(part, _)| part.as_str()).collect();
+    let second: String = parts[split_idx + 1..].iter().map(|(part, _)| part.as_str()).collect();
+    Some((first.trim_end().to_string(), second.trim_start().to_string()))
+}
+
+/// Whether a non-word part from `map_text_parts()` is formatting or an escape.
+fn is_markup(part: &str) -> bool {
+    part.starts_with('<') || part.starts_with('{') || part.starts_with('\\')
+}
+
+/// Convert the text to sentence case. `sentence_start` is whether the text starts a new sentence;
+/// it is set to whether the next text starts a new sentence.
+fn to_sentence_case(s: &str, acronyms: &[&str], sentence_start: &mut bool) -> String {
+    let mut after_dotted_word = false;
+    map_text_parts(s, |part, is_word| {
+        if !is_word {
+            for c in part.chars() {
+                match c {
+                    // the dot after `U.S` doesn't end the sentence
+                    '.' if after_dotted_word => {}
+                    '.' | '!' | '?' | '…' => *sentence_start = true,
+                    _ => {}
+                }
+                if !c.is_whitespace() {
+                    after_dotted_word = false;
+                }
+            }
+            return part.to_string();
+        }
+
+        // keep the suffix of a possessive or contraction like `FBI'S` or `I'M` separate
+        let (stem, suffix) = match part.find('\'') {
+            Some(pos) => part.split_at(pos),
+            None => (part, ""),
+        };
+        after_dotted_word = stem.contains('.');
+
+        let mut result = if after_dotted_word || acronyms.iter().any(|acronym| acronym.eq_ignore_ascii_case(stem)) {
+            stem.to_uppercase()
+        } else if stem.eq_ignore_ascii_case("i") || *sentence_start {
+            capitalize(stem)
+        } else {
+            stem.to_lowercase()
+        };
+        result.push_str(&suffix.to_lowercase());
+
+        *sentence_start = false;
+        result
+    })
+}
+
+/// Uppercase the first letter and lowercase the rest.
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+/// Apply `f` to all words of the text (everything outside of formatting and escapes).
+fn map_text<F: FnMut(&str) -> String>(s: &str, mut f: F) -> String {
+    map_text_parts(s, |part, is_word| if is_word { f(part) } else { part.to_string() })
+}
+
+/// Split the text into words and everything else and join the results of `f`. The second argument of `f` is `true`
+/// for words (letters, digits, apostrophes and dots within words). Formatting in `<...>` and `{...}` and escapes like
+/// `\N` are passed as non-words.
+fn map_text_parts<F: FnMut(&str, bool) -> String>(s: &str, mut f: F) -> String {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let is_word_char = |i: usize| match chars.get(i) {
+        Some(&(_, c)) => c.is_alphanumeric(),
+        None => false,
+    };
+    let byte_pos = |i: usize| chars.get(i).map(|&(pos, _)| pos).unwrap_or(s.len());
+
+    let mut result = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let is_word = is_word_char(i);
+        if is_word {
+            while is_word_char(i) || (matches!(chars.get(i), Some(&(_, '\'')) | Some(&(_, '.'))) && is_word_char(i + 1)) {
+                i += 1;
+            }
+        } else {
+            match chars[i].1 {
+                '<' | '{' => {
+                    let closing = if chars[i].1 == '<' { '>' } else { '}' };
+                    while i < chars.len() && chars[i].1 != closing {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                '\\' => i += 2,
+                _ => i += 1,
+            }
+            i = i.min(chars.len());
+        }
+        result.push_str(&f(&s[byte_pos(start)..byte_pos(i)], is_word));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::{TimePoint, TimeSpan};
+
+    fn entries(lines: &[&str]) -> Vec<SubtitleEntry> {
+        let ts = TimeSpan::new(TimePoint::from_msecs(0), TimePoint::from_msecs(1000));
+        lines.iter().map(|line| SubtitleEntry::new(ts, line.to_string())).collect()
+    }
+
+    fn lines(entries: &[SubtitleEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.line.as_deref().unwrap()).collect()
+    }
+
+    #[test]
+    fn visible_len_test() {
+        assert_eq!(visible_len("{\\i1}Cafe\u{301}{\\i0}\\N"), 4);
+        assert_eq!(visible_width("<i>日本</i> ok"), 7);
+    }
+
+    #[test]
+    fn line_break_style_test() {
+        let lines = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        let two_lines = LineBreakStyle::TwoLines { max_line_len: 20 };
+
+        assert_eq!(LineBreakStyle::SingleLine.apply(lines(&["- Hello ", " - Hi"])), lines(&["- Hello - Hi"]));
+        assert_eq!(two_lines.apply(lines(&["Short line"])), lines(&["Short line"]));
+        assert_eq!(
+            two_lines.apply(lines(&["This is a <font color=\"red\">rather</font> long line"])),
+            lines(&["This is a", "<font color=\"red\">rather</font> long line"])
+        );
+        assert_eq!(two_lines.apply(lines(&["Averyveryverylongword"])), lines(&["Averyveryverylongword"]));
+    }
+
+    #[test]
+    fn repair_mojibake_test() {
+        assert_eq!(repair_mojibake_text("CafÃ© â€“ donâ€™t"), "Café – don’t");
+        // encoded twice
+        assert_eq!(repair_mojibake_text("CafÃƒÂ©"), "Café");
+        // correct characters are kept
+        assert_eq!(repair_mojibake_text("Ärger über CafÃ©"), "Ärger über Café");
+        assert_eq!(repair_mojibake_text("Ã and Â alone"), "Ã and Â alone");
+        assert_eq!(mojibake_count("Ãœber Ã¤"), 2);
+
+        let mut entries = entries(&["GrÃ¼ÃŸe", "Fine"]);
+        assert_eq!(repair_mojibake(&mut entries), 1);
+        assert_eq!(lines(&entries), vec!["Grüße", "Fine"]);
+    }
+
+    #[test]
+    fn normalize_dialogue_test() {
+        let mut v = entries(&[
+            "-Hello!\n-Hi.",
+            "- Where are you\ngoing?",
+            "<i>- Home.</i>",
+            "- One.\n- Two.\n- Three four five six.",
+            "- Alone",
+            "No dialogue\nat all",
+        ]);
+        let spans = [(0, 1000), (1000, 2000), (2200, 3000), (3000, 5000), (9000, 10000), (10000, 11000)];
+        for (entry, &(start, end)) in v.iter_mut().zip(&spans) {
+            entry.timespan = TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end));
+        }
+
+        let normalized = normalize_dialogue(&v, "\n", &DialogueOptions::default());
+        assert_eq!(
+            lines(&normalized),
+            vec![
+                "- Hello!\n- Hi.",
+                "- Where are you going?\n<i>- Home.</i>",
+                "- One.\n- Two.",
+                "Three four five six.",
+                "Alone",
+                "No dialogue\nat all",
+            ]
+        );
+        let spans: Vec<(i64, i64)> = normalized.iter().map(|e| (e.timespan.start.msecs(), e.timespan.end.msecs())).collect();
+        assert_eq!(
+            spans,
+            vec![(0, 1000), (1000, 3000), (3000, 3571), (3571, 5000), (9000, 10000), (10000, 11000)]
+        );
+
+        let options = DialogueOptions {
+            dash: '–',
+            spacing: DashSpacing::None,
+            max_merge_gap: TimeDelta::from_msecs(0),
+        };
+        let normalized = normalize_dialogue(&v[..3], "\n", &options);
+        assert_eq!(lines(&normalized), vec!["–Hello!\n–Hi.", "Where are you going?", "<i>Home.</i>"]);
+    }
+
+    #[test]
+    fn fix_punctuation_test() {
+        let mut v = entries(&[
+            "<i>Quoi?</i> Il a dit : \"Non!\"",
+            "Rendez-vous à 12:30 ; voir http://example.com",
+            "« Bonjour »",
+        ]);
+        assert_eq!(fix_punctuation(&mut v, "fr-FR"), 3);
+        assert_eq!(
+            lines(&v),
+            vec![
+                "<i>Quoi\u{202F}?</i> Il a dit\u{202F}: «\u{202F}Non\u{202F}!\u{202F}»",
+                "Rendez-vous à 12:30\u{202F}; voir http://example.com",
+                "«\u{202F}Bonjour\u{202F}»",
+            ]
+        );
+        assert_eq!(fix_punctuation(&mut v, "fr"), 0);
+
+        let mut v = entries(&["- Qué haces? Nada!", "- ¿Vienes? {\\i1}Sí{\\i0}, claro!", "Dime, ¿qué pasa?"]);
+        assert_eq!(fix_punctuation(&mut v, "es"), 2);
+        assert_eq!(
+            lines(&v),
+            vec!["- ¿Qué haces? ¡Nada!", "- ¿Vienes? {\\i1}¡Sí{\\i0}, claro!", "Dime, ¿qué pasa?"]
+        );
+
+        assert_eq!(fix_punctuation_text("He said \"no\" (\"never\").", "en"), "He said “no” (“never”).");
+        assert_eq!(fix_punctuation_text("Er sagte \"nein\".", "de"), "Er sagte „nein“.");
+        assert_eq!(fix_punctuation_text("\"Ok?\"", "xx"), "\"Ok?\"");
+    }
+
+    #[test]
+    fn convert_case_test() {
+        let mut v = entries(&[
+            "<i>THE FBI'S AGENT IS HERE.</i> I'M SURE",
+            "HE CAME FROM THE U.S. ARMY!\\NWHAT?",
+            "{\\i1}NASA KNOWS{\\i0}",
+        ]);
+        convert_case(&mut v, TextCase::Sentence, &["FBI", "nasa"]);
+        assert_eq!(
+            lines(&v),
+            vec![
+                "<i>The FBI's agent is here.</i> I'm sure",
+                "he came from the U.S. army!\\NWhat?",
+                "{\\i1}NASA knows{\\i0}",
+            ]
+        );
+
+        convert_case(&mut v, TextCase::Upper, &[]);
+        assert_eq!(
+            lines(&v),
+            vec![
+                "<i>THE FBI'S AGENT IS HERE.</i> I'M SURE",
+                "HE CAME FROM THE U.S. ARMY!\\NWHAT?",
+                "{\\i1}NASA KNOWS{\\i0}",
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_windows_test() {
+        let mut v = entries(&["A", "B", "C"]);
+        for (i, entry) in v.iter_mut().enumerate() {
+            entry.timespan = TimeSpan::new(TimePoint::from_msecs(i as i64 * 2000), TimePoint::from_msecs(i as i64 * 2000 + 500));
+        }
+
+        let windows: Vec<_> = entry_windows(&v)
+            .map(|(prev, current, next)| (prev.map(|e| &e.line), &current.line, next.map(|e| &e.line)))
+            .collect();
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0], (None, &v[0].line, Some(&v[1].line)));
+        assert_eq!(windows[1], (Some(&v[0].line), &v[1].line, Some(&v[2].line)));
+        assert_eq!(windows[2], (Some(&v[1].line), &v[2].line, None));
+        assert_eq!(entry_windows(&v).len(), 3);
+        assert_eq!(entry_windows(&[]).next(), None);
+
+        // extend every entry to the next one
+        for_each_entry_window_mut(&mut v, |_, current, next| {
+            if let Some(next) = next {
+                current.timespan.end = next.timespan.start;
+            }
+        });
+        let ends: Vec<i64> = v.iter().map(|e| e.timespan.end.msecs()).collect();
+        assert_eq!(ends, vec![2000, 4000, 4500]);
+    }
+
+    #[test]
+    fn extend_durations_test() {
+        let mut v = entries(&["A", "B", "C", "D"]);
+        let spans = [(0, 500), (1000, 1200), (3000, 4000), (3900, 4200)];
+        for (entry, &(start, end)) in v.iter_mut().zip(&spans) {
+            entry.timespan = TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end));
+        }
+
+        assert_eq!(extend_durations(&mut v, TimeDelta::from_msecs(1000), TimeDelta::from_msecs(100)), 3);
+        let ends: Vec<i64> = v.iter().map(|e| e.timespan.end.msecs()).collect();
+        // bounded by the gap, by the extension, overlapping (unchanged) and the last entry
+        assert_eq!(ends, vec![900, 2200, 4000, 5200]);
+    }
+
+    #[test]
+    fn shift_matching_test() {
+        let mut v = entries(&["Hello", "[SIGN] Exit", "World"]);
+        v[2].speaker = Some("Anna".to_string());
+        let delta = TimeDelta::from_msecs(-300);
+
+        assert_eq!(
+            shift_matching(&mut v, delta, |e| e.line.as_deref().map_or(false, |l| l.starts_with("[SIGN]"))),
+            1
+        );
+        assert_eq!(shift_matching(&mut v, delta, |e| e.speaker.as_deref() == Some("Anna")), 1);
+        let starts: Vec<i64> = v.iter().map(|e| e.timespan.start.msecs()).collect();
+        assert_eq!(starts, vec![0, -300, -300]);
+    }
+
+    #[test]
+    fn clip_to_test() {
+        let mut v = entries(&["A", "B", "C", "D"]);
+        let spans = [(0, 1000), (1500, 2500), (3000, 4000), (4000, 5000)];
+        for (entry, &(start, end)) in v.iter_mut().zip(&spans) {
+            entry.timespan = TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end));
+        }
+
+        let clipped = clip_to(&v, TimeSpan::new(TimePoint::from_msecs(1000), TimePoint::from_msecs(4000)));
+        let spans: Vec<(i64, i64)> = clipped.iter().map(|e| (e.timespan.start.msecs(), e.timespan.end.msecs())).collect();
+        // entries which only touch the range are dropped
+        assert_eq!(spans, vec![(1500, 2500), (3000, 4000)]);
+
+        let clipped = clip_to(&v, TimeSpan::new(TimePoint::from_msecs(2000), TimePoint::from_msecs(3500)));
+        let spans: Vec<(i64, i64)> = clipped.iter().map(|e| (e.timespan.start.msecs(), e.timespan.end.msecs())).collect();
+        assert_eq!(spans, vec![(2000, 2500), (3000, 3500)]);
+        assert_eq!(clipped[1].line.as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn scale_times_test() {
+        let mut v = entries(&["A", "B"]);
+        let spans = [(10_000, 12_000), (3_610_000, 3_612_000)];
+        for (entry, &(start, end)) in v.iter_mut().zip(&spans) {
+            entry.timespan = TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end));
+        }
+        let spans = |v: &[SubtitleEntry]| v.iter().map(|e| (e.timespan.start.msecs(), e.timespan.end.msecs())).collect::<Vec<_>>();
+
+        let mut scaled = v.clone();
+        scale_times(&mut scaled, 0.5, TimePoint::from_msecs(10_000)).unwrap();
+        assert_eq!(spans(&scaled), vec![(10_000, 11_000), (1_810_000, 1_811_000)]);
+
+        // 25 fps to 24000/1001 fps
+        let mut scaled = v.clone();
+        scale_times_exact(&mut scaled, 25025, 24000, TimePoint::from_msecs(0)).unwrap();
+        assert_eq!(spans(&scaled), vec![(10_427, 12_513), (3_764_177, 3_766_263)]);
+
+        // halves are rounded away from the anchor
+        let mut scaled = v.clone();
+        scale_times_exact(&mut scaled, 1, 2, TimePoint::from_msecs(3_611_001)).unwrap();
+        assert_eq!(spans(&scaled), vec![(1_810_500, 1_811_500), (3_610_500, 3_611_501)]);
+
+        // invalid factors and times which don't fit are errors, and the entries are unchanged
+        let mut scaled = v.clone();
+        assert_eq!(
+            scale_times(&mut scaled, f64::NAN, TimePoint::from_msecs(0)),
+            Err(TimeConversionError::NotANumber)
+        );
+        assert_eq!(
+            scale_times(&mut scaled, f64::INFINITY, TimePoint::from_msecs(0)),
+            Err(TimeConversionError::OutOfRange)
+        );
+        assert_eq!(
+            scale_times_exact(&mut scaled, 1, 0, TimePoint::from_msecs(0)),
+            Err(TimeConversionError::NotANumber)
+        );
+        assert_eq!(
+            scale_times_exact(&mut scaled, i64::MAX, 1, TimePoint::from_msecs(0)),
+            Err(TimeConversionError::OutOfRange)
+        );
+        assert_eq!(scaled, v);
+    }
+}
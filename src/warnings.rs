@@ -0,0 +1,158 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::timetypes::TimePoint;
+use crate::SubtitleEntry;
+use std::fmt;
+
+/// A problem in a subtitle file which did not prevent parsing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseWarning {
+    /// The (zero-based) number of the line in which the problem was found, like in the parse errors (`0` if the
+    /// position of the entry is not known, like in binary formats).
+    pub line_num: usize,
+
+    /// The (zero-based) index of the entry which is affected by the problem (or was changed to repair it).
+    pub entry_idx: Option<usize>,
+
+    /// What kind of problem was found.
+    pub kind: ParseWarningKind,
+}
+
+/// The different problems which are reported by `ParseWarning`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseWarningKind {
+    /// The end of the timespan is the same as its start, so the subtitle is never shown.
+    ZeroLengthTimespan,
+
+    /// The end of the timespan lies before its start.
+    NegativeTimespan,
+
+    /// Lines which did not belong to any entry were added to the text of the previous entry (by lenient parsing).
+    StrayLinesFolded,
+
+    /// A malformed line (like one with a missing closing brace) was repaired (by lenient parsing).
+    MalformedLineRepaired,
+
+    /// The index of the entry was missing and was replaced by the one after the previous entry.
+    IndexFixed,
+
+    /// The entry starts before the previous entry ends.
+    OverlappingEntries,
+
+    /// A timestamp of the entry is negative or later than 24 hours (usually a typo or a wrong frame rate).
+    SuspiciousTimestamp,
+
+    /// The image of the entry could not be decoded, so the entry has no image.
+    ImageNotDecoded,
+}
+
+impl ParseWarningKind {
+    /// A stable identifier of the kind of problem (like `"parse-zero-length-timespan"`), so frontends can show
+    /// their own (translated) messages.
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            ParseWarningKind::ZeroLengthTimespan => "parse-zero-length-timespan",
+            ParseWarningKind::NegativeTimespan => "parse-negative-timespan",
+            ParseWarningKind::StrayLinesFolded => "parse-stray-lines-folded",
+            ParseWarningKind::MalformedLineRepaired => "parse-malformed-line-repaired",
+            ParseWarningKind::IndexFixed => "parse-index-fixed",
+            ParseWarningKind::OverlappingEntries => "parse-overlapping-entries",
+            ParseWarningKind::SuspiciousTimestamp => "parse-suspicious-timestamp",
+            ParseWarningKind::ImageNotDecoded => "parse-image-not-decoded",
+        }
+    }
+}
+
+impl fmt::Display for ParseWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarningKind::ZeroLengthTimespan => write!(f, "the timespan has zero length"),
+            ParseWarningKind::NegativeTimespan => write!(f, "the timespan ends before it starts"),
+            ParseWarningKind::StrayLinesFolded => write!(f, "stray lines were folded into the previous entry"),
+            ParseWarningKind::MalformedLineRepaired => write!(f, "a malformed line was repaired"),
+            ParseWarningKind::IndexFixed => write!(f, "the missing index was replaced"),
+            ParseWarningKind::OverlappingEntries => write!(f, "the entry overlaps the previous entry"),
+            ParseWarningKind::SuspiciousTimestamp => write!(f, "the timestamp is negative or later than 24 hours"),
+            ParseWarningKind::ImageNotDecoded => write!(f, "the image could not be decoded"),
+        }
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (line {})", self.kind, self.line_num)
+    }
+}
+
+/// Check the entries of a parsed file for problems which every format can have: entries which start before the
+/// previous entry ends (`ParseWarningKind::OverlappingEntries`) and negative or very late timestamps
+/// (`ParseWarningKind::SuspiciousTimestamp`).
+///
+/// The line numbers of the warnings are `0`; `parse_str_with_warnings()` and `parse_bytes_with_warnings()` set them
+/// for the formats which know the positions of their entries.
+pub fn check_entries(entries: &[SubtitleEntry]) -> Vec<ParseWarning> {
+    let latest = TimePoint::from_components(24, 0, 0, 0);
+    let is_suspicious = |t: TimePoint| t.msecs() < 0 || t > latest;
+
+    let mut result = Vec::new();
+    for (entry_idx, entry) in entries.iter().enumerate() {
+        let mut push = |kind| {
+            result.push(ParseWarning {
+                line_num: 0,
+                entry_idx: Some(entry_idx),
+                kind,
+            })
+        };
+        if entry_idx > 0 && entry.timespan.start < entries[entry_idx - 1].timespan.end {
+            push(ParseWarningKind::OverlappingEntries);
+        }
+        if is_suspicious(entry.timespan.start) || is_suspicious(entry.timespan.end) {
+            push(ParseWarningKind::SuspiciousTimestamp);
+        }
+    }
+    result
+}
+
+/// The warnings of a parsed file: the ones which were found by the parser (`stored`) and the ones of
+/// `check_entries()`, whose line numbers are taken from the byte positions of the entries in `content`. The
+/// warnings are ordered by their entries.
+pub(crate) fn collect_warnings(
+    stored: &[ParseWarning],
+    entries: &[SubtitleEntry],
+    content: &str,
+    entry_start: impl Fn(usize) -> Option<usize>,
+) -> Vec<ParseWarning> {
+    let mut result = stored.to_vec();
+    for mut warning in check_entries(entries) {
+        if let Some(start) = warning.entry_idx.and_then(&entry_start) {
+            warning.line_num = content.as_bytes()[..start.min(content.len())].iter().filter(|&&b| b == b'\n').count();
+        }
+        result.push(warning);
+    }
+    // the sort is stable, so the warnings of an entry keep their order
+    result.sort_by_key(|warning| warning.entry_idx);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetypes::TimeSpan;
+
+    #[test]
+    fn check_entries_test() {
+        let entry = |start, end| SubtitleEntry::new(TimeSpan::new(TimePoint::from_msecs(start), TimePoint::from_msecs(end)), "A".to_string());
+        let entries = vec![entry(0, 2000), entry(1000, 3000), entry(3000, 4000), entry(-500, 100_000_000)];
+        let kinds: Vec<_> = check_entries(&entries).iter().map(|warning| (warning.entry_idx, warning.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Some(1), ParseWarningKind::OverlappingEntries),
+                (Some(3), ParseWarningKind::OverlappingEntries),
+                (Some(3), ParseWarningKind::SuspiciousTimestamp),
+            ]
+        );
+    }
+}